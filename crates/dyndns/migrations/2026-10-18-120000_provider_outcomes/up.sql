@@ -0,0 +1,16 @@
+-- DNS providers that domain records are updated on.
+CREATE TABLE providers (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    name VARCHAR UNIQUE NOT NULL
+);
+
+-- Stores the outcome of each updater attempt on each provider. Fact table.
+CREATE TABLE provider_updates (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    updater_attempt_id INTEGER NOT NULL REFERENCES updater_attempts(id),
+    provider_id INTEGER NOT NULL REFERENCES providers(id),
+    success BOOLEAN NOT NULL,
+    error_message VARCHAR,
+    http_status INTEGER,
+    latency_ms INTEGER
+);
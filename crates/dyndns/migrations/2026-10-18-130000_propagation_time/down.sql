@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE provider_updates DROP COLUMN propagation_ms;
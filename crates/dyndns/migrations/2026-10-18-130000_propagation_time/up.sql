@@ -0,0 +1,3 @@
+-- Time until the authoritative nameservers served the updated value, when
+-- post-update verification is enabled.
+ALTER TABLE provider_updates ADD COLUMN propagation_ms INTEGER;
@@ -1,4 +1,6 @@
 use clap::{crate_version, ArgMatches, Args, Command};
+#[cfg(feature = "stats")]
+use clap::{Subcommand, ValueEnum};
 use serde::Serialize;
 use serde_with::skip_serializing_none;
 
@@ -157,6 +159,85 @@ impl ClapAllArgs {
     }
 }
 
+/// Subcommands operating on the statistics database.
+#[cfg(feature = "stats")]
+#[derive(Subcommand, Debug, Clone)]
+pub enum StatsSubcommand {
+    /// Print the IP change history, IP fetch success rates and per-record attempt counts.
+    Show(StatsShowArgs),
+
+    /// Export one of the raw statistics tables as CSV or NDJSON.
+    Export(StatsExportArgs),
+
+    /// Delete statistics that fall outside of the configured retention policy.
+    Prune(StatsPruneArgs),
+
+    /// Regenerate the summary tables from the recorded IP fetches and domain record updates.
+    Rebuild,
+}
+
+#[cfg(feature = "stats")]
+#[derive(Args, Debug, Clone)]
+pub struct StatsShowArgs {
+    /// Maximum number of most recent IP changes to print.
+    #[arg(short = 'l', long, default_value_t = 20)]
+    pub limit: usize,
+}
+
+#[cfg(feature = "stats")]
+#[derive(Args, Debug, Clone)]
+pub struct StatsExportArgs {
+    /// The table to export.
+    #[arg(long, value_enum)]
+    pub table: StatsTable,
+
+    /// Output format.
+    #[arg(short = 'f', long, value_enum, default_value_t = StatsExportFormat::Csv)]
+    pub format: StatsExportFormat,
+
+    /// File path where to write the exported rows. Defaults to stdout.
+    #[arg(short = 'o', long)]
+    pub output: Option<std::path::PathBuf>,
+}
+
+#[cfg(feature = "stats")]
+#[derive(Args, Debug, Clone)]
+pub struct StatsPruneArgs {
+    /// Delete statistics older than the given age, overriding `stats_max_age`.
+    ///
+    /// Uses rust's humantime format.
+    /// Example: '30days'
+    #[arg(long, value_parser = humantime::parse_duration)]
+    pub max_age: Option<std::time::Duration>,
+
+    /// Keep at most the given number of newest rows per table, overriding `stats_max_rows`.
+    #[arg(long)]
+    pub max_rows: Option<u64>,
+
+    /// Run VACUUM after pruning to shrink the database file.
+    #[arg(long)]
+    pub vacuum: bool,
+}
+
+#[cfg(feature = "stats")]
+#[derive(ValueEnum, Debug, Clone, Copy)]
+#[value(rename_all = "snake_case")]
+pub enum StatsTable {
+    DomainRecords,
+    DomainIpFetches,
+    DomainRecordUpdates,
+    UpdaterAttempts,
+    Providers,
+    ProviderUpdates,
+}
+
+#[cfg(feature = "stats")]
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum StatsExportFormat {
+    Csv,
+    Ndjson,
+}
+
 fn get_cli_command_definition_base() -> Command {
     Command::new("Multi-provider dynamic DNS updater")
         .version(crate_version!())
@@ -231,5 +312,14 @@ pub fn get_cli_command_definition() -> Command {
     command = CommonArgs::augment_args(command);
     command = ConditionalArgs::augment_args(command);
 
+    #[cfg(feature = "stats")]
+    {
+        let stats_command = Command::new(crate::config::consts::STATS_SUBCOMMAND)
+            .about("Inspect and export the collected statistics database")
+            .subcommand_required(true)
+            .arg_required_else_help(true);
+        command = command.subcommand(StatsSubcommand::augment_subcommands(stats_command));
+    }
+
     command
 }
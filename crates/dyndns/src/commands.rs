@@ -1,15 +1,26 @@
 use crate::build_info::print_build_info;
+#[cfg(feature = "stats")]
+use crate::cli::StatsSubcommand;
 use crate::config::early::EarlyConfig;
 use crate::daemon::start_daemon;
 use crate::global_state::GlobalState;
+#[cfg(feature = "stats")]
+use crate::stats_commands::handle_stats_command;
 use color_eyre::eyre::Result;
 
 pub enum Command {
     PrintBuildInfo,
     StartDaemon,
+    #[cfg(feature = "stats")]
+    Stats(StatsSubcommand),
 }
 
 pub fn decide_command(early_config: &EarlyConfig) -> Command {
+    #[cfg(feature = "stats")]
+    if let Some(stats_command) = early_config.stats_subcommand() {
+        return Command::Stats(stats_command);
+    }
+
     if early_config.should_print_build_info() {
         Command::PrintBuildInfo
     } else {
@@ -26,6 +37,10 @@ pub fn handle_command(early_config: &EarlyConfig, command: &Command) -> Result<(
             let global_state = GlobalState::new(early_config)?;
             start_daemon(global_state)?;
         }
+        #[cfg(feature = "stats")]
+        Command::Stats(stats_command) => {
+            handle_stats_command(early_config, stats_command)?;
+        }
     };
     Ok(())
 }
@@ -1,9 +1,18 @@
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{bail, Result, WrapErr};
 use humantime::parse_duration;
 use serde::{Deserialize, Serialize};
-use std::{ops::Deref, sync::Arc, time::Duration};
-
+use serde_with::DeserializeFromStr;
+use std::{ops::Deref, str::FromStr, sync::Arc, time::Duration};
+
+use super::bind_config::BindConfig;
+use super::failover_config::FailoverConfig;
+use super::hook_config::HookConfig;
+use super::http_client_config::HttpClientConfig;
+use super::mqtt_config::MqttConfig;
+use super::notifier_config::NotifierConfig;
 use super::provider_config::{ProviderType, ProvidersConfig, SecretProviderToken};
+use super::verification_config::VerificationConfig;
+use super::web_auth_config::WebAuthConfig;
 
 #[derive(Debug, Clone)]
 pub struct AppConfig {
@@ -36,7 +45,12 @@ pub struct AppConfigInner {
 #[non_exhaustive]
 #[derive(Debug, Deserialize)]
 pub struct GeneralOptions {
-    pub update_interval: UpdateInterval,
+    /// Defaults to every 10 minutes, unless a `schedule` is set.
+    #[serde(default)]
+    pub update_interval: Option<UpdateInterval>,
+    /// Cron expression used instead of `update_interval` when set.
+    #[serde(default)]
+    pub schedule: Option<UpdateSchedule>,
     pub digital_ocean_token: Option<SecretProviderToken>,
     #[serde(default, flatten)]
     pub providers_config: ProvidersConfig,
@@ -48,16 +62,42 @@ pub struct GeneralOptions {
     pub collect_stats: bool,
     #[serde(rename = "database_path")]
     pub db_path: Option<std::path::PathBuf>,
+    /// File the public IPs put into merged record sets are remembered in.
+    #[serde(default)]
+    pub state_path: Option<std::path::PathBuf>,
     pub enable_web: bool,
     pub listen_hostname: String,
     pub listen_port: u16,
     pub update_all_providers_by_default: bool,
+    pub health_failure_threshold: u32,
+    /// How many zones are updated at the same time, across all providers.
+    pub update_concurrency: usize,
+    #[serde(default)]
+    pub web_auth: WebAuthConfig,
+    #[serde(default)]
+    pub notifiers: Vec<NotifierConfig>,
+    #[serde(default)]
+    pub hooks: Vec<HookConfig>,
+    #[serde(default)]
+    pub mqtt: Option<MqttConfig>,
+    #[serde(default)]
+    pub verification: Option<VerificationConfig>,
+    /// Interface or source address the public IP queries are sent from.
+    #[serde(default)]
+    pub ip_fetch_bind: BindConfig,
+    #[serde(default)]
+    pub http_client: HttpClientConfig,
+    #[serde(flatten)]
+    pub web_tls: WebTlsOptions,
+    #[serde(flatten)]
+    pub stats_retention: StatsRetentionOptions,
+    #[serde(flatten)]
+    pub network_watch: NetworkWatchOptions,
 }
 
 #[non_exhaustive]
 #[derive(Debug, Serialize)]
 pub struct GeneralOptionsDefaults {
-    pub update_interval: UpdateInterval,
     pub digital_ocean_token: Option<SecretProviderToken>,
     #[serde(flatten)]
     pub providers_config: ProvidersConfig,
@@ -73,12 +113,19 @@ pub struct GeneralOptionsDefaults {
     pub listen_hostname: String,
     pub listen_port: u16,
     pub update_all_providers_by_default: bool,
+    pub health_failure_threshold: u32,
+    pub update_concurrency: usize,
+    #[serde(flatten)]
+    pub web_tls: WebTlsOptions,
+    #[serde(flatten)]
+    pub stats_retention: StatsRetentionOptions,
+    #[serde(flatten)]
+    pub network_watch: NetworkWatchOptions,
 }
 
 impl Default for GeneralOptionsDefaults {
     fn default() -> Self {
         Self {
-            update_interval: Default::default(),
             digital_ocean_token: None,
             providers_config: Default::default(),
             log_level: tracing::Level::INFO,
@@ -91,10 +138,118 @@ impl Default for GeneralOptionsDefaults {
             listen_hostname: "localhost".to_owned(),
             listen_port: 8095,
             update_all_providers_by_default: true,
+            health_failure_threshold: 3,
+            update_concurrency: 4,
+            web_tls: Default::default(),
+            stats_retention: Default::default(),
+            network_watch: Default::default(),
         }
     }
 }
 
+/// Serves the web server over HTTPS.
+///
+/// TLS is disabled unless both `tls_cert_path` and `tls_key_path` are set.
+/// Without `tls_listen_port`, HTTPS replaces plain HTTP on `listen_port`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WebTlsOptions {
+    /// PEM file with the certificate chain, leaf certificate first.
+    #[serde(default)]
+    pub tls_cert_path: Option<std::path::PathBuf>,
+    /// PEM file with the private key of the leaf certificate.
+    #[serde(default)]
+    pub tls_key_path: Option<std::path::PathBuf>,
+    /// Port to serve HTTPS on, in addition to plain HTTP on `listen_port`.
+    #[serde(default)]
+    pub tls_listen_port: Option<u16>,
+}
+
+impl WebTlsOptions {
+    pub fn is_enabled(&self) -> bool {
+        self.tls_cert_path.is_some() && self.tls_key_path.is_some()
+    }
+
+    pub fn validate(&self, listen_port: u16) -> Result<()> {
+        if self.tls_cert_path.is_some() != self.tls_key_path.is_some() {
+            bail!("Both 'tls_cert_path' and 'tls_key_path' need to be set to enable TLS");
+        }
+        if !self.is_enabled() && self.tls_listen_port.is_some() {
+            bail!("'tls_listen_port' requires 'tls_cert_path' and 'tls_key_path' to be set");
+        }
+        if self.tls_listen_port == Some(listen_port) {
+            bail!("'tls_listen_port' must differ from 'listen_port', omit it to serve only HTTPS on 'listen_port'");
+        }
+        Ok(())
+    }
+}
+
+/// Controls how long the collected statistics are kept around.
+///
+/// Pruning is disabled unless at least one of `stats_max_age` or
+/// `stats_max_rows` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsRetentionOptions {
+    #[serde(default, with = "humantime_serde")]
+    pub stats_max_age: Option<Duration>,
+    #[serde(default)]
+    pub stats_max_rows: Option<u64>,
+    #[serde(with = "humantime_serde")]
+    pub stats_prune_interval: Duration,
+    pub stats_vacuum_after_prune: bool,
+}
+
+impl StatsRetentionOptions {
+    pub fn is_enabled(&self) -> bool {
+        self.stats_max_age.is_some() || self.stats_max_rows.is_some()
+    }
+}
+
+impl Default for StatsRetentionOptions {
+    fn default() -> Self {
+        Self {
+            stats_max_age: None,
+            stats_max_rows: None,
+            stats_prune_interval: Duration::from_secs(60 * 60 * 24),
+            stats_vacuum_after_prune: false,
+        }
+    }
+}
+
+/// Wakes up the updater as soon as network interfaces or addresses change,
+/// instead of waiting for the next `update_interval`. Only supported on Linux.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkWatchOptions {
+    pub watch_network_changes: bool,
+    /// How long the network needs to be quiet after a change before
+    /// updating, so a reconnect causing a burst of changes updates once.
+    #[serde(with = "humantime_serde")]
+    pub network_change_debounce: Duration,
+}
+
+impl Default for NetworkWatchOptions {
+    fn default() -> Self {
+        Self {
+            watch_network_changes: false,
+            network_change_debounce: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Subset of the configuration used by the `stats` subcommands.
+///
+/// Unlike [`GeneralOptions`], extracting it doesn't require any providers or
+/// domains to be configured.
+#[non_exhaustive]
+#[derive(Debug, Deserialize)]
+pub struct StatsOptions {
+    #[serde(deserialize_with = "deserialize_log_level_from_u8_or_string")]
+    pub log_level: tracing::Level,
+    #[serde(rename = "database_path")]
+    pub db_path: Option<std::path::PathBuf>,
+    #[serde(flatten)]
+    pub stats_retention: StatsRetentionOptions,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SimpleModeDomainConfig {
     pub domain_root: String,
@@ -110,6 +265,33 @@ pub struct DomainRecord {
     pub name: String,
     #[serde(default)]
     pub providers: Option<Vec<ProviderType>>,
+    /// Overrides the domain's and the global update cadence.
+    #[serde(default)]
+    pub update_interval: Option<UpdateInterval>,
+    #[serde(default)]
+    pub schedule: Option<UpdateSchedule>,
+    #[serde(default)]
+    pub values_mode: RecordValuesMode,
+    /// Values that are always part of the record set, next to the public IP.
+    #[serde(default)]
+    pub static_values: Vec<String>,
+    /// Points the record at the healthy one of several candidate IPs,
+    /// instead of the public IP.
+    #[serde(default)]
+    pub failover: Option<FailoverConfig>,
+}
+
+/// How an update treats the values of a record set that were not set by the
+/// updater.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordValuesMode {
+    /// The record set only contains the public IP and the static values.
+    #[default]
+    Replace,
+    /// Values added by others are kept, only the public IP set by a previous
+    /// update is swapped for the new one.
+    Merge,
 }
 
 #[non_exhaustive]
@@ -117,6 +299,11 @@ pub struct DomainRecord {
 pub struct Domain {
     pub name: String,
     pub records: Vec<DomainRecord>,
+    /// Overrides the global update cadence for all records of the domain.
+    #[serde(default)]
+    pub update_interval: Option<UpdateInterval>,
+    #[serde(default)]
+    pub schedule: Option<UpdateSchedule>,
 }
 
 #[non_exhaustive]
@@ -126,6 +313,63 @@ pub struct Domains {
     pub domains: Vec<Domain>,
 }
 
+impl Domains {
+    pub fn validate(&self) -> Result<()> {
+        let validate_cadence = |name: &str,
+                                update_interval: &Option<UpdateInterval>,
+                                schedule: &Option<UpdateSchedule>| {
+            if update_interval.is_some() && schedule.is_some() {
+                bail!(
+                    "'{}' sets both 'update_interval' and 'schedule', please use only one",
+                    name
+                );
+            }
+            if update_interval
+                .as_ref()
+                .is_some_and(|interval| interval.0.is_zero())
+            {
+                bail!("'{}' has a zero 'update_interval'", name);
+            }
+            Ok(())
+        };
+        for domain in &self.domains {
+            validate_cadence(&domain.name, &domain.update_interval, &domain.schedule)?;
+            for record in &domain.records {
+                let name = format!("{}.{}", record.name, domain.name);
+                validate_cadence(&name, &record.update_interval, &record.schedule)?;
+                validate_static_values(&name, record)?;
+                if let Some(failover) = &record.failover {
+                    failover
+                        .validate(&record.record_type)
+                        .wrap_err_with(|| format!("Invalid failover configuration of '{name}'"))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Static values of address records need to be addresses of the matching IP
+/// family.
+fn validate_static_values(name: &str, record: &DomainRecord) -> Result<()> {
+    for value in &record.static_values {
+        let valid = match record.record_type.as_str() {
+            "A" => value.parse::<std::net::Ipv4Addr>().is_ok(),
+            "AAAA" => value.parse::<std::net::Ipv6Addr>().is_ok(),
+            _ => !value.is_empty(),
+        };
+        if !valid {
+            bail!(
+                "'{}' has an invalid static value '{}' for a record of type '{}'",
+                name,
+                value,
+                record.record_type
+            );
+        }
+    }
+    Ok(())
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct UpdateInterval(#[serde(with = "humantime_serde")] pub Duration);
 
@@ -149,6 +393,54 @@ impl std::fmt::Display for UpdateInterval {
     }
 }
 
+/// Cron expression for when to update, evaluated in local time.
+///
+/// Accepts the standard 5 fields, or 6 to 7 fields starting with seconds.
+#[derive(Clone, Debug, DeserializeFromStr)]
+pub struct UpdateSchedule {
+    expression: String,
+    schedule: cron::Schedule,
+}
+
+impl UpdateSchedule {
+    pub fn next_after(
+        &self,
+        after: &chrono::DateTime<chrono::Local>,
+    ) -> Option<chrono::DateTime<chrono::Local>> {
+        self.schedule.after(after).next()
+    }
+
+    pub fn previous_before(
+        &self,
+        before: &chrono::DateTime<chrono::Local>,
+    ) -> Option<chrono::DateTime<chrono::Local>> {
+        self.schedule.after(before).next_back()
+    }
+}
+
+impl FromStr for UpdateSchedule {
+    type Err = cron::error::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // The cron crate always expects a leading seconds field.
+        let schedule = if s.split_whitespace().count() == 5 {
+            cron::Schedule::from_str(&format!("0 {s}"))?
+        } else {
+            cron::Schedule::from_str(s)?
+        };
+        Ok(UpdateSchedule {
+            expression: s.to_owned(),
+            schedule,
+        })
+    }
+}
+
+impl std::fmt::Display for UpdateSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
 pub fn deserialize_log_level_from_u8_or_string<'de, D>(
     deserializer: D,
 ) -> Result<tracing::Level, D::Error>
@@ -446,6 +738,31 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_provider_config_bind() {
+        let toml = r#"
+        [[providers]]
+        provider = "digitalocean"
+        token = "test_token"
+
+        [[providers]]
+        provider = "hetzner"
+        token = "test_token"
+        bind = { source_address = "192.168.2.10" }
+    "#;
+
+        let config: ProvidersConfig = toml::from_str(toml).unwrap();
+        let [digitalocean, hetzner] = config.providers.as_slice() else {
+            panic!("Expected two providers");
+        };
+        assert!(!digitalocean.bind.is_set());
+        assert_eq!(
+            hetzner.bind.source_address,
+            Some("192.168.2.10".parse().unwrap())
+        );
+        assert!(config.validate().is_ok());
+    }
+
     #[test]
     fn test_provider_config_missing_token_field() {
         let toml = r#"
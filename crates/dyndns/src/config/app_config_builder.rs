@@ -2,7 +2,7 @@ use crate::cli::ClapAllArgs;
 
 use super::app_config::{
     AppConfig, AppConfigInner, Domain, DomainRecord, Domains, GeneralOptions,
-    GeneralOptionsDefaults, SimpleModeDomainConfig,
+    GeneralOptionsDefaults, RecordValuesMode, SimpleModeDomainConfig, StatsOptions,
 };
 use super::consts::*;
 use super::early::EarlyConfig;
@@ -103,6 +103,14 @@ pub fn config_with_args(early_config: &EarlyConfig) -> Result<AppConfig> {
     Ok(config)
 }
 
+pub fn stats_options_with_args(early_config: &EarlyConfig) -> Result<StatsOptions> {
+    let clap_matches = early_config.get_clap_matches();
+    let config_file_path = get_config_path(clap_matches);
+    AppConfigBuilder::new(Some(clap_matches), config_file_path)?
+        .build_stats_options()
+        .wrap_err(eyre!("Failed to configure the statistics database options"))
+}
+
 fn get_advanced_mode_domains(builder: &AppConfigBuilder) -> Result<Domains> {
     let domains: Domains = builder
         .figment
@@ -185,7 +193,14 @@ impl AppConfigBuilder {
                     record_type: "A".to_owned(),
                     name: config.1,
                     providers: None, // None means update on all providers
+                    update_interval: None,
+                    schedule: None,
+                    values_mode: RecordValuesMode::Replace,
+                    static_values: vec![],
+                    failover: None,
                 }],
+                update_interval: None,
+                schedule: None,
             }],
         };
         Ok(domains)
@@ -210,6 +225,7 @@ impl AppConfigBuilder {
                 bail!("Both simple and advanced config modes settings were specified. Please use only one mode")
             }
         };
+        domains.validate()?;
         Ok(domains)
     }
 
@@ -239,12 +255,68 @@ impl AppConfigBuilder {
             bail!("At least one DNS provider must be configured via [[providers]]");
         }
 
+        general_options.web_auth.validate()?;
+        for notifier in &general_options.notifiers {
+            notifier.validate().wrap_err_with(|| {
+                format!("Invalid {} notifier configuration", notifier.kind.as_str())
+            })?;
+        }
+        for hook in &general_options.hooks {
+            hook.validate().wrap_err_with(|| {
+                format!(
+                    "Invalid hook configuration for '{}'",
+                    hook.command.display()
+                )
+            })?;
+        }
+        if let Some(mqtt) = &general_options.mqtt {
+            mqtt.validate().wrap_err("Invalid MQTT configuration")?;
+        }
+        if let Some(verification) = &general_options.verification {
+            verification
+                .validate()
+                .wrap_err("Invalid verification configuration")?;
+        }
+        general_options
+            .ip_fetch_bind
+            .validate()
+            .wrap_err("Invalid 'ip_fetch_bind' configuration")?;
+        general_options
+            .http_client
+            .validate()
+            .wrap_err("Invalid HTTP client configuration")?;
+        general_options
+            .web_tls
+            .validate(general_options.listen_port)?;
+
+        if general_options.update_concurrency == 0 {
+            bail!("'update_concurrency' must be at least 1");
+        }
+        if general_options.update_interval.is_some() && general_options.schedule.is_some() {
+            bail!("Both 'update_interval' and 'schedule' are set, please use only one");
+        }
+        if general_options
+            .update_interval
+            .as_ref()
+            .is_some_and(|interval| interval.0.is_zero())
+        {
+            bail!("'update_interval' must not be zero");
+        }
+        if general_options.health_failure_threshold == 0 {
+            bail!("'health_failure_threshold' must be at least 1");
+        }
+
         if !general_options.ipv4 && !general_options.ipv6 {
             bail!("At least one kind of ip family support needs to be enabled, both are disabled.");
         }
         Ok(general_options)
     }
 
+    pub fn build_stats_options(&self) -> Result<StatsOptions> {
+        let stats_options: StatsOptions = self.figment.extract()?;
+        Ok(stats_options)
+    }
+
     pub fn build(&self) -> Result<AppConfig> {
         let general_options = self.build_general_options()?;
 
@@ -261,7 +333,10 @@ impl AppConfigBuilder {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::notifier_config::{NotificationKind, NotifierKindConfig};
+    use crate::config::web_auth_config::WebAuthScope;
     use figment::providers::{Format, Serialized, Toml};
+    use std::time::Duration;
 
     #[test]
     fn test_legacy_and_new_digitalocean_config_fails() {
@@ -295,4 +370,308 @@ mod tests {
                 && error_msg.contains("digital_ocean_token")
         );
     }
+
+    #[test]
+    fn test_zero_health_failure_threshold_fails() {
+        let toml = r#"
+        health_failure_threshold = 0
+
+        [[providers]]
+        provider = "digitalocean"
+        token = "token_123"
+
+        [[domains]]
+        name = "example.com"
+
+        [[domains.records]]
+        type = "A"
+        name = "www"
+    "#;
+
+        let figment = Figment::new()
+            .merge(Serialized::defaults(GeneralOptionsDefaults::default()))
+            .merge(Toml::string(toml));
+
+        let builder = AppConfigBuilder { figment };
+        let error_msg = builder.build().unwrap_err().to_string();
+        assert!(error_msg.contains("'health_failure_threshold' must be at least 1"));
+    }
+
+    #[test]
+    fn test_stats_retention_options() {
+        let figment = Figment::new().merge(Serialized::defaults(GeneralOptionsDefaults::default()));
+        let builder = AppConfigBuilder { figment };
+        let stats_options = builder.build_stats_options().unwrap();
+        assert!(!stats_options.stats_retention.is_enabled());
+        assert_eq!(
+            stats_options.stats_retention.stats_prune_interval,
+            std::time::Duration::from_secs(60 * 60 * 24)
+        );
+
+        let toml = r#"
+        stats_max_age = "30days"
+        stats_max_rows = 10000
+        stats_vacuum_after_prune = true
+    "#;
+        let figment = Figment::new()
+            .merge(Serialized::defaults(GeneralOptionsDefaults::default()))
+            .merge(Toml::string(toml));
+        let builder = AppConfigBuilder { figment };
+        let stats_retention = builder.build_stats_options().unwrap().stats_retention;
+        assert!(stats_retention.is_enabled());
+        assert_eq!(
+            stats_retention.stats_max_age,
+            Some(std::time::Duration::from_secs(60 * 60 * 24 * 30))
+        );
+        assert_eq!(stats_retention.stats_max_rows, Some(10000));
+        assert!(stats_retention.stats_vacuum_after_prune);
+    }
+
+    #[test]
+    fn test_web_auth_config() {
+        let toml = r#"
+        [[providers]]
+        provider = "digitalocean"
+        token = "token_123"
+
+        [[domains]]
+        name = "example.com"
+
+        [[domains.records]]
+        type = "A"
+        name = "www"
+
+        [[web_auth.tokens]]
+        name = "home-assistant"
+        token = "s3cr3t"
+        scope = "write"
+
+        [[web_auth.users]]
+        username = "admin"
+        password_hash = "$2b$04$abcdefghijklmnopqrstuu"
+    "#;
+        let figment = Figment::new()
+            .merge(Serialized::defaults(GeneralOptionsDefaults::default()))
+            .merge(Toml::string(toml));
+        let config = AppConfigBuilder { figment }.build().unwrap();
+        let web_auth = &config.general_options.web_auth;
+        assert!(web_auth.is_enabled());
+        assert!(web_auth
+            .tokens
+            .iter()
+            .all(|token| token.scope == WebAuthScope::Write));
+        assert!(web_auth
+            .users
+            .iter()
+            .all(|user| user.scope == WebAuthScope::Read));
+
+        let toml = toml.replace("$2b$04$abcdefghijklmnopqrstuu", "plaintext");
+        let figment = Figment::new()
+            .merge(Serialized::defaults(GeneralOptionsDefaults::default()))
+            .merge(Toml::string(&toml));
+        let error_msg = AppConfigBuilder { figment }
+            .build()
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("Unsupported password hash for web auth user 'admin'"));
+    }
+
+    #[test]
+    fn test_notifiers_config() {
+        let toml = r#"
+        [[providers]]
+        provider = "digitalocean"
+        token = "token_123"
+
+        [[domains]]
+        name = "example.com"
+
+        [[domains.records]]
+        type = "A"
+        name = "www"
+
+        [[notifiers]]
+        type = "webhook"
+        url = "https://hooks.example.com/dyndns"
+        events = ["update_failed"]
+        failure_threshold = 3
+        body = '{"text": "{{ fqdn }}: {{ error }}"}'
+    "#;
+        let figment = Figment::new()
+            .merge(Serialized::defaults(GeneralOptionsDefaults::default()))
+            .merge(Toml::string(toml));
+        let config = AppConfigBuilder { figment }.build().unwrap();
+        let notifiers = &config.general_options.notifiers;
+        assert_eq!(notifiers.len(), 1);
+        assert!(notifiers.iter().all(|notifier| {
+            notifier.events == [NotificationKind::UpdateFailed]
+                && notifier.failure_threshold == 3
+                && notifier.retries == 3
+                && matches!(
+                    &notifier.kind,
+                    NotifierKindConfig::Webhook(webhook) if webhook.method == "POST"
+                )
+        }));
+
+        let toml = toml.replace("{{ error }}", "{{ reason }}");
+        let figment = Figment::new()
+            .merge(Serialized::defaults(GeneralOptionsDefaults::default()))
+            .merge(Toml::string(&toml));
+        let error = AppConfigBuilder { figment }.build().unwrap_err();
+        assert!(format!("{error:#}").contains("Unknown template variable 'reason'"));
+    }
+
+    #[test]
+    fn test_hooks_config() {
+        let toml = r#"
+        [[providers]]
+        provider = "digitalocean"
+        token = "token_123"
+
+        [[domains]]
+        name = "example.com"
+
+        [[domains.records]]
+        type = "A"
+        name = "www"
+
+        [[hooks]]
+        event = "ip_changed"
+        command = "/usr/local/bin/update-firewall"
+        args = ["--reload"]
+        env = { FIREWALL_ZONE = "wan" }
+    "#;
+        let figment = Figment::new()
+            .merge(Serialized::defaults(GeneralOptionsDefaults::default()))
+            .merge(Toml::string(toml));
+        let config = AppConfigBuilder { figment }.build().unwrap();
+        let hooks = &config.general_options.hooks;
+        assert_eq!(hooks.len(), 1);
+        assert!(hooks.iter().all(|hook| {
+            hook.event == NotificationKind::IpChanged
+                && hook.args == ["--reload"]
+                && hook.timeout == Duration::from_secs(30)
+                && hook.env.get("FIREWALL_ZONE").map(String::as_str) == Some("wan")
+        }));
+
+        let toml = toml.replace(r#"args = ["--reload"]"#, r#"timeout = "0s""#);
+        let figment = Figment::new()
+            .merge(Serialized::defaults(GeneralOptionsDefaults::default()))
+            .merge(Toml::string(&toml));
+        let error = AppConfigBuilder { figment }.build().unwrap_err();
+        assert!(format!("{error:#}").contains("Hook 'timeout' must be greater than zero"));
+    }
+
+    #[test]
+    fn test_update_cadence_config() {
+        let toml = r#"
+        schedule = "0 * * * *"
+
+        [[providers]]
+        provider = "digitalocean"
+        token = "token_123"
+
+        [[domains]]
+        name = "example.com"
+        update_interval = "1hour"
+
+        [[domains.records]]
+        type = "A"
+        name = "www"
+        update_interval = "1min"
+
+        [[domains.records]]
+        type = "A"
+        name = "home"
+    "#;
+        let figment = Figment::new()
+            .merge(Serialized::defaults(GeneralOptionsDefaults::default()))
+            .merge(Toml::string(toml));
+        let config = AppConfigBuilder { figment }.build().unwrap();
+        assert_eq!(
+            config
+                .general_options
+                .schedule
+                .as_ref()
+                .map(ToString::to_string),
+            Some("0 * * * *".to_owned())
+        );
+        let domain = config.domains.domains.first().unwrap();
+        let record_intervals = domain
+            .records
+            .iter()
+            .map(|record| record.update_interval.as_ref().map(|interval| interval.0))
+            .collect::<Vec<_>>();
+        assert_eq!(record_intervals, [Some(Duration::from_secs(60)), None]);
+
+        let toml = toml.replace(
+            r#"update_interval = "1min""#,
+            r#"update_interval = "1min"
+        schedule = "*/5 * * * *""#,
+        );
+        let figment = Figment::new()
+            .merge(Serialized::defaults(GeneralOptionsDefaults::default()))
+            .merge(Toml::string(&toml));
+        let error = AppConfigBuilder { figment }.build().unwrap_err();
+        assert!(format!("{error:#}").contains("sets both 'update_interval' and 'schedule'"));
+
+        let toml = toml.replace(
+            r#"schedule = "0 * * * *""#,
+            r#"schedule = "0 * * * *"
+        update_interval = "5min""#,
+        );
+        let figment = Figment::new()
+            .merge(Serialized::defaults(GeneralOptionsDefaults::default()))
+            .merge(Toml::string(&toml));
+        let error = AppConfigBuilder { figment }.build().unwrap_err();
+        assert!(format!("{error:#}")
+            .contains("Both 'update_interval' and 'schedule' are set, please use only one"));
+    }
+
+    #[test]
+    fn test_record_values_config() {
+        let toml = r#"
+        [[providers]]
+        provider = "hetzner"
+        token = "token_123"
+
+        [[domains]]
+        name = "example.com"
+
+        [[domains.records]]
+        type = "A"
+        name = "www"
+        values_mode = "merge"
+        static_values = ["192.0.2.10"]
+
+        [[domains.records]]
+        type = "A"
+        name = "home"
+    "#;
+        let figment = Figment::new()
+            .merge(Serialized::defaults(GeneralOptionsDefaults::default()))
+            .merge(Toml::string(toml));
+        let config = AppConfigBuilder { figment }.build().unwrap();
+        let domain = config.domains.domains.first().unwrap();
+        let values_modes = domain
+            .records
+            .iter()
+            .map(|record| record.values_mode)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values_modes,
+            [RecordValuesMode::Merge, RecordValuesMode::Replace]
+        );
+        assert_eq!(
+            domain.records.first().unwrap().static_values,
+            ["192.0.2.10"]
+        );
+
+        let toml = toml.replace("192.0.2.10", "2001:db8::10");
+        let figment = Figment::new()
+            .merge(Serialized::defaults(GeneralOptionsDefaults::default()))
+            .merge(Toml::string(&toml));
+        let error = AppConfigBuilder { figment }.build().unwrap_err();
+        assert!(format!("{error:#}").contains("invalid static value '2001:db8::10'"));
+    }
 }
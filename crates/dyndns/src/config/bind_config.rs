@@ -0,0 +1,58 @@
+use color_eyre::eyre::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// Sends outbound requests through a specific network interface or from a
+/// specific local address, e.g. to use a secondary uplink of a multi-homed
+/// host instead of the default route.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BindConfig {
+    /// Name of the network interface to bind to (`SO_BINDTODEVICE`). Only
+    /// supported on Linux.
+    pub interface: Option<String>,
+    /// Local address to send requests from.
+    pub source_address: Option<IpAddr>,
+}
+
+impl BindConfig {
+    pub fn is_set(&self) -> bool {
+        self.interface.is_some() || self.source_address.is_some()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some(interface) = &self.interface {
+            if cfg!(not(target_os = "linux")) {
+                bail!(
+                    "Binding to interface '{}' is only supported on Linux",
+                    interface
+                );
+            }
+            if interface.is_empty() || interface.contains(['/', '\0']) {
+                bail!("Invalid interface name '{}'", interface);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bind_config_validation() {
+        assert!(BindConfig::default().validate().is_ok());
+
+        let invalid = BindConfig {
+            interface: Some("eth/0".to_owned()),
+            source_address: None,
+        };
+        assert!(invalid.validate().is_err());
+
+        let interface = BindConfig {
+            interface: Some("wwan0".to_owned()),
+            source_address: None,
+        };
+        assert_eq!(interface.validate().is_ok(), cfg!(target_os = "linux"));
+    }
+}
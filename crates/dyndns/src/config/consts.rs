@@ -5,3 +5,5 @@ pub static ENV_VAR_PREFIX: &str = "DO_DYNDNS_";
 pub static BUILD_INFO: &str = "build_info";
 
 pub static DOMAINS_CONFIG_KEY: &str = "domains";
+
+pub static STATS_SUBCOMMAND: &str = "stats";
@@ -1,7 +1,11 @@
 use clap::ArgMatches;
 
 use super::consts::BUILD_INFO;
+#[cfg(feature = "stats")]
+use super::consts::STATS_SUBCOMMAND;
 use crate::cli::get_cli_args;
+#[cfg(feature = "stats")]
+use crate::cli::StatsSubcommand;
 
 pub struct EarlyConfig {
     clap_matches: ArgMatches,
@@ -17,6 +21,19 @@ impl EarlyConfig {
         self.clap_matches.get_flag(BUILD_INFO)
     }
 
+    #[cfg(feature = "stats")]
+    pub fn stats_subcommand(&self) -> Option<StatsSubcommand> {
+        use clap::FromArgMatches;
+
+        match self.clap_matches.subcommand() {
+            Some((name, matches)) if name == STATS_SUBCOMMAND => Some(
+                StatsSubcommand::from_arg_matches(matches)
+                    .expect("stats subcommand arguments should have been validated by clap"),
+            ),
+            _ => None,
+        }
+    }
+
     pub fn get_clap_matches(&self) -> &ArgMatches {
         &self.clap_matches
     }
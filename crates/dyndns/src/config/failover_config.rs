@@ -0,0 +1,170 @@
+use color_eyre::eyre::{bail, Result, WrapErr};
+use serde::Deserialize;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use super::bind_config::BindConfig;
+
+fn default_healthy_threshold() -> u32 {
+    3
+}
+
+fn default_unhealthy_threshold() -> u32 {
+    2
+}
+
+fn default_health_check_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+/// Points a record at the highest-priority healthy one of several candidate
+/// IPs, e.g. the public IPs of a primary and a backup uplink.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FailoverConfig {
+    /// Candidates ordered by priority, the first one is preferred.
+    pub candidates: Vec<FailoverCandidateConfig>,
+    /// Consecutive successful checks after which an unhealthy candidate is
+    /// considered healthy again.
+    #[serde(default = "default_healthy_threshold")]
+    pub healthy_threshold: u32,
+    /// Consecutive failed checks after which a healthy candidate is
+    /// considered unhealthy.
+    #[serde(default = "default_unhealthy_threshold")]
+    pub unhealthy_threshold: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FailoverCandidateConfig {
+    pub name: String,
+    /// Fixed public IP of the candidate. If not set, the public IP is
+    /// fetched with requests sent via `interface` or from `source_address`.
+    pub ip: Option<IpAddr>,
+    /// Network interface of the uplink, used for the public IP lookup and
+    /// the health check. Only supported on Linux.
+    pub interface: Option<String>,
+    /// Local address of the uplink, used as the source address of the public
+    /// IP lookup and of the health check.
+    pub source_address: Option<IpAddr>,
+    pub health_check: HealthCheckConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HealthCheckConfig {
+    #[serde(flatten)]
+    pub kind: HealthCheckKind,
+    #[serde(default = "default_health_check_timeout", with = "humantime_serde")]
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum HealthCheckKind {
+    /// Healthy if a TCP connection to `address` ("host:port") can be opened.
+    Tcp { address: String },
+    /// Healthy if a GET request to `url` returns a success status.
+    Http { url: String },
+}
+
+impl FailoverConfig {
+    pub fn validate(&self, record_type: &str) -> Result<()> {
+        if self.candidates.is_empty() {
+            bail!("Failover needs at least one candidate");
+        }
+        if self.healthy_threshold == 0 || self.unhealthy_threshold == 0 {
+            bail!("Failover 'healthy_threshold' and 'unhealthy_threshold' must be at least 1");
+        }
+        for candidate in &self.candidates {
+            candidate
+                .validate(record_type)
+                .wrap_err_with(|| format!("Invalid failover candidate '{}'", candidate.name))?;
+        }
+        Ok(())
+    }
+}
+
+impl FailoverCandidateConfig {
+    /// How requests for the candidate are sent.
+    pub fn bind(&self) -> BindConfig {
+        BindConfig {
+            interface: self.interface.clone(),
+            source_address: self.source_address,
+        }
+    }
+
+    fn validate(&self, record_type: &str) -> Result<()> {
+        let bind = self.bind();
+        if self.ip.is_none() && !bind.is_set() {
+            bail!("One of 'ip', 'interface' or 'source_address' needs to be set");
+        }
+        bind.validate()?;
+        let matches_record_type = |ip: &IpAddr| match record_type {
+            "A" => ip.is_ipv4(),
+            "AAAA" => ip.is_ipv6(),
+            _ => true,
+        };
+        if let Some(ip) = self.ip.filter(|ip| !matches_record_type(ip)) {
+            bail!(
+                "'ip' {} doesn't match the record type '{}'",
+                ip,
+                record_type
+            );
+        }
+        if self.ip.is_none()
+            && self
+                .source_address
+                .is_some_and(|source_address| !matches_record_type(&source_address))
+        {
+            bail!(
+                "'source_address' can't be used to fetch the public IP of a '{}' record",
+                record_type
+            );
+        }
+        match &self.health_check.kind {
+            HealthCheckKind::Tcp { address } => {
+                if address.rsplit_once(':').is_none() {
+                    bail!("Health check address '{}' needs to be 'host:port'", address);
+                }
+            }
+            HealthCheckKind::Http { url } => {
+                reqwest::Url::parse(url)
+                    .wrap_err_with(|| format!("Invalid health check url '{url}'"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failover_config() {
+        let config: FailoverConfig = toml::from_str(
+            r#"
+            [[candidates]]
+            name = "fiber"
+            source_address = "192.168.1.2"
+            health_check = { type = "tcp", address = "1.1.1.1:443" }
+
+            [[candidates]]
+            name = "lte"
+            ip = "198.51.100.7"
+            health_check = { type = "http", url = "https://example.com/", timeout = "2s" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.healthy_threshold, 3);
+        assert_eq!(config.unhealthy_threshold, 2);
+        let timeouts = config
+            .candidates
+            .iter()
+            .map(|candidate| candidate.health_check.timeout)
+            .collect::<Vec<_>>();
+        assert_eq!(timeouts, [Duration::from_secs(5), Duration::from_secs(2)]);
+        assert!(config.validate("A").is_ok());
+
+        let error = config.validate("AAAA").unwrap_err();
+        assert!(format!("{error:#}").contains("Invalid failover candidate 'fiber'"));
+    }
+}
@@ -0,0 +1,47 @@
+use color_eyre::eyre::{bail, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::notifier_config::{default_failure_threshold, NotificationKind};
+
+fn default_hook_timeout() -> Duration {
+    Duration::from_secs(30)
+}
+
+/// A local command to run when an updater event happens.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HookConfig {
+    pub event: NotificationKind,
+    /// Path of the executable. It is not run through a shell.
+    pub command: PathBuf,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// The command is killed when it runs for longer.
+    #[serde(default = "default_hook_timeout", with = "humantime_serde")]
+    pub timeout: Duration,
+    /// Extra environment variables, on top of the `DYNDNS_*` ones describing
+    /// the event.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Number of consecutive failed update attempts after which an
+    /// `update_failed` hook runs.
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+}
+
+impl HookConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.command.as_os_str().is_empty() {
+            bail!("Hook 'command' must not be empty");
+        }
+        if self.timeout.is_zero() {
+            bail!("Hook 'timeout' must be greater than zero");
+        }
+        if self.failure_threshold == 0 {
+            bail!("Hook 'failure_threshold' must be at least 1");
+        }
+        Ok(())
+    }
+}
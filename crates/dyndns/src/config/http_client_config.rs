@@ -0,0 +1,119 @@
+use color_eyre::eyre::{bail, Result, WrapErr};
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+fn default_connect_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_request_timeout() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+/// Settings of the HTTP client used for the DNS provider APIs.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HttpClientConfig {
+    pub proxy: Option<HttpProxyConfig>,
+    /// PEM file with CA certificates to trust in addition to the system ones,
+    /// e.g. the one of a TLS intercepting proxy.
+    pub ca_cert_path: Option<PathBuf>,
+    #[serde(default = "default_connect_timeout", with = "humantime_serde")]
+    pub connect_timeout: Duration,
+    /// Timeout of a whole request, from connecting until the response body
+    /// is read.
+    #[serde(default = "default_request_timeout", with = "humantime_serde")]
+    pub request_timeout: Duration,
+    pub user_agent: Option<String>,
+    /// How often a throttled or failed request is retried.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            ca_cert_path: None,
+            connect_timeout: default_connect_timeout(),
+            request_timeout: default_request_timeout(),
+            user_agent: None,
+            max_retries: default_max_retries(),
+        }
+    }
+}
+
+/// Proxy all requests are sent through, instead of the one configured with
+/// the `HTTP_PROXY` and `HTTPS_PROXY` environment variables.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HttpProxyConfig {
+    /// `http://host:port` or `https://host:port`.
+    pub url: String,
+    pub username: Option<String>,
+    /// File containing the proxy password.
+    pub password_file: Option<PathBuf>,
+}
+
+impl HttpClientConfig {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(proxy) = &self.proxy {
+            proxy.validate()?;
+        }
+        if self.connect_timeout.is_zero() || self.request_timeout.is_zero() {
+            bail!("HTTP client 'connect_timeout' and 'request_timeout' must not be zero");
+        }
+        if let Some(user_agent) = &self.user_agent {
+            reqwest::header::HeaderValue::from_str(user_agent)
+                .wrap_err_with(|| format!("Invalid HTTP client 'user_agent' '{user_agent}'"))?;
+        }
+        Ok(())
+    }
+}
+
+impl HttpProxyConfig {
+    fn validate(&self) -> Result<()> {
+        let url = reqwest::Url::parse(&self.url)
+            .wrap_err_with(|| format!("Invalid proxy url '{}'", self.url))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            bail!("Unsupported proxy url scheme '{}'", url.scheme());
+        }
+        if self.password_file.is_some() && self.username.is_none() {
+            bail!("Proxy 'password_file' requires 'username'");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http_client_config() {
+        let config: HttpClientConfig = toml::from_str(
+            r#"
+            request_timeout = "1m"
+            user_agent = "do_ddns"
+            proxy = { url = "http://proxy.internal:3128", username = "ddns" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.connect_timeout, Duration::from_secs(10));
+        assert_eq!(config.request_timeout, Duration::from_secs(60));
+        assert_eq!(config.max_retries, 3);
+        assert!(config.validate().is_ok());
+
+        let config: HttpClientConfig = toml::from_str(
+            r#"
+            proxy = { url = "ftp://proxy.internal", password_file = "/run/secrets/proxy" }
+            "#,
+        )
+        .unwrap();
+        let error = config.validate().unwrap_err();
+        assert!(error.to_string().contains("Unsupported proxy url scheme"));
+    }
+}
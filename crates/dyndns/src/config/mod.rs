@@ -1,5 +1,13 @@
 pub mod app_config;
 pub mod app_config_builder;
+pub mod bind_config;
 pub mod consts;
 pub mod early;
+pub mod failover_config;
+pub mod hook_config;
+pub mod http_client_config;
+pub mod mqtt_config;
+pub mod notifier_config;
 pub mod provider_config;
+pub mod verification_config;
+pub mod web_auth_config;
@@ -0,0 +1,124 @@
+use color_eyre::eyre::{bail, eyre, Result, WrapErr};
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+fn default_client_id() -> String {
+    "do_ddns".to_owned()
+}
+
+fn default_topic_prefix() -> String {
+    "dyndns".to_owned()
+}
+
+fn default_discovery() -> bool {
+    true
+}
+
+fn default_discovery_prefix() -> String {
+    "homeassistant".to_owned()
+}
+
+fn default_keep_alive() -> Duration {
+    Duration::from_secs(30)
+}
+
+/// Publishes the updater state to an MQTT broker as retained topics.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MqttConfig {
+    /// `mqtt://host:port` or `mqtts://host:port` for TLS. The port defaults
+    /// to 1883 and 8883 respectively.
+    pub broker_url: String,
+    #[serde(default = "default_client_id")]
+    pub client_id: String,
+    pub username: Option<String>,
+    /// File containing the broker password.
+    pub password_file: Option<PathBuf>,
+    /// PEM file with the CA certificates to trust for `mqtts`, instead of
+    /// the system ones.
+    pub ca_cert_path: Option<PathBuf>,
+    #[serde(default = "default_topic_prefix")]
+    pub topic_prefix: String,
+    /// Whether to publish Home Assistant MQTT discovery configs.
+    #[serde(default = "default_discovery")]
+    pub discovery: bool,
+    #[serde(default = "default_discovery_prefix")]
+    pub discovery_prefix: String,
+    #[serde(default = "default_keep_alive", with = "humantime_serde")]
+    pub keep_alive: Duration,
+}
+
+/// Broker address parsed from [`MqttConfig::broker_url`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MqttBroker {
+    pub host: String,
+    pub port: u16,
+    pub tls: bool,
+}
+
+impl MqttConfig {
+    pub fn broker(&self) -> Result<MqttBroker> {
+        let url = reqwest::Url::parse(&self.broker_url)
+            .wrap_err_with(|| format!("Invalid MQTT broker url '{}'", self.broker_url))?;
+        let tls = match url.scheme() {
+            "mqtt" | "tcp" => false,
+            "mqtts" | "ssl" => true,
+            scheme => bail!("Unsupported MQTT broker url scheme '{}'", scheme),
+        };
+        let host = url
+            .host_str()
+            .ok_or_else(|| eyre!("MQTT broker url '{}' has no host", self.broker_url))?
+            .to_owned();
+        let port = url.port().unwrap_or(if tls { 8883 } else { 1883 });
+        Ok(MqttBroker { host, port, tls })
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        self.broker()?;
+        if self.client_id.is_empty() {
+            bail!("MQTT 'client_id' must not be empty");
+        }
+        if self.password_file.is_some() && self.username.is_none() {
+            bail!("MQTT 'password_file' requires 'username'");
+        }
+        let invalid_topic =
+            |topic: &str| topic.is_empty() || topic.contains(['#', '+']) || topic.ends_with('/');
+        if invalid_topic(&self.topic_prefix) || invalid_topic(&self.discovery_prefix) {
+            bail!("MQTT topic prefixes must be non-empty, without wildcards or a trailing '/'");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mqtt_broker_url() {
+        let config: MqttConfig = toml::from_str(r#"broker_url = "mqtts://broker.lan""#).unwrap();
+        assert_eq!(
+            config.broker().unwrap(),
+            MqttBroker {
+                host: "broker.lan".to_owned(),
+                port: 8883,
+                tls: true,
+            }
+        );
+        assert!(config.validate().is_ok());
+
+        let config: MqttConfig =
+            toml::from_str(r#"broker_url = "http://broker.lan:1883""#).unwrap();
+        assert!(config.validate().is_err());
+
+        let config: MqttConfig = toml::from_str(
+            r#"
+            broker_url = "mqtt://127.0.0.1:1884"
+            topic_prefix = "home/#"
+            "#,
+        )
+        .unwrap();
+        assert!(config.broker().is_ok_and(|broker| broker.port == 1884));
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,200 @@
+use color_eyre::eyre::{bail, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Events that notifiers can be subscribed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// The public IPv4 or IPv6 address changed.
+    IpChanged,
+    /// A domain record was set to a new value on a provider.
+    RecordUpdated,
+    /// Consecutive update attempts failed.
+    UpdateFailed,
+}
+
+impl NotificationKind {
+    pub fn all() -> Vec<NotificationKind> {
+        vec![
+            NotificationKind::IpChanged,
+            NotificationKind::RecordUpdated,
+            NotificationKind::UpdateFailed,
+        ]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationKind::IpChanged => "ip_changed",
+            NotificationKind::RecordUpdated => "record_updated",
+            NotificationKind::UpdateFailed => "update_failed",
+        }
+    }
+}
+
+pub(crate) fn default_failure_threshold() -> u32 {
+    1
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+fn default_retry_delay() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_webhook_method() -> String {
+    "POST".to_owned()
+}
+
+fn default_email_batch_window() -> Duration {
+    Duration::from_secs(10)
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NotifierConfig {
+    #[serde(default = "NotificationKind::all")]
+    pub events: Vec<NotificationKind>,
+    /// Number of consecutive failed update attempts after which an
+    /// `update_failed` notification is sent.
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    /// How many times to retry sending a notification that failed.
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    #[serde(default = "default_retry_delay", with = "humantime_serde")]
+    pub retry_delay: Duration,
+    #[serde(flatten)]
+    pub kind: NotifierKindConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum NotifierKindConfig {
+    Webhook(WebhookNotifierConfig),
+    Email(EmailNotifierConfig),
+}
+
+impl NotifierKindConfig {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotifierKindConfig::Webhook(_) => "webhook",
+            NotifierKindConfig::Email(_) => "email",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookNotifierConfig {
+    pub url: String,
+    #[serde(default = "default_webhook_method")]
+    pub method: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// JSON body template. `{{ variable }}` placeholders are replaced with
+    /// JSON escaped values. Defaults to a JSON object with all variables.
+    pub body: Option<String>,
+    #[serde(default = "default_timeout", with = "humantime_serde")]
+    pub timeout: Duration,
+}
+
+/// How the connection to the SMTP server is secured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Plain connection, upgraded with the STARTTLS command.
+    #[default]
+    StartTls,
+    /// Implicit TLS from the start of the connection.
+    Tls,
+    /// Unencrypted, only meant for servers on the local network.
+    None,
+}
+
+impl SmtpSecurity {
+    pub fn default_port(&self) -> u16 {
+        match self {
+            SmtpSecurity::StartTls => 587,
+            SmtpSecurity::Tls => 465,
+            SmtpSecurity::None => 25,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EmailNotifierConfig {
+    pub smtp_host: String,
+    /// Defaults to the usual port of the `smtp_security` mode.
+    pub smtp_port: Option<u16>,
+    #[serde(default)]
+    pub smtp_security: SmtpSecurity,
+    pub smtp_username: Option<String>,
+    /// File containing the SMTP password, so it doesn't have to be part of
+    /// the config file.
+    pub smtp_password_file: Option<PathBuf>,
+    pub from: String,
+    pub to: Vec<String>,
+    /// Subject template, rendered with the first notification of a batch.
+    pub subject: Option<String>,
+    /// Body template, rendered for each notification of a batch.
+    pub body: Option<String>,
+    /// How long to wait for more notifications after the first one, to send
+    /// them in a single email.
+    #[serde(default = "default_email_batch_window", with = "humantime_serde")]
+    pub batch_window: Duration,
+    #[serde(default = "default_timeout", with = "humantime_serde")]
+    pub timeout: Duration,
+}
+
+impl NotifierConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.failure_threshold == 0 {
+            bail!("Notifier 'failure_threshold' must be at least 1");
+        }
+        match &self.kind {
+            NotifierKindConfig::Webhook(webhook) => webhook.validate(),
+            NotifierKindConfig::Email(email) => email.validate(),
+        }
+    }
+}
+
+impl WebhookNotifierConfig {
+    pub fn validate(&self) -> Result<()> {
+        reqwest::Url::parse(&self.url)
+            .wrap_err_with(|| format!("Invalid webhook notifier url '{}'", self.url))?;
+        reqwest::Method::from_bytes(self.method.as_bytes())
+            .wrap_err_with(|| format!("Invalid webhook notifier method '{}'", self.method))?;
+        for (name, value) in &self.headers {
+            reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                .wrap_err_with(|| format!("Invalid webhook notifier header name '{name}'"))?;
+            reqwest::header::HeaderValue::from_str(value)
+                .wrap_err_with(|| format!("Invalid value for webhook notifier header '{name}'"))?;
+        }
+        if let Some(body) = &self.body {
+            crate::notifiers::webhook::validate_body_template(body)?;
+        }
+        Ok(())
+    }
+}
+
+impl EmailNotifierConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.smtp_host.is_empty() {
+            bail!("Email notifier 'smtp_host' must not be empty");
+        }
+        if self.to.is_empty() {
+            bail!("Email notifier needs at least one 'to' address");
+        }
+        if self.smtp_password_file.is_some() && self.smtp_username.is_none() {
+            bail!("Email notifier 'smtp_password_file' requires 'smtp_username'");
+        }
+        crate::notifiers::email::validate_email_config(self)
+    }
+}
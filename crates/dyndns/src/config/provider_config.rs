@@ -1,7 +1,9 @@
-use color_eyre::eyre::{bail, Error, Result};
+use color_eyre::eyre::{bail, Error, Result, WrapErr};
 use secrecy::{zeroize::Zeroize, SecretBox};
 use serde::{Deserialize, Serialize};
 
+use super::bind_config::BindConfig;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ProviderType {
@@ -63,6 +65,9 @@ pub fn parse_secret_token(s: &str) -> Result<SecretProviderToken, Error> {
 pub struct ProviderConfig {
     pub provider: ProviderType,
     pub token: SecretProviderToken,
+    /// Interface or source address the provider API requests are sent from.
+    #[serde(default)]
+    pub bind: BindConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -87,6 +92,14 @@ impl ProvidersConfig {
     pub fn validate(&self) -> Result<()> {
         self.validate_not_empty()?;
         self.validate_no_duplicates()?;
+        for provider_config in &self.providers {
+            provider_config.bind.validate().wrap_err_with(|| {
+                format!(
+                    "Invalid 'bind' configuration of provider '{}'",
+                    provider_config.provider.as_str()
+                )
+            })?;
+        }
 
         Ok(())
     }
@@ -0,0 +1,69 @@
+use color_eyre::eyre::{bail, Result};
+use serde::Deserialize;
+use std::time::Duration;
+
+fn default_verification_timeout() -> Duration {
+    Duration::from_secs(5 * 60)
+}
+
+fn default_poll_interval() -> Duration {
+    Duration::from_secs(10)
+}
+
+/// What to do when the new value is not served in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerificationFailureMode {
+    /// Log a warning, the update still counts as successful.
+    #[default]
+    Warn,
+    /// Count the update as failed.
+    Fail,
+}
+
+/// Checks that updated records are served by the authoritative nameservers
+/// of their domain.
+#[derive(Debug, Clone, Deserialize)]
+pub struct VerificationConfig {
+    /// How long to wait for all authoritative nameservers to serve the new
+    /// value.
+    #[serde(default = "default_verification_timeout", with = "humantime_serde")]
+    pub timeout: Duration,
+    /// How long to wait between queries to the nameservers.
+    #[serde(default = "default_poll_interval", with = "humantime_serde")]
+    pub poll_interval: Duration,
+    #[serde(default)]
+    pub on_timeout: VerificationFailureMode,
+}
+
+impl VerificationConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.timeout.is_zero() || self.poll_interval.is_zero() {
+            bail!("Verification 'timeout' and 'poll_interval' must be greater than zero");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verification_config() {
+        let config: VerificationConfig = toml::from_str("").unwrap();
+        assert_eq!(config.timeout, Duration::from_secs(300));
+        assert_eq!(config.on_timeout, VerificationFailureMode::Warn);
+        assert!(config.validate().is_ok());
+
+        let config: VerificationConfig = toml::from_str(
+            r#"
+            poll_interval = "0s"
+            on_timeout = "fail"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.on_timeout, VerificationFailureMode::Fail);
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use color_eyre::eyre::{bail, Result};
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
+use std::collections::HashSet;
+
+/// Access level granted to a web API client.
+///
+/// `write` implies `read`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebAuthScope {
+    #[default]
+    Read,
+    Write,
+}
+
+impl WebAuthScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebAuthScope::Read => "read",
+            WebAuthScope::Write => "write",
+        }
+    }
+
+    pub fn allows(&self, required_scope: WebAuthScope) -> bool {
+        *self >= required_scope
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebAuthToken {
+    pub name: String,
+    pub token: SecretString,
+    #[serde(default)]
+    pub scope: WebAuthScope,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebAuthUser {
+    pub username: String,
+    /// An argon2 PHC string or a bcrypt hash.
+    pub password_hash: String,
+    #[serde(default)]
+    pub scope: WebAuthScope,
+}
+
+/// Credentials accepted by the web server. Authentication is disabled when
+/// neither tokens nor users are configured.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WebAuthConfig {
+    #[serde(default)]
+    pub tokens: Vec<WebAuthToken>,
+    #[serde(default)]
+    pub users: Vec<WebAuthUser>,
+}
+
+impl WebAuthConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.tokens.is_empty() || !self.users.is_empty()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for token in &self.tokens {
+            if token.token.expose_secret().is_empty() {
+                bail!("Web auth token '{}' must not be empty", token.name);
+            }
+        }
+
+        let mut seen_usernames = HashSet::new();
+        for user in &self.users {
+            if !seen_usernames.insert(user.username.as_str()) {
+                bail!("Duplicate web auth user '{}'", user.username);
+            }
+            if PasswordHashKind::detect(&user.password_hash).is_none() {
+                bail!(
+                    "Unsupported password hash for web auth user '{}'. Only argon2 and bcrypt hashes are supported",
+                    user.username
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordHashKind {
+    Argon2,
+    Bcrypt,
+}
+
+impl PasswordHashKind {
+    pub fn detect(password_hash: &str) -> Option<Self> {
+        if password_hash.starts_with("$argon2") {
+            Some(Self::Argon2)
+        } else if ["$2a$", "$2b$", "$2x$", "$2y$"]
+            .iter()
+            .any(|prefix| password_hash.starts_with(prefix))
+        {
+            Some(Self::Bcrypt)
+        } else {
+            None
+        }
+    }
+}
@@ -1,15 +1,22 @@
-use crate::config::app_config::GeneralOptions;
+use crate::config::app_config::{AppConfig, GeneralOptions};
+use crate::config::bind_config::BindConfig;
 use crate::config::provider_config::ProviderType;
-use crate::domain_record_api::digital_ocean_api::DigitalOceanApi;
+use crate::domain_record_api::digital_ocean_api::{DigitalOceanApi, DIGITAL_OCEAN_RATE_LIMIT};
+use crate::domain_record_api::rate_limit::RateLimitedClient;
 use crate::domain_record_api::{create_provider, DomainRecordApi};
 use crate::global_state::GlobalState;
+use crate::http_client::build_http_client;
 use crate::logger::setup_logger;
+use crate::notifiers::Notifiers;
 use crate::signal_handlers::{setup_forceful_term_signal_handling, AppTerminationHandler};
 use crate::updater::Updater;
+use crate::updater_events::UpdaterEvents;
 use color_eyre::eyre::{bail, Result};
 use std::collections::HashSet;
 use tracing::warn;
 
+#[cfg(feature = "mqtt")]
+use crate::mqtt::MqttPublisher;
 #[cfg(feature = "web")]
 use crate::web::server::start_web_server_and_wait;
 
@@ -18,28 +25,57 @@ pub fn start_daemon(global_state: GlobalState) -> Result<()> {
     setup_forceful_term_signal_handling()?;
 
     let dns_providers = create_dns_providers(&global_state.config.general_options)?;
+    let notifiers = Notifiers::from_config(
+        &global_state.config.general_options.notifiers,
+        &global_state.config.general_options.hooks,
+    )?;
+    let config = global_state.config.clone();
 
     let term_handler = AppTerminationHandler::new()?;
     term_handler.setup_exit_panic_hook();
 
     #[cfg(feature = "web")]
-    start_web_server_and_wait(term_handler.clone(), &global_state.config);
+    start_web_server_and_wait(
+        term_handler.clone(),
+        &global_state.config,
+        global_state.shared.clone(),
+    );
 
-    let updater = Updater::new(global_state, dns_providers, term_handler.clone());
+    let updater = Updater::new(global_state, dns_providers, notifiers, term_handler.clone());
+    // Publishes until the daemon exits.
+    let _mqtt_publisher = start_mqtt_publisher(&config, &updater.events())?;
     let updater_thread_handle = updater.start_update_loop_detached();
     term_handler.set_updater_thread(updater_thread_handle);
     term_handler.handle_term_signals_gracefully()?;
     Ok(())
 }
 
-fn create_dns_providers(
-    general_options: &GeneralOptions,
-) -> Result<Vec<Box<dyn DomainRecordApi + Send + 'static>>> {
-    let mut dns_providers: Vec<Box<dyn DomainRecordApi + Send>> = vec![];
+#[cfg(feature = "mqtt")]
+fn start_mqtt_publisher(
+    config: &AppConfig,
+    events: &UpdaterEvents,
+) -> Result<Option<MqttPublisher>> {
+    let Some(mqtt_config) = &config.general_options.mqtt else {
+        return Ok(None);
+    };
+    let records_to_update = Updater::build_records_to_update(config);
+    MqttPublisher::start(mqtt_config, &records_to_update, events).map(Some)
+}
+
+#[cfg(not(feature = "mqtt"))]
+fn start_mqtt_publisher(config: &AppConfig, _events: &UpdaterEvents) -> Result<Option<()>> {
+    if config.general_options.mqtt.is_some() {
+        warn!("MQTT is configured, but MQTT support was not enabled at build time - skipping it.");
+    }
+    Ok(None)
+}
+
+fn create_dns_providers(general_options: &GeneralOptions) -> Result<Vec<Box<dyn DomainRecordApi>>> {
+    let mut dns_providers: Vec<Box<dyn DomainRecordApi>> = vec![];
     let mut added_provider_types: HashSet<ProviderType> = HashSet::new();
     if !general_options.providers_config.providers.is_empty() {
         for provider_config in &general_options.providers_config.providers {
-            let provider = create_provider(provider_config)?;
+            let provider = create_provider(provider_config, &general_options.http_client)?;
             added_provider_types.insert(provider_config.provider);
             dns_providers.push(provider);
         }
@@ -48,7 +84,14 @@ fn create_dns_providers(
         if added_provider_types.contains(&ProviderType::DigitalOcean) {
             warn!("DigitalOcean provider already configured via [[providers]] - skipping digital_ocean_token field.");
         } else {
-            let provider = Box::new(DigitalOceanApi::new(token));
+            let request_client =
+                build_http_client(&general_options.http_client, &BindConfig::default())?;
+            let request_client = RateLimitedClient::new(
+                request_client,
+                DIGITAL_OCEAN_RATE_LIMIT,
+                general_options.http_client.max_retries,
+            );
+            let provider = Box::new(DigitalOceanApi::new(token, request_client));
             dns_providers.push(provider);
         }
     }
@@ -1,8 +1,19 @@
+use crate::db::history::{IpFetchHistoryQuery, SortOrder};
 use crate::db::types::*;
 use color_eyre::eyre::Result;
 use diesel::prelude::*;
 use diesel::sqlite::SqliteConnection;
 use diesel::OptionalExtension;
+#[cfg(feature = "web")]
+use schemars::JsonSchema;
+use serde::Serialize;
+
+#[cfg_attr(feature = "web", derive(JsonSchema))]
+#[derive(Serialize)]
+pub struct DomainIpFetches {
+    pub fetches: Vec<DomainIpFetch>,
+    pub next_cursor: Option<PrimaryKey>,
+}
 
 pub fn get_domain_ip_fetch_by_id(
     conn: &mut SqliteConnection,
@@ -22,6 +33,53 @@ pub fn get_domain_ip_fetches(conn: &mut SqliteConnection) -> Result<Vec<DomainIp
     Ok(results)
 }
 
+/// Returns a single page of IP fetches, matching the query filters.
+pub fn get_domain_ip_fetches_page(
+    conn: &mut SqliteConnection,
+    query: &IpFetchHistoryQuery,
+) -> Result<DomainIpFetches> {
+    use super::super::schema::domain_ip_fetches::dsl::*;
+    let page = query.page();
+    let mut db_query = domain_ip_fetches.into_boxed();
+    if let Some(since) = page.since {
+        db_query = db_query.filter(attempt_date.ge(since));
+    }
+    if let Some(until) = page.until {
+        db_query = db_query.filter(attempt_date.lt(until));
+    }
+    db_query = match (page.order, page.cursor) {
+        (SortOrder::Asc, Some(cursor)) => db_query.filter(id.gt(cursor)),
+        (SortOrder::Desc, Some(cursor)) => db_query.filter(id.lt(cursor)),
+        (_, None) => db_query,
+    };
+    db_query = match page.order {
+        SortOrder::Asc => db_query.order(id.asc()),
+        SortOrder::Desc => db_query.order(id.desc()),
+    };
+    let fetches = db_query
+        .limit(page.query_limit())
+        .load::<DomainIpFetch>(conn)?;
+
+    let (fetches, next_cursor) = page.finish(fetches, |fetch| fetch.id);
+    Ok(DomainIpFetches {
+        fetches,
+        next_cursor,
+    })
+}
+
+/// Returns the total and the successful number of IP fetches.
+pub fn get_domain_ip_fetch_counts(
+    conn: &mut SqliteConnection,
+) -> Result<(DBIntegerType, DBIntegerType)> {
+    use super::super::schema::domain_ip_fetches::dsl::*;
+    let total = domain_ip_fetches.count().get_result(conn)?;
+    let successful = domain_ip_fetches
+        .filter(success.eq(true))
+        .count()
+        .get_result(conn)?;
+    Ok((total, successful))
+}
+
 define_sql_function! {
     /// Represents the SQL `last_insert_row()` function
     fn last_insert_rowid() -> diesel::sql_types::BigInt;
@@ -0,0 +1,54 @@
+use crate::db::types::*;
+use chrono::NaiveDateTime;
+use color_eyre::eyre::Result;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+
+use diesel::sql_types::{BigInt, Text, Timestamp};
+use serde::Serialize;
+
+#[derive(QueryableByName, Debug, Serialize)]
+pub struct DomainRecordAttemptCount {
+    #[diesel(sql_type = Text)]
+    pub name: String,
+    #[diesel(sql_type = Text)]
+    pub record_type: String,
+    #[diesel(sql_type = BigInt)]
+    pub attempt_count: DBIntegerType,
+    #[diesel(sql_type = BigInt)]
+    pub success_count: DBIntegerType,
+    #[diesel(sql_type = Timestamp)]
+    pub last_attempt_date: NaiveDateTime,
+}
+
+impl DomainRecordAttemptCount {
+    /// Attempts that didn't result in a successful update, including the ones
+    /// where no public IP was available.
+    pub fn fail_count(&self) -> DBIntegerType {
+        self.attempt_count - self.success_count
+    }
+}
+
+pub fn get_domain_record_attempt_counts(
+    conn: &mut SqliteConnection,
+) -> Result<Vec<DomainRecordAttemptCount>> {
+    // Computed from the fact tables, so that the result doesn't depend on the
+    // state of the domain_record_last_updates summary table.
+    let counts = diesel::sql_query(
+        "
+        SELECT r.name AS name,
+               r.record_type AS record_type,
+               COUNT(a.id) AS attempt_count,
+               COUNT(CASE WHEN u.success THEN 1 END) AS success_count,
+               MAX(a.attempt_date) AS last_attempt_date
+        FROM updater_attempts a
+        INNER JOIN domain_records r
+        ON a.domain_record_id=r.id
+        LEFT JOIN domain_record_updates u
+        ON a.domain_record_updates_id=u.id
+        GROUP BY r.id
+        ORDER BY r.name ASC, r.record_type ASC",
+    )
+    .load::<DomainRecordAttemptCount>(conn)?;
+    Ok(counts)
+}
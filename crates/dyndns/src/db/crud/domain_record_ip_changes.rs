@@ -1,13 +1,16 @@
+use crate::db::history::RecordHistoryQuery;
 use crate::db::types::*;
 use color_eyre::eyre::Result;
 use diesel::prelude::*;
-use diesel::sqlite::SqliteConnection;
+use diesel::sqlite::{Sqlite, SqliteConnection};
 
-use diesel::sql_types::Text;
+use diesel::sql_types::{BigInt, Text, Timestamp};
+#[cfg(feature = "web")]
 use schemars::JsonSchema;
 use serde::Serialize;
 
-#[derive(Queryable, Debug, Serialize, QueryableByName, JsonSchema)]
+#[cfg_attr(feature = "web", derive(JsonSchema))]
+#[derive(Queryable, Debug, Serialize, QueryableByName)]
 pub struct DomainRecordIpChange {
     #[diesel(embed)]
     #[serde(flatten)]
@@ -16,18 +19,20 @@ pub struct DomainRecordIpChange {
     pub name: String,
 }
 
-#[derive(Serialize, JsonSchema)]
+#[cfg_attr(feature = "web", derive(JsonSchema))]
+#[derive(Serialize)]
 pub struct DomainRecordIpChanges {
     pub changes: Vec<DomainRecordIpChange>,
+    pub next_cursor: Option<PrimaryKey>,
 }
 
-pub fn get_domain_record_ip_changes(conn: &mut SqliteConnection) -> Result<DomainRecordIpChanges> {
-    // For each domain row, get previous ip and only return results
-    // where the ip has changed.
-    let changes = diesel::sql_query(
-        "
+// For each domain row, get previous ip and only return results
+// where the ip has changed. The previous ip has to be looked up in the whole
+// history, so further filters can only be appended to the outer query.
+const DOMAIN_RECORD_IP_CHANGES_QUERY: &str = "
         SELECT t.* FROM
         (SELECT u.*, r.*,
+         u.id as update_id,
          lag(u.set_ip) over (
              partition by domain_record_id
              order by u.attempt_date ASC
@@ -36,11 +41,64 @@ pub fn get_domain_record_ip_changes(conn: &mut SqliteConnection) -> Result<Domai
          INNER JOIN domain_records r
          ON u.domain_record_id=r.id
          WHERE u.success = true
-         ORDER BY u.attempt_date DESC
         ) t
-        WHERE prev_set_ip IS NULL OR prev_set_ip != set_ip",
-    )
+        WHERE (prev_set_ip IS NULL OR prev_set_ip != set_ip)";
+
+pub fn get_domain_record_ip_changes(conn: &mut SqliteConnection) -> Result<DomainRecordIpChanges> {
+    let changes = diesel::sql_query(format!(
+        "{DOMAIN_RECORD_IP_CHANGES_QUERY} ORDER BY attempt_date DESC"
+    ))
     .load::<DomainRecordIpChange>(conn)?;
-    let results = DomainRecordIpChanges { changes };
+    let results = DomainRecordIpChanges {
+        changes,
+        next_cursor: None,
+    };
     Ok(results)
 }
+
+/// Returns a single page of domain record ip changes, matching the query filters.
+pub fn get_domain_record_ip_changes_page(
+    conn: &mut SqliteConnection,
+    query: &RecordHistoryQuery,
+) -> Result<DomainRecordIpChanges> {
+    let page = query.page();
+    let mut sql_query = diesel::sql_query(DOMAIN_RECORD_IP_CHANGES_QUERY).into_boxed::<Sqlite>();
+    if let Some(record) = &query.record {
+        sql_query = sql_query
+            .sql(" AND name = ?")
+            .bind::<Text, _>(record.clone());
+    }
+    if let Some(record_type) = &query.record_type {
+        sql_query = sql_query
+            .sql(" AND record_type = ?")
+            .bind::<Text, _>(record_type.clone());
+    }
+    if let Some(since) = page.since {
+        sql_query = sql_query
+            .sql(" AND attempt_date >= ?")
+            .bind::<Timestamp, _>(since);
+    }
+    if let Some(until) = page.until {
+        sql_query = sql_query
+            .sql(" AND attempt_date < ?")
+            .bind::<Timestamp, _>(until);
+    }
+    if let Some(cursor) = page.cursor {
+        sql_query = sql_query
+            .sql(format!(" AND update_id {} ?", page.order.cursor_operator()))
+            .bind::<BigInt, _>(cursor);
+    }
+    let changes = sql_query
+        .sql(format!(
+            " ORDER BY update_id {} LIMIT ?",
+            page.order.as_sql()
+        ))
+        .bind::<BigInt, _>(page.query_limit())
+        .load::<DomainRecordIpChange>(conn)?;
+
+    let (changes, next_cursor) = page.finish(changes, |change| change.domain_record_update.id);
+    Ok(DomainRecordIpChanges {
+        changes,
+        next_cursor,
+    })
+}
@@ -1,8 +1,10 @@
 pub mod domain_ip_fetches;
 pub mod domain_ip_last_fetches;
-#[cfg(feature = "web")]
+pub mod domain_record_attempt_counts;
 pub mod domain_record_ip_changes;
 pub mod domain_record_last_updates;
 pub mod domain_record_updates;
 pub mod domain_records;
+pub mod provider_updates;
+pub mod providers;
 pub mod updater_attempts;
@@ -0,0 +1,21 @@
+use crate::db::types::*;
+use color_eyre::eyre::Result;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+
+pub fn get_provider_updates(conn: &mut SqliteConnection) -> Result<Vec<ProviderUpdate>> {
+    use super::super::schema::provider_updates::dsl::*;
+    let results = provider_updates.load::<ProviderUpdate>(conn)?;
+    Ok(results)
+}
+
+pub fn create_provider_update(
+    conn: &mut SqliteConnection,
+    new_value: &NewProviderUpdate,
+) -> Result<()> {
+    use super::super::schema::provider_updates;
+    diesel::insert_into(provider_updates::table)
+        .values(new_value)
+        .execute(conn)?;
+    Ok(())
+}
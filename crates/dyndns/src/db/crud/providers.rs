@@ -0,0 +1,33 @@
+use crate::db::types::*;
+use color_eyre::eyre::Result;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use diesel::OptionalExtension;
+
+pub fn get_provider(conn: &mut SqliteConnection, provider_name: &str) -> Result<Option<Provider>> {
+    use super::super::schema::providers::dsl::*;
+    let maybe_provider = providers
+        .filter(name.eq(provider_name))
+        .first(conn)
+        .optional()?;
+    Ok(maybe_provider)
+}
+
+pub fn get_providers(conn: &mut SqliteConnection) -> Result<Vec<Provider>> {
+    use super::super::schema::providers::dsl::*;
+    let results = providers.load::<Provider>(conn)?;
+    Ok(results)
+}
+
+pub fn create_provider(
+    conn: &mut SqliteConnection,
+    new_provider: &NewProvider<'_>,
+) -> Result<Option<Provider>> {
+    use super::super::schema::providers;
+    if get_provider(conn, new_provider.name)?.is_none() {
+        diesel::insert_into(providers::table)
+            .values(new_provider)
+            .execute(conn)?;
+    }
+    get_provider(conn, new_provider.name)
+}
@@ -1,8 +1,28 @@
+use crate::db::history::{RecordHistoryQuery, SortOrder};
 use crate::db::types::*;
 use color_eyre::eyre::Result;
 use diesel::prelude::*;
 use diesel::sqlite::SqliteConnection;
 use diesel::OptionalExtension;
+#[cfg(feature = "web")]
+use schemars::JsonSchema;
+use serde::Serialize;
+
+#[cfg_attr(feature = "web", derive(JsonSchema))]
+#[derive(Debug, Serialize)]
+pub struct UpdaterAttemptEntry {
+    #[serde(flatten)]
+    pub attempt: UpdaterAttempt,
+    pub name: String,
+    pub record_type: String,
+}
+
+#[cfg_attr(feature = "web", derive(JsonSchema))]
+#[derive(Serialize)]
+pub struct UpdaterAttemptEntries {
+    pub attempts: Vec<UpdaterAttemptEntry>,
+    pub next_cursor: Option<PrimaryKey>,
+}
 
 pub fn get_updater_attempts_by_id(
     conn: &mut SqliteConnection,
@@ -22,6 +42,61 @@ pub fn get_updater_attempts(conn: &mut SqliteConnection) -> Result<Vec<UpdaterAt
     Ok(results)
 }
 
+/// Returns a single page of updater attempts together with the name and type
+/// of their domain record, matching the query filters.
+pub fn get_updater_attempts_page(
+    conn: &mut SqliteConnection,
+    query: &RecordHistoryQuery,
+) -> Result<UpdaterAttemptEntries> {
+    use super::super::schema::{domain_records, updater_attempts};
+    let page = query.page();
+    let mut db_query = updater_attempts::table
+        .inner_join(domain_records::table)
+        .select((
+            updater_attempts::all_columns,
+            domain_records::name,
+            domain_records::record_type,
+        ))
+        .into_boxed();
+    if let Some(record) = &query.record {
+        db_query = db_query.filter(domain_records::name.eq(record.clone()));
+    }
+    if let Some(record_type) = &query.record_type {
+        db_query = db_query.filter(domain_records::record_type.eq(record_type.clone()));
+    }
+    if let Some(since) = page.since {
+        db_query = db_query.filter(updater_attempts::attempt_date.ge(since));
+    }
+    if let Some(until) = page.until {
+        db_query = db_query.filter(updater_attempts::attempt_date.lt(until));
+    }
+    db_query = match (page.order, page.cursor) {
+        (SortOrder::Asc, Some(cursor)) => db_query.filter(updater_attempts::id.gt(cursor)),
+        (SortOrder::Desc, Some(cursor)) => db_query.filter(updater_attempts::id.lt(cursor)),
+        (_, None) => db_query,
+    };
+    db_query = match page.order {
+        SortOrder::Asc => db_query.order(updater_attempts::id.asc()),
+        SortOrder::Desc => db_query.order(updater_attempts::id.desc()),
+    };
+    let attempts = db_query
+        .limit(page.query_limit())
+        .load::<(UpdaterAttempt, String, String)>(conn)?
+        .into_iter()
+        .map(|(attempt, name, record_type)| UpdaterAttemptEntry {
+            attempt,
+            name,
+            record_type,
+        })
+        .collect::<Vec<_>>();
+
+    let (attempts, next_cursor) = page.finish(attempts, |entry| entry.attempt.id);
+    Ok(UpdaterAttemptEntries {
+        attempts,
+        next_cursor,
+    })
+}
+
 define_sql_function! {
     /// Represents the SQL `last_insert_row()` function
     fn last_insert_rowid() -> diesel::sql_types::BigInt;
@@ -0,0 +1,243 @@
+use chrono::NaiveDateTime;
+#[cfg(feature = "web")]
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::db::types::PrimaryKey;
+
+pub const DEFAULT_PAGE_LIMIT: u32 = 100;
+pub const MAX_PAGE_LIMIT: u32 = 1000;
+
+#[cfg_attr(feature = "web", derive(JsonSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortOrder {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+
+    /// Operator selecting the rows after the cursor, in this order.
+    pub fn cursor_operator(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => ">",
+            SortOrder::Desc => "<",
+        }
+    }
+}
+
+/// Filters and pagination for history of individual domain records.
+///
+/// Rows are ordered by their id, which follows the order in which they were
+/// recorded. Pass the `next_cursor` of a page as `cursor` to get the next one.
+#[cfg_attr(feature = "web", derive(JsonSchema))]
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RecordHistoryQuery {
+    pub record: Option<String>,
+    pub record_type: Option<String>,
+    pub since: Option<NaiveDateTime>,
+    pub until: Option<NaiveDateTime>,
+    pub limit: Option<u32>,
+    pub cursor: Option<PrimaryKey>,
+    pub order: Option<SortOrder>,
+}
+
+/// Filters and pagination for public IP fetch history.
+#[cfg_attr(feature = "web", derive(JsonSchema))]
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct IpFetchHistoryQuery {
+    pub since: Option<NaiveDateTime>,
+    pub until: Option<NaiveDateTime>,
+    pub limit: Option<u32>,
+    pub cursor: Option<PrimaryKey>,
+    pub order: Option<SortOrder>,
+}
+
+/// Pagination values shared by all history queries, with defaults applied.
+#[derive(Debug, Clone, Copy)]
+pub struct Page {
+    pub since: Option<NaiveDateTime>,
+    pub until: Option<NaiveDateTime>,
+    pub limit: u32,
+    pub cursor: Option<PrimaryKey>,
+    pub order: SortOrder,
+}
+
+impl Page {
+    fn new(
+        since: Option<NaiveDateTime>,
+        until: Option<NaiveDateTime>,
+        limit: Option<u32>,
+        cursor: Option<PrimaryKey>,
+        order: Option<SortOrder>,
+    ) -> Self {
+        Self {
+            since,
+            until,
+            limit: limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT),
+            cursor,
+            order: order.unwrap_or_default(),
+        }
+    }
+
+    /// Number of rows to query. One more than the page size, to know
+    /// whether there is a next page.
+    pub fn query_limit(&self) -> i64 {
+        i64::from(self.limit) + 1
+    }
+
+    /// Truncates the queried rows to the page size, and returns the cursor of
+    /// the next page, if there is one.
+    pub fn finish<T>(
+        &self,
+        mut rows: Vec<T>,
+        id_of: impl Fn(&T) -> PrimaryKey,
+    ) -> (Vec<T>, Option<PrimaryKey>) {
+        let limit = usize::try_from(self.limit).unwrap_or(usize::MAX);
+        if rows.len() <= limit {
+            return (rows, None);
+        }
+        rows.truncate(limit);
+        let next_cursor = rows.last().map(id_of);
+        (rows, next_cursor)
+    }
+}
+
+impl RecordHistoryQuery {
+    pub fn page(&self) -> Page {
+        Page::new(self.since, self.until, self.limit, self.cursor, self.order)
+    }
+}
+
+impl IpFetchHistoryQuery {
+    pub fn page(&self) -> Page {
+        Page::new(self.since, self.until, self.limit, self.cursor, self.order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_defaults_and_limits() {
+        let page = RecordHistoryQuery::default().page();
+        assert_eq!(page.limit, DEFAULT_PAGE_LIMIT);
+        assert_eq!(page.order, SortOrder::Desc);
+
+        let page = IpFetchHistoryQuery {
+            limit: Some(MAX_PAGE_LIMIT + 1),
+            ..Default::default()
+        }
+        .page();
+        assert_eq!(page.limit, MAX_PAGE_LIMIT);
+        assert_eq!(page.query_limit(), i64::from(MAX_PAGE_LIMIT) + 1);
+    }
+
+    #[test]
+    fn test_page_finish() {
+        let page = RecordHistoryQuery {
+            limit: Some(2),
+            ..Default::default()
+        }
+        .page();
+        assert_eq!(page.finish(vec![5, 4], |id| *id), (vec![5, 4], None));
+        assert_eq!(page.finish(vec![5, 4, 3], |id| *id), (vec![5, 4], Some(4)));
+    }
+
+    #[test]
+    fn test_history_pages() -> color_eyre::eyre::Result<()> {
+        use crate::db::crud::domain_ip_fetches::get_domain_ip_fetches_page;
+        use crate::db::crud::domain_record_ip_changes::get_domain_record_ip_changes_page;
+        use crate::db::crud::updater_attempts::get_updater_attempts_page;
+        use crate::db::logic::{handle_ip_fetch, handle_updater_attempt};
+        use crate::db::setup::{establish_connection, run_migrations};
+        use crate::types::{IpAddrKind, IpAddrV4AndV6};
+
+        let conn = &mut establish_connection(":memory:")?;
+        run_migrations(conn)?;
+        for ip in ["127.0.1.2", "127.0.1.2", "127.0.1.3"] {
+            let fetched_ips = Some(IpAddrV4AndV6 {
+                ipv4: Some(ip.parse().expect("valid ip")),
+                ipv6: None,
+            });
+            let (domain_ip_fetch, attempt_date) = handle_ip_fetch(conn, fetched_ips)?;
+            let domain_ip_fetch = domain_ip_fetch.expect("ip fetch should be created");
+            for name in ["home.site.com", "www.site.com"] {
+                handle_updater_attempt(
+                    conn,
+                    name,
+                    "A",
+                    &domain_ip_fetch,
+                    attempt_date,
+                    true,
+                    Some(IpAddrKind::V4),
+                )?;
+            }
+        }
+
+        // Each record changed its ip twice, the most recent change comes first.
+        let query = RecordHistoryQuery {
+            record: Some("home.site.com".to_owned()),
+            limit: Some(1),
+            ..Default::default()
+        };
+        let first_page = get_domain_record_ip_changes_page(conn, &query)?;
+        assert_eq!(first_page.changes.len(), 1);
+        assert!(first_page
+            .changes
+            .iter()
+            .all(|change| change.name == "home.site.com"
+                && change.domain_record_update.set_ip == "127.0.1.3"));
+        assert!(first_page.next_cursor.is_some());
+
+        let second_page = get_domain_record_ip_changes_page(
+            conn,
+            &RecordHistoryQuery {
+                cursor: first_page.next_cursor,
+                ..query
+            },
+        )?;
+        assert_eq!(second_page.changes.len(), 1);
+        assert!(second_page
+            .changes
+            .iter()
+            .all(|change| change.domain_record_update.set_ip == "127.0.1.2"));
+        assert_eq!(second_page.next_cursor, None);
+
+        let attempts = get_updater_attempts_page(
+            conn,
+            &RecordHistoryQuery {
+                record: Some("www.site.com".to_owned()),
+                order: Some(SortOrder::Asc),
+                ..Default::default()
+            },
+        )?
+        .attempts;
+        assert_eq!(attempts.len(), 3);
+        assert!(attempts
+            .iter()
+            .zip(attempts.iter().skip(1))
+            .all(|(previous, next)| previous.attempt.id < next.attempt.id
+                && next.name == "www.site.com"));
+
+        let fetches = get_domain_ip_fetches_page(
+            conn,
+            &IpFetchHistoryQuery {
+                limit: Some(2),
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(fetches.fetches.len(), 2);
+        assert!(fetches.next_cursor.is_some());
+        Ok(())
+    }
+}
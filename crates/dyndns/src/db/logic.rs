@@ -1,11 +1,14 @@
 use crate::db::crud::domain_records::*;
 use crate::db::types::*;
-use crate::types::{IpAddrKind, IpAddrV4AndV6};
+use crate::types::{IpAddrKind, IpAddrV4AndV6, ProviderUpdateOutcome};
 use chrono::NaiveDateTime;
-use color_eyre::eyre::{eyre, Error, Result};
+use color_eyre::eyre::{eyre, Error, Result, WrapErr};
 use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Nullable, Timestamp};
 use diesel::sqlite::SqliteConnection;
-use tracing::trace;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use tracing::{info, trace, warn};
 
 pub fn handle_ip_fetch(
     conn: &mut SqliteConnection,
@@ -48,6 +51,81 @@ pub fn handle_updater_attempt(
     is_domain_record_update_successful: bool,
     ip_kind: Option<IpAddrKind>,
 ) -> Result<()> {
+    let domain_record = get_or_create_domain_record(conn, domain_record_name, record_type)?;
+    create_updater_attempt(
+        conn,
+        &domain_record,
+        domain_ip_fetch,
+        attempt_date,
+        is_domain_record_update_successful,
+        ip_kind,
+    )?;
+    Ok(())
+}
+
+/// Records one updater attempt for the domain record, along with the outcome
+/// of the update on each provider it was sent to.
+#[allow(clippy::too_many_arguments)]
+pub fn handle_updater_attempt_for_providers(
+    conn: &mut SqliteConnection,
+    domain_record_name: &str,
+    record_type: &str,
+    domain_ip_fetch: &DomainIpFetch,
+    attempt_date: NaiveDateTime,
+    is_domain_record_update_successful: bool,
+    ip_kind: Option<IpAddrKind>,
+    provider_outcomes: &[ProviderUpdateOutcome],
+) -> Result<()> {
+    use super::crud::provider_updates::create_provider_update;
+    use super::crud::providers::create_provider;
+
+    conn.transaction::<_, Error, _>(|conn| {
+        let domain_record = get_or_create_domain_record(conn, domain_record_name, record_type)?;
+        let updater_attempt = create_updater_attempt(
+            conn,
+            &domain_record,
+            domain_ip_fetch,
+            attempt_date,
+            is_domain_record_update_successful,
+            ip_kind,
+        )?;
+        for outcome in provider_outcomes {
+            let provider = create_provider(
+                conn,
+                &NewProvider {
+                    name: outcome.provider_name,
+                },
+            )?
+            .ok_or_else(|| eyre!("No provider could be found for {}", outcome.provider_name))?;
+
+            create_provider_update(
+                conn,
+                &NewProviderUpdate {
+                    updater_attempt_id: updater_attempt.id,
+                    provider_id: provider.id,
+                    success: outcome.success,
+                    error_message: outcome.error_message.clone(),
+                    http_status: outcome.http_status.map(DBIntegerType::from),
+                    latency_ms: Some(
+                        DBIntegerType::try_from(outcome.latency.as_millis())
+                            .unwrap_or(DBIntegerType::MAX),
+                    ),
+                    propagation_ms: outcome.propagation.map(|propagation| {
+                        DBIntegerType::try_from(propagation.as_millis())
+                            .unwrap_or(DBIntegerType::MAX)
+                    }),
+                },
+            )?;
+        }
+        Ok(())
+    })
+}
+
+fn get_or_create_domain_record(
+    conn: &mut SqliteConnection,
+    domain_record_name: &str,
+    record_type: &str,
+) -> Result<DomainRecord> {
     let new_domain_record = NewDomainRecord {
         name: domain_record_name,
         record_type,
@@ -57,22 +135,13 @@ pub fn handle_updater_attempt(
         "Creating / updating domain record in the DB {:?}",
         new_domain_record
     );
-    let domain_record = create_domain_record(conn, &new_domain_record)?.ok_or_else(|| {
+    create_domain_record(conn, &new_domain_record)?.ok_or_else(|| {
         eyre!(
             "No domain record could be found for {}:{}",
             domain_record_name,
             record_type
         )
-    })?;
-    create_updater_attempt(
-        conn,
-        &domain_record,
-        domain_ip_fetch,
-        attempt_date,
-        is_domain_record_update_successful,
-        ip_kind,
-    )?;
-    Ok(())
+    })
 }
 
 fn create_domain_ip_last_fetch(
@@ -246,7 +315,7 @@ pub fn create_updater_attempt(
     attempt_date: NaiveDateTime,
     is_domain_record_update_successful: bool,
     ip_kind: Option<IpAddrKind>,
-) -> Result<()> {
+) -> Result<UpdaterAttempt> {
     use super::crud::domain_record_updates::create_domain_record_update;
     use super::crud::updater_attempts::create_updater_attempt;
 
@@ -296,14 +365,210 @@ pub fn create_updater_attempt(
                 domain_record_updates_id: domain_record_update,
                 attempt_date,
             },
-        )?;
+        )?
+        .ok_or_else(|| eyre!("No updater attempt could be found after inserting it"))?;
 
         trace!("Inserted updater attempt into DB {:?}", updater_attempt);
 
+        Ok(updater_attempt)
+    })
+}
+
+/// Regenerates the `domain_ip_last_fetches` and `domain_record_last_updates`
+/// summary tables by replaying every row of the fact tables in chronological
+/// order.
+pub fn rebuild_summary_tables(conn: &mut SqliteConnection) -> Result<()> {
+    use super::schema::domain_ip_fetches::dsl as fetches;
+    use super::schema::domain_ip_last_fetches::dsl::domain_ip_last_fetches;
+    use super::schema::domain_record_last_updates::dsl::domain_record_last_updates;
+    use super::schema::domain_record_updates::dsl as updates;
+
+    info!("Rebuilding statistics summary tables from the fact tables");
+
+    conn.transaction::<_, Error, _>(|conn| {
+        diesel::delete(domain_ip_last_fetches).execute(conn)?;
+        diesel::delete(domain_record_last_updates).execute(conn)?;
+
+        let domain_ip_fetches = fetches::domain_ip_fetches
+            .order((fetches::attempt_date.asc(), fetches::id.asc()))
+            .load::<DomainIpFetch>(conn)?;
+        for domain_ip_fetch in domain_ip_fetches {
+            let maybe_fetched_ips = fetched_ips_from_domain_ip_fetch(&domain_ip_fetch)?;
+            create_domain_ip_last_fetch(conn, domain_ip_fetch.attempt_date, maybe_fetched_ips)?;
+        }
+
+        let domain_record_updates = updates::domain_record_updates
+            .order((updates::attempt_date.asc(), updates::id.asc()))
+            .load::<DomainRecordUpdate>(conn)?;
+        let mut domain_records: HashMap<ForeignKey, DomainRecord> = HashMap::new();
+        for domain_record_update in domain_record_updates {
+            let record_id = domain_record_update.domain_record_id;
+            let domain_record = match domain_records.entry(record_id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    entry.insert(get_domain_record_by_id(conn, record_id)?.ok_or_else(|| {
+                        eyre!("No domain record could be found with id {}", record_id)
+                    })?)
+                }
+            };
+            create_domain_record_last_update(
+                conn,
+                domain_record_update.attempt_date,
+                domain_record,
+                domain_record_update.set_ip,
+                domain_record_update.success,
+            )?;
+        }
         Ok(())
     })
 }
 
+fn fetched_ips_from_domain_ip_fetch(
+    domain_ip_fetch: &DomainIpFetch,
+) -> Result<Option<IpAddrV4AndV6>> {
+    if !domain_ip_fetch.success {
+        return Ok(None);
+    }
+    let ipv4 = domain_ip_fetch
+        .fetched_ipv4
+        .as_deref()
+        .map(str::parse::<std::net::Ipv4Addr>)
+        .transpose()
+        .wrap_err("Invalid ipv4 address stored in domain_ip_fetches")?;
+    let ipv6 = domain_ip_fetch
+        .fetched_ipv6
+        .as_deref()
+        .map(str::parse::<std::net::Ipv6Addr>)
+        .transpose()
+        .wrap_err("Invalid ipv6 address stored in domain_ip_fetches")?;
+    Ok(Some(IpAddrV4AndV6 { ipv4, ipv6 }))
+}
+
+#[derive(QueryableByName)]
+struct DomainIpFetchCounts {
+    #[diesel(sql_type = BigInt)]
+    attempt_count: DBIntegerType,
+    #[diesel(sql_type = BigInt)]
+    success_count: DBIntegerType,
+    #[diesel(sql_type = Nullable<Timestamp>)]
+    last_attempt_date: Option<NaiveDateTime>,
+}
+
+#[derive(QueryableByName)]
+struct DomainRecordUpdateCounts {
+    #[diesel(sql_type = BigInt)]
+    domain_record_id: ForeignKey,
+    #[diesel(sql_type = BigInt)]
+    attempt_count: DBIntegerType,
+    #[diesel(sql_type = BigInt)]
+    success_count: DBIntegerType,
+    #[diesel(sql_type = Timestamp)]
+    last_attempt_date: NaiveDateTime,
+}
+
+/// Compares the summary tables against aggregates computed from the fact
+/// tables, and returns a description of each mismatch that was found.
+pub fn find_summary_table_mismatches(conn: &mut SqliteConnection) -> Result<Vec<String>> {
+    use super::crud::domain_ip_last_fetches::get_domain_ip_last_fetches;
+    use super::crud::domain_record_last_updates::get_domain_record_last_updates;
+
+    let mut mismatches = vec![];
+
+    let fetch_counts = diesel::sql_query(
+        "
+        SELECT COUNT(id) AS attempt_count,
+               COUNT(CASE WHEN success THEN 1 END) AS success_count,
+               MAX(attempt_date) AS last_attempt_date
+        FROM domain_ip_fetches",
+    )
+    .get_result::<DomainIpFetchCounts>(conn)?;
+    let last_fetches = get_domain_ip_last_fetches(conn)?;
+    match (last_fetches.as_slice(), fetch_counts.last_attempt_date) {
+        ([], None) => {}
+        ([last_fetch], Some(last_attempt_date)) => {
+            if last_fetch.attempt_count != fetch_counts.attempt_count
+                || last_fetch.success_count != fetch_counts.success_count
+                || last_fetch.fail_count != fetch_counts.attempt_count - fetch_counts.success_count
+                || last_fetch.last_attempt_date != last_attempt_date
+            {
+                mismatches.push(format!(
+                    "domain_ip_last_fetches counts {}/{}/{} don't match the {} recorded IP fetches",
+                    last_fetch.attempt_count,
+                    last_fetch.success_count,
+                    last_fetch.fail_count,
+                    fetch_counts.attempt_count
+                ));
+            }
+        }
+        (last_fetches, _) => mismatches.push(format!(
+            "domain_ip_last_fetches has {} row(s) for {} recorded IP fetches",
+            last_fetches.len(),
+            fetch_counts.attempt_count
+        )),
+    }
+
+    let update_counts = diesel::sql_query(
+        "
+        SELECT domain_record_id,
+               COUNT(id) AS attempt_count,
+               COUNT(CASE WHEN success THEN 1 END) AS success_count,
+               MAX(attempt_date) AS last_attempt_date
+        FROM domain_record_updates
+        GROUP BY domain_record_id",
+    )
+    .load::<DomainRecordUpdateCounts>(conn)?;
+    let mut last_updates: HashMap<ForeignKey, DomainRecordLastUpdate> =
+        get_domain_record_last_updates(conn)?
+            .into_iter()
+            .map(|last_update| (last_update.domain_record_id, last_update))
+            .collect();
+    for counts in update_counts {
+        let Some(last_update) = last_updates.remove(&counts.domain_record_id) else {
+            mismatches.push(format!(
+                "domain_record_last_updates is missing a row for domain record {}",
+                counts.domain_record_id
+            ));
+            continue;
+        };
+        if last_update.attempt_count != counts.attempt_count
+            || last_update.success_count != counts.success_count
+            || last_update.fail_count != counts.attempt_count - counts.success_count
+            || last_update.last_attempt_date != counts.last_attempt_date
+        {
+            mismatches.push(format!(
+                "domain_record_last_updates counts {}/{}/{} for domain record {} don't match its {} recorded updates",
+                last_update.attempt_count,
+                last_update.success_count,
+                last_update.fail_count,
+                counts.domain_record_id,
+                counts.attempt_count
+            ));
+        }
+    }
+    for domain_record_id in last_updates.keys() {
+        mismatches.push(format!(
+            "domain_record_last_updates has a row for domain record {domain_record_id} without any recorded updates"
+        ));
+    }
+
+    Ok(mismatches)
+}
+
+/// Rebuilds the summary tables if they don't match the fact tables.
+///
+/// Returns whether a rebuild was done.
+pub fn rebuild_summary_tables_if_inconsistent(conn: &mut SqliteConnection) -> Result<bool> {
+    let mismatches = find_summary_table_mismatches(conn)?;
+    if mismatches.is_empty() {
+        return Ok(false);
+    }
+    for mismatch in &mismatches {
+        warn!("Statistics integrity check failed: {}", mismatch);
+    }
+    rebuild_summary_tables(conn)?;
+    Ok(true)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -352,4 +617,167 @@ mod tests {
         )?;
         Ok(())
     }
+
+    #[test]
+    fn test_rebuild_summary_tables() -> Result<()> {
+        use crate::db::crud::domain_ip_last_fetches::get_domain_ip_last_fetch;
+        use crate::db::crud::domain_record_last_updates::get_domain_record_last_updates;
+        use crate::db::schema::domain_ip_last_fetches::dsl::*;
+        use crate::db::setup::{establish_connection, run_migrations};
+
+        let conn = &mut establish_connection(":memory:")?;
+        run_migrations(conn)?;
+        let domain_record = create_domain_record(
+            conn,
+            &NewDomainRecord {
+                name: "home.site.com",
+                record_type: "A",
+            },
+        )?
+        .expect("domain record should be created");
+
+        for (ip, success) in [
+            (Some("127.0.1.2"), true),
+            (None, false),
+            (Some("127.0.1.3"), false),
+        ] {
+            let maybe_fetched_ips = ip.map(|ip| IpAddrV4AndV6 {
+                ipv4: Some(ip.parse().expect("valid ip")),
+                ipv6: None,
+            });
+            let (domain_ip_fetch, attempt_date) = handle_ip_fetch(conn, maybe_fetched_ips)?;
+            create_updater_attempt(
+                conn,
+                &domain_record,
+                &domain_ip_fetch.expect("ip fetch should be created"),
+                attempt_date,
+                success,
+                Some(IpAddrKind::V4),
+            )?;
+        }
+        assert!(find_summary_table_mismatches(conn)?.is_empty());
+        let expected_last_fetch = get_domain_ip_last_fetch(conn)?.expect("summary should exist");
+        let expected_last_updates = get_domain_record_last_updates(conn)?;
+
+        diesel::update(domain_ip_last_fetches)
+            .set(attempt_count.eq(42))
+            .execute(conn)?;
+        assert_eq!(find_summary_table_mismatches(conn)?.len(), 1);
+
+        assert!(rebuild_summary_tables_if_inconsistent(conn)?);
+        assert!(find_summary_table_mismatches(conn)?.is_empty());
+        assert!(!rebuild_summary_tables_if_inconsistent(conn)?);
+
+        let last_fetch = get_domain_ip_last_fetch(conn)?.expect("summary should exist");
+        assert_eq!(last_fetch.attempt_count, expected_last_fetch.attempt_count);
+        assert_eq!(last_fetch.success_count, 2);
+        assert_eq!(last_fetch.fail_count, 1);
+        assert_eq!(
+            last_fetch.last_successful_fetched_ipv4,
+            expected_last_fetch.last_successful_fetched_ipv4
+        );
+
+        let last_updates = get_domain_record_last_updates(conn)?;
+        let last_update = last_updates.first().expect("summary should exist");
+        let expected_last_update = expected_last_updates.first().expect("summary should exist");
+        assert_eq!(last_updates.len(), 1);
+        assert_eq!(last_update.attempt_count, 2);
+        assert_eq!(last_update.success_count, 1);
+        assert_eq!(last_update.last_set_ip, expected_last_update.last_set_ip);
+        Ok(())
+    }
+
+    #[test]
+    fn test_handle_updater_attempt_for_providers() -> Result<()> {
+        use crate::db::crud::domain_record_updates::get_domain_record_updates;
+        use crate::db::crud::provider_updates::get_provider_updates;
+        use crate::db::crud::providers::get_providers;
+        use crate::db::setup::{establish_connection, run_migrations};
+
+        let conn = &mut establish_connection(":memory:")?;
+        run_migrations(conn)?;
+
+        let maybe_fetched_ips = Some(IpAddrV4AndV6 {
+            ipv4: Some("127.0.1.2".parse().expect("valid ip")),
+            ipv6: None,
+        });
+        let (domain_ip_fetch, attempt_date) = handle_ip_fetch(conn, maybe_fetched_ips)?;
+        let domain_ip_fetch = domain_ip_fetch.expect("ip fetch should be created");
+
+        let provider_outcomes = [
+            ProviderUpdateOutcome {
+                provider_name: "DigitalOcean",
+                success: true,
+                ip_kind: Some(IpAddrKind::V4),
+                error_message: None,
+                http_status: None,
+                record_value: Some("127.0.1.2".to_owned()),
+                latency: std::time::Duration::from_millis(120),
+                propagation: Some(std::time::Duration::from_millis(4500)),
+            },
+            ProviderUpdateOutcome {
+                provider_name: "Hetzner",
+                success: false,
+                ip_kind: Some(IpAddrKind::V4),
+                error_message: Some("Hetzner API error (unauthorized): invalid token".to_owned()),
+                http_status: Some(401),
+                record_value: None,
+                latency: std::time::Duration::from_millis(80),
+                propagation: None,
+            },
+        ];
+        for _ in 0..2 {
+            handle_updater_attempt_for_providers(
+                conn,
+                "home.site.com",
+                "A",
+                &domain_ip_fetch,
+                attempt_date,
+                false,
+                Some(IpAddrKind::V4),
+                &provider_outcomes,
+            )?;
+        }
+
+        let providers = get_providers(conn)?;
+        assert_eq!(providers.len(), 2);
+        let hetzner = providers
+            .iter()
+            .find(|provider| provider.name == "Hetzner")
+            .expect("provider should be created");
+
+        let attempts = get_updater_attempts(conn)?;
+        assert_eq!(attempts.len(), 2);
+        let updates = get_domain_record_updates(conn)?;
+        assert_eq!(updates.len(), 2);
+        assert!(updates.iter().all(|update| !update.success));
+
+        let provider_updates = get_provider_updates(conn)?;
+        assert_eq!(provider_updates.len(), 4);
+        assert!(attempts.iter().all(|attempt| {
+            provider_updates
+                .iter()
+                .filter(|provider_update| provider_update.updater_attempt_id == attempt.id)
+                .count()
+                == 2
+        }));
+        let failed_update = provider_updates
+            .iter()
+            .find(|provider_update| !provider_update.success)
+            .expect("failed provider update should be recorded");
+        assert_eq!(failed_update.provider_id, hetzner.id);
+        assert_eq!(failed_update.http_status, Some(401));
+        assert_eq!(failed_update.latency_ms, Some(80));
+        assert_eq!(failed_update.propagation_ms, None);
+        assert!(provider_updates
+            .iter()
+            .filter(|provider_update| provider_update.success)
+            .all(|provider_update| provider_update.propagation_ms == Some(4500)));
+        assert!(failed_update
+            .error_message
+            .as_deref()
+            .is_some_and(|message| message.contains("invalid token")));
+        assert!(find_summary_table_mismatches(conn)?.is_empty());
+        Ok(())
+    }
 }
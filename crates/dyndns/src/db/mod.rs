@@ -1,5 +1,7 @@
 pub mod crud;
+pub mod history;
 pub mod logic;
+pub mod retention;
 pub mod schema;
 pub mod setup;
 pub mod sqlite_mapping;
@@ -0,0 +1,482 @@
+use crate::config::app_config::StatsRetentionOptions;
+use crate::db::types::*;
+use chrono::NaiveDateTime;
+use color_eyre::eyre::{eyre, Error, Result};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Timestamp};
+use diesel::sqlite::{Sqlite, SqliteConnection};
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::{info, trace};
+
+/// Maximum number of bound parameters used in a single `IN (...)` clause.
+const DELETE_CHUNK_SIZE: usize = 500;
+
+/// Describes which fact table rows should be kept.
+///
+/// A row is pruned when it's older than `max_age`, or when it's not one of the
+/// newest `max_rows` rows of its table.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RetentionPolicy {
+    pub max_age: Option<Duration>,
+    pub max_rows: Option<u64>,
+}
+
+impl From<&StatsRetentionOptions> for RetentionPolicy {
+    fn from(options: &StatsRetentionOptions) -> Self {
+        Self {
+            max_age: options.stats_max_age,
+            max_rows: options.stats_max_rows,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.max_age.is_some() || self.max_rows.is_some()
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PruneSummary {
+    pub updater_attempts: usize,
+    pub provider_updates: usize,
+    pub domain_ip_fetches: usize,
+    pub domain_record_updates: usize,
+}
+
+impl PruneSummary {
+    pub fn total(&self) -> usize {
+        self.updater_attempts
+            + self.provider_updates
+            + self.domain_ip_fetches
+            + self.domain_record_updates
+    }
+}
+
+#[derive(QueryableByName)]
+struct RowId {
+    #[diesel(sql_type = BigInt)]
+    id: PrimaryKey,
+}
+
+/// Deletes the fact table rows that fall outside of the retention policy.
+///
+/// `updater_attempts` are pruned first, together with the `provider_updates` that
+/// reference them, so that the IP fetches and record updates that were only
+/// referenced by pruned attempts can be pruned as well. Rows still referenced by a
+/// kept updater attempt are never deleted.
+///
+/// The `domain_ip_last_fetches` and `domain_record_last_updates` summary tables are
+/// adjusted in the same transaction, so that their counters match the remaining
+/// fact rows.
+pub fn prune_stats(
+    conn: &mut SqliteConnection,
+    policy: &RetentionPolicy,
+    now: NaiveDateTime,
+) -> Result<PruneSummary> {
+    if !policy.is_enabled() {
+        return Ok(PruneSummary::default());
+    }
+
+    let cutoff = policy
+        .max_age
+        .map(|max_age| -> Result<NaiveDateTime> {
+            let max_age = chrono::Duration::from_std(max_age)
+                .map_err(|e| eyre!("Invalid statistics max age: {}", e))?;
+            Ok(now
+                .checked_sub_signed(max_age)
+                .unwrap_or(NaiveDateTime::MIN))
+        })
+        .transpose()?;
+    let max_rows = policy
+        .max_rows
+        .map(i64::try_from)
+        .transpose()
+        .map_err(|e| eyre!("Invalid statistics max rows: {}", e))?;
+
+    trace!(
+        "Pruning statistics older than {:?} and beyond the newest {:?} rows",
+        cutoff,
+        max_rows
+    );
+
+    let summary = conn.transaction::<_, Error, _>(|conn| {
+        let (updater_attempts, provider_updates) = prune_updater_attempts(conn, cutoff, max_rows)?;
+        let domain_ip_fetches = prune_domain_ip_fetches(conn, cutoff, max_rows)?;
+        let domain_record_updates = prune_domain_record_updates(conn, cutoff, max_rows)?;
+        Ok(PruneSummary {
+            updater_attempts,
+            provider_updates,
+            domain_ip_fetches,
+            domain_record_updates,
+        })
+    })?;
+
+    info!(
+        "Pruned {} updater attempt(s), {} provider update(s), {} IP fetch(es) and {} domain record update(s) from the statistics database",
+        summary.updater_attempts,
+        summary.provider_updates,
+        summary.domain_ip_fetches,
+        summary.domain_record_updates
+    );
+    Ok(summary)
+}
+
+/// Reclaims the disk space freed by pruning.
+pub fn vacuum(conn: &mut SqliteConnection) -> Result<()> {
+    trace!("Vacuuming the statistics database");
+    diesel::sql_query("VACUUM").execute(conn)?;
+    Ok(())
+}
+
+fn select_prunable_ids(
+    conn: &mut SqliteConnection,
+    table: &str,
+    extra_condition: &str,
+    cutoff: Option<NaiveDateTime>,
+    max_rows: Option<i64>,
+) -> Result<Vec<PrimaryKey>> {
+    let mut conditions = vec![];
+    if cutoff.is_some() {
+        conditions.push("attempt_date < ?".to_owned());
+    }
+    if max_rows.is_some() {
+        conditions.push(format!(
+            "id NOT IN (SELECT id FROM {table} ORDER BY attempt_date DESC, id DESC LIMIT ?)"
+        ));
+    }
+
+    let sql = format!(
+        "SELECT id FROM {table} WHERE ({}){extra_condition}",
+        conditions.join(" OR ")
+    );
+    let mut query = diesel::sql_query(sql).into_boxed::<Sqlite>();
+    if let Some(cutoff) = cutoff {
+        query = query.bind::<Timestamp, _>(cutoff);
+    }
+    if let Some(max_rows) = max_rows {
+        query = query.bind::<BigInt, _>(max_rows);
+    }
+    let ids = query
+        .load::<RowId>(conn)?
+        .into_iter()
+        .map(|row| row.id)
+        .collect();
+    Ok(ids)
+}
+
+/// Returns the number of deleted updater attempts and provider updates.
+fn prune_updater_attempts(
+    conn: &mut SqliteConnection,
+    cutoff: Option<NaiveDateTime>,
+    max_rows: Option<i64>,
+) -> Result<(usize, usize)> {
+    use super::schema::provider_updates::dsl::{provider_updates, updater_attempt_id};
+    use super::schema::updater_attempts::dsl::*;
+
+    let ids = select_prunable_ids(conn, "updater_attempts", "", cutoff, max_rows)?;
+    let mut deleted = 0;
+    let mut deleted_provider_updates = 0;
+    for chunk in ids.chunks(DELETE_CHUNK_SIZE) {
+        deleted_provider_updates +=
+            diesel::delete(provider_updates.filter(updater_attempt_id.eq_any(chunk)))
+                .execute(conn)?;
+        deleted += diesel::delete(updater_attempts.filter(id.eq_any(chunk))).execute(conn)?;
+    }
+    Ok((deleted, deleted_provider_updates))
+}
+
+fn prune_domain_ip_fetches(
+    conn: &mut SqliteConnection,
+    cutoff: Option<NaiveDateTime>,
+    max_rows: Option<i64>,
+) -> Result<usize> {
+    use super::schema::domain_ip_fetches::dsl::*;
+
+    let ids = select_prunable_ids(
+        conn,
+        "domain_ip_fetches",
+        " AND id NOT IN (SELECT domain_ip_fetches_id FROM updater_attempts)",
+        cutoff,
+        max_rows,
+    )?;
+
+    let mut deleted = 0;
+    let mut deleted_success_count = 0;
+    for chunk in ids.chunks(DELETE_CHUNK_SIZE) {
+        deleted_success_count += domain_ip_fetches
+            .filter(id.eq_any(chunk))
+            .filter(success.eq(true))
+            .count()
+            .get_result::<i64>(conn)?;
+        deleted += diesel::delete(domain_ip_fetches.filter(id.eq_any(chunk))).execute(conn)?;
+    }
+
+    subtract_from_domain_ip_last_fetch(conn, i64::try_from(deleted)?, deleted_success_count)?;
+    Ok(deleted)
+}
+
+fn prune_domain_record_updates(
+    conn: &mut SqliteConnection,
+    cutoff: Option<NaiveDateTime>,
+    max_rows: Option<i64>,
+) -> Result<usize> {
+    use super::schema::domain_record_updates::dsl::*;
+
+    let ids = select_prunable_ids(
+        conn,
+        "domain_record_updates",
+        " AND id NOT IN (SELECT domain_record_updates_id FROM updater_attempts \
+          WHERE domain_record_updates_id IS NOT NULL)",
+        cutoff,
+        max_rows,
+    )?;
+
+    let mut deleted = 0;
+    // domain_record_id -> (deleted count, deleted success count)
+    let mut deleted_per_record: HashMap<ForeignKey, (i64, i64)> = HashMap::new();
+    for chunk in ids.chunks(DELETE_CHUNK_SIZE) {
+        let rows = domain_record_updates
+            .filter(id.eq_any(chunk))
+            .select((domain_record_id, success))
+            .load::<(ForeignKey, bool)>(conn)?;
+        for (record_id, is_success) in rows {
+            let counts = deleted_per_record.entry(record_id).or_default();
+            counts.0 += 1;
+            counts.1 += i64::from(is_success);
+        }
+        deleted += diesel::delete(domain_record_updates.filter(id.eq_any(chunk))).execute(conn)?;
+    }
+
+    for (record_id, (deleted_count, deleted_success_count)) in deleted_per_record {
+        subtract_from_domain_record_last_update(
+            conn,
+            record_id,
+            deleted_count,
+            deleted_success_count,
+        )?;
+    }
+    Ok(deleted)
+}
+
+fn subtract_from_domain_ip_last_fetch(
+    conn: &mut SqliteConnection,
+    deleted_count: i64,
+    deleted_success_count: i64,
+) -> Result<()> {
+    use super::crud::domain_ip_last_fetches::get_domain_ip_last_fetch;
+    use super::schema::domain_ip_last_fetches::dsl::*;
+
+    if deleted_count == 0 {
+        return Ok(());
+    }
+    let Some(last_fetch) = get_domain_ip_last_fetch(conn)? else {
+        return Ok(());
+    };
+
+    let new_attempt_count = last_fetch.attempt_count - deleted_count;
+    if new_attempt_count <= 0 {
+        // Nothing is left to summarize.
+        diesel::delete(&last_fetch).execute(conn)?;
+        return Ok(());
+    }
+    let new_success_count = (last_fetch.success_count - deleted_success_count).max(0);
+    diesel::update(&last_fetch)
+        .set((
+            attempt_count.eq(new_attempt_count),
+            success_count.eq(new_success_count),
+            fail_count.eq(new_attempt_count - new_success_count),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+fn subtract_from_domain_record_last_update(
+    conn: &mut SqliteConnection,
+    query_domain_record_id: ForeignKey,
+    deleted_count: i64,
+    deleted_success_count: i64,
+) -> Result<()> {
+    use super::crud::domain_record_last_updates::get_domain_record_last_update_by_domain_record_id;
+    use super::schema::domain_record_last_updates::dsl::*;
+
+    let Some(last_update) =
+        get_domain_record_last_update_by_domain_record_id(conn, query_domain_record_id)?
+    else {
+        return Ok(());
+    };
+
+    let new_attempt_count = last_update.attempt_count - deleted_count;
+    if new_attempt_count <= 0 {
+        diesel::delete(&last_update).execute(conn)?;
+        return Ok(());
+    }
+    let new_success_count = (last_update.success_count - deleted_success_count).max(0);
+    diesel::update(&last_update)
+        .set((
+            attempt_count.eq(new_attempt_count),
+            success_count.eq(new_success_count),
+            fail_count.eq(new_attempt_count - new_success_count),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::crud::domain_ip_fetches::get_domain_ip_fetches;
+    use crate::db::crud::domain_ip_last_fetches::get_domain_ip_last_fetch;
+    use crate::db::crud::domain_record_last_updates::get_domain_record_last_updates;
+    use crate::db::crud::domain_record_updates::get_domain_record_updates;
+    use crate::db::crud::domain_records::create_domain_record;
+    use crate::db::crud::updater_attempts::get_updater_attempts;
+    use crate::db::logic::{create_updater_attempt, handle_ip_fetch};
+    use crate::db::setup::{establish_connection, run_migrations};
+    use crate::types::{IpAddrKind, IpAddrV4AndV6};
+
+    fn setup_db_with_attempts(attempt_count: usize) -> Result<SqliteConnection> {
+        let mut conn = establish_connection(":memory:")?;
+        run_migrations(&mut conn)?;
+        let domain_record = create_domain_record(
+            &mut conn,
+            &NewDomainRecord {
+                name: "home.site.com",
+                record_type: "A",
+            },
+        )?
+        .expect("domain record should be created");
+
+        for _ in 0..attempt_count {
+            let fetched_ips = Some(IpAddrV4AndV6 {
+                ipv4: Some("127.0.1.2".parse().expect("valid ip")),
+                ipv6: None,
+            });
+            let (domain_ip_fetch, attempt_date) = handle_ip_fetch(&mut conn, fetched_ips)?;
+            create_updater_attempt(
+                &mut conn,
+                &domain_record,
+                &domain_ip_fetch.expect("ip fetch should be created"),
+                attempt_date,
+                true,
+                Some(IpAddrKind::V4),
+            )?;
+        }
+        Ok(conn)
+    }
+
+    #[test]
+    fn test_prune_disabled_policy_is_noop() -> Result<()> {
+        let conn = &mut setup_db_with_attempts(3)?;
+        let now = chrono::offset::Utc::now().naive_utc();
+        let summary = prune_stats(conn, &RetentionPolicy::default(), now)?;
+        assert_eq!(summary.total(), 0);
+        assert_eq!(get_updater_attempts(conn)?.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_prune_max_rows_keeps_summaries_consistent() -> Result<()> {
+        let conn = &mut setup_db_with_attempts(5)?;
+        let now = chrono::offset::Utc::now().naive_utc();
+        let policy = RetentionPolicy {
+            max_age: None,
+            max_rows: Some(2),
+        };
+        let summary = prune_stats(conn, &policy, now)?;
+        assert_eq!(
+            summary,
+            PruneSummary {
+                updater_attempts: 3,
+                provider_updates: 0,
+                domain_ip_fetches: 3,
+                domain_record_updates: 3,
+            }
+        );
+        assert_eq!(get_updater_attempts(conn)?.len(), 2);
+        assert_eq!(get_domain_ip_fetches(conn)?.len(), 2);
+        assert_eq!(get_domain_record_updates(conn)?.len(), 2);
+
+        let last_fetch = get_domain_ip_last_fetch(conn)?.expect("summary should be kept");
+        assert_eq!(last_fetch.attempt_count, 2);
+        assert_eq!(last_fetch.success_count, 2);
+        assert_eq!(last_fetch.fail_count, 0);
+
+        let last_updates = get_domain_record_last_updates(conn)?;
+        let last_update = last_updates.first().expect("summary should be kept");
+        assert_eq!(last_update.attempt_count, 2);
+        assert_eq!(last_update.success_count, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_prune_max_age_removes_everything_older() -> Result<()> {
+        let conn = &mut setup_db_with_attempts(2)?;
+        let in_the_future = chrono::offset::Utc::now().naive_utc() + chrono::Duration::days(2);
+        let policy = RetentionPolicy {
+            max_age: Some(Duration::from_secs(60 * 60 * 24)),
+            max_rows: None,
+        };
+        let summary = prune_stats(conn, &policy, in_the_future)?;
+        assert_eq!(summary.total(), 6);
+        assert!(get_domain_ip_last_fetch(conn)?.is_none());
+        assert!(get_domain_record_last_updates(conn)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_prune_removes_provider_updates_of_pruned_attempts() -> Result<()> {
+        use crate::db::crud::provider_updates::get_provider_updates;
+        use crate::db::logic::handle_updater_attempt_for_providers;
+        use crate::types::ProviderUpdateOutcome;
+
+        let conn = &mut establish_connection(":memory:")?;
+        run_migrations(conn)?;
+        let provider_outcomes = [ProviderUpdateOutcome {
+            provider_name: "DigitalOcean",
+            success: true,
+            ip_kind: Some(IpAddrKind::V4),
+            error_message: None,
+            http_status: None,
+            record_value: Some("127.0.1.2".to_owned()),
+            latency: Duration::from_millis(120),
+            propagation: None,
+        }];
+        for _ in 0..3 {
+            let fetched_ips = Some(IpAddrV4AndV6 {
+                ipv4: Some("127.0.1.2".parse().expect("valid ip")),
+                ipv6: None,
+            });
+            let (domain_ip_fetch, attempt_date) = handle_ip_fetch(conn, fetched_ips)?;
+            handle_updater_attempt_for_providers(
+                conn,
+                "home.site.com",
+                "A",
+                &domain_ip_fetch.expect("ip fetch should be created"),
+                attempt_date,
+                true,
+                Some(IpAddrKind::V4),
+                &provider_outcomes,
+            )?;
+        }
+
+        let now = chrono::offset::Utc::now().naive_utc();
+        let policy = RetentionPolicy {
+            max_age: None,
+            max_rows: Some(1),
+        };
+        let summary = prune_stats(conn, &policy, now)?;
+        assert_eq!(summary.updater_attempts, 2);
+        assert_eq!(summary.provider_updates, 2);
+
+        let attempts = get_updater_attempts(conn)?;
+        let attempt = attempts.first().expect("newest attempt should be kept");
+        let provider_updates = get_provider_updates(conn)?;
+        assert_eq!(provider_updates.len(), 1);
+        assert!(provider_updates
+            .iter()
+            .all(|provider_update| provider_update.updater_attempt_id == attempt.id));
+        Ok(())
+    }
+}
@@ -76,6 +76,30 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use crate::db::sqlite_mapping::*;
+
+    provider_updates (id) {
+        id -> Integer,
+        updater_attempt_id -> Integer,
+        provider_id -> Integer,
+        success -> Bool,
+        error_message -> Nullable<Text>,
+        http_status -> Nullable<Integer>,
+        latency_ms -> Nullable<Integer>,
+        propagation_ms -> Nullable<Integer>,
+    }
+}
+
+diesel::table! {
+    use crate::db::sqlite_mapping::*;
+
+    providers (id) {
+        id -> Integer,
+        name -> Text,
+    }
+}
+
 diesel::table! {
     use crate::db::sqlite_mapping::*;
 
@@ -90,6 +114,8 @@ diesel::table! {
 
 diesel::joinable!(domain_record_last_updates -> domain_records (domain_record_id));
 diesel::joinable!(domain_record_updates -> domain_records (domain_record_id));
+diesel::joinable!(provider_updates -> providers (provider_id));
+diesel::joinable!(provider_updates -> updater_attempts (updater_attempt_id));
 diesel::joinable!(updater_attempts -> domain_ip_fetches (domain_ip_fetches_id));
 diesel::joinable!(updater_attempts -> domain_record_updates (domain_record_updates_id));
 diesel::joinable!(updater_attempts -> domain_records (domain_record_id));
@@ -101,5 +127,7 @@ diesel::allow_tables_to_appear_in_same_query!(
     domain_record_updates,
     domain_records,
     info,
+    provider_updates,
+    providers,
     updater_attempts,
 );
@@ -3,6 +3,8 @@ use crate::db::schema::domain_ip_last_fetches;
 use crate::db::schema::domain_record_last_updates;
 use crate::db::schema::domain_record_updates;
 use crate::db::schema::domain_records;
+use crate::db::schema::provider_updates;
+use crate::db::schema::providers;
 use crate::db::schema::updater_attempts;
 
 use chrono::NaiveDateTime;
@@ -29,7 +31,8 @@ pub struct NewDomainRecord<'a, 'b> {
     pub record_type: &'b str,
 }
 
-#[derive(Identifiable, Queryable, Debug)]
+#[cfg_attr(feature = "web", derive(JsonSchema))]
+#[derive(Identifiable, Queryable, Debug, Serialize)]
 #[diesel(table_name = domain_ip_fetches)]
 pub struct DomainIpFetch {
     pub id: PrimaryKey,
@@ -122,6 +125,7 @@ pub struct NewDomainRecordLastUpdate {
     pub last_set_ip: String,
 }
 
+#[cfg_attr(feature = "web", derive(JsonSchema))]
 #[derive(Identifiable, Queryable, Debug, Serialize)]
 pub struct UpdaterAttempt {
     pub id: PrimaryKey,
@@ -139,3 +143,39 @@ pub struct NewUpdaterAttempt {
     pub domain_record_updates_id: Option<ForeignKey>,
     pub attempt_date: NaiveDateTime,
 }
+
+#[derive(Identifiable, Queryable, Debug, Serialize)]
+pub struct Provider {
+    pub id: PrimaryKey,
+    pub name: String,
+}
+
+#[derive(Insertable, Debug, AsChangeset)]
+#[diesel(table_name = providers)]
+pub struct NewProvider<'a> {
+    pub name: &'a str,
+}
+
+#[derive(Identifiable, Queryable, Debug, Serialize)]
+pub struct ProviderUpdate {
+    pub id: PrimaryKey,
+    pub updater_attempt_id: ForeignKey,
+    pub provider_id: ForeignKey,
+    pub success: bool,
+    pub error_message: Option<String>,
+    pub http_status: Option<DBIntegerType>,
+    pub latency_ms: Option<DBIntegerType>,
+    pub propagation_ms: Option<DBIntegerType>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = provider_updates)]
+pub struct NewProviderUpdate {
+    pub updater_attempt_id: ForeignKey,
+    pub provider_id: ForeignKey,
+    pub success: bool,
+    pub error_message: Option<String>,
+    pub http_status: Option<DBIntegerType>,
+    pub latency_ms: Option<DBIntegerType>,
+    pub propagation_ms: Option<DBIntegerType>,
+}
@@ -0,0 +1,368 @@
+use async_trait::async_trait;
+use color_eyre::eyre::{bail, eyre, Report, Result, WrapErr};
+use hickory_resolver::config::{NameServerConfigGroup, ResolveHosts, ResolverConfig, ResolverOpts};
+use hickory_resolver::proto::rr::{RData, RecordType};
+use hickory_resolver::{name_server::TokioConnectionProvider, Resolver, TokioResolver};
+use humantime::format_duration;
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+use crate::config::verification_config::{VerificationConfig, VerificationFailureMode};
+use crate::signal_handlers::AppTerminationHandler;
+use crate::types::DomainRecordToUpdate;
+
+/// A nameserver that is checked for the new value of a record.
+#[async_trait]
+trait Nameserver: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Whether the nameserver answers `expected_ip` for the record.
+    async fn serves(&self, fqdn: &str, expected_ip: IpAddr) -> bool;
+}
+
+/// Finds the nameservers to check for a domain.
+#[async_trait]
+trait NameserverFinder: Send + Sync {
+    async fn find_nameservers(&self, domain_name: &str) -> Result<Vec<Box<dyn Nameserver>>>;
+}
+
+/// Why the new value of a record couldn't be verified.
+#[derive(Debug)]
+pub enum VerificationError {
+    /// The authoritative nameservers of the domain couldn't be found, so
+    /// the record wasn't checked at all.
+    NameserverDiscovery(Report),
+    /// Not all nameservers served the new value within the timeout.
+    NotPropagated(Report),
+    /// The app is shutting down, so the check was given up.
+    Interrupted,
+}
+
+/// An authoritative nameserver of a domain, queried without recursion or
+/// caching.
+struct AuthoritativeNameserver {
+    name: String,
+    resolver: TokioResolver,
+}
+
+impl AuthoritativeNameserver {
+    fn new(name: String, ips: &[IpAddr]) -> Self {
+        let resolver_config = ResolverConfig::from_parts(
+            None,
+            vec![],
+            NameServerConfigGroup::from_ips_clear(ips, 53, true),
+        );
+        let mut resolver_options = ResolverOpts::default();
+        resolver_options.use_hosts_file = ResolveHosts::Never;
+        resolver_options.recursion_desired = false;
+        resolver_options.cache_size = 0;
+        resolver_options.attempts = 1;
+
+        let mut builder =
+            Resolver::builder_with_config(resolver_config, TokioConnectionProvider::default());
+        *builder.options_mut() = resolver_options;
+        Self {
+            name,
+            resolver: builder.build(),
+        }
+    }
+}
+
+#[async_trait]
+impl Nameserver for AuthoritativeNameserver {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn serves(&self, fqdn: &str, expected_ip: IpAddr) -> bool {
+        let record_type = match expected_ip {
+            IpAddr::V4(_) => RecordType::A,
+            IpAddr::V6(_) => RecordType::AAAA,
+        };
+        match self.resolver.lookup(fqdn, record_type).await {
+            Ok(lookup) => lookup
+                .iter()
+                .filter_map(RData::ip_addr)
+                .any(|ip| ip == expected_ip),
+            Err(e) => {
+                debug!(
+                    "Nameserver '{}' failed to resolve '{}': {}",
+                    self.name, fqdn, e
+                );
+                false
+            }
+        }
+    }
+}
+
+/// Checks that updated records are served by the authoritative nameservers
+/// of their domain.
+pub struct DnsVerifier {
+    config: VerificationConfig,
+    nameserver_finder: Box<dyn NameserverFinder>,
+    term_handler: AppTerminationHandler,
+}
+
+impl DnsVerifier {
+    pub fn new(config: VerificationConfig, term_handler: AppTerminationHandler) -> Self {
+        Self {
+            config,
+            nameserver_finder: Box::new(SystemNameserverFinder),
+            term_handler,
+        }
+    }
+
+    pub fn fails_on_timeout(&self) -> bool {
+        self.config.on_timeout == VerificationFailureMode::Fail
+    }
+
+    /// Verifies the records updated by one update attempt concurrently.
+    ///
+    /// All of them share one timeout, so verifying doesn't hold up the
+    /// updater for longer than the configured timeout, however many records
+    /// changed. Returns one result per record.
+    pub async fn verify_all(
+        &self,
+        updated_records: &[(&DomainRecordToUpdate, IpAddr)],
+    ) -> Vec<Result<Duration, VerificationError>> {
+        let deadline = Instant::now() + self.config.timeout;
+        futures_util::future::join_all(
+            updated_records
+                .iter()
+                .map(|(record, expected_ip)| self.verify(record, *expected_ip, deadline)),
+        )
+        .await
+    }
+
+    /// Queries the authoritative nameservers until all of them serve
+    /// `expected_ip` for the record, or until `deadline`. Returns how long
+    /// that took.
+    async fn verify(
+        &self,
+        record: &DomainRecordToUpdate,
+        expected_ip: IpAddr,
+        deadline: Instant,
+    ) -> Result<Duration, VerificationError> {
+        let start = Instant::now();
+        let fqdn = format!("{}.", record.fqdn());
+        let mut pending = self
+            .nameserver_finder
+            .find_nameservers(&record.domain_name)
+            .await
+            .map_err(VerificationError::NameserverDiscovery)?;
+        loop {
+            let mut still_pending = vec![];
+            for nameserver in pending {
+                if !nameserver.serves(&fqdn, expected_ip).await {
+                    still_pending.push(nameserver);
+                }
+            }
+            pending = still_pending;
+            if pending.is_empty() {
+                let propagation = start.elapsed();
+                info!(
+                    "Record '{}' is served with '{}' by all authoritative nameservers after {}",
+                    record.fqdn(),
+                    expected_ip,
+                    format_duration(Duration::from_secs(propagation.as_secs()))
+                );
+                return Ok(propagation);
+            }
+            if Instant::now() + self.config.poll_interval > deadline {
+                return Err(VerificationError::NotPropagated(eyre!(
+                    "Record '{}' was not served with '{}' by nameserver(s) {} within {}",
+                    record.fqdn(),
+                    expected_ip,
+                    pending
+                        .iter()
+                        .map(|nameserver| format!("'{}'", nameserver.name()))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    format_duration(self.config.timeout)
+                )));
+            }
+            debug!(
+                "Waiting for {} nameserver(s) to serve '{}' for '{}'",
+                pending.len(),
+                expected_ip,
+                record.fqdn()
+            );
+            tokio::select! {
+                () = tokio::time::sleep(self.config.poll_interval) => {}
+                () = self.term_handler.wait_for_exit() => return Err(VerificationError::Interrupted),
+            }
+        }
+    }
+}
+
+/// Looks up the NS records of the domain and their addresses using the
+/// system resolver.
+struct SystemNameserverFinder;
+
+#[async_trait]
+impl NameserverFinder for SystemNameserverFinder {
+    async fn find_nameservers(&self, domain_name: &str) -> Result<Vec<Box<dyn Nameserver>>> {
+        let resolver = Resolver::builder(TokioConnectionProvider::default())
+            .wrap_err("Failed to read the system resolver configuration")?
+            .build();
+        let ns_lookup = resolver
+            .ns_lookup(format!("{domain_name}."))
+            .await
+            .wrap_err_with(|| format!("Failed to find the nameservers of '{domain_name}'"))?;
+
+        let mut nameservers = vec![];
+        for ns in ns_lookup.iter() {
+            let name = ns.0.to_utf8();
+            let ips = resolver
+                .lookup_ip(ns.0.clone())
+                .await
+                .wrap_err_with(|| format!("Failed to resolve nameserver '{name}'"))?
+                .iter()
+                .collect::<Vec<_>>();
+            nameservers
+                .push(Box::new(AuthoritativeNameserver::new(name, &ips)) as Box<dyn Nameserver>);
+        }
+        if nameservers.is_empty() {
+            bail!("No nameservers found for '{}'", domain_name);
+        }
+        Ok(nameservers)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Serves the expected IP once it was queried `queries_before_serving`
+    /// times.
+    struct StubNameserver {
+        name: String,
+        queries_before_serving: AtomicU32,
+    }
+
+    #[async_trait]
+    impl Nameserver for StubNameserver {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        async fn serves(&self, _fqdn: &str, _expected_ip: IpAddr) -> bool {
+            self.queries_before_serving
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |queries| {
+                    queries.checked_sub(1)
+                })
+                .is_err()
+        }
+    }
+
+    /// Finds stub nameservers that serve the new value after the given
+    /// numbers of queries, or none if `None`.
+    struct StubNameserverFinder {
+        queries_before_serving: Option<Vec<u32>>,
+    }
+
+    #[async_trait]
+    impl NameserverFinder for StubNameserverFinder {
+        async fn find_nameservers(&self, domain_name: &str) -> Result<Vec<Box<dyn Nameserver>>> {
+            let Some(queries_before_serving) = self.queries_before_serving.as_ref() else {
+                bail!("Failed to find the nameservers of '{}'", domain_name);
+            };
+            Ok(queries_before_serving
+                .iter()
+                .enumerate()
+                .map(|(index, queries)| {
+                    Box::new(StubNameserver {
+                        name: format!("ns{}.{}", index + 1, domain_name),
+                        queries_before_serving: AtomicU32::new(*queries),
+                    }) as Box<dyn Nameserver>
+                })
+                .collect())
+        }
+    }
+
+    /// A verifier whose nameservers serve the new value after the given
+    /// numbers of queries, or whose nameservers can't be found if `None`.
+    pub fn stub_verifier(
+        on_timeout: VerificationFailureMode,
+        queries_before_serving: Option<Vec<u32>>,
+    ) -> DnsVerifier {
+        DnsVerifier {
+            config: VerificationConfig {
+                timeout: Duration::from_millis(200),
+                poll_interval: Duration::from_millis(10),
+                on_timeout,
+            },
+            nameserver_finder: Box::new(StubNameserverFinder {
+                queries_before_serving,
+            }),
+            term_handler: AppTerminationHandler::default(),
+        }
+    }
+
+    #[test]
+    fn test_verify() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let record = DomainRecordToUpdate::new("site.com", "home", "A", None);
+        let ip: IpAddr = "85.212.89.12".parse().unwrap();
+
+        let verifier = stub_verifier(VerificationFailureMode::Warn, Some(vec![0, 2]));
+        let results = rt.block_on(verifier.verify_all(&[(&record, ip)]));
+        assert!(matches!(results.as_slice(), [Ok(_)]));
+
+        let verifier = stub_verifier(VerificationFailureMode::Warn, Some(vec![0, u32::MAX]));
+        let results = rt.block_on(verifier.verify_all(&[(&record, ip)]));
+        let [Err(VerificationError::NotPropagated(e))] = results.as_slice() else {
+            panic!("Expected the record not to propagate");
+        };
+        assert!(e.to_string().contains("'ns2.site.com'"));
+
+        let verifier = stub_verifier(VerificationFailureMode::Warn, None);
+        let results = rt.block_on(verifier.verify_all(&[(&record, ip)]));
+        assert!(matches!(
+            results.as_slice(),
+            [Err(VerificationError::NameserverDiscovery(_))]
+        ));
+    }
+
+    #[test]
+    fn test_verify_all_shares_timeout() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let records = ["home", "www", "mail", "vpn"]
+            .map(|name| DomainRecordToUpdate::new("site.com", name, "A", None));
+        let ip: IpAddr = "85.212.89.12".parse().unwrap();
+        let updated_records = records
+            .iter()
+            .map(|record| (record, ip))
+            .collect::<Vec<_>>();
+
+        // Records that never propagate are given up together, after one
+        // timeout rather than one timeout each.
+        let verifier = stub_verifier(VerificationFailureMode::Warn, Some(vec![u32::MAX]));
+        let start = std::time::Instant::now();
+        let results = rt.block_on(verifier.verify_all(&updated_records));
+        assert!(start.elapsed() < Duration::from_millis(600));
+        assert_eq!(results.len(), 4);
+        assert!(results
+            .iter()
+            .all(|result| matches!(result, Err(VerificationError::NotPropagated(_)))));
+    }
+
+    #[test]
+    fn test_verify_interrupted_by_shutdown() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let record = DomainRecordToUpdate::new("site.com", "home", "A", None);
+        let ip: IpAddr = "85.212.89.12".parse().unwrap();
+
+        let mut verifier = stub_verifier(VerificationFailureMode::Warn, Some(vec![u32::MAX]));
+        verifier.config.timeout = Duration::from_secs(60);
+        verifier.term_handler.set_should_exit();
+        let results = rt.block_on(verifier.verify_all(&[(&record, ip)]));
+        assert!(matches!(
+            results.as_slice(),
+            [Err(VerificationError::Interrupted)]
+        ));
+    }
+}
@@ -1,18 +1,30 @@
+use async_trait::async_trait;
 use color_eyre::eyre::{bail, eyre, Error, Result, WrapErr};
-use reqwest::blocking::Client;
 use secrecy::ExposeSecret;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::net::IpAddr;
 use std::sync::RwLock;
 use tracing::{debug, info};
 
 use crate::config::provider_config::{ProviderType, SecretProviderToken};
-use crate::domain_record_api::{format_record_value, strip_record_value, DomainRecordApi};
-use crate::types::DomainRecordToUpdate;
+use crate::domain_record_api::rate_limit::{RateLimit, RateLimitedClient};
+use crate::domain_record_api::{
+    format_record_value, group_record_sets, strip_record_value, ApiHttpError, BatchChangeOutcome,
+    DomainRecordApi, RecordSetChange, RecordValueChanges,
+};
+use crate::types::{DomainRecordToUpdate, DomainRecordValue};
 
 const CLOUDFLARE_API_BASE_URL: &str = "https://api.cloudflare.com/client/v4";
 
+/// Cloudflare allows 1200 requests per 5 minutes.
+pub const CLOUDFLARE_RATE_LIMIT: RateLimit = RateLimit {
+    burst: 20,
+    per_minute: 240,
+};
+
+/// Most operations a batch request may contain on the Free plan.
+const CLOUDFLARE_MAX_BATCH_OPERATIONS: usize = 200;
+
 #[derive(Deserialize, Debug)]
 struct CloudflareZonesResponse {
     result: Vec<CloudflareZone>,
@@ -52,6 +64,33 @@ struct CloudflareUpdateRecordRequest {
     proxied: bool,
 }
 
+/// Body of a batch request. Cloudflare applies all of its operations in one
+/// transaction, deletes first.
+#[derive(Serialize, Debug, Default)]
+struct CloudflareBatchRequest {
+    deletes: Vec<CloudflareBatchDelete>,
+    puts: Vec<CloudflareBatchPut>,
+    posts: Vec<CloudflareUpdateRecordRequest>,
+}
+
+impl CloudflareBatchRequest {
+    fn operation_count(&self) -> usize {
+        self.deletes.len() + self.puts.len() + self.posts.len()
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct CloudflareBatchDelete {
+    id: String,
+}
+
+#[derive(Serialize, Debug)]
+struct CloudflareBatchPut {
+    id: String,
+    #[serde(flatten)]
+    record: CloudflareUpdateRecordRequest,
+}
+
 #[derive(Deserialize, Debug)]
 struct CloudflareErrorResponse {
     errors: Vec<CloudflareError>,
@@ -93,60 +132,67 @@ impl<'a> TryFrom<CloudflareDnsRecordWithZone<'a>> for crate::types::DomainRecord
         };
 
         // Encode proxy status and TTL into the composite ID so they can be
-        // preserved when update_domain_ip is called later.
+        // preserved when set_domain_record_values is called later.
         let composite_id = format!("{}/{}/{}", record.id, record.ttl, record.proxied);
 
         Ok(Self {
-            id: composite_id,
+            id: composite_id.clone(),
             record_type: record.record_type,
             name: hostname_part,
-            ip_value: strip_record_value(&record.content).to_string(),
+            values: vec![DomainRecordValue {
+                id: composite_id,
+                value: strip_record_value(&record.content).to_string(),
+            }],
         })
     }
 }
 
 /// Handle Cloudflare API error responses.
-fn handle_error_response(
-    response: reqwest::blocking::Response,
-) -> Result<reqwest::blocking::Response> {
+async fn handle_error_response(response: reqwest::Response) -> Result<reqwest::Response> {
     let status = response.status();
     if !status.is_success() {
         let error_text = response
             .text()
+            .await
             .unwrap_or_else(|_| "Unable to read error response".to_string());
 
         if let Ok(error_response) = serde_json::from_str::<CloudflareErrorResponse>(&error_text) {
             if let Some(error) = error_response.errors.first() {
-                bail!(
-                    "Cloudflare API error (code {}): {}",
-                    error.code,
-                    error.message
-                );
+                return Err(ApiHttpError::new(
+                    status,
+                    format!(
+                        "Cloudflare API error (code {}): {}",
+                        error.code, error.message
+                    ),
+                )
+                .into());
             }
         }
 
-        bail!("HTTP {} error: {}", status, error_text);
+        return Err(
+            ApiHttpError::new(status, format!("HTTP {} error: {}", status, error_text)).into(),
+        );
     }
 
     Ok(response)
 }
 
 pub struct CloudflareApi {
-    request_client: Client,
+    request_client: RateLimitedClient,
     token: SecretProviderToken,
     zone_cache: RwLock<HashMap<String, String>>, // domain_name -> zone_id
 }
 
 impl CloudflareApi {
-    pub fn new(token: SecretProviderToken) -> Self {
+    pub fn new(token: SecretProviderToken, request_client: RateLimitedClient) -> Self {
         Self {
-            request_client: Client::new(),
+            request_client,
             token,
             zone_cache: RwLock::new(HashMap::new()),
         }
     }
 
-    fn find_zone_id(&self, domain_name: &str) -> Result<String> {
+    async fn find_zone_id(&self, domain_name: &str) -> Result<String> {
         {
             let cache = self
                 .zone_cache
@@ -166,12 +212,14 @@ impl CloudflareApi {
             .get(&url)
             .bearer_auth(self.token.expose_secret().as_str())
             .send()
+            .await
             .wrap_err("Failed to query Cloudflare API for zones")?;
 
-        let response = handle_error_response(response)?;
+        let response = handle_error_response(response).await?;
 
         let zones_response: CloudflareZonesResponse = response
             .json()
+            .await
             .wrap_err("Failed to parse Cloudflare zones JSON response")?;
 
         if !zones_response.success {
@@ -210,6 +258,7 @@ impl CloudflareApi {
     }
 }
 
+#[async_trait]
 impl DomainRecordApi for CloudflareApi {
     fn provider_name(&self) -> &'static str {
         "Cloudflare"
@@ -219,8 +268,11 @@ impl DomainRecordApi for CloudflareApi {
         ProviderType::Cloudflare
     }
 
-    fn get_domain_records(&self, domain_name: &str) -> Result<crate::types::DomainRecordsCommon> {
-        let zone_id = self.find_zone_id(domain_name)?;
+    async fn get_domain_records(
+        &self,
+        domain_name: &str,
+    ) -> Result<crate::types::DomainRecordsCommon> {
+        let zone_id = self.find_zone_id(domain_name).await?;
 
         let url = format!(
             "{}/zones/{}/dns_records?per_page=5000",
@@ -233,12 +285,14 @@ impl DomainRecordApi for CloudflareApi {
             .get(&url)
             .bearer_auth(self.token.expose_secret().as_str())
             .send()
+            .await
             .wrap_err("Failed to query Cloudflare API for DNS records")?;
 
-        let response = handle_error_response(response)?;
+        let response = handle_error_response(response).await?;
 
         let records_response: CloudflareDnsRecordsResponse = response
             .json()
+            .await
             .wrap_err("Failed to parse Cloudflare DNS records JSON response")?;
 
         if !records_response.success {
@@ -267,72 +321,224 @@ impl DomainRecordApi for CloudflareApi {
             .map(|record| CloudflareDnsRecordWithZone::new(record, domain_name).try_into())
             .collect();
 
+        // Cloudflare has a separate record for each value of a record set.
         Ok(crate::types::DomainRecordsCommon {
-            records: converted_records?,
+            records: group_record_sets(converted_records?),
         })
     }
 
-    fn update_domain_ip(
+    async fn set_domain_record_values(
         &self,
-        domain_record_id: &str,
+        domain_record: &crate::types::DomainRecordCommon,
         record_to_update: &DomainRecordToUpdate,
-        new_ip: &IpAddr,
+        new_values: &[String],
     ) -> Result<()> {
-        // Parse composite ID: "{dns_record_id}/{ttl}/{proxied}"
-        let parts: Vec<&str> = domain_record_id.split('/').collect();
-        let (dns_record_id, ttl_str, proxied_str) = match parts.as_slice() {
-            [id, ttl, proxied] => (*id, *ttl, *proxied),
-            _ => {
-                bail!(
-                    "Invalid Cloudflare record ID format: '{}'. Expected '{{id}}/{{ttl}}/{{proxied}}'",
-                    domain_record_id
-                );
-            }
-        };
+        let zone_id = self.find_zone_id(&record_to_update.domain_name).await?;
+        let fqdn = record_to_update.fqdn();
+        let records_url = format!("{}/zones/{}/dns_records", CLOUDFLARE_API_BASE_URL, zone_id);
+        let changes = RecordValueChanges::plan(domain_record, new_values);
+
+        // New records get the TTL and proxy status of the existing ones.
+        let (_, default_ttl, default_proxied) = parse_composite_id(&domain_record.id)?;
+
+        for (domain_record_id, value) in &changes.updated {
+            let (dns_record_id, ttl, proxied) = parse_composite_id(domain_record_id)?;
+            let url = format!("{}/{}", records_url, dns_record_id);
+            debug!("Updating Cloudflare DNS record at: {}", url);
+
+            let payload = CloudflareUpdateRecordRequest {
+                record_type: record_to_update.record_type.clone(),
+                name: fqdn.clone(),
+                content: format_record_value(value, &record_to_update.record_type),
+                ttl,
+                proxied,
+            };
+
+            let response = self
+                .request_client
+                .put(&url)
+                .bearer_auth(self.token.expose_secret().as_str())
+                .json(&payload)
+                .send()
+                .await
+                .wrap_err(format!(
+                    "Failed to update Cloudflare DNS record for: {}",
+                    fqdn
+                ))?;
+
+            handle_error_response(response).await?;
+        }
 
-        let ttl: u32 = ttl_str
-            .parse()
-            .wrap_err(format!("Failed to parse TTL from record ID: '{}'", ttl_str))?;
-        let proxied: bool = proxied_str.parse().wrap_err(format!(
-            "Failed to parse proxied flag from record ID: '{}'",
-            proxied_str
-        ))?;
+        for value in &changes.created {
+            debug!("Creating Cloudflare DNS record at: {}", records_url);
+
+            let payload = CloudflareUpdateRecordRequest {
+                record_type: record_to_update.record_type.clone(),
+                name: fqdn.clone(),
+                content: format_record_value(value, &record_to_update.record_type),
+                ttl: default_ttl,
+                proxied: default_proxied,
+            };
+
+            let response = self
+                .request_client
+                .post(&records_url)
+                .bearer_auth(self.token.expose_secret().as_str())
+                .json(&payload)
+                .send()
+                .await
+                .wrap_err(format!(
+                    "Failed to create Cloudflare DNS record for: {}",
+                    fqdn
+                ))?;
+
+            handle_error_response(response).await?;
+        }
 
-        let zone_id = self.find_zone_id(&record_to_update.domain_name)?;
-        let fqdn = record_to_update.fqdn();
+        for domain_record_id in &changes.deleted {
+            let (dns_record_id, _, _) = parse_composite_id(domain_record_id)?;
+            let url = format!("{}/{}", records_url, dns_record_id);
+            debug!("Deleting Cloudflare DNS record at: {}", url);
+
+            let response = self
+                .request_client
+                .delete(&url)
+                .bearer_auth(self.token.expose_secret().as_str())
+                .send()
+                .await
+                .wrap_err(format!(
+                    "Failed to delete Cloudflare DNS record for: {}",
+                    fqdn
+                ))?;
+
+            handle_error_response(response).await?;
+        }
 
+        info!("Successfully updated public IP for: {}", fqdn);
+        Ok(())
+    }
+
+    fn supports_batch_updates(&self) -> bool {
+        true
+    }
+
+    async fn set_domain_record_values_batch(
+        &self,
+        domain_name: &str,
+        changes: &[RecordSetChange<'_>],
+    ) -> Result<Vec<BatchChangeOutcome>> {
+        let payload = batch_request(changes)?;
+        if payload.operation_count() > CLOUDFLARE_MAX_BATCH_OPERATIONS {
+            bail!(
+                "Batch of {} operations exceeds the Cloudflare limit of {}",
+                payload.operation_count(),
+                CLOUDFLARE_MAX_BATCH_OPERATIONS
+            );
+        }
+
+        let zone_id = self.find_zone_id(domain_name).await?;
         let url = format!(
-            "{}/zones/{}/dns_records/{}",
-            CLOUDFLARE_API_BASE_URL, zone_id, dns_record_id
+            "{}/zones/{}/dns_records/batch",
+            CLOUDFLARE_API_BASE_URL, zone_id
+        );
+        debug!(
+            "Submitting batch of {} Cloudflare DNS record operations to: {}",
+            payload.operation_count(),
+            url
         );
-        debug!("Updating Cloudflare DNS record at: {}", url);
-
-        let payload = CloudflareUpdateRecordRequest {
-            record_type: record_to_update.record_type.clone(),
-            name: fqdn.clone(),
-            content: format_record_value(&new_ip.to_string(), &record_to_update.record_type),
-            ttl,
-            proxied,
-        };
 
         let response = self
             .request_client
-            .put(&url)
+            .post(&url)
             .bearer_auth(self.token.expose_secret().as_str())
             .json(&payload)
             .send()
+            .await
             .wrap_err(format!(
-                "Failed to update Cloudflare DNS record for: {}",
-                fqdn
+                "Failed to submit Cloudflare DNS record batch for: {}",
+                domain_name
             ))?;
 
-        handle_error_response(response)?;
+        // The batch is applied as a whole, so a failure can't be attributed
+        // to single changes here.
+        handle_error_response(response).await?;
 
-        info!("Successfully updated public IP for: {}", fqdn);
-        Ok(())
+        Ok(changes
+            .iter()
+            .map(|change| {
+                info!(
+                    "Successfully updated public IP for: {}",
+                    change.record_to_update.fqdn()
+                );
+                BatchChangeOutcome::Applied
+            })
+            .collect())
     }
 }
 
+/// Build the batch request that gives each record set its new values.
+fn batch_request(changes: &[RecordSetChange<'_>]) -> Result<CloudflareBatchRequest> {
+    let mut request = CloudflareBatchRequest::default();
+    for change in changes {
+        let record_to_update = change.record_to_update;
+        let fqdn = record_to_update.fqdn();
+        let record = |value: &str, ttl: u32, proxied: bool| CloudflareUpdateRecordRequest {
+            record_type: record_to_update.record_type.clone(),
+            name: fqdn.clone(),
+            content: format_record_value(value, &record_to_update.record_type),
+            ttl,
+            proxied,
+        };
+        let value_changes = RecordValueChanges::plan(change.domain_record, change.new_values);
+
+        // New records get the TTL and proxy status of the existing ones.
+        let (_, default_ttl, default_proxied) = parse_composite_id(&change.domain_record.id)?;
+
+        for (domain_record_id, value) in &value_changes.updated {
+            let (dns_record_id, ttl, proxied) = parse_composite_id(domain_record_id)?;
+            request.puts.push(CloudflareBatchPut {
+                id: dns_record_id.to_owned(),
+                record: record(value, ttl, proxied),
+            });
+        }
+        for value in &value_changes.created {
+            request
+                .posts
+                .push(record(value, default_ttl, default_proxied));
+        }
+        for domain_record_id in &value_changes.deleted {
+            let (dns_record_id, _, _) = parse_composite_id(domain_record_id)?;
+            request.deletes.push(CloudflareBatchDelete {
+                id: dns_record_id.to_owned(),
+            });
+        }
+    }
+    Ok(request)
+}
+
+/// Parse a composite ID: `{dns_record_id}/{ttl}/{proxied}`
+fn parse_composite_id(domain_record_id: &str) -> Result<(&str, u32, bool)> {
+    let parts: Vec<&str> = domain_record_id.split('/').collect();
+    let (dns_record_id, ttl_str, proxied_str) = match parts.as_slice() {
+        [id, ttl, proxied] => (*id, *ttl, *proxied),
+        _ => {
+            bail!(
+                "Invalid Cloudflare record ID format: '{}'. Expected '{{id}}/{{ttl}}/{{proxied}}'",
+                domain_record_id
+            );
+        }
+    };
+
+    let ttl: u32 = ttl_str
+        .parse()
+        .wrap_err(format!("Failed to parse TTL from record ID: '{}'", ttl_str))?;
+    let proxied: bool = proxied_str.parse().wrap_err(format!(
+        "Failed to parse proxied flag from record ID: '{}'",
+        proxied_str
+    ))?;
+    Ok((dns_record_id, ttl, proxied))
+}
+
 impl Drop for CloudflareApi {
     fn drop(&mut self) {
         tracing::trace!("CloudflareApi object destroyed");
@@ -377,6 +583,7 @@ mod tests {
         }
     }
 
+    #[async_trait]
     impl DomainRecordApi for MockApi {
         fn provider_name(&self) -> &'static str {
             "Mock Cloudflare"
@@ -386,7 +593,7 @@ mod tests {
             ProviderType::Cloudflare
         }
 
-        fn get_domain_records(
+        async fn get_domain_records(
             &self,
             _domain_name: &str,
         ) -> Result<crate::types::DomainRecordsCommon> {
@@ -400,15 +607,15 @@ mod tests {
                 .collect();
 
             Ok(crate::types::DomainRecordsCommon {
-                records: converted_records?,
+                records: group_record_sets(converted_records?),
             })
         }
 
-        fn update_domain_ip(
+        async fn set_domain_record_values(
             &self,
-            _domain_record_id: &str,
+            _domain_record: &crate::types::DomainRecordCommon,
             _record_to_update: &DomainRecordToUpdate,
-            _new_ip: &IpAddr,
+            _new_values: &[String],
         ) -> Result<()> {
             if self.return_success {
                 Ok(())
@@ -420,7 +627,7 @@ mod tests {
 
     #[test]
     fn test_basic() {
-        use crate::updater::{get_record_to_update, should_update_domain_ip};
+        use crate::updater::{desired_record_values, get_record_to_update, record_values_differ};
 
         figment::Jail::expect_with(|jail| {
             jail.create_file(
@@ -459,24 +666,94 @@ token = "123"
             let record_to_update =
                 DomainRecordToUpdate::new(domain_name, hostname_part, record_type, None);
 
-            let records = updater.get_domain_records(domain_name).unwrap();
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let records = runtime
+                .block_on(updater.get_domain_records(domain_name))
+                .unwrap();
 
             // Verify name normalization: fixture has "home.site.com", expect "home"
             let domain_record = get_record_to_update(&records, &record_to_update).unwrap();
+            // Both values of the record set are kept
+            assert_eq!(domain_record.joined_values(), "1.2.3.4, 5.6.7.8");
             assert_eq!(domain_record.name, "home");
 
             // Verify composite ID encoding from fixture (id="rec789id000", ttl=3600, proxied=false)
             assert_eq!(domain_record.id, "rec789id000/3600/false");
 
             let (ip_addr, _ip_kind) = public_ips.to_ip_addr_from_any();
-            let should_update = should_update_domain_ip(&ip_addr, domain_record);
+            let new_values =
+                desired_record_values(&record_to_update, domain_record, &ip_addr, None);
+            assert_eq!(new_values, [ip_addr.to_string()]);
+            let should_update = record_values_differ(domain_record, &new_values);
             // IPs differ (85.212.89.12 vs 1.2.3.4), so should update
             assert!(should_update);
 
-            let result = updater.update_domain_ip(&domain_record.id, &record_to_update, &ip_addr);
+            let result = runtime.block_on(updater.set_domain_record_values(
+                domain_record,
+                &record_to_update,
+                &new_values,
+            ));
             assert!(result.is_err());
 
             Ok(())
         });
     }
+
+    #[test]
+    fn test_batch_request() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let records = runtime
+            .block_on(MockApi::new("site.com").get_domain_records("site.com"))
+            .unwrap();
+        let home = DomainRecordToUpdate::new("site.com", "home", "A", None);
+        let home_record = crate::updater::get_record_to_update(&records, &home).unwrap();
+        let www = DomainRecordToUpdate::new("site.com", "www", "AAAA", None);
+        let www_record = crate::types::DomainRecordCommon {
+            id: "rec123/300/true".to_owned(),
+            record_type: "AAAA".to_owned(),
+            name: "www".to_owned(),
+            values: vec![DomainRecordValue {
+                id: "rec123/300/true".to_owned(),
+                value: "2001:db8::1".to_owned(),
+            }],
+        };
+        let home_values = ["85.212.89.12".to_owned()];
+        let www_values = ["2001:db8::1".to_owned(), "2001:db8::2".to_owned()];
+        let changes = [
+            RecordSetChange {
+                domain_record: home_record,
+                record_to_update: &home,
+                new_values: &home_values,
+            },
+            RecordSetChange {
+                domain_record: &www_record,
+                record_to_update: &www,
+                new_values: &www_values,
+            },
+        ];
+
+        let request = batch_request(&changes).unwrap();
+        assert_eq!(request.operation_count(), 3);
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "deletes": [{ "id": "rec789id001" }],
+                "puts": [{
+                    "id": "rec789id000",
+                    "type": "A",
+                    "name": "home.site.com",
+                    "content": "85.212.89.12",
+                    "ttl": 3600,
+                    "proxied": false
+                }],
+                "posts": [{
+                    "type": "AAAA",
+                    "name": "www.site.com",
+                    "content": "2001:db8::2",
+                    "ttl": 300,
+                    "proxied": true
+                }]
+            })
+        );
+    }
 }
@@ -1,16 +1,26 @@
-use color_eyre::eyre::{bail, eyre, Error, Result, WrapErr};
-use reqwest::blocking::Client;
+use async_trait::async_trait;
+use color_eyre::eyre::{bail, Error, Result, WrapErr};
 use secrecy::ExposeSecret;
 use serde::{Deserialize, Serialize};
-use std::net::IpAddr;
 use tracing::{debug, info};
 
 use crate::config::provider_config::{ProviderType, SecretProviderToken};
-use crate::domain_record_api::{format_record_value, strip_record_value, DomainRecordApi};
-use crate::types::DomainRecordToUpdate;
+use crate::domain_record_api::rate_limit::{RateLimit, RateLimitedClient};
+use crate::domain_record_api::{
+    format_record_value, strip_record_value, ApiHttpError, BatchChangeOutcome, DomainRecordApi,
+    RecordSetChange,
+};
+use crate::types::{DomainRecordToUpdate, DomainRecordValue};
 
 const DESEC_API_BASE_URL: &str = "https://desec.io/api/v1";
 
+/// deSEC allows 2 `RRset` changes per second and 15 per minute per domain, and
+/// throttles reads separately. The stricter limit is used for all requests.
+pub const DESEC_RATE_LIMIT: RateLimit = RateLimit {
+    burst: 2,
+    per_minute: 15,
+};
+
 #[derive(Deserialize, Debug, Clone)]
 struct DesecRRSet {
     subname: String,
@@ -26,28 +36,42 @@ struct DesecPatchRRSetRequest {
     records: Vec<String>,
 }
 
+/// One `RRset` of a bulk PATCH of the `RRsets` of a domain.
+#[derive(Serialize, Debug)]
+struct DesecBulkRRSetRequest {
+    subname: String,
+    #[serde(rename = "type")]
+    record_type: String,
+    records: Vec<String>,
+}
+
 #[derive(Deserialize, Debug)]
 struct DesecErrorResponse {
     detail: Option<String>,
 }
 
 /// Handle deSEC API error responses.
-fn handle_error_response(
-    response: reqwest::blocking::Response,
-) -> Result<reqwest::blocking::Response> {
+async fn handle_error_response(response: reqwest::Response) -> Result<reqwest::Response> {
     let status = response.status();
     if !status.is_success() {
         let error_text = response
             .text()
+            .await
             .unwrap_or_else(|_| "Unable to read error response".to_string());
 
         if let Ok(error_response) = serde_json::from_str::<DesecErrorResponse>(&error_text) {
             if let Some(detail) = error_response.detail {
-                bail!("deSEC API error ({}): {}", status, detail);
+                return Err(ApiHttpError::new(
+                    status,
+                    format!("deSEC API error ({}): {}", status, detail),
+                )
+                .into());
             }
         }
 
-        bail!("HTTP {} error: {}", status, error_text);
+        return Err(
+            ApiHttpError::new(status, format!("HTTP {} error: {}", status, error_text)).into(),
+        );
     }
 
     Ok(response)
@@ -57,14 +81,13 @@ impl TryFrom<DesecRRSet> for crate::types::DomainRecordCommon {
     type Error = Error;
 
     fn try_from(rrset: DesecRRSet) -> Result<Self, Self::Error> {
-        let ip_value = strip_record_value(rrset.records.first().ok_or_else(|| {
-            eyre!(
+        if rrset.records.is_empty() {
+            bail!(
                 "RRSet '{}' (type: {}) has no records",
                 rrset.subname,
                 rrset.record_type
-            )
-        })?)
-        .to_string();
+            );
+        }
 
         // Normalize subname: deSEC uses "" for apex, we use "@"
         let hostname_part = if rrset.subname.is_empty() {
@@ -76,29 +99,39 @@ impl TryFrom<DesecRRSet> for crate::types::DomainRecordCommon {
         // Composite ID: "{subname}/{type}" — same format as Hetzner
         let composite_id = format!("{}/{}", hostname_part, rrset.record_type);
 
+        let values = rrset
+            .records
+            .iter()
+            .map(|record| DomainRecordValue {
+                id: composite_id.clone(),
+                value: strip_record_value(record).to_string(),
+            })
+            .collect();
+
         Ok(Self {
             id: composite_id,
             record_type: rrset.record_type,
             name: hostname_part,
-            ip_value,
+            values,
         })
     }
 }
 
 pub struct DesecApi {
-    request_client: Client,
+    request_client: RateLimitedClient,
     token: SecretProviderToken,
 }
 
 impl DesecApi {
-    pub fn new(token: SecretProviderToken) -> Self {
+    pub fn new(token: SecretProviderToken, request_client: RateLimitedClient) -> Self {
         Self {
-            request_client: Client::new(),
+            request_client,
             token,
         }
     }
 }
 
+#[async_trait]
 impl DomainRecordApi for DesecApi {
     fn provider_name(&self) -> &'static str {
         "deSEC"
@@ -108,7 +141,10 @@ impl DomainRecordApi for DesecApi {
         ProviderType::Desec
     }
 
-    fn get_domain_records(&self, domain_name: &str) -> Result<crate::types::DomainRecordsCommon> {
+    async fn get_domain_records(
+        &self,
+        domain_name: &str,
+    ) -> Result<crate::types::DomainRecordsCommon> {
         let url = format!("{}/domains/{}/rrsets/", DESEC_API_BASE_URL, domain_name);
         debug!("Fetching deSEC RRsets from: {}", url);
 
@@ -120,12 +156,14 @@ impl DomainRecordApi for DesecApi {
                 format!("Token {}", self.token.expose_secret().as_str()),
             )
             .send()
+            .await
             .wrap_err("Failed to query deSEC API for RRsets")?;
 
-        let response = handle_error_response(response)?;
+        let response = handle_error_response(response).await?;
 
         let rrsets: Vec<DesecRRSet> = response
             .json()
+            .await
             .wrap_err("Failed to parse deSEC RRsets JSON response")?;
 
         debug!("Found {} RRsets for domain '{}'", rrsets.len(), domain_name);
@@ -149,22 +187,13 @@ impl DomainRecordApi for DesecApi {
         })
     }
 
-    fn update_domain_ip(
+    async fn set_domain_record_values(
         &self,
-        domain_record_id: &str,
+        domain_record: &crate::types::DomainRecordCommon,
         record_to_update: &DomainRecordToUpdate,
-        new_ip: &IpAddr,
+        new_values: &[String],
     ) -> Result<()> {
-        let parts: Vec<&str> = domain_record_id.split('/').collect();
-        let (subname, rr_type) = match parts.as_slice() {
-            [name, record_type] => (*name, *record_type),
-            _ => {
-                bail!(
-                    "Invalid deSEC record ID format: '{}'. Expected '{{subname}}/{{type}}'",
-                    domain_record_id
-                );
-            }
-        };
+        let (subname, rr_type) = parse_composite_id(&domain_record.id)?;
 
         let fqdn = record_to_update.fqdn();
 
@@ -176,10 +205,10 @@ impl DomainRecordApi for DesecApi {
         debug!("Updating deSEC RRset at: {}", url);
 
         let payload = DesecPatchRRSetRequest {
-            records: vec![format_record_value(
-                &new_ip.to_string(),
-                &record_to_update.record_type,
-            )],
+            records: new_values
+                .iter()
+                .map(|value| format_record_value(value, &record_to_update.record_type))
+                .collect(),
         };
 
         let response = self
@@ -191,13 +220,125 @@ impl DomainRecordApi for DesecApi {
             )
             .json(&payload)
             .send()
+            .await
             .wrap_err(format!("Failed to update deSEC RRset for: {}", fqdn))?;
 
-        handle_error_response(response)?;
+        handle_error_response(response).await?;
 
         info!("Successfully updated public IP for: {}", fqdn);
         Ok(())
     }
+
+    fn supports_batch_updates(&self) -> bool {
+        true
+    }
+
+    async fn set_domain_record_values_batch(
+        &self,
+        domain_name: &str,
+        changes: &[RecordSetChange<'_>],
+    ) -> Result<Vec<BatchChangeOutcome>> {
+        let payload = changes
+            .iter()
+            .map(|change| {
+                let (subname, rr_type) = parse_composite_id(&change.domain_record.id)?;
+                Ok(DesecBulkRRSetRequest {
+                    // deSEC uses "" for apex in request bodies
+                    subname: if subname == "@" { "" } else { subname }.to_owned(),
+                    record_type: rr_type.to_owned(),
+                    records: change
+                        .new_values
+                        .iter()
+                        .map(|value| {
+                            format_record_value(value, &change.record_to_update.record_type)
+                        })
+                        .collect(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let url = format!("{}/domains/{}/rrsets/", DESEC_API_BASE_URL, domain_name);
+        debug!("Updating {} deSEC RRsets at: {}", payload.len(), url);
+
+        let response = self
+            .request_client
+            .patch(&url)
+            .header(
+                "Authorization",
+                format!("Token {}", self.token.expose_secret().as_str()),
+            )
+            .json(&payload)
+            .send()
+            .await
+            .wrap_err(format!("Failed to update deSEC RRsets of: {}", domain_name))?;
+
+        // deSEC applies bulk requests as a whole. When some RRsets are
+        // invalid, it responds with one error object per RRset.
+        let status = response.status();
+        if status == reqwest::StatusCode::BAD_REQUEST {
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unable to read error response".to_string());
+            if let Some(outcomes) = bulk_error_outcomes(status, &error_text, changes.len()) {
+                return Ok(outcomes);
+            }
+            return Err(ApiHttpError::new(
+                status,
+                format!("HTTP {} error: {}", status, error_text),
+            )
+            .into());
+        }
+        handle_error_response(response).await?;
+
+        Ok(changes
+            .iter()
+            .map(|change| {
+                info!(
+                    "Successfully updated public IP for: {}",
+                    change.record_to_update.fqdn()
+                );
+                BatchChangeOutcome::Applied
+            })
+            .collect())
+    }
+}
+
+/// Parse a composite ID: "{subname}/{type}"
+fn parse_composite_id(domain_record_id: &str) -> Result<(&str, &str)> {
+    let parts: Vec<&str> = domain_record_id.split('/').collect();
+    match parts.as_slice() {
+        [subname, record_type] => Ok((*subname, *record_type)),
+        _ => bail!(
+            "Invalid deSEC record ID format: '{}'. Expected '{{subname}}/{{type}}'",
+            domain_record_id
+        ),
+    }
+}
+
+/// Attribute the errors of a failed bulk request to its `RRsets`. `RRsets`
+/// without errors weren't applied either, as the request failed as a whole.
+fn bulk_error_outcomes(
+    status: reqwest::StatusCode,
+    error_text: &str,
+    change_count: usize,
+) -> Option<Vec<BatchChangeOutcome>> {
+    let errors: Vec<serde_json::Value> = serde_json::from_str(error_text).ok()?;
+    if errors.len() != change_count {
+        return None;
+    }
+    Some(
+        errors
+            .into_iter()
+            .map(|error| match error.as_object() {
+                Some(fields) if fields.is_empty() => BatchChangeOutcome::NotApplied,
+                _ => BatchChangeOutcome::Failed(
+                    ApiHttpError::new(status, format!("deSEC API error ({}): {}", status, error))
+                        .into(),
+                ),
+            })
+            .collect(),
+    )
 }
 
 impl Drop for DesecApi {
@@ -242,6 +383,7 @@ mod tests {
         }
     }
 
+    #[async_trait]
     impl DomainRecordApi for MockApi {
         fn provider_name(&self) -> &'static str {
             "Mock deSEC"
@@ -251,7 +393,7 @@ mod tests {
             ProviderType::Desec
         }
 
-        fn get_domain_records(
+        async fn get_domain_records(
             &self,
             _domain_name: &str,
         ) -> Result<crate::types::DomainRecordsCommon> {
@@ -269,11 +411,11 @@ mod tests {
             })
         }
 
-        fn update_domain_ip(
+        async fn set_domain_record_values(
             &self,
-            _domain_record_id: &str,
+            _domain_record: &crate::types::DomainRecordCommon,
             _record_to_update: &DomainRecordToUpdate,
-            _new_ip: &IpAddr,
+            _new_values: &[String],
         ) -> Result<()> {
             if self.return_success {
                 Ok(())
@@ -285,7 +427,7 @@ mod tests {
 
     #[test]
     fn test_basic() {
-        use crate::updater::{get_record_to_update, should_update_domain_ip};
+        use crate::updater::{desired_record_values, get_record_to_update, record_values_differ};
 
         figment::Jail::expect_with(|jail| {
             jail.create_file(
@@ -324,25 +466,58 @@ token = "123"
             let record_to_update =
                 DomainRecordToUpdate::new(domain_name, hostname_part, record_type, None);
 
-            let records = updater.get_domain_records(domain_name).unwrap();
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let records = runtime
+                .block_on(updater.get_domain_records(domain_name))
+                .unwrap();
 
             // Verify name is preserved (subname "home" stays "home")
             let domain_record = get_record_to_update(&records, &record_to_update).unwrap();
+            // Both values of the record set are kept
+            assert_eq!(domain_record.joined_values(), "1.2.3.4, 5.6.7.8");
             assert_eq!(domain_record.name, "home");
 
             // Verify composite ID: "{subname}/{type}"
             assert_eq!(domain_record.id, "home/A");
 
             let (ip_addr, _ip_kind) = public_ips.to_ip_addr_from_any();
-            let should_update = should_update_domain_ip(&ip_addr, domain_record);
+            let new_values =
+                desired_record_values(&record_to_update, domain_record, &ip_addr, None);
+            assert_eq!(new_values, [ip_addr.to_string()]);
+            let should_update = record_values_differ(domain_record, &new_values);
             // IPs differ (85.212.89.12 vs 1.2.3.4), so should update
             assert!(should_update);
 
-            let result = updater.update_domain_ip(&domain_record.id, &record_to_update, &ip_addr);
+            let result = runtime.block_on(updater.set_domain_record_values(
+                domain_record,
+                &record_to_update,
+                &new_values,
+            ));
             // Mock always returns Err
             assert!(result.is_err());
 
             Ok(())
         });
     }
+
+    #[test]
+    fn test_bulk_error_outcomes() {
+        let status = reqwest::StatusCode::BAD_REQUEST;
+        let error_text = r#"[{}, {"records": ["Enter a valid IPv4 address."]}]"#;
+        let outcomes = bulk_error_outcomes(status, error_text, 2).unwrap();
+        assert!(matches!(
+            outcomes.as_slice(),
+            [
+                BatchChangeOutcome::NotApplied,
+                BatchChangeOutcome::Failed(_)
+            ]
+        ));
+        if let Some(BatchChangeOutcome::Failed(error)) = outcomes.get(1) {
+            assert!(error.to_string().contains("Enter a valid IPv4 address."));
+        }
+
+        // Errors that can't be attributed fail the whole batch.
+        assert!(bulk_error_outcomes(status, error_text, 3).is_none());
+        assert!(bulk_error_outcomes(status, r#"{"detail": "Bad request"}"#, 2).is_none());
+    }
 }
@@ -1,13 +1,19 @@
+use async_trait::async_trait;
 use color_eyre::eyre::{bail, Error, Result, WrapErr};
-use reqwest::blocking::Client;
 use secrecy::ExposeSecret;
 use serde::Deserialize;
-use std::net::IpAddr;
+use std::collections::HashMap;
 use tracing::{info, trace};
 
 use crate::config::provider_config::{ProviderType, SecretProviderToken};
-use crate::domain_record_api::{format_record_value, strip_record_value, DomainRecordApi};
-use crate::types::{DomainRecordCommon, DomainRecordToUpdate, DomainRecordsCommon};
+use crate::domain_record_api::rate_limit::{RateLimit, RateLimitedClient};
+use crate::domain_record_api::{
+    format_record_value, group_record_sets, record_values_match, strip_record_value, ApiHttpError,
+    DomainRecordApi, RecordValueChanges,
+};
+use crate::types::{
+    DomainRecordCommon, DomainRecordToUpdate, DomainRecordValue, DomainRecordsCommon,
+};
 
 #[derive(Deserialize, Debug)]
 pub struct DomainRecordDigitalOcean {
@@ -29,6 +35,12 @@ pub struct UpdateDomainRecordResponse {
     pub domain_record: DomainRecordDigitalOcean,
 }
 
+#[derive(Deserialize, Debug)]
+struct DigitalOceanErrorResponse {
+    id: String,
+    message: String,
+}
+
 impl TryFrom<DomainRecordDigitalOcean> for DomainRecordCommon {
     type Error = Error;
 
@@ -37,7 +49,10 @@ impl TryFrom<DomainRecordDigitalOcean> for DomainRecordCommon {
             id: record.id.to_string(),
             record_type: record.record_type,
             name: record.name,
-            ip_value: strip_record_value(&record.data).to_string(),
+            values: vec![DomainRecordValue {
+                id: record.id.to_string(),
+                value: strip_record_value(&record.data).to_string(),
+            }],
         })
     }
 }
@@ -52,28 +67,63 @@ impl TryFrom<DomainRecordsDigitalOcean> for DomainRecordsCommon {
             .map(|record| record.try_into())
             .collect();
 
+        // DigitalOcean has a separate record for each value of a record set.
         Ok(Self {
-            records: converted_records?,
+            records: group_record_sets(converted_records?),
         })
     }
 }
 
+async fn handle_error_response(response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Unable to read error response".to_string());
+
+        if let Ok(error_response) = serde_json::from_str::<DigitalOceanErrorResponse>(&error_text) {
+            return Err(ApiHttpError::new(
+                status,
+                format!(
+                    "DigitalOcean API error ({}): {}",
+                    error_response.id, error_response.message
+                ),
+            )
+            .into());
+        }
+
+        return Err(
+            ApiHttpError::new(status, format!("HTTP {} error: {}", status, error_text)).into(),
+        );
+    }
+
+    Ok(response)
+}
+
 const DIGITAL_OCEAN_API_HOST_NAME: &str = "https://api.digitalocean.com";
 
+/// `DigitalOcean` allows 250 requests per minute and 5000 per hour.
+pub const DIGITAL_OCEAN_RATE_LIMIT: RateLimit = RateLimit {
+    burst: 10,
+    per_minute: 80,
+};
+
 pub struct DigitalOceanApi {
-    request_client: Client,
+    request_client: RateLimitedClient,
     token: SecretProviderToken,
 }
 
 impl DigitalOceanApi {
-    pub fn new(token: SecretProviderToken) -> Self {
+    pub fn new(token: SecretProviderToken, request_client: RateLimitedClient) -> Self {
         Self {
-            request_client: Client::new(),
+            request_client,
             token,
         }
     }
 }
 
+#[async_trait]
 impl DomainRecordApi for DigitalOceanApi {
     fn provider_name(&self) -> &'static str {
         "DigitalOcean"
@@ -83,7 +133,7 @@ impl DomainRecordApi for DigitalOceanApi {
         ProviderType::DigitalOcean
     }
 
-    fn get_domain_records(&self, domain_name: &str) -> Result<DomainRecordsCommon> {
+    async fn get_domain_records(&self, domain_name: &str) -> Result<DomainRecordsCommon> {
         let endpoint = format!("/v2/domains/{domain_name}/records?per_page=200");
         let request_url = format!("{DIGITAL_OCEAN_API_HOST_NAME}{endpoint}");
         let response = self
@@ -91,9 +141,12 @@ impl DomainRecordApi for DigitalOceanApi {
             .get(request_url)
             .bearer_auth(self.token.expose_secret().as_str())
             .send()
+            .await
             .wrap_err("Failed to query DO for domain records")?;
+        let response = handle_error_response(response).await?;
         let response_text = response
             .text()
+            .await
             .wrap_err("Failed to retrieve domain records response text")?;
         let records: DomainRecordsDigitalOcean =
             serde_json::from_str(&response_text).wrap_err(format!(
@@ -104,42 +157,84 @@ impl DomainRecordApi for DigitalOceanApi {
     }
 
     // Extract domain and hostname part into separate struct.
-    fn update_domain_ip(
+    async fn set_domain_record_values(
         &self,
-        domain_record_id: &str,
+        domain_record: &DomainRecordCommon,
         record_to_update: &DomainRecordToUpdate,
-        new_ip: &IpAddr,
+        new_values: &[String],
     ) -> Result<()> {
         let fqdn = record_to_update.fqdn();
-        let endpoint = format!(
-            "/v2/domains/{}/records/{}",
-            record_to_update.domain_name, domain_record_id
+        let records_url = format!(
+            "{DIGITAL_OCEAN_API_HOST_NAME}/v2/domains/{}/records",
+            record_to_update.domain_name
         );
-        let request_url = format!("{DIGITAL_OCEAN_API_HOST_NAME}{endpoint}");
-        let client = Client::new();
-        let mut body = std::collections::HashMap::new();
-        let value = format_record_value(&new_ip.to_string(), &record_to_update.record_type);
-        body.insert("data", value);
-        let response = client
-            .put(request_url)
-            .bearer_auth(self.token.expose_secret().as_str())
-            .json(&body)
-            .send()
-            .wrap_err(format!("Failed to update domain record for: {fqdn}"))?;
-
-        let record: UpdateDomainRecordResponse = response
-            .json()
-            .wrap_err("Failed to parse domain record response JSON data")?;
-        let response_ip = record
-            .domain_record
-            .data
-            .parse::<IpAddr>()
-            .expect("Failed to parse IP from response");
-        if &response_ip != new_ip {
-            bail!(format!("Failed to update IP for: {fqdn}"))
-        } else {
-            info!("Successfully updated public IP for: {}", fqdn);
+        let changes = RecordValueChanges::plan(domain_record, new_values);
+
+        for (domain_record_id, value) in &changes.updated {
+            let mut body = HashMap::new();
+            body.insert(
+                "data",
+                format_record_value(value, &record_to_update.record_type),
+            );
+            let response = self
+                .request_client
+                .put(format!("{records_url}/{domain_record_id}"))
+                .bearer_auth(self.token.expose_secret().as_str())
+                .json(&body)
+                .send()
+                .await
+                .wrap_err(format!("Failed to update domain record for: {fqdn}"))?;
+            let response = handle_error_response(response).await?;
+
+            let record: UpdateDomainRecordResponse = response
+                .json()
+                .await
+                .wrap_err("Failed to parse domain record response JSON data")?;
+            if !record_values_match(strip_record_value(&record.domain_record.data), value) {
+                bail!(format!("Failed to update IP for: {fqdn}"))
+            }
         }
+
+        for value in &changes.created {
+            let mut body = HashMap::new();
+            body.insert("type", record_to_update.record_type.clone());
+            body.insert("name", record_to_update.hostname_part.clone());
+            body.insert(
+                "data",
+                format_record_value(value, &record_to_update.record_type),
+            );
+            let response = self
+                .request_client
+                .post(&records_url)
+                .bearer_auth(self.token.expose_secret().as_str())
+                .json(&body)
+                .send()
+                .await
+                .wrap_err(format!("Failed to create domain record for: {fqdn}"))?;
+            let response = handle_error_response(response).await?;
+
+            let record: UpdateDomainRecordResponse = response
+                .json()
+                .await
+                .wrap_err("Failed to parse domain record response JSON data")?;
+            if !record_values_match(strip_record_value(&record.domain_record.data), value) {
+                bail!(format!("Failed to add value '{value}' to: {fqdn}"))
+            }
+        }
+
+        for domain_record_id in &changes.deleted {
+            self.request_client
+                .delete(format!("{records_url}/{domain_record_id}"))
+                .bearer_auth(self.token.expose_secret().as_str())
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .wrap_err(format!(
+                    "Failed to delete domain record '{domain_record_id}' of: {fqdn}"
+                ))?;
+        }
+
+        info!("Successfully updated public IP for: {}", fqdn);
         Ok(())
     }
 }
@@ -184,6 +279,7 @@ mod tests {
         }
     }
 
+    #[async_trait]
     impl DomainRecordApi for MockApi {
         fn provider_name(&self) -> &'static str {
             "Mock"
@@ -193,16 +289,16 @@ mod tests {
             ProviderType::DigitalOcean
         }
 
-        fn get_domain_records(&self, _domain_name: &str) -> Result<DomainRecordsCommon> {
+        async fn get_domain_records(&self, _domain_name: &str) -> Result<DomainRecordsCommon> {
             let s = Self::get_mock_domain_records_response();
             Self::parse_domain_records(&s).and_then(|records| records.try_into())
         }
 
-        fn update_domain_ip(
+        async fn set_domain_record_values(
             &self,
-            _domain_record_id: &str,
+            _domain_record: &DomainRecordCommon,
             _record_to_update: &DomainRecordToUpdate,
-            _new_ip: &IpAddr,
+            _new_values: &[String],
         ) -> Result<()> {
             if self.return_success {
                 Ok(())
@@ -214,7 +310,7 @@ mod tests {
 
     #[test]
     fn test_basic() {
-        use crate::updater::{get_record_to_update, should_update_domain_ip};
+        use crate::updater::{desired_record_values, get_record_to_update, record_values_differ};
 
         figment::Jail::expect_with(|jail| {
             jail.create_file(
@@ -249,14 +345,26 @@ digital_ocean_token = "123"
             let record_to_update =
                 DomainRecordToUpdate::new(domain_name, hostname_part, record_type, None);
 
-            let records = updater.get_domain_records(domain_name).unwrap();
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let records = runtime
+                .block_on(updater.get_domain_records(domain_name))
+                .unwrap();
             let domain_record = get_record_to_update(&records, &record_to_update).unwrap();
+            // Both values of the record set are kept
+            assert_eq!(domain_record.joined_values(), "1.2.3.4, 5.6.7.8");
             let (ip_addr, _ip_kind) = public_ips.to_ip_addr_from_any();
-            let should_update = should_update_domain_ip(&ip_addr, domain_record);
+            let new_values =
+                desired_record_values(&record_to_update, domain_record, &ip_addr, None);
+            assert_eq!(new_values, [ip_addr.to_string()]);
+            let should_update = record_values_differ(domain_record, &new_values);
 
             assert!(should_update);
 
-            let result = updater.update_domain_ip(&domain_record.id, &record_to_update, &ip_addr);
+            let result = runtime.block_on(updater.set_domain_record_values(
+                domain_record,
+                &record_to_update,
+                &new_values,
+            ));
             assert!(result.is_err());
 
             Ok(())
@@ -1,18 +1,26 @@
+use async_trait::async_trait;
 use color_eyre::eyre::{bail, eyre, Error, Result, WrapErr};
-use reqwest::blocking::Client;
 use secrecy::ExposeSecret;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::net::IpAddr;
 use std::sync::RwLock;
 use tracing::{debug, info, trace};
 
 use crate::config::provider_config::{ProviderType, SecretProviderToken};
-use crate::domain_record_api::{format_record_value, strip_record_value, DomainRecordApi};
-use crate::types::DomainRecordToUpdate;
+use crate::domain_record_api::rate_limit::{RateLimit, RateLimitedClient};
+use crate::domain_record_api::{
+    format_record_value, strip_record_value, ApiHttpError, DomainRecordApi,
+};
+use crate::types::{DomainRecordToUpdate, DomainRecordValue};
 
 const HETZNER_API_BASE_URL: &str = "https://api.hetzner.cloud/v1";
 
+/// Hetzner allows 3600 requests per hour.
+pub const HETZNER_RATE_LIMIT: RateLimit = RateLimit {
+    burst: 10,
+    per_minute: 60,
+};
+
 #[derive(Deserialize, Debug)]
 struct HetznerCloudZonesResponse {
     zones: Vec<HetznerZone>,
@@ -89,21 +97,21 @@ impl<'a> TryFrom<HetznerRRSetWithZone<'a>> for crate::types::DomainRecordCommon
         let rrset = wrapper.rrset;
         let zone_name = wrapper.zone_name;
 
-        // Extract first record value (typical for A/AAAA single-IP records)
-        let ip_value = strip_record_value(
-            &rrset
-                .records
-                .first()
-                .ok_or_else(|| {
-                    eyre!(
-                        "RRSet '{}' (type: {}) has no records",
-                        rrset.name,
-                        rrset.record_type
-                    )
-                })?
-                .value,
-        )
-        .to_string();
+        if rrset.records.is_empty() {
+            bail!(
+                "RRSet '{}' (type: {}) has no records",
+                rrset.name,
+                rrset.record_type
+            );
+        }
+        let values = rrset
+            .records
+            .iter()
+            .map(|record| DomainRecordValue {
+                id: rrset.id.clone(),
+                value: strip_record_value(&record.value).to_string(),
+            })
+            .collect();
 
         // Normalize the name field to contain only the hostname part
         // Hetzner returns full FQDNs like "subdomain.domain.com" or "@"
@@ -126,7 +134,7 @@ impl<'a> TryFrom<HetznerRRSetWithZone<'a>> for crate::types::DomainRecordCommon
             id: rrset.id,
             record_type: rrset.record_type,
             name: hostname_part,
-            ip_value,
+            values,
         })
     }
 }
@@ -135,10 +143,10 @@ impl<'a> TryFrom<HetznerRRSetWithZone<'a>> for crate::types::DomainRecordCommon
 ///
 /// The closure can return `Some(error_msg)` to provide a custom error, or
 /// `None` to fall back to the default error message.
-fn handle_error_response_with_custom<F>(
-    response: reqwest::blocking::Response,
+async fn handle_error_response_with_custom<F>(
+    response: reqwest::Response,
     custom_error_handler: F,
-) -> Result<reqwest::blocking::Response>
+) -> Result<reqwest::Response>
 where
     F: FnOnce(String, String) -> Option<String>,
 {
@@ -146,6 +154,7 @@ where
     if !status.is_success() {
         let error_text = response
             .text()
+            .await
             .unwrap_or_else(|_| "Unable to read error response".to_string());
 
         if let Ok(error_response) = serde_json::from_str::<HetznerErrorResponse>(&error_text) {
@@ -154,46 +163,49 @@ where
                 error_response.error.code.clone(),
                 error_response.error.message.clone(),
             ) {
-                bail!("{}", custom_msg);
+                return Err(ApiHttpError::new(status, custom_msg).into());
             }
 
             // Default error message
-            bail!(
-                "Hetzner API error ({}): {}",
-                error_response.error.code,
-                error_response.error.message
-            );
+            return Err(ApiHttpError::new(
+                status,
+                format!(
+                    "Hetzner API error ({}): {}",
+                    error_response.error.code, error_response.error.message
+                ),
+            )
+            .into());
         }
 
-        bail!("HTTP {} error: {}", status, error_text);
+        return Err(
+            ApiHttpError::new(status, format!("HTTP {} error: {}", status, error_text)).into(),
+        );
     }
 
     Ok(response)
 }
 
-fn handle_error_response(
-    response: reqwest::blocking::Response,
-) -> Result<reqwest::blocking::Response> {
-    handle_error_response_with_custom(response, |_, _| None)
+async fn handle_error_response(response: reqwest::Response) -> Result<reqwest::Response> {
+    handle_error_response_with_custom(response, |_, _| None).await
 }
 
 pub struct HetznerApi {
-    request_client: Client,
+    request_client: RateLimitedClient,
     token: SecretProviderToken,
     zone_cache: RwLock<HashMap<String, i64>>,
 }
 
 impl HetznerApi {
-    pub fn new(token: SecretProviderToken) -> Self {
+    pub fn new(token: SecretProviderToken, request_client: RateLimitedClient) -> Self {
         Self {
-            request_client: Client::new(),
+            request_client,
             token,
             zone_cache: RwLock::new(HashMap::new()),
         }
     }
 
     /// Find zone ID by name, with caching to avoid redundant API calls.
-    fn find_zone_id(&self, zone_name: &str) -> Result<i64> {
+    async fn find_zone_id(&self, zone_name: &str) -> Result<i64> {
         {
             let cache = self
                 .zone_cache
@@ -214,12 +226,14 @@ impl HetznerApi {
             .get(&url)
             .bearer_auth(self.token.expose_secret().as_str())
             .send()
+            .await
             .wrap_err("Failed to query Hetzner Cloud API for zones")?;
 
-        let response = handle_error_response(response)?;
+        let response = handle_error_response(response).await?;
 
         let zones_response: HetznerCloudZonesResponse = response
             .json()
+            .await
             .wrap_err("Failed to parse zones JSON response")?;
 
         let zone = zones_response
@@ -248,6 +262,7 @@ impl HetznerApi {
     }
 }
 
+#[async_trait]
 impl DomainRecordApi for HetznerApi {
     fn provider_name(&self) -> &'static str {
         "Hetzner Cloud"
@@ -257,8 +272,11 @@ impl DomainRecordApi for HetznerApi {
         ProviderType::Hetzner
     }
 
-    fn get_domain_records(&self, domain_name: &str) -> Result<crate::types::DomainRecordsCommon> {
-        let zone_id = self.find_zone_id(domain_name)?;
+    async fn get_domain_records(
+        &self,
+        domain_name: &str,
+    ) -> Result<crate::types::DomainRecordsCommon> {
+        let zone_id = self.find_zone_id(domain_name).await?;
 
         let url = format!("{}/zones/{}/rrsets", HETZNER_API_BASE_URL, zone_id);
         debug!("Fetching RRSets from: {}", url);
@@ -268,6 +286,7 @@ impl DomainRecordApi for HetznerApi {
             .get(&url)
             .bearer_auth(self.token.expose_secret().as_str())
             .send()
+            .await
             .wrap_err("Failed to query Hetzner Cloud API for RRSets")?;
 
         let domain_name_owned = domain_name.to_string();
@@ -280,10 +299,12 @@ impl DomainRecordApi for HetznerApi {
             } else {
                 None
             }
-        })?;
+        })
+        .await?;
 
         let rrsets_response: HetznerRRSetsResponse = response
             .json()
+            .await
             .wrap_err("Failed to parse RRSets JSON response")?;
 
         debug!(
@@ -311,13 +332,14 @@ impl DomainRecordApi for HetznerApi {
         })
     }
 
-    fn update_domain_ip(
+    async fn set_domain_record_values(
         &self,
-        domain_record_id: &str,
+        domain_record: &crate::types::DomainRecordCommon,
         record_to_update: &DomainRecordToUpdate,
-        new_ip: &IpAddr,
+        new_values: &[String],
     ) -> Result<()> {
-        let zone_id = self.find_zone_id(&record_to_update.domain_name)?;
+        let domain_record_id = domain_record.id.as_str();
+        let zone_id = self.find_zone_id(&record_to_update.domain_name).await?;
         let fqdn = record_to_update.fqdn();
 
         // Parse composite ID (format: "name/type", e.g., "www/A")
@@ -338,12 +360,14 @@ impl DomainRecordApi for HetznerApi {
         );
         debug!("Updating RRSet at: {}", url);
 
-        let value = format_record_value(&new_ip.to_string(), &record_to_update.record_type);
         let payload = SetRecordsRequest {
-            records: vec![HetznerRecordInput {
-                value,
-                comment: None,
-            }],
+            records: new_values
+                .iter()
+                .map(|value| HetznerRecordInput {
+                    value: format_record_value(value, &record_to_update.record_type),
+                    comment: None,
+                })
+                .collect(),
         };
 
         let response = self
@@ -352,6 +376,7 @@ impl DomainRecordApi for HetznerApi {
             .bearer_auth(self.token.expose_secret().as_str())
             .json(&payload)
             .send()
+            .await
             .wrap_err(format!("Failed to update RRSet for: {}", fqdn))?;
 
         let domain_record_id_owned = domain_record_id.to_string();
@@ -364,7 +389,8 @@ impl DomainRecordApi for HetznerApi {
                 domain_record_id_owned
             )),
             _ => None,
-        })?;
+        })
+        .await?;
 
         info!("Successfully updated public IP for: {}", fqdn);
         Ok(())
@@ -414,6 +440,7 @@ mod tests {
         }
     }
 
+    #[async_trait]
     impl DomainRecordApi for MockApi {
         fn provider_name(&self) -> &'static str {
             "Mock Hetzner"
@@ -423,7 +450,7 @@ mod tests {
             ProviderType::Hetzner
         }
 
-        fn get_domain_records(
+        async fn get_domain_records(
             &self,
             _domain_name: &str,
         ) -> Result<crate::types::DomainRecordsCommon> {
@@ -442,11 +469,11 @@ mod tests {
             })
         }
 
-        fn update_domain_ip(
+        async fn set_domain_record_values(
             &self,
-            _domain_record_id: &str,
+            _domain_record: &crate::types::DomainRecordCommon,
             _record_to_update: &DomainRecordToUpdate,
-            _new_ip: &IpAddr,
+            _new_values: &[String],
         ) -> Result<()> {
             if self.return_success {
                 Ok(())
@@ -458,7 +485,7 @@ mod tests {
 
     #[test]
     fn test_basic() {
-        use crate::updater::{get_record_to_update, should_update_domain_ip};
+        use crate::updater::{desired_record_values, get_record_to_update, record_values_differ};
 
         figment::Jail::expect_with(|jail| {
             jail.create_file(
@@ -496,14 +523,26 @@ token = "123"
             let record_to_update =
                 DomainRecordToUpdate::new(domain_name, hostname_part, record_type, None);
 
-            let records = updater.get_domain_records(domain_name).unwrap();
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let records = runtime
+                .block_on(updater.get_domain_records(domain_name))
+                .unwrap();
             let domain_record = get_record_to_update(&records, &record_to_update).unwrap();
+            // Both values of the record set are kept
+            assert_eq!(domain_record.joined_values(), "1.2.3.4, 5.6.7.8");
             let (ip_addr, _ip_kind) = public_ips.to_ip_addr_from_any();
-            let should_update = should_update_domain_ip(&ip_addr, domain_record);
+            let new_values =
+                desired_record_values(&record_to_update, domain_record, &ip_addr, None);
+            assert_eq!(new_values, [ip_addr.to_string()]);
+            let should_update = record_values_differ(domain_record, &new_values);
 
             assert!(should_update);
 
-            let result = updater.update_domain_ip(&domain_record.id, &record_to_update, &ip_addr);
+            let result = runtime.block_on(updater.set_domain_record_values(
+                domain_record,
+                &record_to_update,
+                &new_values,
+            ));
             assert!(result.is_err());
 
             Ok(())
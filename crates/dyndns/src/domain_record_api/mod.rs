@@ -1,19 +1,27 @@
-use color_eyre::eyre::{Context, Result};
+use async_trait::async_trait;
+use color_eyre::eyre::{bail, Context, Report, Result};
 use secrecy::ExposeSecret;
 use std::net::IpAddr;
 use std::str::FromStr;
 
+use crate::config::http_client_config::HttpClientConfig;
 use crate::config::provider_config::{
     ProviderConfig, ProviderToken, ProviderType, SecretProviderToken,
 };
-use crate::types::{DomainRecordToUpdate, DomainRecordsCommon};
+use crate::domain_record_api::rate_limit::RateLimitedClient;
+use crate::http_client::build_http_client;
+use crate::types::{DomainRecordCommon, DomainRecordToUpdate, DomainRecordsCommon};
 
 /// Trait for DNS provider implementations.
 ///
 /// This trait abstracts over different DNS providers (`DigitalOcean`, `Hetzner`, etc.)
 /// using provider-agnostic common types. Implementors should convert their
 /// provider-specific API types to the common types defined in `types`.
-pub trait DomainRecordApi {
+///
+/// The updater calls a provider concurrently for different zones, so
+/// implementations must be `Send + Sync`.
+#[async_trait]
+pub trait DomainRecordApi: Send + Sync {
     /// Returns the name of the DNS provider (e.g., `DigitalOcean`, `Hetzner`).
     fn provider_name(&self) -> &'static str;
 
@@ -23,24 +31,123 @@ pub trait DomainRecordApi {
     /// Fetch all DNS records for a given domain.
     ///
     /// Returns records in the provider-agnostic common format.
-    fn get_domain_records(&self, domain_name: &str) -> Result<DomainRecordsCommon>;
+    async fn get_domain_records(&self, domain_name: &str) -> Result<DomainRecordsCommon>;
 
-    /// Update a specific domain record with a new IP address.
+    /// Set the values of a domain record set, replacing all of its current
+    /// values.
     ///
     /// # Arguments
-    /// * `domain_record_id` - The record identifier (String to support all providers)
+    /// * `domain_record` - The record set as returned by `get_domain_records`
     /// * `record_to_update` - Details about the record being updated
-    /// * `new_ip` - The new IP address to set
-    fn update_domain_ip(
+    /// * `new_values` - All values the record set should have afterwards
+    async fn set_domain_record_values(
         &self,
-        domain_record_id: &str,
+        domain_record: &DomainRecordCommon,
         record_to_update: &DomainRecordToUpdate,
-        new_ip: &IpAddr,
+        new_values: &[String],
     ) -> Result<()>;
+
+    /// Whether the provider can set the values of several record sets of a
+    /// zone in one request, with `set_domain_record_values_batch`.
+    fn supports_batch_updates(&self) -> bool {
+        false
+    }
+
+    /// Set the values of several record sets of a zone in one request.
+    ///
+    /// Returns one outcome per change, in the order of `changes`. An error
+    /// means that the batch as a whole failed, in which case the updater
+    /// sets the values of each record set with `set_domain_record_values`.
+    async fn set_domain_record_values_batch(
+        &self,
+        domain_name: &str,
+        _changes: &[RecordSetChange<'_>],
+    ) -> Result<Vec<BatchChangeOutcome>> {
+        bail!(
+            "{} doesn't support batch updates of '{}'",
+            self.provider_name(),
+            domain_name
+        )
+    }
+}
+
+/// New values of a record set, submitted with others in a batch.
+#[derive(Debug)]
+pub struct RecordSetChange<'a> {
+    /// The record set as returned by `get_domain_records`.
+    pub domain_record: &'a DomainRecordCommon,
+    pub record_to_update: &'a DomainRecordToUpdate,
+    pub new_values: &'a [String],
+}
+
+/// Outcome of a single change of a batch.
+#[derive(Debug)]
+pub enum BatchChangeOutcome {
+    Applied,
+    Failed(Report),
+    /// Not applied because another change of the batch failed, and the
+    /// provider applies batches as a whole. The change is retried on its own.
+    NotApplied,
+}
+
+/// Error returned when a provider API responds with a non-success HTTP status.
+///
+/// Kept as a distinct type so that the status code can be recovered from an
+/// error report with [`http_status_of_error`].
+#[derive(Debug)]
+pub struct ApiHttpError {
+    pub status: reqwest::StatusCode,
+    pub message: String,
+}
+
+impl ApiHttpError {
+    pub fn new(status: reqwest::StatusCode, message: String) -> Self {
+        Self { status, message }
+    }
+}
+
+impl std::fmt::Display for ApiHttpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ApiHttpError {}
+
+/// Returns the HTTP status code of the first provider API error found in the
+/// error chain, if any.
+pub fn http_status_of_error(error: &color_eyre::eyre::Report) -> Option<reqwest::StatusCode> {
+    error.chain().find_map(|cause| {
+        if let Some(api_error) = cause.downcast_ref::<ApiHttpError>() {
+            return Some(api_error.status);
+        }
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .and_then(reqwest::Error::status)
+    })
+}
+
+/// Returns whether a failed provider request may still have been applied,
+/// because it timed out or failed with a server error after it was sent.
+pub fn request_may_have_been_applied(error: &color_eyre::eyre::Report) -> bool {
+    error.chain().any(|cause| {
+        if let Some(api_error) = cause.downcast_ref::<ApiHttpError>() {
+            return api_error.status.is_server_error();
+        }
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| match e.status() {
+                Some(status) => status.is_server_error(),
+                None => !e.is_connect() && !e.is_builder(),
+            })
+    })
 }
 
 /// Factory function to create a provider from configuration.
-pub fn create_provider(config: &ProviderConfig) -> Result<Box<dyn DomainRecordApi + Send>> {
+pub fn create_provider(
+    config: &ProviderConfig,
+    http_client_config: &HttpClientConfig,
+) -> Result<Box<dyn DomainRecordApi>> {
     let token_str = config.token.expose_secret().as_str();
     let token = ProviderToken::from_str(token_str).context(format!(
         "Failed to parse token for provider '{}'. \
@@ -48,14 +155,36 @@ pub fn create_provider(config: &ProviderConfig) -> Result<Box<dyn DomainRecordAp
         config.provider.as_str(),
     ))?;
     let secret_token = SecretProviderToken::new(Box::new(token));
+    let request_client = build_http_client(http_client_config, &config.bind).context(format!(
+        "Failed to create the HTTP client for provider '{}'",
+        config.provider.as_str(),
+    ))?;
+
+    let rate_limited_client = |limit| {
+        RateLimitedClient::new(
+            request_client.clone(),
+            limit,
+            http_client_config.max_retries,
+        )
+    };
 
     match config.provider {
         ProviderType::DigitalOcean => Ok(Box::new(digital_ocean_api::DigitalOceanApi::new(
             secret_token,
+            rate_limited_client(digital_ocean_api::DIGITAL_OCEAN_RATE_LIMIT),
+        ))),
+        ProviderType::Hetzner => Ok(Box::new(hetzner_api::HetznerApi::new(
+            secret_token,
+            rate_limited_client(hetzner_api::HETZNER_RATE_LIMIT),
+        ))),
+        ProviderType::Cloudflare => Ok(Box::new(cloudflare_api::CloudflareApi::new(
+            secret_token,
+            rate_limited_client(cloudflare_api::CLOUDFLARE_RATE_LIMIT),
+        ))),
+        ProviderType::Desec => Ok(Box::new(desec_api::DesecApi::new(
+            secret_token,
+            rate_limited_client(desec_api::DESEC_RATE_LIMIT),
         ))),
-        ProviderType::Hetzner => Ok(Box::new(hetzner_api::HetznerApi::new(secret_token))),
-        ProviderType::Cloudflare => Ok(Box::new(cloudflare_api::CloudflareApi::new(secret_token))),
-        ProviderType::Desec => Ok(Box::new(desec_api::DesecApi::new(secret_token))),
     }
 }
 
@@ -78,7 +207,153 @@ pub fn strip_record_value(value: &str) -> &str {
         .unwrap_or(value)
 }
 
+/// Whether two record values are the same, comparing IP addresses by value
+/// so that differently formatted IPv6 addresses match.
+pub fn record_values_match(a: &str, b: &str) -> bool {
+    if let (Ok(a), Ok(b)) = (a.parse::<IpAddr>(), b.parse::<IpAddr>()) {
+        return a == b;
+    }
+    a == b
+}
+
+/// Merge the records of providers that store each value of a record set as
+/// a separate record into one record set per name and type.
+pub fn group_record_sets(records: Vec<DomainRecordCommon>) -> Vec<DomainRecordCommon> {
+    let mut record_sets: Vec<DomainRecordCommon> = vec![];
+    for record in records {
+        match record_sets.iter_mut().find(|record_set| {
+            record_set.name == record.name && record_set.record_type == record.record_type
+        }) {
+            Some(record_set) => record_set.values.extend(record.values),
+            None => record_sets.push(record),
+        }
+    }
+    record_sets
+}
+
+/// Changes to the single records of a record set, for providers that store
+/// each value as a separate record.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RecordValueChanges {
+    /// Ids of records to change, and their new value.
+    pub updated: Vec<(String, String)>,
+    /// Values to create new records for.
+    pub created: Vec<String>,
+    /// Ids of records to delete.
+    pub deleted: Vec<String>,
+}
+
+impl RecordValueChanges {
+    /// Records that already hold one of the new values are kept. The others
+    /// are changed to the remaining new values, and records are created or
+    /// deleted if the number of values changes.
+    pub fn plan(domain_record: &DomainRecordCommon, new_values: &[String]) -> Self {
+        let mut missing_values: Vec<&String> = new_values.iter().collect();
+        let mut stale_ids = vec![];
+        for existing in &domain_record.values {
+            match missing_values
+                .iter()
+                .position(|value| record_values_match(value, &existing.value))
+            {
+                Some(position) => {
+                    missing_values.remove(position);
+                }
+                None => stale_ids.push(existing.id.clone()),
+            }
+        }
+
+        let mut changes = Self::default();
+        let mut stale_ids = stale_ids.into_iter();
+        for value in missing_values {
+            match stale_ids.next() {
+                Some(id) => changes.updated.push((id, value.clone())),
+                None => changes.created.push(value.clone()),
+            }
+        }
+        changes.deleted = stale_ids.collect();
+        changes
+    }
+}
+
 pub mod cloudflare_api;
 pub mod desec_api;
 pub mod digital_ocean_api;
 pub mod hetzner_api;
+pub mod rate_limit;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::test_record_set;
+
+    #[test]
+    fn test_group_record_sets() {
+        let mut www = test_record_set(&[("3", "1.1.1.3")]);
+        www.name = "www".to_owned();
+        let record_sets = group_record_sets(vec![
+            test_record_set(&[("1", "1.1.1.1")]),
+            www,
+            test_record_set(&[("2", "1.1.1.2")]),
+        ]);
+        assert_eq!(record_sets.len(), 2);
+        let home = record_sets.first().unwrap();
+        assert_eq!(home.joined_values(), "1.1.1.1, 1.1.1.2");
+    }
+
+    #[test]
+    fn test_request_may_have_been_applied() {
+        use color_eyre::eyre::eyre;
+        use reqwest::StatusCode;
+
+        let http_error = |status| -> color_eyre::eyre::Report {
+            ApiHttpError::new(status, format!("HTTP {status} error")).into()
+        };
+        assert!(!request_may_have_been_applied(&http_error(
+            StatusCode::BAD_REQUEST
+        )));
+        assert!(request_may_have_been_applied(
+            &http_error(StatusCode::BAD_GATEWAY).wrap_err("Failed to update records")
+        ));
+        assert!(!request_may_have_been_applied(&eyre!(
+            "Batch exceeds the provider limit"
+        )));
+    }
+
+    #[test]
+    fn test_plan_record_value_changes() {
+        let values = |values: &[&str]| values.iter().map(|v| (*v).to_owned()).collect::<Vec<_>>();
+        let domain_record = test_record_set(&[("1", "1.1.1.1"), ("2", "2.2.2.2")]);
+
+        let changes = RecordValueChanges::plan(&domain_record, &values(&["2.2.2.2", "3.3.3.3"]));
+        assert_eq!(
+            changes,
+            RecordValueChanges {
+                updated: vec![("1".to_owned(), "3.3.3.3".to_owned())],
+                ..Default::default()
+            }
+        );
+
+        let changes = RecordValueChanges::plan(&domain_record, &values(&["3.3.3.3"]));
+        assert_eq!(
+            changes,
+            RecordValueChanges {
+                updated: vec![("1".to_owned(), "3.3.3.3".to_owned())],
+                deleted: vec!["2".to_owned()],
+                ..Default::default()
+            }
+        );
+
+        let changes =
+            RecordValueChanges::plan(&domain_record, &values(&["1.1.1.1", "2.2.2.2", "4.4.4.4"]));
+        assert_eq!(
+            changes,
+            RecordValueChanges {
+                created: vec!["4.4.4.4".to_owned()],
+                ..Default::default()
+            }
+        );
+
+        assert!(record_values_match("2001:db8::1", "2001:0db8:0:0::1"));
+        assert!(!record_values_match("2001:db8::1", "2001:db8::2"));
+    }
+}
@@ -0,0 +1,402 @@
+use chrono::{DateTime, Utc};
+use humantime::format_duration;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use reqwest::{Client, IntoUrl, Method, Request, RequestBuilder, Response};
+use serde::Serialize;
+use std::fmt::Display;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Longest `Retry-After` delay that is waited for. Requests throttled for
+/// longer fail right away, and are retried with the next update instead.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(5 * 60);
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Documented request rate limit of a provider API.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    /// Requests that can be sent at once after being idle.
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+impl RateLimit {
+    fn per_second(&self) -> f64 {
+        f64::from(self.per_minute) / 60.0
+    }
+}
+
+/// Token bucket that spaces out requests to stay within a [`RateLimit`].
+#[derive(Debug)]
+struct TokenBucket {
+    limit: RateLimit,
+    /// Negative when requests are waiting for tokens to be refilled.
+    tokens: f64,
+    last_refill: Instant,
+    /// Set when the API asked not to send requests until then.
+    blocked_until: Option<Instant>,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            limit,
+            tokens: f64::from(limit.burst),
+            last_refill: now,
+            blocked_until: None,
+        }
+    }
+
+    /// Takes a token, and returns how long to wait before sending the
+    /// request.
+    fn acquire(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.limit.per_second())
+            .min(f64::from(self.limit.burst));
+        self.last_refill = now;
+        self.tokens -= 1.0;
+
+        let refill_wait = if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.limit.per_second())
+        };
+        let blocked_wait = self
+            .blocked_until
+            .map_or(Duration::ZERO, |until| until.saturating_duration_since(now));
+        refill_wait.max(blocked_wait)
+    }
+
+    fn block_until(&mut self, until: Instant) {
+        if self.blocked_until.map_or(true, |blocked| blocked < until) {
+            self.blocked_until = Some(until);
+        }
+    }
+}
+
+/// HTTP client of a provider API, which keeps to the rate limit of the
+/// provider and retries throttled or failed requests.
+///
+/// Requests can only be built with the methods of the client, so that all of
+/// them are sent through the rate limit.
+///
+/// Throttled requests (HTTP 429) are always retried, as the API didn't act
+/// on them. Server errors and connection failures are only retried for
+/// idempotent requests.
+#[derive(Debug)]
+pub struct RateLimitedClient {
+    client: Client,
+    bucket: Mutex<TokenBucket>,
+    max_retries: u32,
+}
+
+impl RateLimitedClient {
+    pub fn new(client: Client, limit: RateLimit, max_retries: u32) -> Self {
+        Self {
+            client,
+            bucket: Mutex::new(TokenBucket::new(limit, Instant::now())),
+            max_retries,
+        }
+    }
+
+    pub fn get(&self, url: impl IntoUrl) -> RateLimitedRequestBuilder<'_> {
+        self.request(Method::GET, url)
+    }
+
+    pub fn post(&self, url: impl IntoUrl) -> RateLimitedRequestBuilder<'_> {
+        self.request(Method::POST, url)
+    }
+
+    pub fn put(&self, url: impl IntoUrl) -> RateLimitedRequestBuilder<'_> {
+        self.request(Method::PUT, url)
+    }
+
+    pub fn patch(&self, url: impl IntoUrl) -> RateLimitedRequestBuilder<'_> {
+        self.request(Method::PATCH, url)
+    }
+
+    pub fn delete(&self, url: impl IntoUrl) -> RateLimitedRequestBuilder<'_> {
+        self.request(Method::DELETE, url)
+    }
+
+    fn request(&self, method: Method, url: impl IntoUrl) -> RateLimitedRequestBuilder<'_> {
+        RateLimitedRequestBuilder {
+            client: self,
+            builder: self.client.request(method, url),
+        }
+    }
+
+    async fn execute(&self, mut request: Request) -> reqwest::Result<Response> {
+        let idempotent = request.method().is_idempotent();
+        let mut attempt = 0;
+        loop {
+            self.wait_for_token().await;
+            let retry_request = if attempt < self.max_retries {
+                request.try_clone()
+            } else {
+                None
+            };
+            let url = request.url().clone();
+            let result = self.client.execute(request).await;
+            if let Ok(response) = &result {
+                self.observe_rate_limit_headers(response.headers());
+            }
+            let Some(next_request) = retry_request else {
+                return result;
+            };
+            let delay = match &result {
+                Ok(response) => self.retry_delay(response, idempotent, attempt),
+                Err(e) if idempotent && (e.is_connect() || e.is_timeout()) => {
+                    Some(backoff(attempt))
+                }
+                Err(_) => None,
+            };
+            let Some(delay) = delay else {
+                return result;
+            };
+            let reason = match &result {
+                Ok(response) => format!("HTTP {}", response.status()),
+                Err(e) => e.to_string(),
+            };
+            warn!(
+                "Request to '{}' failed with {}, retrying in {}",
+                url.path(),
+                reason,
+                format_duration(delay)
+            );
+            tokio::time::sleep(delay).await;
+            request = next_request;
+            attempt += 1;
+        }
+    }
+
+    async fn wait_for_token(&self) {
+        let wait = self
+            .bucket
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .acquire(Instant::now());
+        if !wait.is_zero() {
+            debug!(
+                "Waiting {} to stay within the provider API rate limit",
+                format_duration(wait)
+            );
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    fn block_until(&self, until: Instant) {
+        self.bucket
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .block_until(until);
+    }
+
+    /// Stops sending requests until the rate limit resets when the API
+    /// reports that no requests are remaining.
+    fn observe_rate_limit_headers(&self, headers: &HeaderMap) {
+        let header = |names: [&str; 2]| {
+            names.iter().find_map(|name| {
+                headers
+                    .get(*name)?
+                    .to_str()
+                    .ok()?
+                    .trim()
+                    .parse::<u64>()
+                    .ok()
+            })
+        };
+        let remaining = header(["x-ratelimit-remaining", "ratelimit-remaining"]);
+        let reset = header(["x-ratelimit-reset", "ratelimit-reset"]);
+        if let (Some(0), Some(reset)) = (remaining, reset) {
+            let wait = rate_limit_reset_delay(reset, Utc::now());
+            debug!(
+                "Provider API rate limit exhausted, pausing requests for {}",
+                format_duration(wait)
+            );
+            self.block_until(Instant::now() + wait.min(MAX_RETRY_AFTER));
+        }
+    }
+
+    fn retry_delay(&self, response: &Response, idempotent: bool, attempt: u32) -> Option<Duration> {
+        let status = response.status();
+        let retryable = status == StatusCode::TOO_MANY_REQUESTS
+            || (idempotent
+                && matches!(
+                    status,
+                    StatusCode::INTERNAL_SERVER_ERROR
+                        | StatusCode::BAD_GATEWAY
+                        | StatusCode::SERVICE_UNAVAILABLE
+                        | StatusCode::GATEWAY_TIMEOUT
+                ));
+        if !retryable {
+            return None;
+        }
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| parse_retry_after(value, Utc::now()));
+        match retry_after {
+            Some(retry_after) if retry_after > MAX_RETRY_AFTER => None,
+            Some(retry_after) => {
+                self.block_until(Instant::now() + retry_after);
+                Some(retry_after)
+            }
+            None => Some(backoff(attempt)),
+        }
+    }
+}
+
+/// Request of a [`RateLimitedClient`], which is sent through its rate limit.
+#[must_use]
+pub struct RateLimitedRequestBuilder<'a> {
+    client: &'a RateLimitedClient,
+    builder: RequestBuilder,
+}
+
+impl RateLimitedRequestBuilder<'_> {
+    pub fn bearer_auth(self, token: impl Display) -> Self {
+        self.map(|builder| builder.bearer_auth(token))
+    }
+
+    pub fn header(self, name: &'static str, value: String) -> Self {
+        self.map(|builder| builder.header(name, value))
+    }
+
+    pub fn json<T: Serialize + ?Sized>(self, json: &T) -> Self {
+        self.map(|builder| builder.json(json))
+    }
+
+    pub async fn send(self) -> reqwest::Result<Response> {
+        self.client.execute(self.builder.build()?).await
+    }
+
+    fn map(self, f: impl FnOnce(RequestBuilder) -> RequestBuilder) -> Self {
+        Self {
+            client: self.client,
+            builder: f(self.builder),
+        }
+    }
+}
+
+fn backoff(attempt: u32) -> Duration {
+    INITIAL_BACKOFF
+        .saturating_mul(2_u32.saturating_pow(attempt))
+        .min(MAX_BACKOFF)
+}
+
+/// Parses a `Retry-After` header, which is either a number of seconds or an
+/// HTTP date.
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        date.with_timezone(&Utc)
+            .signed_duration_since(now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// `X-RateLimit-Reset` is either a unix timestamp or a number of seconds,
+/// depending on the provider.
+fn rate_limit_reset_delay(reset: u64, now: DateTime<Utc>) -> Duration {
+    const UNIX_TIMESTAMP_THRESHOLD: u64 = 1_000_000_000;
+    if reset < UNIX_TIMESTAMP_THRESHOLD {
+        return Duration::from_secs(reset);
+    }
+    let now = u64::try_from(now.timestamp()).unwrap_or(0);
+    Duration::from_secs(reset.saturating_sub(now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(
+            RateLimit {
+                burst: 2,
+                per_minute: 60,
+            },
+            start,
+        );
+        assert_eq!(bucket.acquire(start), Duration::ZERO);
+        assert_eq!(bucket.acquire(start), Duration::ZERO);
+        assert_eq!(bucket.acquire(start), Duration::from_secs(1));
+        assert_eq!(bucket.acquire(start), Duration::from_secs(2));
+
+        // Idle time refills at most `burst` tokens.
+        let later = start + Duration::from_secs(60);
+        assert_eq!(bucket.acquire(later), Duration::ZERO);
+        assert_eq!(bucket.acquire(later), Duration::ZERO);
+
+        let later = later + Duration::from_secs(10);
+        bucket.block_until(later + Duration::from_secs(30));
+        assert_eq!(bucket.acquire(later), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = DateTime::parse_from_rfc3339("2015-10-21T07:27:30Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+
+        assert_eq!(
+            rate_limit_reset_delay(1_445_412_470, now),
+            Duration::from_secs(20)
+        );
+        assert_eq!(rate_limit_reset_delay(15, now), Duration::from_secs(15));
+    }
+
+    #[test]
+    fn test_retries_throttled_requests() {
+        let server = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/records", server.local_addr().unwrap());
+        let server_thread = std::thread::spawn(move || {
+            let responses = [
+                "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 0\r\nContent-Length: 0\r\n\r\n",
+                "HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n",
+            ];
+            for response in responses {
+                let (mut stream, _) = server.accept().unwrap();
+                let mut request = vec![0; 4096];
+                assert!(stream.read(&mut request).unwrap() > 0);
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        let client = RateLimitedClient::new(
+            Client::builder().pool_max_idle_per_host(0).build().unwrap(),
+            RateLimit {
+                burst: 5,
+                per_minute: 600,
+            },
+            3,
+        );
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let response = runtime.block_on(client.post(&url).send()).unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        server_thread.join().unwrap();
+    }
+}
@@ -0,0 +1,128 @@
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use directories::ProjectDirs;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{trace, warn};
+
+/// Public IP last put into each merged record set on each provider, which is
+/// swapped for the new one on the next update.
+///
+/// The values are kept in a state file, so that an address set before a
+/// restart is still recognized as the daemon's own and doesn't stay in the
+/// record set.
+#[derive(Debug)]
+pub struct DynamicValues {
+    path: Option<PathBuf>,
+    values: Mutex<HashMap<String, String>>,
+}
+
+impl DynamicValues {
+    /// Loads the values from the state file at `path`. Without a path, the
+    /// values are only kept in memory.
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let values = path
+            .as_deref()
+            .map(|path| {
+                read_state_file(path).unwrap_or_else(|e| {
+                    warn!("Failed to load the previously set public IPs: {:#}", e);
+                    HashMap::new()
+                })
+            })
+            .unwrap_or_default();
+        Self {
+            path,
+            values: Mutex::new(values),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.values
+            .lock()
+            .expect("dynamic_values mutex poisoned")
+            .get(key)
+            .cloned()
+    }
+
+    /// Remembers the value, and writes the state file if it changed.
+    pub fn set(&self, key: String, value: String) {
+        let mut values = self.values.lock().expect("dynamic_values mutex poisoned");
+        if values.get(&key) == Some(&value) {
+            return;
+        }
+        values.insert(key, value);
+        if let Some(path) = &self.path {
+            if let Err(e) = write_state_file(path, &values) {
+                warn!("Failed to save the set public IPs: {:#}", e);
+            }
+        }
+    }
+}
+
+/// Default location of the state file, next to the statistics database.
+pub fn default_state_path() -> Result<PathBuf> {
+    let project_dirs = ProjectDirs::from("org", "alcroito", "digitalocean-dyndns")
+        .ok_or_else(|| eyre!("Could not retrieve path to store the state file."))?;
+    Ok(project_dirs.data_dir().join("dynamic_values.json"))
+}
+
+fn read_state_file(path: &Path) -> Result<HashMap<String, String>> {
+    if !path.exists() {
+        trace!("No state file at '{}' yet", path.display());
+        return Ok(HashMap::new());
+    }
+    let contents = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("Failed to read state file '{}'", path.display()))?;
+    serde_json::from_str(&contents)
+        .wrap_err_with(|| format!("Failed to parse state file '{}'", path.display()))
+}
+
+/// Writes the file via a temporary one, so a crash doesn't leave it
+/// truncated.
+fn write_state_file(path: &Path, values: &HashMap<String, String>) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).wrap_err_with(|| {
+            format!("Failed to create state file directory '{}'", dir.display())
+        })?;
+    }
+    let temp_path = path.with_extension("json.tmp");
+    let contents = serde_json::to_string_pretty(values)?;
+    std::fs::write(&temp_path, contents)
+        .wrap_err_with(|| format!("Failed to write state file '{}'", temp_path.display()))?;
+    std::fs::rename(&temp_path, path)
+        .wrap_err_with(|| format!("Failed to replace state file '{}'", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::app_config::RecordValuesMode;
+    use crate::types::{test_record_set, DomainRecordToUpdate};
+    use crate::updater::desired_record_values;
+    use std::net::IpAddr;
+
+    #[test]
+    fn test_merge_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state").join("dynamic_values.json");
+        let key = "Hetzner:home.site.com:A".to_owned();
+        let record = DomainRecordToUpdate::new("site.com", "home", "A", None)
+            .with_values(RecordValuesMode::Merge, vec![]);
+
+        let dynamic_values = DynamicValues::load(Some(path.clone()));
+        assert_eq!(dynamic_values.get(&key), None);
+        dynamic_values.set(key.clone(), "1.2.3.4".to_owned());
+        drop(dynamic_values);
+
+        // The other uplink's address is kept, and the one set before the
+        // restart is swapped for the new public IP.
+        let dynamic_values = DynamicValues::load(Some(path));
+        let previous = dynamic_values.get(&key);
+        assert_eq!(previous.as_deref(), Some("1.2.3.4"));
+        let curr_ip: IpAddr = "85.212.89.12".parse().unwrap();
+        let domain_record = test_record_set(&[("home/A", "1.2.3.4"), ("home/A", "5.6.7.8")]);
+        let new_values =
+            desired_record_values(&record, &domain_record, &curr_ip, previous.as_deref());
+        assert_eq!(new_values, ["5.6.7.8", "85.212.89.12"]);
+    }
+}
@@ -0,0 +1,297 @@
+use color_eyre::eyre::{bail, eyre, Result, WrapErr};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::config::bind_config::BindConfig;
+use crate::config::failover_config::{
+    FailoverCandidateConfig, FailoverConfig, HealthCheckConfig, HealthCheckKind,
+};
+use crate::ip_fetcher::{DnsIpFetcher, PublicIpFetcher};
+use crate::net_bind::{bind_client_builder, connect_tcp};
+use crate::types::DomainRecordToUpdate;
+
+/// Health of a failover candidate. It only changes after enough consecutive
+/// check results contradict it, so a flaky uplink doesn't cause flapping.
+#[derive(Debug, Default)]
+struct CandidateHealth {
+    /// `None` until the first check.
+    healthy: Option<bool>,
+    /// Consecutive check results that contradict `healthy`.
+    contradicting_results: u32,
+}
+
+impl CandidateHealth {
+    fn record_check(&mut self, success: bool, config: &FailoverConfig) {
+        match self.healthy {
+            Some(healthy) if healthy != success => {
+                self.contradicting_results += 1;
+                let threshold = if healthy {
+                    config.unhealthy_threshold
+                } else {
+                    config.healthy_threshold
+                };
+                if self.contradicting_results >= threshold {
+                    self.healthy = Some(success);
+                    self.contradicting_results = 0;
+                }
+            }
+            _ => {
+                self.healthy = Some(success);
+                self.contradicting_results = 0;
+            }
+        }
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.healthy == Some(true)
+    }
+}
+
+#[derive(Debug, Default)]
+struct CandidateState {
+    health: CandidateHealth,
+    /// Public IP of the candidate, as of its last successful check.
+    ip: Option<IpAddr>,
+}
+
+#[derive(Debug)]
+struct FailoverState {
+    candidates: Vec<CandidateState>,
+    /// Name of the candidate the record currently points at.
+    active: Option<String>,
+}
+
+/// Keeps track of the health of the failover candidates of each record.
+#[derive(Default)]
+pub struct FailoverMonitor {
+    states: HashMap<String, FailoverState>,
+}
+
+impl FailoverMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks all candidates of the record, and returns the IP of the
+    /// highest-priority healthy one.
+    pub fn select_ip(
+        &mut self,
+        record: &DomainRecordToUpdate,
+        config: &FailoverConfig,
+    ) -> Result<IpAddr> {
+        let fqdn = record.fqdn();
+        let checks = config
+            .candidates
+            .iter()
+            .map(|candidate| {
+                let check = check_candidate(candidate, &record.record_type);
+                if let Err(e) = &check {
+                    warn!(
+                        "Failover candidate '{}' of '{}' failed its health check: {:#}",
+                        candidate.name, fqdn, e
+                    );
+                }
+                check.ok()
+            })
+            .collect::<Vec<_>>();
+        self.select_ip_from_checks(record, config, &checks)
+    }
+
+    fn select_ip_from_checks(
+        &mut self,
+        record: &DomainRecordToUpdate,
+        config: &FailoverConfig,
+        checks: &[Option<IpAddr>],
+    ) -> Result<IpAddr> {
+        let fqdn = record.fqdn();
+        let state = self
+            .states
+            .entry(format!("{}:{}", fqdn, record.record_type))
+            .or_insert_with(|| FailoverState {
+                candidates: config
+                    .candidates
+                    .iter()
+                    .map(|_| CandidateState::default())
+                    .collect(),
+                active: None,
+            });
+        for (candidate, check) in state.candidates.iter_mut().zip(checks) {
+            candidate.health.record_check(check.is_some(), config);
+            if let Some(ip) = check {
+                candidate.ip = Some(*ip);
+            }
+        }
+
+        let selected = config.candidates.iter().zip(&state.candidates).find_map(
+            |(candidate_config, candidate)| {
+                candidate
+                    .ip
+                    .filter(|_| candidate.health.is_healthy())
+                    .map(|ip| (candidate_config.name.clone(), ip))
+            },
+        );
+        let Some((selected_name, selected_ip)) = selected else {
+            state.active = None;
+            bail!("No healthy failover candidate for '{}'", fqdn);
+        };
+        match &state.active {
+            Some(active) if *active == selected_name => {
+                debug!(
+                    "Failover candidate '{}' of '{}' is still healthy",
+                    selected_name, fqdn
+                );
+            }
+            Some(active) => warn!(
+                "Failing over '{}' from candidate '{}' to '{}' with IP '{}'",
+                fqdn, active, selected_name, selected_ip
+            ),
+            None => info!(
+                "Pointing '{}' at failover candidate '{}' with IP '{}'",
+                fqdn, selected_name, selected_ip
+            ),
+        }
+        state.active = Some(selected_name);
+        Ok(selected_ip)
+    }
+}
+
+/// Runs the health check of the candidate, and returns its public IP.
+fn check_candidate(candidate: &FailoverCandidateConfig, record_type: &str) -> Result<IpAddr> {
+    let bind = candidate.bind();
+    run_health_check(&candidate.health_check, &bind)?;
+    if let Some(ip) = candidate.ip {
+        return Ok(ip);
+    }
+    let (lookup_ipv4, lookup_ipv6) = match candidate.source_address {
+        Some(source_address) => (source_address.is_ipv4(), source_address.is_ipv6()),
+        None => (record_type != "AAAA", record_type == "AAAA"),
+    };
+    let public_ips = DnsIpFetcher::with_bind(bind).fetch_public_ips(lookup_ipv4, lookup_ipv6)?;
+    Ok(public_ips.to_ip_addr_from_any().0)
+}
+
+fn run_health_check(health_check: &HealthCheckConfig, bind: &BindConfig) -> Result<()> {
+    tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(async {
+            match &health_check.kind {
+                HealthCheckKind::Tcp { address } => {
+                    check_tcp_connect(address, bind, health_check.timeout).await
+                }
+                HealthCheckKind::Http { url } => {
+                    check_http_get(url, bind, health_check.timeout).await
+                }
+            }
+        })
+    })
+}
+
+async fn check_http_get(url: &str, bind: &BindConfig, timeout: Duration) -> Result<()> {
+    let client_builder = reqwest::Client::builder().timeout(timeout);
+    bind_client_builder(bind, client_builder)
+        .build()
+        .wrap_err("Failed to create the health check HTTP client")?
+        .get(url)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .wrap_err_with(|| format!("Health check request to '{url}' failed"))?;
+    Ok(())
+}
+
+async fn check_tcp_connect(address: &str, bind: &BindConfig, timeout: Duration) -> Result<()> {
+    let connect = async {
+        let target = tokio::net::lookup_host(address)
+            .await
+            .wrap_err_with(|| format!("Failed to resolve '{address}'"))?
+            .find(|target| {
+                bind.source_address.map_or(true, |source_address| {
+                    source_address.is_ipv4() == target.is_ipv4()
+                })
+            })
+            .ok_or_else(|| eyre!("'{}' has no address of the source address family", address))?;
+        connect_tcp(bind, target)
+            .await
+            .wrap_err_with(|| format!("Failed to connect to '{address}'"))?;
+        Ok::<_, color_eyre::eyre::Report>(())
+    };
+    tokio::time::timeout(timeout, connect)
+        .await
+        .wrap_err_with(|| format!("Timed out connecting to '{address}'"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failover_config() -> FailoverConfig {
+        toml::from_str(
+            r#"
+            healthy_threshold = 2
+            unhealthy_threshold = 2
+
+            [[candidates]]
+            name = "fiber"
+            ip = "203.0.113.1"
+            health_check = { type = "tcp", address = "192.0.2.1:443" }
+
+            [[candidates]]
+            name = "lte"
+            ip = "198.51.100.1"
+            health_check = { type = "tcp", address = "192.0.2.1:443" }
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_failover_hysteresis() {
+        let config = failover_config();
+        let record = DomainRecordToUpdate::new("site.com", "home", "A", None);
+        let fiber: IpAddr = "203.0.113.1".parse().unwrap();
+        let lte: IpAddr = "198.51.100.1".parse().unwrap();
+        let mut monitor = FailoverMonitor::new();
+        let mut select = |fiber_up: bool, lte_up: bool| {
+            monitor
+                .select_ip_from_checks(
+                    &record,
+                    &config,
+                    &[fiber_up.then_some(fiber), lte_up.then_some(lte)],
+                )
+                .ok()
+        };
+
+        // The first check decides right away.
+        assert_eq!(select(true, true), Some(fiber));
+        // A single failed check doesn't fail over yet.
+        assert_eq!(select(false, true), Some(fiber));
+        assert_eq!(select(false, true), Some(lte));
+        // Neither does a single successful one fail back.
+        assert_eq!(select(true, true), Some(lte));
+        assert_eq!(select(true, true), Some(fiber));
+
+        assert_eq!(select(false, false), Some(fiber));
+        assert_eq!(select(false, false), None);
+    }
+
+    #[test]
+    fn test_tcp_health_check() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let localhost = BindConfig {
+            interface: None,
+            source_address: Some("127.0.0.1".parse().unwrap()),
+        };
+        let timeout = Duration::from_secs(5);
+        assert!(runtime
+            .block_on(check_tcp_connect(&address, &localhost, timeout))
+            .is_ok());
+
+        drop(listener);
+        assert!(runtime
+            .block_on(check_tcp_connect(&address, &localhost, timeout))
+            .is_err());
+    }
+}
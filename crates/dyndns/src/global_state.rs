@@ -1,16 +1,59 @@
 use crate::config::app_config::AppConfig;
 use crate::config::app_config_builder::config_with_args;
 use crate::config::early::EarlyConfig;
+#[cfg(feature = "web")]
+use crate::metrics::Metrics;
+#[cfg(feature = "web")]
+use crate::update_trigger::UpdateTrigger;
+#[cfg(feature = "web")]
+use crate::updater_events::UpdaterEvents;
+#[cfg(feature = "web")]
+use crate::updater_status::UpdaterStatus;
 use color_eyre::eyre::Result;
+#[cfg(feature = "web")]
+use std::sync::Arc;
 
 pub struct GlobalState {
     pub config: AppConfig,
+    #[cfg(feature = "web")]
+    pub shared: SharedState,
 }
 
 impl GlobalState {
     pub fn new(early_config: &EarlyConfig) -> Result<Self> {
         let config = config_with_args(early_config)?;
-        let global_state = GlobalState { config };
-        Ok(global_state)
+        Ok(Self::from_config(config))
+    }
+
+    pub fn from_config(config: AppConfig) -> Self {
+        GlobalState {
+            #[cfg(feature = "web")]
+            shared: SharedState::new(&config),
+            config,
+        }
+    }
+}
+
+/// State shared between the updater thread and the web server.
+#[cfg(feature = "web")]
+#[derive(Clone)]
+pub struct SharedState {
+    pub metrics: Arc<Metrics>,
+    pub updater_status: Arc<UpdaterStatus>,
+    pub update_trigger: Arc<UpdateTrigger>,
+    pub events: Arc<UpdaterEvents>,
+}
+
+#[cfg(feature = "web")]
+impl SharedState {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            metrics: Arc::new(Metrics::new()),
+            updater_status: Arc::new(UpdaterStatus::new(
+                config.general_options.health_failure_threshold,
+            )),
+            update_trigger: Arc::new(UpdateTrigger::new()),
+            events: Arc::new(UpdaterEvents::new()),
+        }
     }
 }
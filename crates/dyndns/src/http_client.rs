@@ -0,0 +1,93 @@
+use color_eyre::eyre::{Result, WrapErr};
+use reqwest::{Certificate, Client, Proxy};
+
+use crate::config::bind_config::BindConfig;
+use crate::config::http_client_config::{HttpClientConfig, HttpProxyConfig};
+use crate::net_bind::bind_client_builder;
+
+/// Builds the HTTP client of a DNS provider API.
+pub fn build_http_client(config: &HttpClientConfig, bind: &BindConfig) -> Result<Client> {
+    let mut builder = Client::builder()
+        .connect_timeout(config.connect_timeout)
+        .timeout(config.request_timeout);
+    if let Some(user_agent) = &config.user_agent {
+        builder = builder.user_agent(user_agent);
+    }
+    if let Some(proxy) = &config.proxy {
+        builder = builder.proxy(build_proxy(proxy)?);
+    }
+    if let Some(path) = &config.ca_cert_path {
+        let pem = std::fs::read(path).wrap_err_with(|| {
+            format!("Failed to read CA certificates from '{}'", path.display())
+        })?;
+        let certificates = Certificate::from_pem_bundle(&pem).wrap_err_with(|| {
+            format!("Failed to parse CA certificates from '{}'", path.display())
+        })?;
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    bind_client_builder(bind, builder)
+        .build()
+        .wrap_err("Failed to create the HTTP client")
+}
+
+fn build_proxy(config: &HttpProxyConfig) -> Result<Proxy> {
+    let mut proxy =
+        Proxy::all(&config.url).wrap_err_with(|| format!("Invalid proxy url '{}'", config.url))?;
+    if let Some(username) = &config.username {
+        let password = match &config.password_file {
+            Some(path) => std::fs::read_to_string(path)
+                .wrap_err_with(|| {
+                    format!("Failed to read proxy password from '{}'", path.display())
+                })?
+                .trim_end_matches(['\r', '\n'])
+                .to_owned(),
+            None => String::new(),
+        };
+        proxy = proxy.basic_auth(username, &password);
+    }
+    Ok(proxy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn test_requests_use_proxy() {
+        let proxy = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let config: HttpClientConfig = toml::from_str(&format!(
+            r#"
+            user_agent = "do_ddns-test"
+            proxy = {{ url = "http://{}", username = "ddns" }}
+            "#,
+            proxy.local_addr().unwrap()
+        ))
+        .unwrap();
+        let client = build_http_client(&config, &BindConfig::default()).unwrap();
+
+        let proxy_thread = std::thread::spawn(move || {
+            let (mut stream, _) = proxy.accept().unwrap();
+            let mut request = vec![0; 4096];
+            let len = stream.read(&mut request).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            String::from_utf8_lossy(request.get(..len).unwrap()).into_owned()
+        });
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let response = runtime
+            .block_on(async { client.get("http://api.example.com/records").send().await })
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+        let request = proxy_thread.join().unwrap();
+        assert!(request.starts_with("GET http://api.example.com/records HTTP/1.1"));
+        let headers = request.to_ascii_lowercase();
+        assert!(headers.contains("user-agent: do_ddns-test"));
+        // base64 of "ddns:"
+        assert!(headers.contains("proxy-authorization: basic zgruczo="));
+    }
+}
@@ -2,10 +2,12 @@ use color_eyre::eyre::{bail, Result, WrapErr};
 use hickory_resolver::config::{
     LookupIpStrategy, NameServerConfigGroup, ResolveHosts, ResolverConfig, ResolverOpts,
 };
-use hickory_resolver::{name_server::TokioConnectionProvider, Resolver};
+use hickory_resolver::Resolver;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use tracing::info;
 
+use crate::config::bind_config::BindConfig;
+use crate::net_bind::{bind_name_servers, bound_connection_provider};
 use crate::types::{DisplayIpAddrV4AndV6Pretty, IpAddrV4AndV6};
 
 /// IP addresses for OpenDNS Public DNS
@@ -19,10 +21,29 @@ const OPEN_DNS_IPS: &[IpAddr] = &[
 
 pub trait PublicIpFetcher {
     fn fetch_public_ips(&self, lookup_ipv4: bool, lookup_ipv6: bool) -> Result<IpAddrV4AndV6>;
+
+    /// Describes where the public IPs are fetched from, e.g. in metrics.
+    fn source(&self) -> String;
 }
 
 #[derive(Default)]
-pub struct DnsIpFetcher {}
+pub struct DnsIpFetcher {
+    /// Interface or local address to send the queries from, to find out the
+    /// public IP of a specific uplink.
+    bind: BindConfig,
+}
+
+impl DnsIpFetcher {
+    pub fn with_bind(bind: BindConfig) -> Self {
+        Self { bind }
+    }
+
+    fn name_servers(&self, ips: &[IpAddr]) -> NameServerConfigGroup {
+        let mut name_servers = NameServerConfigGroup::from_ips_clear(ips, 53, true);
+        bind_name_servers(&self.bind, &mut name_servers);
+        name_servers
+    }
+}
 
 impl PublicIpFetcher for DnsIpFetcher {
     /// Fetch public IP of current machine by querying the OpenDNS myip resolver
@@ -33,17 +54,20 @@ impl PublicIpFetcher for DnsIpFetcher {
             "Fetching public IP using OpenDNS, IPv4: {}, IPv6: {}",
             lookup_ipv4, lookup_ipv6
         );
+        if self.bind.is_set() {
+            info!(
+                "Sending the queries via interface {:?} from address {:?}",
+                self.bind.interface, self.bind.source_address
+            );
+        }
         let hostname_to_lookup = "myip.opendns.com.";
 
         let mut result = IpAddrV4AndV6::default();
 
         if lookup_ipv4 {
             let ipv4_dns_ips = OPEN_DNS_IPS.get(..2).expect("No IPv4 addresses");
-            let resolver_config = ResolverConfig::from_parts(
-                None,
-                vec![],
-                NameServerConfigGroup::from_ips_clear(ipv4_dns_ips, 53, true),
-            );
+            let resolver_config =
+                ResolverConfig::from_parts(None, vec![], self.name_servers(ipv4_dns_ips));
 
             let mut resolver_options = ResolverOpts::default();
             resolver_options.use_hosts_file = ResolveHosts::Never;
@@ -51,8 +75,10 @@ impl PublicIpFetcher for DnsIpFetcher {
             resolver_options.attempts = 1;
             resolver_options.num_concurrent_reqs = 1;
 
-            let mut builder =
-                Resolver::builder_with_config(resolver_config, TokioConnectionProvider::default());
+            let mut builder = Resolver::builder_with_config(
+                resolver_config,
+                bound_connection_provider(&self.bind),
+            );
             *builder.options_mut() = resolver_options;
             let resolver = builder.build();
 
@@ -71,11 +97,8 @@ impl PublicIpFetcher for DnsIpFetcher {
 
         if lookup_ipv6 {
             let ipv6_dns_ips = OPEN_DNS_IPS.get(2..).expect("No IPv6 addresses");
-            let resolver_config = ResolverConfig::from_parts(
-                None,
-                vec![],
-                NameServerConfigGroup::from_ips_clear(ipv6_dns_ips, 53, true),
-            );
+            let resolver_config =
+                ResolverConfig::from_parts(None, vec![], self.name_servers(ipv6_dns_ips));
 
             let mut resolver_options = ResolverOpts::default();
             resolver_options.use_hosts_file = ResolveHosts::Never;
@@ -83,8 +106,10 @@ impl PublicIpFetcher for DnsIpFetcher {
             resolver_options.attempts = 1;
             resolver_options.num_concurrent_reqs = 1;
 
-            let mut builder =
-                Resolver::builder_with_config(resolver_config, TokioConnectionProvider::default());
+            let mut builder = Resolver::builder_with_config(
+                resolver_config,
+                bound_connection_provider(&self.bind),
+            );
             *builder.options_mut() = resolver_options;
             let resolver = builder.build();
 
@@ -107,6 +132,17 @@ impl PublicIpFetcher for DnsIpFetcher {
         info!("{}", DisplayIpAddrV4AndV6Pretty(&result));
         Ok(result)
     }
+
+    fn source(&self) -> String {
+        let mut source = "opendns".to_owned();
+        if let Some(interface) = &self.bind.interface {
+            source.push_str(&format!(" via {interface}"));
+        }
+        if let Some(source_address) = &self.bind.source_address {
+            source.push_str(&format!(" from {source_address}"));
+        }
+        source
+    }
 }
 
 #[cfg(test)]
@@ -120,5 +156,19 @@ pub mod tests {
         fn fetch_public_ips(&self, _: bool, _: bool) -> Result<IpAddrV4AndV6> {
             Ok(IpAddr::V4(Ipv4Addr::new(85, 212, 89, 12)).into())
         }
+
+        fn source(&self) -> String {
+            "mock".to_owned()
+        }
+    }
+
+    #[test]
+    fn test_dns_ip_fetcher_source() {
+        assert_eq!(DnsIpFetcher::default().source(), "opendns");
+        let fetcher = DnsIpFetcher::with_bind(BindConfig {
+            interface: Some("wwan0".to_owned()),
+            source_address: Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))),
+        });
+        assert_eq!(fetcher.source(), "opendns via wwan0 from 10.0.0.2");
     }
 }
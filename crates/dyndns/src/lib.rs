@@ -8,16 +8,38 @@ pub mod daemon;
 extern crate diesel;
 #[cfg(feature = "stats")]
 pub mod db;
+pub mod dns_verifier;
 pub mod domain_record_api;
+pub mod dynamic_values;
+pub mod failover;
 pub mod global_state;
+pub mod http_client;
 pub mod ip_fetcher;
 pub mod logger;
+#[cfg(feature = "web")]
+pub mod metrics;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod net_bind;
+pub mod network_watcher;
+pub mod notifiers;
+pub mod scheduler;
 pub mod signal_handlers;
+#[cfg(feature = "stats")]
+pub mod stats_commands;
 pub mod stats_handler;
 #[cfg(feature = "stats")]
 pub mod stats_handler_db;
+#[cfg(feature = "web")]
+pub mod stats_handler_metrics;
+#[cfg(feature = "web")]
+pub mod stats_handler_status;
 pub mod types;
+pub mod update_trigger;
 pub mod updater;
+pub mod updater_events;
+#[cfg(feature = "web")]
+pub mod updater_status;
 
 #[cfg(feature = "web")]
 pub mod web;
@@ -0,0 +1,334 @@
+use color_eyre::eyre::{eyre, Result};
+use prometheus_client::encoding::text::encode;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use prometheus_client::registry::Registry;
+use std::collections::HashMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Content type of the encoded metrics, as expected by Prometheus scrapers.
+pub const METRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct IpFetchLabels {
+    pub source: String,
+    pub family: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct PublicIpLabels {
+    pub family: String,
+    pub address: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct RecordLabels {
+    pub name: String,
+    pub record_type: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ProviderLabels {
+    pub provider: String,
+}
+
+type HistogramFamily<S> = Family<S, Histogram, fn() -> Histogram>;
+
+/// In-memory metrics about the updater, exposed in the Prometheus format.
+///
+/// Kept independently of the statistics database, so they are available even
+/// when `collect_stats` is disabled.
+pub struct Metrics {
+    registry: Registry,
+    ip_fetch_attempts: Family<IpFetchLabels, Counter>,
+    ip_fetch_failures: Family<IpFetchLabels, Counter>,
+    public_ip_info: Family<PublicIpLabels, Gauge>,
+    /// Address currently exposed in `public_ip_info` for each IP family.
+    public_ips: Mutex<HashMap<String, String>>,
+    record_update_attempts: Family<RecordLabels, Counter>,
+    record_update_successes: Family<RecordLabels, Counter>,
+    record_update_failures: Family<RecordLabels, Counter>,
+    provider_update_attempts: Family<ProviderLabels, Counter>,
+    provider_update_successes: Family<ProviderLabels, Counter>,
+    provider_update_failures: Family<ProviderLabels, Counter>,
+    provider_api_latency_seconds: HistogramFamily<ProviderLabels>,
+    seconds_since_last_successful_update: Family<RecordLabels, Gauge<f64, AtomicU64>>,
+    last_successful_updates: Mutex<HashMap<RecordLabels, Instant>>,
+    consecutive_failures: Gauge,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let mut registry = Registry::with_prefix("dyndns");
+
+        let ip_fetch_attempts = Family::<IpFetchLabels, Counter>::default();
+        registry.register(
+            "ip_fetch_attempts",
+            "Number of public IP fetch attempts",
+            ip_fetch_attempts.clone(),
+        );
+        let ip_fetch_failures = Family::<IpFetchLabels, Counter>::default();
+        registry.register(
+            "ip_fetch_failures",
+            "Number of failed public IP fetch attempts",
+            ip_fetch_failures.clone(),
+        );
+        let public_ip_info = Family::<PublicIpLabels, Gauge>::default();
+        registry.register(
+            "public_ip_info",
+            "Currently known public IP addresses",
+            public_ip_info.clone(),
+        );
+        let record_update_attempts = Family::<RecordLabels, Counter>::default();
+        registry.register(
+            "record_update_attempts",
+            "Number of domain record update attempts",
+            record_update_attempts.clone(),
+        );
+        let record_update_successes = Family::<RecordLabels, Counter>::default();
+        registry.register(
+            "record_update_successes",
+            "Number of successful domain record update attempts",
+            record_update_successes.clone(),
+        );
+        let record_update_failures = Family::<RecordLabels, Counter>::default();
+        registry.register(
+            "record_update_failures",
+            "Number of failed domain record update attempts",
+            record_update_failures.clone(),
+        );
+        let provider_update_attempts = Family::<ProviderLabels, Counter>::default();
+        registry.register(
+            "provider_update_attempts",
+            "Number of domain record update attempts per DNS provider",
+            provider_update_attempts.clone(),
+        );
+        let provider_update_successes = Family::<ProviderLabels, Counter>::default();
+        registry.register(
+            "provider_update_successes",
+            "Number of successful domain record updates per DNS provider",
+            provider_update_successes.clone(),
+        );
+        let provider_update_failures = Family::<ProviderLabels, Counter>::default();
+        registry.register(
+            "provider_update_failures",
+            "Number of failed domain record updates per DNS provider",
+            provider_update_failures.clone(),
+        );
+        let provider_api_latency_seconds: HistogramFamily<ProviderLabels> =
+            Family::new_with_constructor(|| Histogram::new(exponential_buckets(0.05, 2.0, 10)));
+        registry.register(
+            "provider_api_latency_seconds",
+            "Time spent on DNS provider API requests per domain record update",
+            provider_api_latency_seconds.clone(),
+        );
+        let seconds_since_last_successful_update =
+            Family::<RecordLabels, Gauge<f64, AtomicU64>>::default();
+        registry.register(
+            "seconds_since_last_successful_update",
+            "Seconds elapsed since the last successful update of a domain record",
+            seconds_since_last_successful_update.clone(),
+        );
+        let consecutive_failures = Gauge::default();
+        registry.register(
+            "updater_consecutive_failures",
+            "Number of consecutive failed updater runs",
+            consecutive_failures.clone(),
+        );
+
+        Self {
+            registry,
+            ip_fetch_attempts,
+            ip_fetch_failures,
+            public_ip_info,
+            public_ips: Mutex::new(HashMap::new()),
+            record_update_attempts,
+            record_update_successes,
+            record_update_failures,
+            provider_update_attempts,
+            provider_update_successes,
+            provider_update_failures,
+            provider_api_latency_seconds,
+            seconds_since_last_successful_update,
+            last_successful_updates: Mutex::new(HashMap::new()),
+            consecutive_failures,
+        }
+    }
+
+    pub fn record_ip_fetch(&self, labels: &IpFetchLabels, is_successful: bool) {
+        self.ip_fetch_attempts.get_or_create(labels).inc();
+        if !is_successful {
+            self.ip_fetch_failures.get_or_create(labels).inc();
+        }
+    }
+
+    /// Replaces the known public IP of the given family, or forgets it if
+    /// `maybe_address` is `None`.
+    pub fn set_public_ip(&self, family: &str, maybe_address: Option<String>) {
+        let mut public_ips = self.public_ips.lock().expect("public_ips mutex poisoned");
+        if let Some(previous_address) = public_ips.remove(family) {
+            self.public_ip_info.remove(&PublicIpLabels {
+                family: family.to_owned(),
+                address: previous_address,
+            });
+        }
+        if let Some(address) = maybe_address {
+            self.public_ip_info
+                .get_or_create(&PublicIpLabels {
+                    family: family.to_owned(),
+                    address: address.clone(),
+                })
+                .set(1);
+            public_ips.insert(family.to_owned(), address);
+        }
+    }
+
+    pub fn record_record_update(&self, labels: &RecordLabels, is_successful: bool) {
+        self.record_update_attempts.get_or_create(labels).inc();
+        if is_successful {
+            self.record_update_successes.get_or_create(labels).inc();
+            self.last_successful_updates
+                .lock()
+                .expect("last_successful_updates mutex poisoned")
+                .insert(labels.clone(), Instant::now());
+        } else {
+            self.record_update_failures.get_or_create(labels).inc();
+        }
+    }
+
+    pub fn record_provider_update(
+        &self,
+        labels: &ProviderLabels,
+        is_successful: bool,
+        latency: Duration,
+    ) {
+        self.provider_update_attempts.get_or_create(labels).inc();
+        if is_successful {
+            self.provider_update_successes.get_or_create(labels).inc();
+        } else {
+            self.provider_update_failures.get_or_create(labels).inc();
+        }
+        self.provider_api_latency_seconds
+            .get_or_create(labels)
+            .observe(latency.as_secs_f64());
+    }
+
+    pub fn record_updater_run(&self, is_successful: bool) {
+        if is_successful {
+            self.consecutive_failures.set(0);
+        } else {
+            self.consecutive_failures.inc();
+        }
+    }
+
+    /// Encodes all metrics in the `OpenMetrics` text format.
+    pub fn encode(&self) -> Result<String> {
+        for (labels, last_success) in self
+            .last_successful_updates
+            .lock()
+            .expect("last_successful_updates mutex poisoned")
+            .iter()
+        {
+            self.seconds_since_last_successful_update
+                .get_or_create(labels)
+                .set(last_success.elapsed().as_secs_f64());
+        }
+
+        let mut buffer = String::new();
+        encode(&mut buffer, &self.registry)
+            .map_err(|e| eyre!("Failed to encode metrics: {}", e))?;
+        Ok(buffer)
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record_labels() -> RecordLabels {
+        RecordLabels {
+            name: "home.example.com".to_owned(),
+            record_type: "A".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_encode_metrics() {
+        let metrics = Metrics::new();
+        let fetch_labels = IpFetchLabels {
+            source: "opendns".to_owned(),
+            family: "ipv4".to_owned(),
+        };
+        metrics.record_ip_fetch(&fetch_labels, true);
+        metrics.record_ip_fetch(&fetch_labels, false);
+        metrics.set_public_ip("ipv4", Some("1.2.3.4".to_owned()));
+        metrics.record_record_update(&record_labels(), true);
+        metrics.record_provider_update(
+            &ProviderLabels {
+                provider: "cloudflare".to_owned(),
+            },
+            false,
+            Duration::from_millis(120),
+        );
+
+        let encoded = metrics.encode().unwrap();
+        assert!(
+            encoded.contains(r#"dyndns_ip_fetch_attempts_total{source="opendns",family="ipv4"} 2"#)
+        );
+        assert!(
+            encoded.contains(r#"dyndns_ip_fetch_failures_total{source="opendns",family="ipv4"} 1"#)
+        );
+        assert!(encoded.contains(r#"dyndns_public_ip_info{family="ipv4",address="1.2.3.4"} 1"#));
+        assert!(encoded.contains(
+            r#"dyndns_record_update_successes_total{name="home.example.com",record_type="A"} 1"#
+        ));
+        assert!(
+            encoded.contains(r#"dyndns_provider_update_failures_total{provider="cloudflare"} 1"#)
+        );
+        assert!(encoded
+            .contains(r#"dyndns_provider_api_latency_seconds_count{provider="cloudflare"} 1"#));
+        assert!(encoded.contains(
+            r#"dyndns_seconds_since_last_successful_update{name="home.example.com",record_type="A"}"#
+        ));
+        assert!(encoded.ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn test_public_ip_is_replaced() {
+        let metrics = Metrics::new();
+        metrics.set_public_ip("ipv4", Some("1.2.3.4".to_owned()));
+        metrics.set_public_ip("ipv4", Some("5.6.7.8".to_owned()));
+
+        let encoded = metrics.encode().unwrap();
+        assert!(!encoded.contains("1.2.3.4"));
+        assert!(encoded.contains(r#"dyndns_public_ip_info{family="ipv4",address="5.6.7.8"} 1"#));
+    }
+
+    #[test]
+    fn test_consecutive_failures_reset_on_success() {
+        let metrics = Metrics::new();
+        metrics.record_updater_run(false);
+        metrics.record_updater_run(false);
+        assert!(metrics
+            .encode()
+            .unwrap()
+            .contains("dyndns_updater_consecutive_failures 2"));
+
+        metrics.record_updater_run(true);
+        assert!(metrics
+            .encode()
+            .unwrap()
+            .contains("dyndns_updater_consecutive_failures 0"));
+    }
+}
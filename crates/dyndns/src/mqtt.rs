@@ -0,0 +1,582 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use color_eyre::eyre::{Result, WrapErr};
+use rumqttc::{Client, Connection, Event, LastWill, MqttOptions, Outgoing, Packet, QoS, Transport};
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, error, info, warn};
+
+use crate::config::mqtt_config::MqttConfig;
+use crate::types::DomainRecordToUpdate;
+use crate::updater_events::{UpdaterEvent, UpdaterEvents};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// Room for queued publishes, on top of the ones sent on each connect.
+const REQUESTS_CHANNEL_CAPACITY: usize = 64;
+const ONLINE: &str = "online";
+const OFFLINE: &str = "offline";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MqttMessage {
+    pub topic: String,
+    pub payload: String,
+}
+
+/// Turns a name into something usable as an MQTT topic level and a Home
+/// Assistant object id.
+fn topic_id(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn format_timestamp(timestamp: DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+struct MqttRecord {
+    id: String,
+    fqdn: String,
+    record_type: String,
+}
+
+/// Maps updater events to the retained topics describing the updater state.
+pub struct MqttTopics {
+    prefix: String,
+    discovery_prefix: Option<String>,
+    node_id: String,
+    records: Vec<MqttRecord>,
+    /// Providers on which the last update of a record failed, by record id.
+    failing_providers: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl MqttTopics {
+    pub fn new(config: &MqttConfig, records: &[DomainRecordToUpdate]) -> Self {
+        Self {
+            prefix: config.topic_prefix.clone(),
+            discovery_prefix: config.discovery.then(|| config.discovery_prefix.clone()),
+            node_id: topic_id(&config.client_id),
+            records: records
+                .iter()
+                .map(|record| MqttRecord {
+                    id: topic_id(&format!("{}_{}", record.fqdn(), record.record_type)),
+                    fqdn: record.fqdn(),
+                    record_type: record.record_type.clone(),
+                })
+                .collect(),
+            failing_providers: BTreeMap::new(),
+        }
+    }
+
+    pub fn availability_topic(&self) -> String {
+        format!("{}/status", self.prefix)
+    }
+
+    fn record_topic(&self, record_id: &str, name: &str) -> String {
+        format!("{}/records/{}/{}", self.prefix, record_id, name)
+    }
+
+    fn message(topic: String, payload: impl Into<String>) -> MqttMessage {
+        MqttMessage {
+            topic,
+            payload: payload.into(),
+        }
+    }
+
+    /// Messages to publish after connecting: the availability, and the Home
+    /// Assistant discovery configs, if enabled.
+    pub fn connect_messages(&self) -> Vec<MqttMessage> {
+        let mut messages = vec![Self::message(self.availability_topic(), ONLINE)];
+        let Some(discovery_prefix) = &self.discovery_prefix else {
+            return messages;
+        };
+
+        let mut sensors = vec![
+            (
+                "public_ipv4".to_owned(),
+                "Public IPv4".to_owned(),
+                format!("{}/ipv4", self.prefix),
+                None,
+            ),
+            (
+                "public_ipv6".to_owned(),
+                "Public IPv6".to_owned(),
+                format!("{}/ipv6", self.prefix),
+                None,
+            ),
+            (
+                "next_update".to_owned(),
+                "Next update".to_owned(),
+                format!("{}/next_update", self.prefix),
+                Some("timestamp"),
+            ),
+        ];
+        for record in &self.records {
+            let name = format!("{} {}", record.fqdn, record.record_type);
+            sensors.extend([
+                (
+                    format!("{}_status", record.id),
+                    format!("{name} status"),
+                    self.record_topic(&record.id, "status"),
+                    None,
+                ),
+                (
+                    format!("{}_value", record.id),
+                    format!("{name} value"),
+                    self.record_topic(&record.id, "value"),
+                    None,
+                ),
+                (
+                    format!("{}_last_update", record.id),
+                    format!("{name} last update"),
+                    self.record_topic(&record.id, "last_update"),
+                    Some("timestamp"),
+                ),
+            ]);
+        }
+
+        messages.extend(
+            sensors
+                .into_iter()
+                .map(|(object_id, name, state_topic, device_class)| {
+                    let mut config = json!({
+                        "name": name,
+                        "unique_id": format!("{}_{}", self.node_id, object_id),
+                        "object_id": format!("{}_{}", self.node_id, object_id),
+                        "state_topic": state_topic,
+                        "availability_topic": self.availability_topic(),
+                        "device": {
+                            "identifiers": [self.node_id],
+                            "name": "Dynamic DNS",
+                            "model": "do_ddns",
+                            "sw_version": env!("CARGO_PKG_VERSION"),
+                        },
+                    });
+                    if let Some(device_class) = device_class {
+                        config["device_class"] = json!(device_class);
+                    }
+                    Self::message(
+                        format!(
+                            "{}/sensor/{}/{}/config",
+                            discovery_prefix, self.node_id, object_id
+                        ),
+                        config.to_string(),
+                    )
+                }),
+        );
+        messages
+    }
+
+    /// Messages updating the topics affected by an updater event.
+    pub fn messages_for_event(&mut self, event: &UpdaterEvent) -> Vec<MqttMessage> {
+        match event {
+            UpdaterEvent::IpFetched { ipv4, ipv6 } => [("ipv4", ipv4), ("ipv6", ipv6)]
+                .into_iter()
+                .filter_map(|(family, ip)| {
+                    ip.as_ref()
+                        .map(|ip| Self::message(format!("{}/{}", self.prefix, family), ip))
+                })
+                .collect(),
+            UpdaterEvent::RecordUpdateSucceeded {
+                name,
+                record_type,
+                provider,
+                value,
+            } => {
+                let record_id = topic_id(&format!("{name}_{record_type}"));
+                self.failing_providers
+                    .entry(record_id.clone())
+                    .or_default()
+                    .remove(provider);
+                vec![
+                    self.record_status_message(&record_id),
+                    Self::message(self.record_topic(&record_id, "value"), value),
+                    Self::message(
+                        self.record_topic(&record_id, "last_update"),
+                        format_timestamp(Utc::now()),
+                    ),
+                ]
+            }
+            UpdaterEvent::RecordUpdateFailed {
+                name,
+                record_type,
+                provider,
+                ..
+            } => {
+                let record_id = topic_id(&format!("{name}_{record_type}"));
+                self.failing_providers
+                    .entry(record_id.clone())
+                    .or_default()
+                    .insert(provider.clone());
+                vec![self.record_status_message(&record_id)]
+            }
+            UpdaterEvent::UpdaterSleeping { until } => vec![Self::message(
+                format!("{}/next_update", self.prefix),
+                format_timestamp(*until),
+            )],
+            UpdaterEvent::IpFetchFailed { .. }
+            | UpdaterEvent::IpChanged { .. }
+            | UpdaterEvent::RecordUpdateStarted { .. } => vec![],
+        }
+    }
+
+    /// A record is `synced` when its last update succeeded on every provider.
+    fn record_status_message(&self, record_id: &str) -> MqttMessage {
+        let synced = self
+            .failing_providers
+            .get(record_id)
+            .map_or(true, BTreeSet::is_empty);
+        Self::message(
+            self.record_topic(record_id, "status"),
+            if synced { "synced" } else { "failed" },
+        )
+    }
+}
+
+/// Publishes the updater state to an MQTT broker, reconnecting as needed.
+///
+/// Dropping it marks the updater as offline and disconnects.
+pub struct MqttPublisher {
+    client: Client,
+    availability_topic: String,
+    shutdown: Arc<AtomicBool>,
+    connection_thread: Option<JoinHandle<()>>,
+}
+
+impl MqttPublisher {
+    pub fn start(
+        config: &MqttConfig,
+        records: &[DomainRecordToUpdate],
+        events: &UpdaterEvents,
+    ) -> Result<Self> {
+        let broker = config.broker()?;
+        let topics = MqttTopics::new(config, records);
+        let availability_topic = topics.availability_topic();
+
+        let mut options = MqttOptions::new(&config.client_id, &broker.host, broker.port);
+        options.set_keep_alive(config.keep_alive);
+        options.set_last_will(LastWill::new(
+            &availability_topic,
+            OFFLINE,
+            QoS::AtLeastOnce,
+            true,
+        ));
+        if let Some(username) = &config.username {
+            let password = match &config.password_file {
+                Some(path) => std::fs::read_to_string(path)
+                    .wrap_err_with(|| {
+                        format!("Failed to read MQTT password from '{}'", path.display())
+                    })?
+                    .trim_end_matches(['\r', '\n'])
+                    .to_owned(),
+                None => String::new(),
+            };
+            options.set_credentials(username, password);
+        }
+        if broker.tls {
+            options.set_transport(match &config.ca_cert_path {
+                Some(path) => Transport::tls(
+                    std::fs::read(path).wrap_err_with(|| {
+                        format!(
+                            "Failed to read MQTT CA certificates from '{}'",
+                            path.display()
+                        )
+                    })?,
+                    None,
+                    None,
+                ),
+                None => Transport::tls_with_default_config(),
+            });
+        }
+
+        let connect_messages = topics.connect_messages();
+        let (client, connection) =
+            Client::new(options, connect_messages.len() + REQUESTS_CHANNEL_CAPACITY);
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let connection_thread = {
+            let client = client.clone();
+            let shutdown = shutdown.clone();
+            std::thread::spawn(move || {
+                run_connection(connection, &client, &connect_messages, &shutdown);
+            })
+        };
+        {
+            let client = client.clone();
+            let receiver = events.subscribe();
+            // Ends on the first event after the connection is closed.
+            std::thread::spawn(move || publish_events(&client, topics, receiver));
+        }
+
+        info!(
+            "Publishing updater state to MQTT broker {}:{}",
+            broker.host, broker.port
+        );
+        Ok(Self {
+            client,
+            availability_topic,
+            shutdown,
+            connection_thread: Some(connection_thread),
+        })
+    }
+}
+
+impl Drop for MqttPublisher {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        if let Err(e) =
+            self.client
+                .try_publish(&self.availability_topic, QoS::AtLeastOnce, true, OFFLINE)
+        {
+            debug!("Failed to publish MQTT offline status: {}", e);
+        }
+        if let Err(e) = self.client.try_disconnect() {
+            debug!("Failed to disconnect from MQTT broker: {}", e);
+        }
+        if let Some(connection_thread) = self.connection_thread.take() {
+            if connection_thread.join().is_err() {
+                error!("MQTT connection thread panicked");
+            }
+        }
+    }
+}
+
+fn run_connection(
+    mut connection: Connection,
+    client: &Client,
+    connect_messages: &[MqttMessage],
+    shutdown: &AtomicBool,
+) {
+    for notification in connection.iter() {
+        match notification {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                info!("Connected to MQTT broker");
+                for message in connect_messages {
+                    if let Err(e) = client.try_publish(
+                        &message.topic,
+                        QoS::AtLeastOnce,
+                        true,
+                        message.payload.clone(),
+                    ) {
+                        warn!("Failed to publish to MQTT topic '{}': {}", message.topic, e);
+                    }
+                }
+            }
+            Ok(Event::Outgoing(Outgoing::Disconnect)) => return,
+            Ok(_) => (),
+            Err(e) => {
+                if shutdown.load(Ordering::SeqCst) {
+                    return;
+                }
+                warn!(
+                    "MQTT connection error: {}. Reconnecting in {:?}",
+                    e, RECONNECT_DELAY
+                );
+                std::thread::sleep(RECONNECT_DELAY);
+            }
+        }
+    }
+}
+
+fn publish_events(
+    client: &Client,
+    mut topics: MqttTopics,
+    mut receiver: broadcast::Receiver<UpdaterEvent>,
+) {
+    loop {
+        let event = match receiver.blocking_recv() {
+            Ok(event) => event,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("MQTT publisher is too slow, skipped {} events", skipped);
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        for message in topics.messages_for_event(&event) {
+            if let Err(e) = client.publish(message.topic, QoS::AtLeastOnce, true, message.payload) {
+                debug!("Stopped publishing updater events to MQTT: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::net::{SocketAddr, TcpListener};
+    use std::time::Instant;
+
+    fn mqtt_config(port: u16) -> MqttConfig {
+        toml::from_str(&format!(
+            r#"broker_url = "mqtt://127.0.0.1:{port}"
+            client_id = "do_ddns_test""#
+        ))
+        .unwrap()
+    }
+
+    fn records() -> Vec<DomainRecordToUpdate> {
+        vec![DomainRecordToUpdate::new("site.com", "home", "A", None)]
+    }
+
+    fn record_succeeded(provider: &str) -> UpdaterEvent {
+        UpdaterEvent::RecordUpdateSucceeded {
+            name: "home.site.com".to_owned(),
+            record_type: "A".to_owned(),
+            provider: provider.to_owned(),
+            value: "192.0.2.2".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_topics_for_events() {
+        let mut topics = MqttTopics::new(&mqtt_config(1883), &records());
+        let messages = topics.messages_for_event(&UpdaterEvent::IpFetched {
+            ipv4: Some("192.0.2.2".to_owned()),
+            ipv6: None,
+        });
+        assert_eq!(
+            messages,
+            vec![MqttMessage {
+                topic: "dyndns/ipv4".to_owned(),
+                payload: "192.0.2.2".to_owned(),
+            }]
+        );
+
+        let status = |messages: Vec<MqttMessage>| {
+            messages
+                .into_iter()
+                .find(|message| message.topic == "dyndns/records/home_site_com_a/status")
+                .map(|message| message.payload)
+        };
+        let failed = UpdaterEvent::RecordUpdateFailed {
+            name: "home.site.com".to_owned(),
+            record_type: "A".to_owned(),
+            provider: "Hetzner".to_owned(),
+            error_message: "timeout".to_owned(),
+        };
+        assert_eq!(
+            status(topics.messages_for_event(&failed)).as_deref(),
+            Some("failed")
+        );
+        // Still failed on the other provider.
+        assert_eq!(
+            status(topics.messages_for_event(&record_succeeded("DigitalOcean"))).as_deref(),
+            Some("failed")
+        );
+        assert_eq!(
+            status(topics.messages_for_event(&record_succeeded("Hetzner"))).as_deref(),
+            Some("synced")
+        );
+
+        let connect_messages = topics.connect_messages();
+        assert!(connect_messages
+            .iter()
+            .any(|message| message.topic == "dyndns/status" && message.payload == ONLINE));
+        assert!(connect_messages.iter().any(|message| message.topic
+            == "homeassistant/sensor/do_ddns_test/home_site_com_a_last_update/config"
+            && message.payload.contains(r#""device_class":"timestamp""#)));
+    }
+
+    fn start_broker() -> u16 {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let server = rumqttd::ServerSettings {
+            name: "v4".to_owned(),
+            listen: SocketAddr::from(([127, 0, 0, 1], port)),
+            tls: None,
+            next_connection_delay_ms: 1,
+            connections: rumqttd::ConnectionSettings {
+                connection_timeout_ms: 5000,
+                max_payload_size: 20480,
+                max_inflight_count: 100,
+                auth: None,
+                external_auth: None,
+                dynamic_filters: true,
+            },
+        };
+        let config = rumqttd::Config {
+            router: rumqttd::RouterConfig {
+                max_connections: 10,
+                max_outgoing_packet_count: 200,
+                max_segment_size: 1024 * 1024,
+                max_segment_count: 10,
+                ..Default::default()
+            },
+            v4: Some(HashMap::from([("1".to_owned(), server)])),
+            ..Default::default()
+        };
+        let mut broker = rumqttd::Broker::new(config);
+        std::thread::spawn(move || broker.start());
+        port
+    }
+
+    #[test]
+    fn test_publish_to_broker() {
+        let port = start_broker();
+        let events = UpdaterEvents::new();
+        let publisher = MqttPublisher::start(&mqtt_config(port), &records(), &events).unwrap();
+        events.publish(UpdaterEvent::IpFetched {
+            ipv4: Some("192.0.2.2".to_owned()),
+            ipv6: None,
+        });
+        events.publish(record_succeeded("DigitalOcean"));
+
+        let (client, mut connection) = Client::new(
+            MqttOptions::new("do_ddns_test_subscriber", "127.0.0.1", port),
+            10,
+        );
+        client.subscribe("dyndns/#", QoS::AtLeastOnce).unwrap();
+        client
+            .subscribe("homeassistant/#", QoS::AtLeastOnce)
+            .unwrap();
+
+        let expected = [
+            ("dyndns/status", ONLINE),
+            ("dyndns/ipv4", "192.0.2.2"),
+            ("dyndns/records/home_site_com_a/status", "synced"),
+        ];
+        let mut received = HashMap::new();
+        let mut discovery_configs = 0;
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while Instant::now() < deadline
+            && (discovery_configs == 0
+                || expected.iter().any(|(topic, payload)| {
+                    received.get(*topic).map(String::as_str) != Some(*payload)
+                }))
+        {
+            match connection.recv_timeout(Duration::from_millis(100)) {
+                Ok(Ok(Event::Incoming(Packet::Publish(publish)))) => {
+                    if publish.topic.starts_with("homeassistant/") {
+                        discovery_configs += 1;
+                    }
+                    received.insert(
+                        publish.topic,
+                        String::from_utf8_lossy(&publish.payload).into_owned(),
+                    );
+                }
+                Ok(Err(_)) => std::thread::sleep(Duration::from_millis(100)),
+                _ => (),
+            }
+        }
+        assert!(discovery_configs > 0);
+        for (topic, payload) in expected {
+            assert_eq!(received.get(topic).map(String::as_str), Some(payload));
+        }
+        drop(publisher);
+    }
+}
@@ -0,0 +1,158 @@
+use hickory_resolver::config::NameServerConfigGroup;
+use hickory_resolver::name_server::GenericConnector;
+use hickory_resolver::proto::runtime::iocompat::AsyncIoTokioAsStd;
+use hickory_resolver::proto::runtime::{
+    RuntimeProvider, TokioHandle, TokioRuntimeProvider, TokioTime,
+};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::time::Duration;
+
+use crate::config::bind_config::BindConfig;
+
+/// Applies the binding to an HTTP client.
+pub fn bind_client_builder(
+    bind: &BindConfig,
+    mut builder: reqwest::ClientBuilder,
+) -> reqwest::ClientBuilder {
+    if let Some(source_address) = bind.source_address {
+        builder = builder.local_address(source_address);
+    }
+    #[cfg(target_os = "linux")]
+    if let Some(interface) = &bind.interface {
+        builder = builder.interface(interface);
+    }
+    builder
+}
+
+/// Makes DNS queries to the name servers use the source address of the
+/// binding. Binding to an interface is done by [`BoundConnectionProvider`].
+pub fn bind_name_servers(bind: &BindConfig, name_servers: &mut NameServerConfigGroup) {
+    if let Some(source_address) = bind.source_address {
+        for name_server in name_servers.iter_mut() {
+            name_server.bind_addr = Some(SocketAddr::new(source_address, 0));
+        }
+    }
+}
+
+/// Creates a socket for connecting to `target`, bound to the interface and
+/// source address of the binding.
+pub fn bound_socket(
+    bind: &BindConfig,
+    target: SocketAddr,
+    socket_type: Type,
+    protocol: Protocol,
+) -> io::Result<Socket> {
+    let socket = Socket::new(Domain::for_address(target), socket_type, Some(protocol))?;
+    if let Some(interface) = &bind.interface {
+        bind_to_interface(&socket, interface)?;
+    }
+    if let Some(source_address) = bind.source_address {
+        socket.bind(&SocketAddr::new(source_address, 0).into())?;
+    }
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+#[cfg(target_os = "linux")]
+fn bind_to_interface(socket: &Socket, interface: &str) -> io::Result<()> {
+    socket.bind_device(Some(interface.as_bytes()))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn bind_to_interface(_socket: &Socket, interface: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("Binding to interface '{interface}' is only supported on Linux"),
+    ))
+}
+
+/// Connects to `target` with a socket bound according to the binding.
+pub async fn connect_tcp(
+    bind: &BindConfig,
+    target: SocketAddr,
+) -> io::Result<tokio::net::TcpStream> {
+    let socket = bound_socket(bind, target, Type::STREAM, Protocol::TCP)?;
+    tokio::net::TcpSocket::from_std_stream(socket.into())
+        .connect(target)
+        .await
+}
+
+/// Tokio runtime for the DNS resolver, whose sockets are bound to the
+/// interface of the binding.
+#[derive(Clone, Default)]
+pub struct BoundRuntimeProvider {
+    runtime: TokioRuntimeProvider,
+    interface: Option<String>,
+}
+
+pub type BoundConnectionProvider = GenericConnector<BoundRuntimeProvider>;
+
+pub fn bound_connection_provider(bind: &BindConfig) -> BoundConnectionProvider {
+    GenericConnector::new(BoundRuntimeProvider {
+        runtime: TokioRuntimeProvider::default(),
+        interface: bind.interface.clone(),
+    })
+}
+
+impl RuntimeProvider for BoundRuntimeProvider {
+    type Handle = TokioHandle;
+    type Timer = TokioTime;
+    type Udp = tokio::net::UdpSocket;
+    type Tcp = AsyncIoTokioAsStd<tokio::net::TcpStream>;
+
+    fn create_handle(&self) -> Self::Handle {
+        self.runtime.create_handle()
+    }
+
+    fn connect_tcp(
+        &self,
+        server_addr: SocketAddr,
+        bind_addr: Option<SocketAddr>,
+        wait_for: Option<Duration>,
+    ) -> Pin<Box<dyn Send + Future<Output = io::Result<Self::Tcp>>>> {
+        let Some(interface) = self.interface.clone() else {
+            return self.runtime.connect_tcp(server_addr, bind_addr, wait_for);
+        };
+        Box::pin(async move {
+            let bind = BindConfig {
+                interface: Some(interface),
+                source_address: bind_addr.map(|bind_addr| bind_addr.ip()),
+            };
+            let connect = connect_tcp(&bind, server_addr);
+            let stream = match wait_for {
+                Some(wait_for) => tokio::time::timeout(wait_for, connect)
+                    .await
+                    .unwrap_or_else(|elapsed| {
+                        Err(io::Error::new(io::ErrorKind::TimedOut, elapsed))
+                    })?,
+                None => connect.await?,
+            };
+            Ok(AsyncIoTokioAsStd(stream))
+        })
+    }
+
+    fn bind_udp(
+        &self,
+        local_addr: SocketAddr,
+        server_addr: SocketAddr,
+    ) -> Pin<Box<dyn Send + Future<Output = io::Result<Self::Udp>>>> {
+        let Some(interface) = self.interface.clone() else {
+            return self.runtime.bind_udp(local_addr, server_addr);
+        };
+        Box::pin(async move {
+            let socket = Socket::new(
+                Domain::for_address(server_addr),
+                Type::DGRAM,
+                Some(Protocol::UDP),
+            )?;
+            bind_to_interface(&socket, &interface)?;
+            socket.bind(&local_addr.into())?;
+            socket.set_nonblocking(true)?;
+            tokio::net::UdpSocket::from_std(socket.into())
+        })
+    }
+}
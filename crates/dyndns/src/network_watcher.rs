@@ -0,0 +1,179 @@
+//! Wakes up the updater when network interfaces or addresses change, so a new
+//! public IP is picked up without waiting for the next update interval.
+
+use color_eyre::eyre::Result;
+use std::sync::Arc;
+
+use crate::update_trigger::UpdateTrigger;
+
+/// Starts a detached thread notifying `update_trigger` about network changes.
+///
+/// The thread stops once the updater is not running anymore.
+#[cfg(target_os = "linux")]
+pub fn start_network_watcher(update_trigger: Arc<UpdateTrigger>) -> Result<()> {
+    linux::start_network_watcher(update_trigger)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn start_network_watcher(_update_trigger: Arc<UpdateTrigger>) -> Result<()> {
+    color_eyre::eyre::bail!("Watching for network changes is only supported on Linux")
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use color_eyre::eyre::{Result, WrapErr};
+    use netlink_sys::{protocols::NETLINK_ROUTE, Socket, SocketAddr};
+    use std::sync::Arc;
+    use tracing::{debug, warn};
+
+    use crate::update_trigger::UpdateTrigger;
+
+    // Multicast groups and message types from linux/rtnetlink.h.
+    const RTMGRP_LINK: u32 = 0x1;
+    const RTMGRP_IPV4_IFADDR: u32 = 0x10;
+    const RTMGRP_IPV6_IFADDR: u32 = 0x100;
+    const RTM_NEWLINK: u16 = 16;
+    const RTM_DELLINK: u16 = 17;
+    const RTM_NEWADDR: u16 = 20;
+    const RTM_DELADDR: u16 = 21;
+
+    /// Size of `struct nlmsghdr`.
+    const NLMSG_HEADER_LEN: usize = 16;
+
+    pub fn start_network_watcher(update_trigger: Arc<UpdateTrigger>) -> Result<()> {
+        let socket = open_route_socket().wrap_err("Failed to subscribe to netlink route events")?;
+        std::thread::Builder::new()
+            .name("network watcher".to_owned())
+            .spawn(move || watch_network_changes(&socket, &update_trigger))
+            .wrap_err("Failed to start the network watcher thread")?;
+        Ok(())
+    }
+
+    fn open_route_socket() -> std::io::Result<Socket> {
+        let mut socket = Socket::new(NETLINK_ROUTE)?;
+        socket.bind(&SocketAddr::new(
+            0,
+            RTMGRP_LINK | RTMGRP_IPV4_IFADDR | RTMGRP_IPV6_IFADDR,
+        ))?;
+        Ok(socket)
+    }
+
+    fn watch_network_changes(socket: &Socket, update_trigger: &UpdateTrigger) {
+        loop {
+            let changes = match socket.recv_from_full() {
+                Ok((buf, _)) => message_types(&buf)
+                    .into_iter()
+                    .filter_map(describe_change)
+                    .collect::<Vec<_>>(),
+                // The kernel dropped messages because the socket buffer was
+                // full. Some changes were missed, but the update will pick them up.
+                Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) => vec!["unknown change"],
+                Err(e) => {
+                    warn!(
+                        "Failed to receive network changes, updating every update interval only: {}",
+                        e
+                    );
+                    return;
+                }
+            };
+            if changes.is_empty() {
+                continue;
+            }
+            debug!("Network changed: {}", changes.join(", "));
+            if !update_trigger.notify_network_change() {
+                debug!("The updater is not running anymore, stopping the network watcher");
+                return;
+            }
+        }
+    }
+
+    fn describe_change(message_type: u16) -> Option<&'static str> {
+        match message_type {
+            RTM_NEWLINK => Some("link added or changed"),
+            RTM_DELLINK => Some("link removed"),
+            RTM_NEWADDR => Some("address added"),
+            RTM_DELADDR => Some("address removed"),
+            _ => None,
+        }
+    }
+
+    /// Returns the types of the netlink messages in a datagram.
+    fn message_types(mut buf: &[u8]) -> Vec<u16> {
+        let read_u32 = |buf: &[u8]| buf.get(..4)?.try_into().ok().map(u32::from_ne_bytes);
+        let read_u16 = |buf: &[u8]| buf.get(4..6)?.try_into().ok().map(u16::from_ne_bytes);
+        let mut types = vec![];
+        while let (Some(len), Some(message_type)) = (read_u32(buf), read_u16(buf)) {
+            let len = len as usize;
+            // Ignore truncated messages.
+            if len < NLMSG_HEADER_LEN || len > buf.len() {
+                break;
+            }
+            types.push(message_type);
+            // Messages are aligned to 4 bytes.
+            buf = buf.get((len + 3) & !3..).unwrap_or_default();
+        }
+        types
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::time::{Duration, Instant};
+
+        fn message(message_type: u16, payload_len: usize) -> Vec<u8> {
+            let len = (NLMSG_HEADER_LEN + payload_len) as u32;
+            let mut message = len.to_ne_bytes().to_vec();
+            message.extend(message_type.to_ne_bytes());
+            message.resize(NLMSG_HEADER_LEN + payload_len, 0);
+            message.resize((message.len() + 3) & !3, 0);
+            message
+        }
+
+        #[test]
+        fn test_message_types() {
+            let mut buf = message(RTM_NEWADDR, 6);
+            buf.extend(message(RTM_NEWLINK, 8));
+            buf.extend(message(3, 0));
+            assert_eq!(message_types(&buf), vec![RTM_NEWADDR, RTM_NEWLINK, 3]);
+            assert_eq!(
+                message_types(&buf)
+                    .into_iter()
+                    .filter_map(describe_change)
+                    .collect::<Vec<_>>(),
+                vec!["address added", "link added or changed"]
+            );
+
+            assert!(message_types(&[]).is_empty());
+            let truncated = message(RTM_DELADDR, 0);
+            assert!(message_types(truncated.get(..10).unwrap()).is_empty());
+        }
+
+        #[test]
+        #[ignore = "needs CAP_NET_ADMIN to add a dummy interface"]
+        fn test_dummy_interface_wakes_updater() {
+            let update_trigger = Arc::new(UpdateTrigger::new());
+            update_trigger.attach_updater_thread();
+            start_network_watcher(update_trigger.clone()).unwrap();
+
+            let ip = |args: &[&str]| {
+                let status = std::process::Command::new("ip").args(args).status();
+                assert!(status.is_ok_and(|status| status.success()));
+            };
+            ip(&["link", "add", "dyndns-test0", "type", "dummy"]);
+            ip(&["addr", "add", "192.0.2.10/24", "dev", "dyndns-test0"]);
+
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while update_trigger
+                .network_change_settles_in(Duration::ZERO)
+                .is_none()
+                && Instant::now() < deadline
+            {
+                std::thread::park_timeout(Duration::from_millis(100));
+            }
+            ip(&["link", "del", "dyndns-test0"]);
+            assert!(update_trigger
+                .network_change_settles_in(Duration::ZERO)
+                .is_some());
+        }
+    }
+}
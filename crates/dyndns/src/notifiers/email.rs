@@ -0,0 +1,283 @@
+use color_eyre::eyre::{Result, WrapErr};
+use lettre::message::header::ContentType;
+use lettre::message::{Mailbox, Message};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::client::{Tls, TlsParameters};
+use lettre::{SmtpTransport, Transport};
+use std::time::Duration;
+
+use super::template::render_template;
+use super::{Notification, Notifier};
+use crate::config::notifier_config::{EmailNotifierConfig, SmtpSecurity};
+
+const DEFAULT_SUBJECT_TEMPLATE: &str = "[dyndns] {{ summary }}";
+const DEFAULT_BODY_TEMPLATE: &str = "{{ timestamp }} {{ summary }}";
+
+/// Sends batches of notifications as a single plain text email.
+pub struct EmailNotifier {
+    transport: SmtpTransport,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    subject: String,
+    body: String,
+    batch_window: Duration,
+}
+
+impl EmailNotifier {
+    pub fn new(config: &EmailNotifierConfig) -> Result<Self> {
+        let (from, to) = parse_addresses(config)?;
+
+        let mut transport = SmtpTransport::builder_dangerous(&config.smtp_host)
+            .port(
+                config
+                    .smtp_port
+                    .unwrap_or_else(|| config.smtp_security.default_port()),
+            )
+            .timeout(Some(config.timeout));
+        let tls_parameters = || {
+            TlsParameters::new(config.smtp_host.clone())
+                .wrap_err("Failed to set up TLS for the email notifier")
+        };
+        match config.smtp_security {
+            SmtpSecurity::StartTls => transport = transport.tls(Tls::Required(tls_parameters()?)),
+            SmtpSecurity::Tls => transport = transport.tls(Tls::Wrapper(tls_parameters()?)),
+            SmtpSecurity::None => (),
+        }
+        if let Some(username) = &config.smtp_username {
+            let password = match &config.smtp_password_file {
+                Some(path) => std::fs::read_to_string(path)
+                    .wrap_err_with(|| {
+                        format!("Failed to read SMTP password from '{}'", path.display())
+                    })?
+                    .trim_end_matches(['\r', '\n'])
+                    .to_owned(),
+                None => String::new(),
+            };
+            transport = transport.credentials(Credentials::new(username.clone(), password));
+        }
+
+        Ok(Self {
+            transport: transport.build(),
+            from,
+            to,
+            subject: config
+                .subject
+                .clone()
+                .unwrap_or_else(|| DEFAULT_SUBJECT_TEMPLATE.to_owned()),
+            body: config
+                .body
+                .clone()
+                .unwrap_or_else(|| DEFAULT_BODY_TEMPLATE.to_owned()),
+            batch_window: config.batch_window,
+        })
+    }
+
+    fn build_message(&self, notifications: &[Notification]) -> Result<Message> {
+        let (subject, body) = render_email(&self.subject, &self.body, notifications)?;
+        let mut message = Message::builder().from(self.from.clone()).subject(subject);
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+        message
+            .header(ContentType::TEXT_PLAIN)
+            .body(body)
+            .wrap_err("Failed to build notification email")
+    }
+}
+
+impl Notifier for EmailNotifier {
+    fn notify(&self, notifications: &[Notification]) -> Result<()> {
+        let message = self.build_message(notifications)?;
+        self.transport
+            .send(&message)
+            .wrap_err("Failed to send notification email")?;
+        Ok(())
+    }
+
+    fn batch_window(&self) -> Duration {
+        self.batch_window
+    }
+}
+
+fn parse_addresses(config: &EmailNotifierConfig) -> Result<(Mailbox, Vec<Mailbox>)> {
+    let parse = |address: &String| {
+        address
+            .parse::<Mailbox>()
+            .wrap_err_with(|| format!("Invalid email address '{address}'"))
+    };
+    let from = parse(&config.from)?;
+    let to = config.to.iter().map(parse).collect::<Result<Vec<_>>>()?;
+    Ok((from, to))
+}
+
+/// Renders the subject for the first notification, and the body for each
+/// notification, one after another.
+fn render_email(
+    subject_template: &str,
+    body_template: &str,
+    notifications: &[Notification],
+) -> Result<(String, String)> {
+    let render = |template: &str, notification: &Notification| {
+        render_template(template, |name| notification.variable(name))
+    };
+    let mut subject = match notifications.first() {
+        Some(notification) => render(subject_template, notification)?,
+        None => String::new(),
+    };
+    if notifications.len() > 1 {
+        subject.push_str(&format!(" (and {} more)", notifications.len() - 1));
+    }
+    let body = notifications
+        .iter()
+        .map(|notification| render(body_template, notification))
+        .collect::<Result<Vec<_>>>()?
+        .join("\n");
+    Ok((subject, body))
+}
+
+/// Checks the addresses and that the templates only use known variables.
+pub fn validate_email_config(config: &EmailNotifierConfig) -> Result<()> {
+    parse_addresses(config)?;
+    render_email(
+        config
+            .subject
+            .as_deref()
+            .unwrap_or(DEFAULT_SUBJECT_TEMPLATE),
+        config.body.as_deref().unwrap_or(DEFAULT_BODY_TEMPLATE),
+        &[Notification::sample()],
+    )
+    .wrap_err("Invalid email notifier template")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::notifier_config::NotificationKind;
+    use crate::notifiers::{ConfiguredNotifier, Notifiers};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+
+    /// Minimal SMTP server accepting a single connection, returning the data
+    /// of the received emails.
+    fn start_smtp_sink() -> (u16, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut messages = vec![];
+            writer.write_all(b"220 localhost ESMTP sink\r\n").unwrap();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 {
+                let command = line.get(..4).unwrap_or_default().to_ascii_uppercase();
+                let reply: &[u8] = match command.as_str() {
+                    "DATA" => {
+                        writer
+                            .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                            .unwrap();
+                        let mut data = String::new();
+                        let mut data_line = String::new();
+                        while reader.read_line(&mut data_line).unwrap() > 0 && data_line != ".\r\n"
+                        {
+                            data.push_str(&data_line);
+                            data_line.clear();
+                        }
+                        messages.push(data);
+                        b"250 OK\r\n"
+                    }
+                    "QUIT" => {
+                        writer.write_all(b"221 Bye\r\n").unwrap();
+                        break;
+                    }
+                    _ => b"250 OK\r\n",
+                };
+                writer.write_all(reply).unwrap();
+                line.clear();
+            }
+            messages
+        });
+        (port, handle)
+    }
+
+    fn email_config(port: u16) -> EmailNotifierConfig {
+        EmailNotifierConfig {
+            smtp_host: "127.0.0.1".to_owned(),
+            smtp_port: Some(port),
+            smtp_security: SmtpSecurity::None,
+            smtp_username: None,
+            smtp_password_file: None,
+            from: "dyndns <dyndns@example.com>".to_owned(),
+            to: vec!["admin@example.com".to_owned()],
+            subject: None,
+            body: None,
+            batch_window: Duration::from_secs(600),
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    fn record_updated(fqdn: &str) -> Notification {
+        Notification {
+            old_ip: Some("192.0.2.1".to_owned()),
+            new_ip: Some("192.0.2.2".to_owned()),
+            fqdn: Some(fqdn.to_owned()),
+            record_type: Some("A".to_owned()),
+            provider: Some("DigitalOcean".to_owned()),
+            ..Notification::new(NotificationKind::RecordUpdated)
+        }
+    }
+
+    #[test]
+    fn test_email_batches_notifications() {
+        let (port, sink) = start_smtp_sink();
+        let config = EmailNotifierConfig {
+            subject: Some("{{ event }}".to_owned()),
+            body: Some("{{ fqdn }}: {{ old_ip }} -> {{ new_ip }}".to_owned()),
+            ..email_config(port)
+        };
+        let notifier = ConfiguredNotifier {
+            name: "email notifier".to_owned(),
+            events: NotificationKind::all(),
+            failure_threshold: 1,
+            retries: 0,
+            retry_delay: Duration::ZERO,
+            notifier: Box::new(EmailNotifier::new(&config).unwrap()),
+        };
+        let notifiers = Notifiers::new(vec![notifier]);
+        notifiers.notify(record_updated("home.site.com"));
+        notifiers.notify(record_updated("www.site.com"));
+        // Ends the batch window, sending the batch right away.
+        drop(notifiers);
+
+        let messages = sink.join().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert!(messages.iter().all(|message| {
+            message.contains("Subject: record_updated (and 1 more)")
+                && message.contains("To: admin@example.com")
+                && message.contains("home.site.com: 192.0.2.1 -> 192.0.2.2")
+                && message.contains("www.site.com: 192.0.2.1 -> 192.0.2.2")
+        }));
+    }
+
+    #[test]
+    fn test_render_email_templates() {
+        let (subject, body) = render_email(
+            "{{ event }} on {{ fqdn }}",
+            "{{ fqdn }}: {{ old_ip }} -> {{ new_ip }}",
+            &[record_updated("home.site.com")],
+        )
+        .unwrap();
+        assert_eq!(subject, "record_updated on home.site.com");
+        assert_eq!(body, "home.site.com: 192.0.2.1 -> 192.0.2.2");
+
+        let mut config = email_config(25);
+        assert!(validate_email_config(&config).is_ok());
+        config.subject = Some("{{ ip }}".to_owned());
+        assert!(validate_email_config(&config).is_err());
+        config.subject = None;
+        config.to = vec!["not an address".to_owned()];
+        assert!(validate_email_config(&config).is_err());
+    }
+}
@@ -0,0 +1,194 @@
+use color_eyre::eyre::{bail, Result, WrapErr};
+use humantime::format_duration;
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use super::{Notification, Notifier, NOTIFICATION_VARIABLES};
+use crate::config::hook_config::HookConfig;
+
+/// How often a running hook is checked for having exited.
+const HOOK_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Runs a local command for each notification, passing the notification
+/// variables as `DYNDNS_*` environment variables.
+pub struct HookNotifier {
+    command: PathBuf,
+    args: Vec<String>,
+    timeout: Duration,
+    env: BTreeMap<String, String>,
+}
+
+impl HookNotifier {
+    pub fn new(config: &HookConfig) -> Self {
+        Self {
+            command: config.command.clone(),
+            args: config.args.clone(),
+            timeout: config.timeout,
+            env: config.env.clone(),
+        }
+    }
+
+    fn run(&self, notification: &Notification) -> Result<()> {
+        let hook_name = self.command.display().to_string();
+        let mut command = Command::new(&self.command);
+        command
+            .args(&self.args)
+            .envs(&self.env)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        for name in NOTIFICATION_VARIABLES {
+            if let Some(value) = notification.variable(name) {
+                command.env(format!("DYNDNS_{}", name.to_ascii_uppercase()), value);
+            }
+        }
+
+        let mut child = command
+            .spawn()
+            .wrap_err_with(|| format!("Failed to start hook '{hook_name}'"))?;
+        let output_loggers = [
+            child
+                .stdout
+                .take()
+                .map(|stdout| spawn_output_logger(stdout, hook_name.clone(), false)),
+            child
+                .stderr
+                .take()
+                .map(|stderr| spawn_output_logger(stderr, hook_name.clone(), true)),
+        ];
+
+        let Some(status) = wait_with_timeout(&mut child, self.timeout)
+            .wrap_err_with(|| format!("Failed to wait for hook '{hook_name}'"))?
+        else {
+            // Processes started by the hook might still hold on to its output,
+            // so the loggers are left to finish on their own.
+            bail!(
+                "Hook '{}' timed out after {} and was killed",
+                hook_name,
+                format_duration(self.timeout)
+            );
+        };
+        for output_logger in output_loggers.into_iter().flatten() {
+            let _ = output_logger.join();
+        }
+        if !status.success() {
+            bail!("Hook '{}' failed with {}", hook_name, status);
+        }
+        Ok(())
+    }
+}
+
+impl Notifier for HookNotifier {
+    /// Runs the hook once per notification.
+    fn notify(&self, notifications: &[Notification]) -> Result<()> {
+        notifications
+            .iter()
+            .try_for_each(|notification| self.run(notification))
+    }
+}
+
+/// Logs each line of a hook's output.
+fn spawn_output_logger(
+    output: impl Read + Send + 'static,
+    hook_name: String,
+    is_stderr: bool,
+) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        for line in BufReader::new(output).lines().map_while(Result::ok) {
+            if is_stderr {
+                warn!("[hook {}] {}", hook_name, line);
+            } else {
+                info!("[hook {}] {}", hook_name, line);
+            }
+        }
+    })
+}
+
+/// Waits for the child to exit, killing it after the timeout. Returns `None`
+/// if it was killed.
+fn wait_with_timeout(child: &mut Child, timeout: Duration) -> std::io::Result<Option<ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            child.kill()?;
+            child.wait()?;
+            return Ok(None);
+        }
+        std::thread::sleep(HOOK_POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::notifier_config::NotificationKind;
+
+    fn shell_hook(script: &str, args: &[&str], timeout: Duration) -> HookNotifier {
+        HookNotifier {
+            command: PathBuf::from("/bin/sh"),
+            args: ["-c", script]
+                .iter()
+                .chain(args)
+                .map(|arg| (*arg).to_owned())
+                .collect(),
+            timeout,
+            env: BTreeMap::from([("EXTRA".to_owned(), "extra".to_owned())]),
+        }
+    }
+
+    fn ip_changed_notification() -> Notification {
+        Notification {
+            old_ip: Some("192.0.2.1".to_owned()),
+            new_ip: Some("192.0.2.2".to_owned()),
+            ..Notification::new(NotificationKind::IpChanged)
+        }
+    }
+
+    #[test]
+    fn test_hook_gets_event_environment() {
+        let output = tempfile::NamedTempFile::new().unwrap();
+        let hook = shell_hook(
+            r#"printf '%s %s %s [%s] %s' "$DYNDNS_EVENT" "$DYNDNS_OLD_IP" "$DYNDNS_NEW_IP" "$DYNDNS_FQDN" "$EXTRA" > "$0""#,
+            &[output.path().to_str().unwrap()],
+            Duration::from_secs(10),
+        );
+        hook.notify(&[ip_changed_notification()]).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output.path()).unwrap(),
+            "ip_changed 192.0.2.1 192.0.2.2 [] extra"
+        );
+    }
+
+    #[test]
+    fn test_hook_failures() {
+        let hook = shell_hook("echo failing; exit 3", &[], Duration::from_secs(10));
+        let error_msg = hook
+            .notify(&[ip_changed_notification()])
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("failed with exit status: 3"));
+
+        let hook = shell_hook("exec sleep 10", &[], Duration::from_millis(100));
+        let started = Instant::now();
+        let error_msg = hook
+            .notify(&[ip_changed_notification()])
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("timed out after 100ms"));
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        let hook = HookNotifier {
+            command: PathBuf::from("/nonexistent/dyndns-hook"),
+            ..shell_hook("", &[], Duration::from_secs(1))
+        };
+        assert!(hook.notify(&[ip_changed_notification()]).is_err());
+    }
+}
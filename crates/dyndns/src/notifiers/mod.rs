@@ -0,0 +1,559 @@
+pub mod email;
+pub mod hook;
+pub mod template;
+pub mod webhook;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use color_eyre::eyre::Result;
+use serde::Serialize;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::{error, warn};
+
+use crate::config::hook_config::HookConfig;
+use crate::config::notifier_config::{NotificationKind, NotifierConfig, NotifierKindConfig};
+use email::EmailNotifier;
+use hook::HookNotifier;
+use webhook::WebhookNotifier;
+
+/// How long dropping [`Notifiers`] waits for the queued notifications to be
+/// sent.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Names of the variables describing a notification, see
+/// [`Notification::variable`].
+pub const NOTIFICATION_VARIABLES: [&str; 10] = [
+    "event",
+    "timestamp",
+    "old_ip",
+    "new_ip",
+    "fqdn",
+    "record_type",
+    "provider",
+    "error",
+    "consecutive_failures",
+    "summary",
+];
+
+/// Something that happened in the updater that notifiers can report.
+///
+/// The fields are also the variables available to notifier templates, with
+/// `event` being the notification kind. Templates can also use `summary`, a
+/// human readable description of the notification.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Notification {
+    #[serde(rename = "event")]
+    pub kind: NotificationKind,
+    pub timestamp: DateTime<Utc>,
+    pub old_ip: Option<String>,
+    pub new_ip: Option<String>,
+    pub fqdn: Option<String>,
+    pub record_type: Option<String>,
+    pub provider: Option<String>,
+    pub error: Option<String>,
+    pub consecutive_failures: u32,
+}
+
+impl Notification {
+    pub fn new(kind: NotificationKind) -> Self {
+        Self {
+            kind,
+            timestamp: Utc::now(),
+            old_ip: None,
+            new_ip: None,
+            fqdn: None,
+            record_type: None,
+            provider: None,
+            error: None,
+            consecutive_failures: 0,
+        }
+    }
+
+    /// A notification with every variable set, used to validate templates.
+    pub fn sample() -> Self {
+        Self {
+            old_ip: Some("192.0.2.1".to_owned()),
+            new_ip: Some("192.0.2.2".to_owned()),
+            fqdn: Some("home.example.com".to_owned()),
+            record_type: Some("A".to_owned()),
+            provider: Some("DigitalOcean".to_owned()),
+            error: Some("Sample error".to_owned()),
+            consecutive_failures: 1,
+            ..Self::new(NotificationKind::UpdateFailed)
+        }
+    }
+
+    /// Value of a template variable, or `None` if there is no such variable.
+    /// Unset values are empty.
+    pub fn variable(&self, name: &str) -> Option<String> {
+        let optional = |value: &Option<String>| Some(value.clone().unwrap_or_default());
+        match name {
+            "event" => Some(self.kind.as_str().to_owned()),
+            "timestamp" => Some(self.timestamp.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            "old_ip" => optional(&self.old_ip),
+            "new_ip" => optional(&self.new_ip),
+            "fqdn" => optional(&self.fqdn),
+            "record_type" => optional(&self.record_type),
+            "provider" => optional(&self.provider),
+            "error" => optional(&self.error),
+            "consecutive_failures" => Some(self.consecutive_failures.to_string()),
+            "summary" => Some(self.summary()),
+            _ => None,
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let value = |value: &Option<String>| value.clone().unwrap_or_else(|| "unknown".to_owned());
+        match self.kind {
+            NotificationKind::IpChanged => format!(
+                "Public IP changed from {} to {}",
+                value(&self.old_ip),
+                value(&self.new_ip)
+            ),
+            NotificationKind::RecordUpdated => format!(
+                "Domain record '{}' of type '{}' was updated on {} from {} to {}",
+                value(&self.fqdn),
+                value(&self.record_type),
+                value(&self.provider),
+                value(&self.old_ip),
+                value(&self.new_ip)
+            ),
+            NotificationKind::UpdateFailed => format!(
+                "{} consecutive update attempts failed: {}",
+                self.consecutive_failures,
+                value(&self.error)
+            ),
+        }
+    }
+}
+
+pub trait Notifier: Send {
+    /// Sends a batch of notifications, which has a single notification unless
+    /// [`Notifier::batch_window`] is non-zero.
+    ///
+    /// Notifiers that send each notification of a batch separately wrap their
+    /// error in [`PartiallyDelivered`], so that only the undelivered ones are
+    /// retried.
+    fn notify(&self, notifications: &[Notification]) -> Result<()>;
+
+    /// How long to wait for more notifications after the first one, to send
+    /// them together.
+    fn batch_window(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+/// Context of a notifier error, telling how many notifications of the batch
+/// were delivered before the failure.
+#[derive(Debug)]
+pub struct PartiallyDelivered {
+    pub delivered: usize,
+}
+
+impl std::fmt::Display for PartiallyDelivered {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Failed after delivering {} notification(s)",
+            self.delivered
+        )
+    }
+}
+
+/// A notifier together with the settings deciding what it is sent.
+pub struct ConfiguredNotifier {
+    /// Describes the notifier in logs.
+    pub name: String,
+    pub events: Vec<NotificationKind>,
+    pub failure_threshold: u32,
+    pub retries: u32,
+    pub retry_delay: Duration,
+    pub notifier: Box<dyn Notifier>,
+}
+
+impl ConfiguredNotifier {
+    pub fn from_config(config: &NotifierConfig) -> Result<Self> {
+        let notifier: Box<dyn Notifier> = match &config.kind {
+            NotifierKindConfig::Webhook(webhook_config) => {
+                Box::new(WebhookNotifier::new(webhook_config)?)
+            }
+            NotifierKindConfig::Email(email_config) => Box::new(EmailNotifier::new(email_config)?),
+        };
+        Ok(Self {
+            name: format!("{} notifier", config.kind.as_str()),
+            events: config.events.clone(),
+            failure_threshold: config.failure_threshold,
+            retries: config.retries,
+            retry_delay: config.retry_delay,
+            notifier,
+        })
+    }
+
+    /// Hooks are not retried, as they might not be idempotent.
+    pub fn from_hook_config(config: &HookConfig) -> Self {
+        Self {
+            name: format!("hook '{}'", config.command.display()),
+            events: vec![config.event],
+            failure_threshold: config.failure_threshold,
+            retries: 0,
+            retry_delay: Duration::ZERO,
+            notifier: Box::new(HookNotifier::new(config)),
+        }
+    }
+
+    /// Failures are only reported once, when the consecutive failures reach
+    /// the configured threshold.
+    fn wants(&self, notification: &Notification) -> bool {
+        self.events.contains(&notification.kind)
+            && (notification.kind != NotificationKind::UpdateFailed
+                || notification.consecutive_failures == self.failure_threshold)
+    }
+
+    /// Sends the wanted notifications until the channel is closed, batching
+    /// the ones that arrive within the notifier's batch window.
+    fn run(&self, receiver: &mpsc::Receiver<Notification>, shutdown: &Shutdown) {
+        let batch_window = self.notifier.batch_window();
+        while let Ok(notification) = receiver.recv() {
+            if !self.wants(&notification) {
+                continue;
+            }
+            let mut batch = vec![notification];
+            let deadline = Instant::now() + batch_window;
+            loop {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                if timeout.is_zero() {
+                    break;
+                }
+                match receiver.recv_timeout(timeout) {
+                    Ok(notification) => {
+                        if self.wants(&notification) {
+                            batch.push(notification);
+                        }
+                    }
+                    // Send what was collected so far also when shutting down.
+                    Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            self.notify_with_retries(&batch, shutdown);
+        }
+    }
+
+    /// Retries the notifications that weren't delivered, unless shutting
+    /// down.
+    fn notify_with_retries(&self, notifications: &[Notification], shutdown: &Shutdown) {
+        let mut notifications = notifications;
+        let mut attempt = 0;
+        loop {
+            match self.notifier.notify(notifications) {
+                Ok(()) => return,
+                Err(e) if attempt < self.retries => {
+                    attempt += 1;
+                    let delivered = e
+                        .downcast_ref::<PartiallyDelivered>()
+                        .map_or(0, |partial| partial.delivered);
+                    notifications = notifications.get(delivered..).unwrap_or_default();
+                    if notifications.is_empty() {
+                        return;
+                    }
+                    warn!(
+                        "Failed to send {} notification(s) to {}, retry {} of {}: {:#}",
+                        notifications.len(),
+                        self.name,
+                        attempt,
+                        self.retries,
+                        e
+                    );
+                    if shutdown.sleep(self.retry_delay) {
+                        warn!(
+                            "Shutting down, dropping {} notification(s) to {}",
+                            notifications.len(),
+                            self.name
+                        );
+                        return;
+                    }
+                }
+                Err(e) => {
+                    error!(
+                        "Failed to send {} notification(s) to {}: {:#}",
+                        notifications.len(),
+                        self.name,
+                        e
+                    );
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Tells notifier threads to stop retrying, because the notifiers are being
+/// dropped.
+#[derive(Default)]
+struct Shutdown {
+    requested: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl Shutdown {
+    fn request(&self) {
+        *self
+            .requested
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = true;
+        self.condvar.notify_all();
+    }
+
+    /// Sleeps for `duration`, or until a shutdown is requested. Returns
+    /// whether it was requested.
+    fn sleep(&self, duration: Duration) -> bool {
+        let requested = self
+            .requested
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let (requested, _) = self
+            .condvar
+            .wait_timeout_while(requested, duration, |requested| !*requested)
+            .unwrap_or_else(PoisonError::into_inner);
+        *requested
+    }
+}
+
+/// Sends notifications on a background thread per notifier, so slow, batched
+/// or retried deliveries don't delay domain record updates or each other.
+///
+/// Dropping it sends the queued notifications without retrying them, waiting
+/// at most [`SHUTDOWN_TIMEOUT`].
+#[derive(Default)]
+pub struct Notifiers {
+    senders: Vec<mpsc::Sender<Notification>>,
+    workers: Vec<JoinHandle<()>>,
+    shutdown: Arc<Shutdown>,
+}
+
+impl Notifiers {
+    pub fn new(notifiers: Vec<ConfiguredNotifier>) -> Self {
+        let mut senders = vec![];
+        let mut workers = vec![];
+        let shutdown = Arc::<Shutdown>::default();
+        for notifier in notifiers {
+            let (sender, receiver) = mpsc::channel::<Notification>();
+            senders.push(sender);
+            let shutdown = shutdown.clone();
+            workers.push(std::thread::spawn(move || {
+                notifier.run(&receiver, &shutdown);
+            }));
+        }
+        Self {
+            senders,
+            workers,
+            shutdown,
+        }
+    }
+
+    pub fn from_config(configs: &[NotifierConfig], hooks: &[HookConfig]) -> Result<Self> {
+        let mut notifiers = configs
+            .iter()
+            .map(ConfiguredNotifier::from_config)
+            .collect::<Result<Vec<_>>>()?;
+        notifiers.extend(hooks.iter().map(ConfiguredNotifier::from_hook_config));
+        Ok(Self::new(notifiers))
+    }
+
+    pub fn notify(&self, notification: Notification) {
+        for sender in &self.senders {
+            if sender.send(notification.clone()).is_err() {
+                error!("Notifier thread is gone, dropping notification");
+            }
+        }
+    }
+}
+
+impl Drop for Notifiers {
+    fn drop(&mut self) {
+        // Closing the channels ends the workers once their queues are drained.
+        self.senders.clear();
+        self.shutdown.request();
+        let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+        for worker in self.workers.drain(..) {
+            while !worker.is_finished() && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            if !worker.is_finished() {
+                warn!("Notifier thread is still sending notifications, not waiting for it");
+                continue;
+            }
+            if worker.join().is_err() {
+                error!("Notifier thread panicked");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    type Batches = Arc<Mutex<Vec<Vec<Notification>>>>;
+
+    /// Records the batches it gets, failing the first `failures` times.
+    struct RecordingNotifier {
+        failures: AtomicU32,
+        batch_window: Duration,
+        received: Batches,
+    }
+
+    impl Notifier for RecordingNotifier {
+        fn notify(&self, notifications: &[Notification]) -> Result<()> {
+            self.received.lock().unwrap().push(notifications.to_vec());
+            if self
+                .failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |failures| {
+                    failures.checked_sub(1)
+                })
+                .is_ok()
+            {
+                color_eyre::eyre::bail!("Notifier failure");
+            }
+            Ok(())
+        }
+
+        fn batch_window(&self) -> Duration {
+            self.batch_window
+        }
+    }
+
+    fn recording_notifier(
+        failures: u32,
+        failure_threshold: u32,
+        batch_window: Duration,
+    ) -> (ConfiguredNotifier, Batches) {
+        let received = Arc::new(Mutex::new(vec![]));
+        let notifier = ConfiguredNotifier {
+            name: "recording notifier".to_owned(),
+            events: NotificationKind::all(),
+            failure_threshold,
+            retries: 2,
+            retry_delay: Duration::ZERO,
+            notifier: Box::new(RecordingNotifier {
+                failures: AtomicU32::new(failures),
+                batch_window,
+                received: received.clone(),
+            }),
+        };
+        (notifier, received)
+    }
+
+    #[test]
+    fn test_notifications_are_retried() {
+        let notification = [Notification::new(NotificationKind::RecordUpdated)];
+        let (notifier, received) = recording_notifier(2, 1, Duration::ZERO);
+        notifier.notify_with_retries(&notification, &Shutdown::default());
+        assert_eq!(received.lock().unwrap().len(), 3);
+
+        // Gives up after the configured retries.
+        let (notifier, received) = recording_notifier(5, 1, Duration::ZERO);
+        notifier.notify_with_retries(&notification, &Shutdown::default());
+        assert_eq!(received.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_shutdown_interrupts_retries() {
+        let (mut notifier, received) = recording_notifier(5, 1, Duration::ZERO);
+        notifier.retry_delay = Duration::from_secs(60);
+        let notifiers = Notifiers::new(vec![notifier]);
+        notifiers.notify(Notification::new(NotificationKind::RecordUpdated));
+        let start = Instant::now();
+        drop(notifiers);
+        assert!(start.elapsed() < SHUTDOWN_TIMEOUT);
+        // Sent once, without waiting to retry it.
+        assert_eq!(received.lock().unwrap().len(), 1);
+    }
+
+    /// Delivers one notification per call, and then fails.
+    struct OneAtATimeNotifier {
+        received: Batches,
+    }
+
+    impl Notifier for OneAtATimeNotifier {
+        fn notify(&self, notifications: &[Notification]) -> Result<()> {
+            self.received.lock().unwrap().push(notifications.to_vec());
+            if notifications.len() > 1 {
+                return Err(color_eyre::eyre::eyre!("Notifier failure")
+                    .wrap_err(PartiallyDelivered { delivered: 1 }));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_only_undelivered_notifications_are_retried() {
+        let received = Arc::new(Mutex::new(vec![]));
+        let notifier = ConfiguredNotifier {
+            name: "one at a time notifier".to_owned(),
+            events: NotificationKind::all(),
+            failure_threshold: 1,
+            retries: 5,
+            retry_delay: Duration::ZERO,
+            notifier: Box::new(OneAtATimeNotifier {
+                received: received.clone(),
+            }),
+        };
+        let batch = [
+            Notification::new(NotificationKind::IpChanged),
+            Notification::new(NotificationKind::RecordUpdated),
+            Notification::new(NotificationKind::UpdateFailed),
+        ];
+        notifier.notify_with_retries(&batch, &Shutdown::default());
+        let batch_sizes = received
+            .lock()
+            .unwrap()
+            .iter()
+            .map(Vec::len)
+            .collect::<Vec<_>>();
+        assert_eq!(batch_sizes, [3, 2, 1]);
+    }
+
+    #[test]
+    fn test_update_failed_notified_at_threshold() {
+        let (notifier, received) = recording_notifier(0, 2, Duration::ZERO);
+        let notifiers = Notifiers::new(vec![notifier]);
+        for consecutive_failures in 1..=3 {
+            notifiers.notify(Notification {
+                consecutive_failures,
+                ..Notification::new(NotificationKind::UpdateFailed)
+            });
+        }
+        drop(notifiers);
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert!(received
+            .iter()
+            .flatten()
+            .all(|notification| notification.consecutive_failures == 2));
+    }
+
+    #[test]
+    fn test_notifications_are_batched() {
+        // Shutting down sends the batch without waiting for the window to end.
+        let (notifier, received) = recording_notifier(0, 1, Duration::from_secs(600));
+        let notifiers = Notifiers::new(vec![notifier]);
+        for _ in 0..3 {
+            notifiers.notify(Notification::new(NotificationKind::RecordUpdated));
+        }
+        drop(notifiers);
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert!(received.iter().all(|batch| batch.len() == 3));
+
+        let (notifier, received) = recording_notifier(0, 1, Duration::ZERO);
+        let notifiers = Notifiers::new(vec![notifier]);
+        for _ in 0..3 {
+            notifiers.notify(Notification::new(NotificationKind::RecordUpdated));
+        }
+        drop(notifiers);
+        assert_eq!(received.lock().unwrap().len(), 3);
+    }
+}
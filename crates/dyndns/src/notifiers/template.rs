@@ -0,0 +1,66 @@
+use color_eyre::eyre::{bail, Result};
+
+/// Replaces `{{ variable }}` placeholders in `template`.
+///
+/// `lookup` returns the already escaped value of a variable, or `None` for
+/// unknown variables, which are an error.
+pub fn render_template(template: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let (before, placeholder) = rest.split_at(start);
+        rendered.push_str(before);
+        let Some(end) = placeholder.find("}}") else {
+            bail!("Unclosed '{{{{' in template");
+        };
+        let name = placeholder.get(2..end).unwrap_or_default().trim();
+        let Some(value) = lookup(name) else {
+            bail!("Unknown template variable '{}'", name);
+        };
+        rendered.push_str(&value);
+        rest = placeholder.get(end + 2..).unwrap_or_default();
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// Escapes a value for use inside a JSON string literal.
+pub fn json_escape(value: &str) -> String {
+    let quoted = serde_json::Value::from(value).to_string();
+    quoted
+        .strip_prefix('"')
+        .and_then(|quoted| quoted.strip_suffix('"'))
+        .unwrap_or_default()
+        .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "fqdn" => Some("home.site.com".to_owned()),
+            "error" => Some(json_escape("bad \"token\"")),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_render_template() {
+        assert_eq!(
+            render_template(r#"{"text": "{{fqdn}}: {{ error }}"}"#, lookup).unwrap(),
+            r#"{"text": "home.site.com: bad \"token\""}"#
+        );
+        assert_eq!(
+            render_template("no variables", lookup).unwrap(),
+            "no variables"
+        );
+
+        let error_msg = render_template("{{ new_ipp }}", lookup)
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("Unknown template variable 'new_ipp'"));
+        assert!(render_template("{{ fqdn", lookup).is_err());
+    }
+}
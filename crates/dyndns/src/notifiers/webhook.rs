@@ -0,0 +1,203 @@
+use color_eyre::eyre::{Result, WrapErr};
+use reqwest::blocking::Client;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::{Method, Url};
+
+use super::template::{json_escape, render_template};
+use super::{Notification, Notifier, PartiallyDelivered};
+use crate::config::notifier_config::WebhookNotifierConfig;
+
+/// Sends each notification as a JSON request to a URL.
+pub struct WebhookNotifier {
+    request_client: Client,
+    url: Url,
+    method: Method,
+    headers: HeaderMap,
+    body: Option<String>,
+}
+
+impl WebhookNotifier {
+    pub fn new(config: &WebhookNotifierConfig) -> Result<Self> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        for (name, value) in &config.headers {
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes())
+                    .wrap_err_with(|| format!("Invalid webhook notifier header name '{name}'"))?,
+                HeaderValue::from_str(value).wrap_err_with(|| {
+                    format!("Invalid value for webhook notifier header '{name}'")
+                })?,
+            );
+        }
+        Ok(Self {
+            request_client: Client::builder()
+                .timeout(config.timeout)
+                .build()
+                .wrap_err("Failed to create webhook notifier HTTP client")?,
+            url: Url::parse(&config.url)
+                .wrap_err_with(|| format!("Invalid webhook notifier url '{}'", config.url))?,
+            method: Method::from_bytes(config.method.as_bytes())
+                .wrap_err_with(|| format!("Invalid webhook notifier method '{}'", config.method))?,
+            headers,
+            body: config.body.clone(),
+        })
+    }
+
+    fn render_body(&self, notification: &Notification) -> Result<String> {
+        match &self.body {
+            Some(template) => render_body_template(template, notification),
+            None => {
+                serde_json::to_string(notification).wrap_err("Failed to serialize notification")
+            }
+        }
+    }
+
+    fn send(&self, notification: &Notification) -> Result<()> {
+        let body = self.render_body(notification)?;
+        self.request_client
+            .request(self.method.clone(), self.url.clone())
+            .headers(self.headers.clone())
+            .body(body)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .wrap_err_with(|| format!("Webhook request to '{}' failed", self.url))?;
+        Ok(())
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    /// Sends a request per notification.
+    fn notify(&self, notifications: &[Notification]) -> Result<()> {
+        for (delivered, notification) in notifications.iter().enumerate() {
+            self.send(notification)
+                .map_err(|e| e.wrap_err(PartiallyDelivered { delivered }))?;
+        }
+        Ok(())
+    }
+}
+
+fn render_body_template(template: &str, notification: &Notification) -> Result<String> {
+    render_template(template, |name| {
+        notification.variable(name).map(|value| json_escape(&value))
+    })
+}
+
+/// Checks that the body template only uses known variables and renders to
+/// valid JSON.
+pub fn validate_body_template(template: &str) -> Result<()> {
+    let body = render_body_template(template, &Notification::sample())
+        .wrap_err("Invalid webhook notifier body template")?;
+    serde_json::from_str::<serde_json::Value>(&body)
+        .wrap_err("Webhook notifier body template does not render to valid JSON")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::notifier_config::NotificationKind;
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    fn webhook_config(url: String, body: Option<&str>) -> WebhookNotifierConfig {
+        WebhookNotifierConfig {
+            url,
+            method: "POST".to_owned(),
+            headers: BTreeMap::from([("X-Token".to_owned(), "s3cr3t".to_owned())]),
+            body: body.map(str::to_owned),
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    fn ip_changed_notification() -> Notification {
+        Notification {
+            old_ip: Some("192.0.2.1".to_owned()),
+            new_ip: Some("192.0.2.2".to_owned()),
+            ..Notification::new(NotificationKind::IpChanged)
+        }
+    }
+
+    #[test]
+    fn test_webhook_sends_templated_body() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("POST", "/hook")
+            .match_header("x-token", "s3cr3t")
+            .match_header("content-type", "application/json")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "text": "ip_changed: 192.0.2.1 -> 192.0.2.2 \"\"",
+            })))
+            .with_status(204)
+            .create();
+
+        let config = webhook_config(
+            format!("{}/hook", server.url()),
+            Some(r#"{"text": "{{ event }}: {{old_ip}} -> {{new_ip}} \"{{fqdn}}\""}"#),
+        );
+        let notifier = WebhookNotifier::new(&config).unwrap();
+        notifier.notify(&[ip_changed_notification()]).unwrap();
+        mock.assert();
+    }
+
+    #[test]
+    fn test_webhook_default_body_and_errors() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("POST", "/hook")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "event": "ip_changed",
+                "new_ip": "192.0.2.2",
+                "fqdn": null,
+            })))
+            .with_status(500)
+            .expect(1)
+            .create();
+
+        let config = webhook_config(format!("{}/hook", server.url()), None);
+        let notifier = WebhookNotifier::new(&config).unwrap();
+        assert!(notifier.notify(&[ip_changed_notification()]).is_err());
+        mock.assert();
+    }
+
+    #[test]
+    fn test_webhook_reports_delivered_notifications() {
+        let mut server = mockito::Server::new();
+        let delivered_mock = server
+            .mock("POST", "/hook")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({ "event": "ip_changed" }),
+            ))
+            .with_status(204)
+            .expect(1)
+            .create();
+        let failed_mock = server
+            .mock("POST", "/hook")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({ "event": "record_updated" }),
+            ))
+            .with_status(500)
+            .expect(1)
+            .create();
+
+        let config = webhook_config(format!("{}/hook", server.url()), None);
+        let notifier = WebhookNotifier::new(&config).unwrap();
+        let error = notifier
+            .notify(&[
+                ip_changed_notification(),
+                Notification::new(NotificationKind::RecordUpdated),
+                Notification::new(NotificationKind::UpdateFailed),
+            ])
+            .unwrap_err();
+        let partial = error.downcast_ref::<PartiallyDelivered>().unwrap();
+        assert_eq!(partial.delivered, 1);
+        delivered_mock.assert();
+        failed_mock.assert();
+    }
+
+    #[test]
+    fn test_validate_body_template() {
+        assert!(validate_body_template(r#"{"ip": "{{new_ip}}"}"#).is_ok());
+        assert!(validate_body_template(r#"{"ip": {{new_ip}}}"#).is_err());
+        assert!(validate_body_template(r#"{"ip": "{{ip}}"}"#).is_err());
+    }
+}
@@ -0,0 +1,286 @@
+use chrono::{DateTime, Local, Utc};
+use std::time::Duration;
+
+use crate::config::app_config::{
+    AppConfig, Domain, DomainRecord, GeneralOptions, UpdateInterval, UpdateSchedule,
+};
+use crate::types::DomainRecordToUpdate;
+
+/// How often a domain record is updated.
+#[derive(Debug, Clone)]
+pub enum UpdateCadence {
+    Interval(Duration),
+    Schedule(Box<UpdateSchedule>),
+}
+
+impl UpdateCadence {
+    /// Picks the most specific of the record, domain and global settings.
+    pub fn for_record(
+        general_options: &GeneralOptions,
+        domain: &Domain,
+        record: &DomainRecord,
+    ) -> Self {
+        let configured = |update_interval: &Option<UpdateInterval>,
+                          schedule: &Option<UpdateSchedule>| {
+            schedule.clone().map(UpdateCadence::schedule).or_else(|| {
+                update_interval
+                    .as_ref()
+                    .map(|interval| UpdateCadence::Interval(interval.0))
+            })
+        };
+        configured(&record.update_interval, &record.schedule)
+            .or_else(|| configured(&domain.update_interval, &domain.schedule))
+            .or_else(|| {
+                general_options
+                    .schedule
+                    .clone()
+                    .map(UpdateCadence::schedule)
+            })
+            .unwrap_or_else(|| {
+                UpdateCadence::Interval(
+                    general_options
+                        .update_interval
+                        .clone()
+                        .unwrap_or_default()
+                        .0,
+                )
+            })
+    }
+
+    fn schedule(schedule: UpdateSchedule) -> Self {
+        UpdateCadence::Schedule(Box::new(schedule))
+    }
+
+    /// When the next update is due, after one at `now`.
+    pub fn next_due(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            UpdateCadence::Interval(interval) => chrono::Duration::from_std(*interval)
+                .ok()
+                .and_then(|interval| now.checked_add_signed(interval)),
+            UpdateCadence::Schedule(schedule) => schedule
+                .next_after(&now.with_timezone(&Local))
+                .map(|next| next.with_timezone(&Utc)),
+        }
+    }
+
+    /// Whether an update at `now`, outside of the regular ones, is allowed.
+    ///
+    /// A schedule allows it as long as no more time passed since its last
+    /// scheduled time than between that and the one before, so that no
+    /// updates happen in the gaps of a schedule, like maintenance windows.
+    pub fn in_window(&self, now: DateTime<Utc>) -> bool {
+        match self {
+            UpdateCadence::Interval(_) => true,
+            UpdateCadence::Schedule(schedule) => {
+                let now = now.with_timezone(&Local);
+                let Some(last) = schedule.previous_before(&now) else {
+                    return false;
+                };
+                schedule
+                    .previous_before(&last)
+                    .is_some_and(|before_last| now - last <= last - before_last)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for UpdateCadence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdateCadence::Interval(interval) => {
+                write!(f, "every {}", humantime::format_duration(*interval))
+            }
+            UpdateCadence::Schedule(schedule) => write!(f, "schedule '{schedule}'"),
+        }
+    }
+}
+
+struct ScheduledRecord {
+    record: DomainRecordToUpdate,
+    cadence: UpdateCadence,
+    next_due: Option<DateTime<Utc>>,
+}
+
+/// Keeps track of when each domain record is due for an update.
+pub struct UpdateScheduler {
+    records: Vec<ScheduledRecord>,
+}
+
+impl UpdateScheduler {
+    /// Records updated at an interval are due right away, while scheduled
+    /// ones wait for their first scheduled time.
+    pub fn new(config: &AppConfig, now: DateTime<Utc>) -> Self {
+        let general_options = &config.general_options;
+        let records = config
+            .domains
+            .domains
+            .iter()
+            .flat_map(|domain| {
+                domain.records.iter().map(move |record| {
+                    (
+                        DomainRecordToUpdate::new(
+                            domain.name.as_str(),
+                            record.name.as_str(),
+                            record.record_type.as_str(),
+                            record.providers.clone(),
+                        )
+                        .with_values(record.values_mode, record.static_values.clone())
+                        .with_failover(record.failover.clone()),
+                        UpdateCadence::for_record(general_options, domain, record),
+                    )
+                })
+            })
+            .collect();
+        Self::from_records(records, now)
+    }
+
+    pub fn from_records(
+        records: Vec<(DomainRecordToUpdate, UpdateCadence)>,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let records = records
+            .into_iter()
+            .map(|(record, cadence)| {
+                let next_due = match cadence {
+                    UpdateCadence::Interval(_) => Some(now),
+                    UpdateCadence::Schedule(_) => cadence.next_due(now),
+                };
+                ScheduledRecord {
+                    record,
+                    cadence,
+                    next_due,
+                }
+            })
+            .collect();
+        Self { records }
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = (&DomainRecordToUpdate, &UpdateCadence)> {
+        self.records
+            .iter()
+            .map(|scheduled| (&scheduled.record, &scheduled.cadence))
+    }
+
+    /// Returns the records that are due at `now`, and schedules their next
+    /// update.
+    pub fn take_due(&mut self, now: DateTime<Utc>) -> Vec<DomainRecordToUpdate> {
+        self.records
+            .iter_mut()
+            .filter(|scheduled| scheduled.next_due.is_some_and(|next_due| next_due <= now))
+            .map(|scheduled| {
+                scheduled.next_due = scheduled.cadence.next_due(now);
+                scheduled.record.clone()
+            })
+            .collect()
+    }
+
+    /// Returns the records that may be updated at `now` outside of their
+    /// regular updates, e.g. because the network changed.
+    pub fn in_window(&self, now: DateTime<Utc>) -> Vec<DomainRecordToUpdate> {
+        self.records
+            .iter()
+            .filter(|scheduled| scheduled.cadence.in_window(now))
+            .map(|scheduled| scheduled.record.clone())
+            .collect()
+    }
+
+    /// When the next record is due, or `None` if none will ever be.
+    pub fn next_due(&self) -> Option<DateTime<Utc>> {
+        self.records
+            .iter()
+            .filter_map(|scheduled| scheduled.next_due)
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn record(name: &str) -> DomainRecordToUpdate {
+        DomainRecordToUpdate::new("site.com", name, "A", None)
+    }
+
+    fn fqdns(records: &[DomainRecordToUpdate]) -> Vec<String> {
+        records.iter().map(DomainRecordToUpdate::fqdn).collect()
+    }
+
+    #[test]
+    fn test_scheduler_intervals() {
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        let mut scheduler = UpdateScheduler::from_records(
+            vec![
+                (
+                    record("critical"),
+                    UpdateCadence::Interval(Duration::from_secs(60)),
+                ),
+                (
+                    record("other"),
+                    UpdateCadence::Interval(Duration::from_secs(60 * 60)),
+                ),
+            ],
+            start,
+        );
+        assert_eq!(
+            fqdns(&scheduler.take_due(start)),
+            ["critical.site.com", "other.site.com"]
+        );
+        assert!(scheduler.take_due(start).is_empty());
+
+        let minute_later = start + chrono::Duration::minutes(1);
+        assert_eq!(scheduler.next_due(), Some(minute_later));
+        assert_eq!(
+            fqdns(&scheduler.take_due(minute_later)),
+            ["critical.site.com"]
+        );
+
+        let hour_later = start + chrono::Duration::hours(1);
+        assert_eq!(
+            fqdns(&scheduler.take_due(hour_later)),
+            ["critical.site.com", "other.site.com"]
+        );
+        // Records updated at an interval can always be updated in between.
+        assert_eq!(
+            fqdns(&scheduler.in_window(hour_later)),
+            ["critical.site.com", "other.site.com"]
+        );
+    }
+
+    #[test]
+    fn test_scheduler_cron_schedule() {
+        // Hourly, except during a maintenance window from 2 to 4 in the night.
+        let schedule: UpdateSchedule = "0 0-1,4-23 * * *".parse().unwrap();
+        let cadence = UpdateCadence::schedule(schedule);
+        assert_eq!(cadence.to_string(), "schedule '0 0-1,4-23 * * *'");
+
+        let start = Local
+            .with_ymd_and_hms(2024, 5, 1, 1, 30, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut scheduler = UpdateScheduler::from_records(vec![(record("home"), cadence)], start);
+        // Not due right away, but only at its first scheduled time.
+        assert!(scheduler.take_due(start).is_empty());
+        let next_due = scheduler.next_due().unwrap();
+        assert_eq!(
+            next_due.with_timezone(&Local),
+            Local.with_ymd_and_hms(2024, 5, 1, 4, 0, 0).unwrap()
+        );
+        assert_eq!(fqdns(&scheduler.take_due(next_due)), ["home.site.com"]);
+
+        // Updates outside of the schedule are only allowed while it runs.
+        let at = |hour, minute| {
+            Local
+                .with_ymd_and_hms(2024, 5, 1, hour, minute, 0)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        assert_eq!(fqdns(&scheduler.in_window(at(1, 30))), ["home.site.com"]);
+        assert!(scheduler.in_window(at(2, 30)).is_empty());
+        assert!(scheduler.in_window(at(3, 59)).is_empty());
+        assert_eq!(fqdns(&scheduler.in_window(at(4, 30))), ["home.site.com"]);
+
+        assert!("not a schedule".parse::<UpdateSchedule>().is_err());
+        assert!("*/30 * * * * *".parse::<UpdateSchedule>().is_ok());
+    }
+}
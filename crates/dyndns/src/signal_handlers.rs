@@ -7,6 +7,7 @@ use signal_hook::iterator::SignalsInfo;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
+use tokio::sync::Notify;
 
 #[cfg(feature = "web")]
 use tokio::sync::oneshot::Sender;
@@ -35,6 +36,8 @@ pub struct AppTerminationHandler {
     #[cfg(feature = "web")]
     web_exit_tx: Arc<Mutex<Option<Sender<()>>>>,
     should_exit_flag: Arc<AtomicBool>,
+    /// Wakes async tasks waiting for the app to exit.
+    exit_notify: Arc<Notify>,
     signals: Arc<Mutex<Option<SignalsInfo<WithOrigin>>>>,
     signals_handle: Arc<Mutex<Option<SignalsHandle>>>,
 }
@@ -101,6 +104,20 @@ impl AppTerminationHandler {
 
     pub fn set_should_exit(&self) {
         self.should_exit_flag.store(true, Ordering::SeqCst);
+        self.exit_notify.notify_waiters();
+    }
+
+    /// Completes once the app is asked to exit.
+    pub async fn wait_for_exit(&self) {
+        let notified = self.exit_notify.notified();
+        tokio::pin!(notified);
+        // Register for the notification before checking the flag, so that
+        // an exit requested in between isn't missed.
+        notified.as_mut().enable();
+        if self.should_exit() {
+            return;
+        }
+        notified.await;
     }
 
     fn unpark_threads(&self) {
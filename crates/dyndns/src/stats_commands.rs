@@ -0,0 +1,336 @@
+use color_eyre::eyre::{bail, Result, WrapErr};
+use diesel::SqliteConnection;
+use serde::Serialize;
+use std::io::Write;
+
+use crate::cli::{
+    StatsExportArgs, StatsExportFormat, StatsPruneArgs, StatsShowArgs, StatsSubcommand, StatsTable,
+};
+use crate::config::app_config::StatsRetentionOptions;
+use crate::config::app_config_builder::stats_options_with_args;
+use crate::config::early::EarlyConfig;
+use crate::db::crud::domain_ip_fetches::{get_domain_ip_fetch_counts, get_domain_ip_fetches};
+use crate::db::crud::domain_record_attempt_counts::get_domain_record_attempt_counts;
+use crate::db::crud::domain_record_ip_changes::get_domain_record_ip_changes;
+use crate::db::crud::domain_record_updates::get_domain_record_updates;
+use crate::db::crud::domain_records::get_domain_records;
+use crate::db::crud::provider_updates::get_provider_updates;
+use crate::db::crud::providers::get_providers;
+use crate::db::crud::updater_attempts::get_updater_attempts;
+use crate::db::logic::{find_summary_table_mismatches, rebuild_summary_tables};
+use crate::db::retention::{prune_stats, vacuum, RetentionPolicy};
+use crate::db::setup::{find_db_path, setup_db};
+use crate::logger::setup_logger;
+
+pub fn handle_stats_command(early_config: &EarlyConfig, command: &StatsSubcommand) -> Result<()> {
+    let stats_options = stats_options_with_args(early_config)?;
+    setup_logger(&stats_options.log_level)?;
+
+    let db_path = find_db_path(stats_options.db_path.as_deref())?;
+    let mut conn = setup_db(stats_options.db_path.clone())?;
+
+    match command {
+        StatsSubcommand::Show(args) => show_stats(&mut conn, &db_path, args),
+        StatsSubcommand::Export(args) => export_stats(&mut conn, args),
+        StatsSubcommand::Prune(args) => {
+            prune_stats_command(&mut conn, &stats_options.stats_retention, args)
+        }
+        StatsSubcommand::Rebuild => rebuild_stats(&mut conn),
+    }
+}
+
+fn show_stats(conn: &mut SqliteConnection, db_path: &str, args: &StatsShowArgs) -> Result<()> {
+    println!("Statistics database: {db_path}");
+
+    let (fetch_count, fetch_success_count) = get_domain_ip_fetch_counts(conn)?;
+    println!("\nPublic IP fetches:");
+    println!(
+        "    attempts: {}, successful: {}, failed: {}, success rate: {}",
+        fetch_count,
+        fetch_success_count,
+        fetch_count - fetch_success_count,
+        format_success_rate(fetch_success_count, fetch_count)
+    );
+
+    let attempt_counts = get_domain_record_attempt_counts(conn)?;
+    println!("\nDomain record update attempts:");
+    if attempt_counts.is_empty() {
+        println!("    none recorded");
+    }
+    for count in &attempt_counts {
+        println!(
+            "    '{}' of type '{}': attempts: {}, successful: {}, failed: {}, success rate: {}, last attempt: {}",
+            count.name,
+            count.record_type,
+            count.attempt_count,
+            count.success_count,
+            count.fail_count(),
+            format_success_rate(count.success_count, count.attempt_count),
+            count.last_attempt_date
+        );
+    }
+
+    let ip_changes = get_domain_record_ip_changes(conn)?;
+    println!(
+        "\nDomain record IP changes (showing {} most recent out of {}):",
+        args.limit.min(ip_changes.changes.len()),
+        ip_changes.changes.len()
+    );
+    if ip_changes.changes.is_empty() {
+        println!("    none recorded");
+    }
+    for change in ip_changes.changes.iter().take(args.limit) {
+        println!(
+            "    {}  {}  {}",
+            change.domain_record_update.attempt_date,
+            change.name,
+            change.domain_record_update.set_ip
+        );
+    }
+    Ok(())
+}
+
+fn format_success_rate(success_count: i64, attempt_count: i64) -> String {
+    if attempt_count == 0 {
+        return "n/a".to_owned();
+    }
+    format!(
+        "{:.2}%",
+        success_count as f64 / attempt_count as f64 * 100.0
+    )
+}
+
+fn export_stats(conn: &mut SqliteConnection, args: &StatsExportArgs) -> Result<()> {
+    let mut writer: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(std::io::BufWriter::new(
+            std::fs::File::create(path)
+                .wrap_err(format!("Failed to create export file {}", path.display()))?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+
+    match args.table {
+        StatsTable::DomainRecords => {
+            write_rows(&get_domain_records(conn)?, args.format, &mut writer)?;
+        }
+        StatsTable::DomainIpFetches => {
+            write_rows(&get_domain_ip_fetches(conn)?, args.format, &mut writer)?;
+        }
+        StatsTable::DomainRecordUpdates => {
+            write_rows(&get_domain_record_updates(conn)?, args.format, &mut writer)?;
+        }
+        StatsTable::UpdaterAttempts => {
+            write_rows(&get_updater_attempts(conn)?, args.format, &mut writer)?;
+        }
+        StatsTable::Providers => {
+            write_rows(&get_providers(conn)?, args.format, &mut writer)?;
+        }
+        StatsTable::ProviderUpdates => {
+            write_rows(&get_provider_updates(conn)?, args.format, &mut writer)?;
+        }
+    }
+    writer.flush().wrap_err("Failed to flush exported rows")?;
+    Ok(())
+}
+
+fn prune_stats_command(
+    conn: &mut SqliteConnection,
+    retention: &StatsRetentionOptions,
+    args: &StatsPruneArgs,
+) -> Result<()> {
+    let policy = retention_policy_with_overrides(retention, args);
+    if !policy.is_enabled() {
+        bail!("No retention policy configured. Set 'stats_max_age' or 'stats_max_rows', or pass --max-age or --max-rows");
+    }
+
+    let now = chrono::offset::Utc::now().naive_utc();
+    let summary = prune_stats(conn, &policy, now)?;
+    println!(
+        "Pruned {} updater attempt(s), {} provider update(s), {} IP fetch(es) and {} domain record update(s)",
+        summary.updater_attempts,
+        summary.provider_updates,
+        summary.domain_ip_fetches,
+        summary.domain_record_updates
+    );
+
+    if args.vacuum || retention.stats_vacuum_after_prune {
+        vacuum(conn)?;
+        println!("Vacuumed the statistics database");
+    }
+    Ok(())
+}
+
+fn rebuild_stats(conn: &mut SqliteConnection) -> Result<()> {
+    let mismatches = find_summary_table_mismatches(conn)?;
+    if mismatches.is_empty() {
+        println!("Summary tables are consistent with the recorded statistics");
+    }
+    for mismatch in &mismatches {
+        println!("Found mismatch: {mismatch}");
+    }
+    rebuild_summary_tables(conn)?;
+    println!("Rebuilt the summary tables");
+    Ok(())
+}
+
+fn retention_policy_with_overrides(
+    retention: &StatsRetentionOptions,
+    args: &StatsPruneArgs,
+) -> RetentionPolicy {
+    let configured = RetentionPolicy::from(retention);
+    RetentionPolicy {
+        max_age: args.max_age.or(configured.max_age),
+        max_rows: args.max_rows.or(configured.max_rows),
+    }
+}
+
+fn write_rows<T: Serialize>(
+    rows: &[T],
+    format: StatsExportFormat,
+    writer: &mut dyn Write,
+) -> Result<()> {
+    match format {
+        StatsExportFormat::Csv => {
+            let mut csv_writer = csv::Writer::from_writer(writer);
+            for row in rows {
+                csv_writer
+                    .serialize(row)
+                    .wrap_err("Failed to write row as CSV")?;
+            }
+            csv_writer.flush()?;
+        }
+        StatsExportFormat::Ndjson => {
+            for row in rows {
+                serde_json::to_writer(&mut *writer, row).wrap_err("Failed to write row as JSON")?;
+                writeln!(writer)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::crud::domain_records::create_domain_record;
+    use crate::db::logic::{create_updater_attempt, handle_ip_fetch};
+    use crate::db::setup::{establish_connection, run_migrations};
+    use crate::db::types::{DomainRecord, NewDomainRecord};
+    use crate::types::{IpAddrKind, IpAddrV4AndV6};
+
+    fn sample_domain_records() -> Vec<DomainRecord> {
+        vec![
+            DomainRecord {
+                id: 1,
+                name: "home.site.com".to_owned(),
+                record_type: "A".to_owned(),
+            },
+            DomainRecord {
+                id: 2,
+                name: "home.site.com".to_owned(),
+                record_type: "AAAA".to_owned(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_write_rows_csv() -> Result<()> {
+        let mut buf = vec![];
+        write_rows(&sample_domain_records(), StatsExportFormat::Csv, &mut buf)?;
+        assert_eq!(
+            String::from_utf8(buf)?,
+            "id,name,record_type\n1,home.site.com,A\n2,home.site.com,AAAA\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_rows_ndjson() -> Result<()> {
+        let mut buf = vec![];
+        write_rows(
+            &sample_domain_records(),
+            StatsExportFormat::Ndjson,
+            &mut buf,
+        )?;
+        assert_eq!(
+            String::from_utf8(buf)?,
+            "{\"id\":1,\"name\":\"home.site.com\",\"record_type\":\"A\"}\n\
+             {\"id\":2,\"name\":\"home.site.com\",\"record_type\":\"AAAA\"}\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_retention_policy_with_overrides() {
+        let retention = StatsRetentionOptions {
+            stats_max_rows: Some(100),
+            ..Default::default()
+        };
+        let args = StatsPruneArgs {
+            max_age: Some(std::time::Duration::from_secs(60)),
+            max_rows: None,
+            vacuum: false,
+        };
+        let policy = retention_policy_with_overrides(&retention, &args);
+        assert_eq!(policy.max_age, Some(std::time::Duration::from_secs(60)));
+        assert_eq!(policy.max_rows, Some(100));
+
+        let args = StatsPruneArgs {
+            max_age: None,
+            max_rows: None,
+            vacuum: false,
+        };
+        let policy = retention_policy_with_overrides(&StatsRetentionOptions::default(), &args);
+        assert!(!policy.is_enabled());
+    }
+
+    #[test]
+    fn test_format_success_rate() {
+        assert_eq!(format_success_rate(0, 0), "n/a");
+        assert_eq!(format_success_rate(1, 4), "25.00%");
+        assert_eq!(format_success_rate(3, 3), "100.00%");
+    }
+
+    #[test]
+    fn test_domain_record_attempt_counts() -> Result<()> {
+        let conn = &mut establish_connection(":memory:")?;
+        run_migrations(conn)?;
+
+        let domain_record = create_domain_record(
+            conn,
+            &NewDomainRecord {
+                name: "home.site.com",
+                record_type: "A",
+            },
+        )?
+        .expect("domain record should be created");
+
+        let fetched_ips = Some(IpAddrV4AndV6 {
+            ipv4: Some("127.0.1.2".parse().expect("valid ip")),
+            ipv6: None,
+        });
+        let (domain_ip_fetch, attempt_date) = handle_ip_fetch(conn, fetched_ips)?;
+        let domain_ip_fetch = domain_ip_fetch.expect("ip fetch should be created");
+        for success in [true, false, true] {
+            create_updater_attempt(
+                conn,
+                &domain_record,
+                &domain_ip_fetch,
+                attempt_date,
+                success,
+                Some(IpAddrKind::V4),
+            )?;
+        }
+
+        let counts = get_domain_record_attempt_counts(conn)?;
+        let count = counts.first().expect("one domain record expected");
+        assert_eq!(counts.len(), 1);
+        assert_eq!(count.name, "home.site.com");
+        assert_eq!(count.attempt_count, 3);
+        assert_eq!(count.success_count, 2);
+        assert_eq!(count.fail_count(), 1);
+
+        assert_eq!(get_domain_ip_fetch_counts(conn)?, (1, 1));
+        Ok(())
+    }
+}
@@ -2,21 +2,43 @@ use color_eyre::eyre::Result;
 
 #[cfg(feature = "stats")]
 use crate::stats_handler_db::StatsHandlerDB;
+#[cfg(feature = "web")]
+use crate::stats_handler_metrics::StatsHandlerMetrics;
+#[cfg(feature = "web")]
+use crate::stats_handler_status::StatsHandlerStatus;
 use crate::{
-    config::app_config::AppConfig,
-    types::{IpAddrKind, IpAddrV4AndV6},
+    global_state::GlobalState,
+    types::{IpAddrKind, IpAddrV4AndV6, ProviderUpdateOutcome},
 };
 
 pub trait StatsHandler: Send {
     fn init(&mut self) -> Result<()>;
-    fn handle_ip_fetch(&mut self, maybe_fetched_ips: Option<IpAddrV4AndV6>) -> Result<()>;
+
+    /// Records a public IP fetch from `source`, see
+    /// [`PublicIpFetcher::source`](crate::ip_fetcher::PublicIpFetcher::source).
+    fn handle_ip_fetch(
+        &mut self,
+        source: &str,
+        maybe_fetched_ips: Option<IpAddrV4AndV6>,
+    ) -> Result<()>;
+
+    /// Records an update attempt of a single domain record.
+    ///
+    /// `provider_outcomes` contains one entry per provider the record was
+    /// updated on. It's empty when no provider was contacted, e.g. because
+    /// fetching the public IP failed.
     fn handle_updater_attempt(
         &mut self,
         domain_record_name: &str,
         record_type: &str,
         is_domain_record_update_successful: bool,
         ip_kind: Option<IpAddrKind>,
+        provider_outcomes: &[ProviderUpdateOutcome],
     ) -> Result<()>;
+
+    /// Records the overall outcome of one updater run, after all domain
+    /// records were processed.
+    fn handle_updater_run(&mut self, is_successful: bool) -> Result<()>;
 }
 
 pub struct StatsHandlerNop;
@@ -39,7 +61,11 @@ impl StatsHandler for StatsHandlerNop {
     }
 
     #[allow(unused_variables)]
-    fn handle_ip_fetch(&mut self, maybe_fetched_ips: Option<IpAddrV4AndV6>) -> Result<()> {
+    fn handle_ip_fetch(
+        &mut self,
+        _source: &str,
+        maybe_fetched_ips: Option<IpAddrV4AndV6>,
+    ) -> Result<()> {
         Ok(())
     }
 
@@ -50,15 +76,44 @@ impl StatsHandler for StatsHandlerNop {
         record_type: &str,
         is_domain_record_update_successful: bool,
         ip_kind: Option<IpAddrKind>,
+        provider_outcomes: &[ProviderUpdateOutcome],
     ) -> Result<()> {
         Ok(())
     }
+
+    #[allow(unused_variables)]
+    fn handle_updater_run(&mut self, is_successful: bool) -> Result<()> {
+        Ok(())
+    }
 }
 
 pub struct StatsHandlerFactory;
 
 impl StatsHandlerFactory {
-    pub fn new_handler(config: AppConfig) -> Box<dyn StatsHandler> {
+    pub fn new_handler(global_state: &GlobalState) -> Box<dyn StatsHandler> {
+        let handler = Self::new_persistent_handler(global_state);
+
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "web")] {
+                let general_options = &global_state.config.general_options;
+                let handler = Box::new(StatsHandlerMetrics::new(
+                    global_state.shared.metrics.clone(),
+                    handler,
+                    general_options.ipv4,
+                    general_options.ipv6,
+                ));
+                Box::new(StatsHandlerStatus::new(
+                    global_state.shared.updater_status.clone(),
+                    handler,
+                ))
+            } else {
+                handler
+            }
+        }
+    }
+
+    fn new_persistent_handler(global_state: &GlobalState) -> Box<dyn StatsHandler> {
+        let config = &global_state.config;
         if !config.general_options.collect_stats {
             return Box::new(StatsHandlerNop::new());
         }
@@ -66,7 +121,8 @@ impl StatsHandlerFactory {
         cfg_if::cfg_if! {
             if #[cfg(feature = "stats")] {
                 let db_path = config.general_options.db_path.clone();
-                Box::new(StatsHandlerDB::new(db_path))
+                let retention = config.general_options.stats_retention.clone();
+                Box::new(StatsHandlerDB::new(db_path, retention))
             } else {
                 Box::new(StatsHandlerNop::new())
             }
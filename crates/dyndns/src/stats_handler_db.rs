@@ -1,13 +1,19 @@
 use chrono::NaiveDateTime;
 use color_eyre::eyre::{bail, Result};
+use std::time::Instant;
+use tracing::error;
 
+use crate::config::app_config::StatsRetentionOptions;
 use crate::db::logic::handle_ip_fetch;
-use crate::db::logic::handle_updater_attempt;
+use crate::db::logic::handle_updater_attempt_for_providers;
+use crate::db::logic::rebuild_summary_tables_if_inconsistent;
+use crate::db::retention::{prune_stats, vacuum, RetentionPolicy};
 use crate::db::setup::setup_db;
 use crate::db::types::DomainIpFetch;
 use crate::stats_handler::StatsHandler;
 use crate::types::IpAddrKind;
 use crate::types::IpAddrV4AndV6;
+use crate::types::ProviderUpdateOutcome;
 use diesel::SqliteConnection;
 
 pub struct StatsHandlerDB {
@@ -15,15 +21,19 @@ pub struct StatsHandlerDB {
     db_connection: Option<SqliteConnection>,
     maybe_domain_ip_fetch: Option<DomainIpFetch>,
     maybe_attempt_date: Option<NaiveDateTime>,
+    retention: StatsRetentionOptions,
+    last_prune: Option<Instant>,
 }
 
 impl StatsHandlerDB {
-    pub fn new(db_path: Option<std::path::PathBuf>) -> Self {
+    pub fn new(db_path: Option<std::path::PathBuf>, retention: StatsRetentionOptions) -> Self {
         Self {
             db_path,
             db_connection: None,
             maybe_domain_ip_fetch: None,
             maybe_attempt_date: None,
+            retention,
+            last_prune: None,
         }
     }
 
@@ -42,16 +52,51 @@ impl StatsHandlerDB {
         self.maybe_domain_ip_fetch = None;
         self.maybe_attempt_date = None;
     }
+
+    fn is_prune_due(&self) -> bool {
+        self.retention.is_enabled()
+            && self.last_prune.map_or(true, |last_prune| {
+                last_prune.elapsed() >= self.retention.stats_prune_interval
+            })
+    }
+
+    fn prune_if_due(&mut self) {
+        if !self.is_prune_due() {
+            return;
+        }
+        self.last_prune = Some(Instant::now());
+
+        let conn = Self::get_db_connection(&mut self.db_connection);
+        let now = chrono::offset::Utc::now().naive_utc();
+        // Failing to prune shouldn't prevent recording new statistics.
+        let result =
+            prune_stats(conn, &RetentionPolicy::from(&self.retention), now).and_then(|summary| {
+                if self.retention.stats_vacuum_after_prune && summary.total() > 0 {
+                    vacuum(conn)?;
+                }
+                Ok(())
+            });
+        if let Err(e) = result {
+            error!("Failed to prune the statistics database: {:#}", e);
+        }
+    }
 }
 
 impl StatsHandler for StatsHandlerDB {
     fn init(&mut self) -> Result<()> {
         self.db_connection = Some(self.new_db_connection()?);
+        rebuild_summary_tables_if_inconsistent(Self::get_db_connection(&mut self.db_connection))?;
+        self.prune_if_due();
         Ok(())
     }
 
-    fn handle_ip_fetch(&mut self, maybe_fetched_ips: Option<IpAddrV4AndV6>) -> Result<()> {
+    fn handle_ip_fetch(
+        &mut self,
+        _source: &str,
+        maybe_fetched_ips: Option<IpAddrV4AndV6>,
+    ) -> Result<()> {
         self.reset_state();
+        self.prune_if_due();
 
         let (maybe_domain_ip_fetch, attempt_date) = handle_ip_fetch(
             Self::get_db_connection(&mut self.db_connection),
@@ -71,18 +116,31 @@ impl StatsHandler for StatsHandlerDB {
         record_type: &str,
         is_domain_record_update_successful: bool,
         ip_kind: Option<IpAddrKind>,
+        provider_outcomes: &[ProviderUpdateOutcome],
     ) -> Result<()> {
-        handle_updater_attempt(
-            Self::get_db_connection(&mut self.db_connection),
+        let conn = Self::get_db_connection(&mut self.db_connection);
+        let domain_ip_fetch = self
+            .maybe_domain_ip_fetch
+            .as_ref()
+            .expect("domain ip should have already been set");
+        let attempt_date = self
+            .maybe_attempt_date
+            .expect("attempt date should have already been set");
+
+        handle_updater_attempt_for_providers(
+            conn,
             domain_record_name,
             record_type,
-            self.maybe_domain_ip_fetch
-                .as_ref()
-                .expect("domain ip should have already been set"),
-            self.maybe_attempt_date
-                .expect("attempt date should have already been set"),
+            domain_ip_fetch,
+            attempt_date,
             is_domain_record_update_successful,
             ip_kind,
+            provider_outcomes,
         )
     }
+
+    #[allow(unused_variables)]
+    fn handle_updater_run(&mut self, is_successful: bool) -> Result<()> {
+        Ok(())
+    }
 }
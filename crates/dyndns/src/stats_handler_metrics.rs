@@ -0,0 +1,115 @@
+use color_eyre::eyre::Result;
+use std::sync::Arc;
+
+use crate::metrics::{IpFetchLabels, Metrics, ProviderLabels, RecordLabels};
+use crate::stats_handler::StatsHandler;
+use crate::types::{IpAddrKind, IpAddrV4AndV6, ProviderUpdateOutcome};
+
+/// Records in-memory metrics and forwards all events to the wrapped handler.
+pub struct StatsHandlerMetrics {
+    metrics: Arc<Metrics>,
+    inner: Box<dyn StatsHandler>,
+    lookup_ipv4: bool,
+    lookup_ipv6: bool,
+}
+
+impl StatsHandlerMetrics {
+    pub fn new(
+        metrics: Arc<Metrics>,
+        inner: Box<dyn StatsHandler>,
+        lookup_ipv4: bool,
+        lookup_ipv6: bool,
+    ) -> Self {
+        Self {
+            metrics,
+            inner,
+            lookup_ipv4,
+            lookup_ipv6,
+        }
+    }
+
+    fn record_ip_fetch_for_family(
+        &self,
+        source: &str,
+        family: &str,
+        maybe_address: Option<String>,
+    ) {
+        let labels = IpFetchLabels {
+            source: source.to_owned(),
+            family: family.to_owned(),
+        };
+        self.metrics
+            .record_ip_fetch(&labels, maybe_address.is_some());
+
+        // Keep exposing the last known address if the fetch failed.
+        if maybe_address.is_some() {
+            self.metrics.set_public_ip(family, maybe_address);
+        }
+    }
+}
+
+impl StatsHandler for StatsHandlerMetrics {
+    fn init(&mut self) -> Result<()> {
+        self.inner.init()
+    }
+
+    fn handle_ip_fetch(
+        &mut self,
+        source: &str,
+        maybe_fetched_ips: Option<IpAddrV4AndV6>,
+    ) -> Result<()> {
+        if self.lookup_ipv4 {
+            let maybe_ipv4 = maybe_fetched_ips
+                .as_ref()
+                .and_then(|ips| ips.ipv4)
+                .map(|ip| ip.to_string());
+            self.record_ip_fetch_for_family(source, "ipv4", maybe_ipv4);
+        }
+        if self.lookup_ipv6 {
+            let maybe_ipv6 = maybe_fetched_ips
+                .as_ref()
+                .and_then(|ips| ips.ipv6)
+                .map(|ip| ip.to_string());
+            self.record_ip_fetch_for_family(source, "ipv6", maybe_ipv6);
+        }
+
+        self.inner.handle_ip_fetch(source, maybe_fetched_ips)
+    }
+
+    fn handle_updater_attempt(
+        &mut self,
+        domain_record_name: &str,
+        record_type: &str,
+        is_domain_record_update_successful: bool,
+        ip_kind: Option<IpAddrKind>,
+        provider_outcomes: &[ProviderUpdateOutcome],
+    ) -> Result<()> {
+        let record_labels = RecordLabels {
+            name: domain_record_name.to_owned(),
+            record_type: record_type.to_owned(),
+        };
+        self.metrics
+            .record_record_update(&record_labels, is_domain_record_update_successful);
+
+        for outcome in provider_outcomes {
+            let provider_labels = ProviderLabels {
+                provider: outcome.provider_name.to_owned(),
+            };
+            self.metrics
+                .record_provider_update(&provider_labels, outcome.success, outcome.latency);
+        }
+
+        self.inner.handle_updater_attempt(
+            domain_record_name,
+            record_type,
+            is_domain_record_update_successful,
+            ip_kind,
+            provider_outcomes,
+        )
+    }
+
+    fn handle_updater_run(&mut self, is_successful: bool) -> Result<()> {
+        self.metrics.record_updater_run(is_successful);
+        self.inner.handle_updater_run(is_successful)
+    }
+}
@@ -0,0 +1,78 @@
+use color_eyre::eyre::Result;
+use std::sync::Arc;
+
+use crate::stats_handler::StatsHandler;
+use crate::types::{IpAddrKind, IpAddrV4AndV6, ProviderUpdateOutcome};
+use crate::updater_status::{UpdaterStatus, UpdaterThreadState};
+
+/// Keeps the [`UpdaterStatus`] up to date and forwards all events to the
+/// wrapped handler.
+///
+/// The handler is owned by the updater, so dropping it means the updater
+/// thread exited, even if it panicked.
+pub struct StatsHandlerStatus {
+    updater_status: Arc<UpdaterStatus>,
+    inner: Box<dyn StatsHandler>,
+}
+
+impl StatsHandlerStatus {
+    pub fn new(updater_status: Arc<UpdaterStatus>, inner: Box<dyn StatsHandler>) -> Self {
+        Self {
+            updater_status,
+            inner,
+        }
+    }
+}
+
+impl StatsHandler for StatsHandlerStatus {
+    fn init(&mut self) -> Result<()> {
+        self.updater_status
+            .set_updater_state(UpdaterThreadState::Running);
+        self.inner.init()
+    }
+
+    fn handle_ip_fetch(
+        &mut self,
+        source: &str,
+        maybe_fetched_ips: Option<IpAddrV4AndV6>,
+    ) -> Result<()> {
+        self.updater_status
+            .record_ip_fetch(maybe_fetched_ips.as_ref());
+        self.inner.handle_ip_fetch(source, maybe_fetched_ips)
+    }
+
+    fn handle_updater_attempt(
+        &mut self,
+        domain_record_name: &str,
+        record_type: &str,
+        is_domain_record_update_successful: bool,
+        ip_kind: Option<IpAddrKind>,
+        provider_outcomes: &[ProviderUpdateOutcome],
+    ) -> Result<()> {
+        self.updater_status.record_updater_attempt(
+            domain_record_name,
+            record_type,
+            is_domain_record_update_successful,
+            provider_outcomes,
+        );
+        self.inner.handle_updater_attempt(
+            domain_record_name,
+            record_type,
+            is_domain_record_update_successful,
+            ip_kind,
+            provider_outcomes,
+        )
+    }
+
+    fn handle_updater_run(&mut self, is_successful: bool) -> Result<()> {
+        self.updater_status.record_updater_run(is_successful);
+        self.inner.handle_updater_run(is_successful)
+    }
+}
+
+impl Drop for StatsHandlerStatus {
+    fn drop(&mut self) {
+        self.updater_status
+            .set_updater_state(UpdaterThreadState::Stopped);
+    }
+}
@@ -2,7 +2,8 @@ use color_eyre::eyre::{eyre, Error};
 use std::{fmt::Display, net::IpAddr};
 use tracing::Level;
 
-use crate::config::app_config::UpdateInterval;
+use crate::config::app_config::{RecordValuesMode, UpdateInterval};
+use crate::config::failover_config::FailoverConfig;
 use crate::config::provider_config::{ProviderType, SecretProviderToken};
 
 /// Controls the default behavior for domain records without an explicit providers list.
@@ -34,7 +35,8 @@ impl From<ProvidersMissingBehavior> for bool {
 /// - Using String for IDs
 /// - Normalizing the `name` field to contain only the hostname part (e.g., "www", "@", "home")
 ///   regardless of what format the provider returns (some return FQDNs, others just hostnames)
-/// - Using a single field name (`ip_value`) for the IP address regardless of provider
+/// - Representing the whole record set, with all of its values, regardless of whether the
+///   provider manages record sets or single records
 #[derive(Debug, Clone)]
 pub struct DomainRecordCommon {
     /// Unique identifier for the record (String to support all providers)
@@ -44,7 +46,28 @@ pub struct DomainRecordCommon {
     /// Hostname part of the record (e.g., "www", "@", "home")
     /// NOTE: This must be normalized to hostname-only format by provider implementations
     pub name: String,
-    pub ip_value: String,
+    /// All values of the record set, e.g. each address of a round-robin A record.
+    pub values: Vec<DomainRecordValue>,
+}
+
+/// A single value of a record set.
+#[derive(Debug, Clone)]
+pub struct DomainRecordValue {
+    /// Identifier of the provider record holding the value. Providers that
+    /// manage whole record sets use the id of the set for all of its values.
+    pub id: String,
+    pub value: String,
+}
+
+impl DomainRecordCommon {
+    /// Values of the record set, separated by commas.
+    pub fn joined_values(&self) -> String {
+        self.values
+            .iter()
+            .map(|value| value.value.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -52,12 +75,7 @@ pub struct DomainRecordsCommon {
     pub records: Vec<DomainRecordCommon>,
 }
 
-/// Cache mapping domain names to their DNS records fetched from provider APIs.
-///
-/// Avoids redundant API calls.
-pub type DomainRecordCache = std::collections::HashMap<String, DomainRecordsCommon>;
-
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DomainRecordToUpdate {
     pub domain_name: String,
     pub hostname_part: String,
@@ -67,6 +85,12 @@ pub struct DomainRecordToUpdate {
     /// - `Some(vec![])`: updates on NO providers (explicitly disabled)
     /// - `Some(vec![...])`: updates only on specified providers
     pub providers: Option<Vec<ProviderType>>,
+    /// Whether the other values of the record set are kept when it is updated.
+    pub values_mode: RecordValuesMode,
+    /// Values that are always part of the record set, next to the public IP.
+    pub static_values: Vec<String>,
+    /// Candidates to pick the IP from, instead of using the public IP.
+    pub failover: Option<FailoverConfig>,
 }
 
 impl DomainRecordToUpdate {
@@ -81,9 +105,27 @@ impl DomainRecordToUpdate {
             hostname_part: hostname_part.to_owned(),
             record_type: record_type.to_owned(),
             providers,
+            values_mode: RecordValuesMode::default(),
+            static_values: vec![],
+            failover: None,
         }
     }
 
+    pub fn with_values(
+        mut self,
+        values_mode: RecordValuesMode,
+        static_values: Vec<String>,
+    ) -> Self {
+        self.values_mode = values_mode;
+        self.static_values = static_values;
+        self
+    }
+
+    pub fn with_failover(mut self, failover: Option<FailoverConfig>) -> Self {
+        self.failover = failover;
+        self
+    }
+
     pub fn fqdn(&self) -> String {
         if self.hostname_part == "@" {
             self.domain_name.clone()
@@ -121,6 +163,25 @@ pub enum IpAddrKind {
     V6,
 }
 
+/// Outcome of updating a domain record on a single DNS provider.
+#[derive(Debug, Clone)]
+pub struct ProviderUpdateOutcome {
+    pub provider_name: &'static str,
+    pub success: bool,
+    /// Kind of the IP that was (or would have been) set on the record.
+    pub ip_kind: Option<IpAddrKind>,
+    pub error_message: Option<String>,
+    pub http_status: Option<u16>,
+    /// Value of the record on the provider after the attempt, if the record
+    /// could be queried.
+    pub record_value: Option<String>,
+    /// Time spent talking to the provider API.
+    pub latency: std::time::Duration,
+    /// Time until the authoritative nameservers served the new value, if the
+    /// update was verified.
+    pub propagation: Option<std::time::Duration>,
+}
+
 impl Display for IpAddrV4AndV6 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "ipv4: {:?} ipv6: {:?}", self.ipv4, self.ipv6)
@@ -276,6 +337,23 @@ macro_rules! impl_value_from_bool_as_error {
 
 impl_value_from_bool_as_error! { String, UpdateInterval, Level, SecretProviderToken, u16 }
 
+/// Record set `home` of type `A` with the given ids and values, for tests.
+#[cfg(test)]
+pub fn test_record_set(values: &[(&str, &str)]) -> DomainRecordCommon {
+    DomainRecordCommon {
+        id: values.first().map_or("1", |(id, _)| *id).to_owned(),
+        record_type: "A".to_owned(),
+        name: "home".to_owned(),
+        values: values
+            .iter()
+            .map(|(id, value)| DomainRecordValue {
+                id: (*id).to_owned(),
+                value: (*value).to_owned(),
+            })
+            .collect(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
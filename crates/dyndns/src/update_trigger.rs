@@ -0,0 +1,319 @@
+use color_eyre::eyre::{bail, Result};
+#[cfg(feature = "web")]
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::thread::Thread;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+/// Restricts an update attempt to a single domain record and / or provider.
+#[cfg_attr(feature = "web", derive(JsonSchema))]
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UpdateScope {
+    pub record: Option<String>,
+    pub provider: Option<String>,
+}
+
+impl UpdateScope {
+    pub fn matches_record(&self, fqdn: &str) -> bool {
+        self.record
+            .as_deref()
+            .map_or(true, |record| record.eq_ignore_ascii_case(fqdn))
+    }
+
+    pub fn matches_provider(&self, provider_name: &str) -> bool {
+        self.provider.as_deref().map_or(true, |provider| {
+            provider.eq_ignore_ascii_case(provider_name)
+        })
+    }
+}
+
+#[cfg_attr(feature = "web", derive(JsonSchema))]
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderUpdateReport {
+    pub provider: String,
+    pub success: bool,
+    pub value: Option<String>,
+    pub error_message: Option<String>,
+}
+
+#[cfg_attr(feature = "web", derive(JsonSchema))]
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordUpdateReport {
+    pub name: String,
+    pub record_type: String,
+    pub success: bool,
+    pub providers: Vec<ProviderUpdateReport>,
+}
+
+#[cfg_attr(feature = "web", derive(JsonSchema))]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UpdateReport {
+    pub success: bool,
+    pub error_message: Option<String>,
+    pub records: Vec<RecordUpdateReport>,
+}
+
+/// The domain records and providers that requested updates can be
+/// restricted to.
+#[derive(Debug, Clone, Default)]
+pub struct UpdateTargets {
+    /// Fully qualified names of the configured domain records.
+    pub records: Vec<String>,
+    /// Names of the configured DNS providers.
+    pub providers: Vec<String>,
+}
+
+/// An update requested outside of the regular update interval.
+pub struct UpdateRequest {
+    pub scope: UpdateScope,
+    response_tx: oneshot::Sender<UpdateReport>,
+}
+
+impl UpdateRequest {
+    pub fn respond(self, report: UpdateReport) {
+        // The requester might have gone away in the meantime, nothing to do then.
+        let _ = self.response_tx.send(report);
+    }
+}
+
+/// Allows waking up the sleeping updater thread to run an update right away.
+#[derive(Default)]
+pub struct UpdateTrigger {
+    updater_thread: Mutex<Option<Thread>>,
+    pending_requests: Mutex<Vec<UpdateRequest>>,
+    /// When the network last changed, if an update for it is still pending.
+    network_changed_at: Mutex<Option<Instant>>,
+    targets: Mutex<UpdateTargets>,
+}
+
+impl UpdateTrigger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the thread running the update loop. Must be called from the
+    /// updater thread itself.
+    pub fn attach_updater_thread(&self) {
+        self.updater_thread
+            .lock()
+            .expect("updater_thread mutex poisoned")
+            .replace(std::thread::current());
+    }
+
+    /// Stops accepting requests. Requests that were not handled yet are
+    /// dropped, which makes their requesters stop waiting.
+    pub fn detach_updater_thread(&self) {
+        let mut updater_thread = self
+            .updater_thread
+            .lock()
+            .expect("updater_thread mutex poisoned");
+        updater_thread.take();
+        self.take_pending_requests();
+        self.clear_network_change();
+    }
+
+    /// Sets the records and providers that update requests are checked
+    /// against.
+    pub fn set_targets(&self, targets: UpdateTargets) {
+        *self.targets.lock().expect("targets mutex poisoned") = targets;
+    }
+
+    /// Checks that the record and provider of the scope are configured.
+    pub fn validate_scope(&self, scope: &UpdateScope) -> Result<()> {
+        let targets = self.targets.lock().expect("targets mutex poisoned");
+        if let Some(record) = &scope.record {
+            if !targets
+                .records
+                .iter()
+                .any(|fqdn| scope.matches_record(fqdn))
+            {
+                bail!("No configured domain record named '{}'", record);
+            }
+        }
+        if let Some(provider) = &scope.provider {
+            if !targets
+                .providers
+                .iter()
+                .any(|provider_name| scope.matches_provider(provider_name))
+            {
+                bail!("No configured DNS provider named '{}'", provider);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn has_pending_requests(&self) -> bool {
+        !self
+            .pending_requests
+            .lock()
+            .expect("pending_requests mutex poisoned")
+            .is_empty()
+    }
+
+    pub fn take_pending_requests(&self) -> Vec<UpdateRequest> {
+        std::mem::take(
+            &mut *self
+                .pending_requests
+                .lock()
+                .expect("pending_requests mutex poisoned"),
+        )
+    }
+
+    /// Queues an update request and wakes up the updater.
+    ///
+    /// The returned receiver resolves with the report of the update attempt.
+    pub fn trigger(&self, scope: UpdateScope) -> Result<oneshot::Receiver<UpdateReport>> {
+        let updater_thread = self
+            .updater_thread
+            .lock()
+            .expect("updater_thread mutex poisoned");
+        let Some(thread) = updater_thread.as_ref() else {
+            bail!("The updater is not running");
+        };
+
+        let (response_tx, response_rx) = oneshot::channel();
+        self.pending_requests
+            .lock()
+            .expect("pending_requests mutex poisoned")
+            .push(UpdateRequest { scope, response_tx });
+        thread.unpark();
+        Ok(response_rx)
+    }
+
+    /// Records that network interfaces or addresses changed and wakes up the
+    /// updater. Returns `false` if the updater is not running.
+    pub fn notify_network_change(&self) -> bool {
+        let updater_thread = self
+            .updater_thread
+            .lock()
+            .expect("updater_thread mutex poisoned");
+        let Some(thread) = updater_thread.as_ref() else {
+            return false;
+        };
+        self.network_changed_at
+            .lock()
+            .expect("network_changed_at mutex poisoned")
+            .replace(Instant::now());
+        thread.unpark();
+        true
+    }
+
+    /// Returns how long to wait until the network has been quiet for
+    /// `debounce` after the last change, or `None` if there was no change.
+    pub fn network_change_settles_in(&self, debounce: Duration) -> Option<Duration> {
+        self.network_changed_at
+            .lock()
+            .expect("network_changed_at mutex poisoned")
+            .map(|changed_at| debounce.saturating_sub(changed_at.elapsed()))
+    }
+
+    pub fn clear_network_change(&self) {
+        self.network_changed_at
+            .lock()
+            .expect("network_changed_at mutex poisoned")
+            .take();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_scope_matching() {
+        let scope = UpdateScope::default();
+        assert!(scope.matches_record("home.site.com"));
+        assert!(scope.matches_provider("DigitalOcean"));
+
+        let scope = UpdateScope {
+            record: Some("home.site.com".to_owned()),
+            provider: Some("digitalocean".to_owned()),
+        };
+        assert!(scope.matches_record("HOME.site.com"));
+        assert!(!scope.matches_record("www.site.com"));
+        assert!(scope.matches_provider("DigitalOcean"));
+        assert!(!scope.matches_provider("Cloudflare"));
+    }
+
+    #[test]
+    fn test_trigger_requires_updater_thread() {
+        let trigger = UpdateTrigger::new();
+        assert!(trigger.trigger(UpdateScope::default()).is_err());
+
+        trigger.attach_updater_thread();
+        let mut response_rx = trigger
+            .trigger(UpdateScope::default())
+            .expect("trigger should succeed");
+        assert!(trigger.has_pending_requests());
+
+        for request in trigger.take_pending_requests() {
+            request.respond(UpdateReport {
+                success: true,
+                ..Default::default()
+            });
+        }
+        assert!(
+            response_rx
+                .try_recv()
+                .expect("report should be sent")
+                .success
+        );
+
+        trigger.detach_updater_thread();
+        assert!(trigger.trigger(UpdateScope::default()).is_err());
+    }
+
+    #[test]
+    fn test_validate_scope() {
+        let trigger = UpdateTrigger::new();
+        trigger.set_targets(UpdateTargets {
+            records: vec!["home.site.com".to_owned()],
+            providers: vec!["DigitalOcean".to_owned()],
+        });
+        assert!(trigger.validate_scope(&UpdateScope::default()).is_ok());
+        assert!(trigger
+            .validate_scope(&UpdateScope {
+                record: Some("HOME.site.com".to_owned()),
+                provider: Some("digitalocean".to_owned()),
+            })
+            .is_ok());
+
+        let error = trigger
+            .validate_scope(&UpdateScope {
+                record: Some("www.site.com".to_owned()),
+                provider: None,
+            })
+            .unwrap_err();
+        assert!(error.to_string().contains("'www.site.com'"));
+        let error = trigger
+            .validate_scope(&UpdateScope {
+                record: None,
+                provider: Some("Cloudflare".to_owned()),
+            })
+            .unwrap_err();
+        assert!(error.to_string().contains("'Cloudflare'"));
+    }
+
+    #[test]
+    fn test_network_change_debounce() {
+        let trigger = UpdateTrigger::new();
+        assert!(!trigger.notify_network_change());
+        assert_eq!(trigger.network_change_settles_in(Duration::ZERO), None);
+
+        trigger.attach_updater_thread();
+        assert!(trigger.notify_network_change());
+        let debounce = Duration::from_secs(60);
+        assert!(trigger
+            .network_change_settles_in(debounce)
+            .is_some_and(|settles_in| settles_in > Duration::ZERO && settles_in <= debounce));
+        assert_eq!(
+            trigger.network_change_settles_in(Duration::ZERO),
+            Some(Duration::ZERO)
+        );
+
+        trigger.clear_network_change();
+        assert_eq!(trigger.network_change_settles_in(Duration::ZERO), None);
+    }
+}
@@ -1,47 +1,176 @@
+use chrono::Utc;
 use color_eyre::eyre::{bail, eyre, Result};
+use futures_util::StreamExt;
 use humantime::format_duration;
+use std::collections::{BTreeMap, HashMap};
 use std::net::IpAddr;
+use std::sync::Arc;
 use std::thread::{park_timeout, JoinHandle};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tracing::{debug, error, info, trace, warn};
 
-use crate::config::app_config;
-use crate::config::app_config::AppConfig;
-use crate::domain_record_api::DomainRecordApi;
+use crate::config::app_config::{AppConfig, RecordValuesMode};
+use crate::config::notifier_config::NotificationKind;
+use crate::dns_verifier::{DnsVerifier, VerificationError};
+use crate::domain_record_api::{
+    http_status_of_error, record_values_match, request_may_have_been_applied, BatchChangeOutcome,
+    DomainRecordApi, RecordSetChange,
+};
+use crate::dynamic_values::{default_state_path, DynamicValues};
+use crate::failover::FailoverMonitor;
 use crate::global_state::GlobalState;
 use crate::ip_fetcher::{DnsIpFetcher, PublicIpFetcher};
+use crate::network_watcher::start_network_watcher;
+use crate::notifiers::{Notification, Notifiers};
+use crate::scheduler::UpdateScheduler;
 use crate::signal_handlers::AppTerminationHandler;
 use crate::stats_handler::{StatsHandler, StatsHandlerFactory};
-use crate::types::{DomainRecordCache, DomainRecordToUpdate, IpAddrKind, IpAddrV4AndV6};
+use crate::types::{
+    DomainRecordCommon, DomainRecordToUpdate, DomainRecordsCommon, IpAddrKind, IpAddrV4AndV6,
+    ProviderUpdateOutcome,
+};
+use crate::update_trigger::{
+    ProviderUpdateReport, RecordUpdateReport, UpdateReport, UpdateRequest, UpdateScope,
+    UpdateTargets, UpdateTrigger,
+};
+use crate::updater_events::{IpFamily, UpdaterEvent, UpdaterEvents};
+
+/// Result of updating a domain record on a single provider.
+struct ProviderRecordUpdate {
+    ip_kind: Option<IpAddrKind>,
+    /// Value of the record on the provider after the update.
+    record_value: String,
+    /// Value of the record before the update, if the update changed it.
+    previous_value: Option<String>,
+    /// Public IP that the update put into the record.
+    updated_ip: Option<IpAddr>,
+}
+
+/// How a record is to be updated on a provider, worked out from its current
+/// values before the changes of its zone are submitted.
+struct PlannedRecordUpdate {
+    domain_record: DomainRecordCommon,
+    ip_kind: Option<IpAddrKind>,
+    /// Public IP the record should point at.
+    curr_ip: Option<IpAddr>,
+    /// Key of merged records in `dynamic_values`, under which `curr_ip` is
+    /// remembered.
+    dynamic_value_key: Option<String>,
+    /// Values to set, if they differ from the current ones.
+    new_values: Option<Vec<String>>,
+}
+
+/// A record to update on the providers, with the public IPs to update it to.
+struct RecordUpdateJob<'a> {
+    /// Position of the record among the records of the update attempt.
+    record_index: usize,
+    record_to_update: &'a DomainRecordToUpdate,
+    public_ips: &'a IpAddrV4AndV6,
+}
+
+/// Result of updating a record on a provider, and how long it took.
+type RecordUpdateResult = (Result<ProviderRecordUpdate>, Duration);
+
+/// Why the updater stopped sleeping.
+enum SleepOutcome {
+    TimedOut,
+    /// An update was requested via the [`UpdateTrigger`].
+    Triggered,
+    /// Network interfaces or addresses changed, and have settled since.
+    NetworkChanged,
+    Exit,
+}
+
 pub struct Updater {
     global_state: GlobalState,
-    dns_providers: Vec<Box<dyn DomainRecordApi + Send>>,
-    failed_attempts: u64,
+    dns_providers: Vec<Box<dyn DomainRecordApi>>,
+    /// Failed regular update attempts of each record, by its name and type.
+    ///
+    /// Counted per record, so that records updated more often than others
+    /// don't use up the attempts of the rest.
+    failed_attempts: HashMap<(String, String), u64>,
     stats_handler: Box<dyn StatsHandler>,
     term_handler: AppTerminationHandler,
+    update_trigger: Arc<UpdateTrigger>,
+    events: Arc<UpdaterEvents>,
+    /// Public IPs of the previous successful fetch, to notice changes.
+    last_public_ips: Option<IpAddrV4AndV6>,
+    notifiers: Notifiers,
+    /// Failed update attempts since the last successful one.
+    consecutive_failures: u32,
+    dns_verifier: Option<DnsVerifier>,
+    dynamic_values: DynamicValues,
+    failover_monitor: FailoverMonitor,
 }
 
 impl Updater {
     pub fn new(
         global_state: GlobalState,
-        dns_providers: Vec<Box<dyn DomainRecordApi + Send>>,
+        dns_providers: Vec<Box<dyn DomainRecordApi>>,
+        notifiers: Notifiers,
         term_handler: AppTerminationHandler,
     ) -> Self {
-        let config = global_state.config.clone();
+        let stats_handler = StatsHandlerFactory::new_handler(&global_state);
+        #[cfg(feature = "web")]
+        let update_trigger = global_state.shared.update_trigger.clone();
+        #[cfg(not(feature = "web"))]
+        let update_trigger = Arc::new(UpdateTrigger::new());
+        #[cfg(feature = "web")]
+        let events = global_state.shared.events.clone();
+        #[cfg(not(feature = "web"))]
+        let events = Arc::new(UpdaterEvents::new());
+        let dns_verifier = global_state
+            .config
+            .general_options
+            .verification
+            .clone()
+            .map(|config| DnsVerifier::new(config, term_handler.clone()));
+        let state_path = match &global_state.config.general_options.state_path {
+            Some(path) => Some(path.clone()),
+            None => match default_state_path() {
+                Ok(path) => Some(path),
+                Err(e) => {
+                    warn!("Previously set public IPs won't be remembered: {:#}", e);
+                    None
+                }
+            },
+        };
+        let dynamic_values = DynamicValues::load(state_path);
         Self {
             global_state,
             dns_providers,
-            failed_attempts: 0,
-            stats_handler: StatsHandlerFactory::new_handler(config),
+            failed_attempts: HashMap::new(),
+            stats_handler,
             term_handler,
+            update_trigger,
+            events,
+            last_public_ips: None,
+            notifiers,
+            consecutive_failures: 0,
+            dns_verifier,
+            dynamic_values,
+            failover_monitor: FailoverMonitor::new(),
         }
     }
 
+    /// Where the updater publishes its activity.
+    pub fn events(&self) -> Arc<UpdaterEvents> {
+        self.events.clone()
+    }
+
+    /// Aggregates the results of updating the record on each provider, in
+    /// the order the providers are configured in, and of verifying the new
+    /// value if it changed.
+    #[allow(clippy::too_many_arguments)]
     fn attempt_update_for_record(
         &self,
         current_public_ips: &IpAddrV4AndV6,
+        record_index: usize,
         record_to_update: &DomainRecordToUpdate,
-        domain_record_cache: &mut DomainRecordCache,
+        provider_results: &mut HashMap<(usize, usize), RecordUpdateResult>,
+        verification: Option<Result<Duration, VerificationError>>,
+        provider_outcomes: &mut Vec<ProviderUpdateOutcome>,
+        scope: &UpdateScope,
     ) -> Result<Option<IpAddrKind>> {
         if self.dns_providers.is_empty() {
             bail!("No DNS providers configured - cannot update records");
@@ -52,16 +181,14 @@ impl Updater {
         let mut success_count = 0;
         let mut error_count = 0;
         let mut filtered_count = 0;
+        // Outcomes of the providers that changed the record.
+        let mut updated_outcomes = vec![];
 
-        for provider in &self.dns_providers {
-            if !record_to_update.should_update_on(
-                provider.provider_type(),
-                self.global_state
-                    .config
-                    .general_options
-                    .update_all_providers_by_default
-                    .into(),
-            ) {
+        for (provider_index, provider) in self.dns_providers.iter().enumerate() {
+            if !scope.matches_provider(provider.provider_name()) {
+                continue;
+            }
+            if !self.should_update_on(provider.as_ref(), record_to_update) {
                 debug!(
                     "[{}] Skipping record '{}' - not configured for this provider",
                     provider.provider_name(),
@@ -71,15 +198,54 @@ impl Updater {
                 continue;
             }
 
-            match self.attempt_update_for_record_for_provider(
-                provider.as_ref(),
-                current_public_ips,
-                record_to_update,
-                domain_record_cache,
-            ) {
-                Ok(ip_kind_res_inner) => {
+            let Some((provider_res, latency)) =
+                provider_results.remove(&(record_index, provider_index))
+            else {
+                continue;
+            };
+            provider_outcomes.push(ProviderUpdateOutcome {
+                provider_name: provider.provider_name(),
+                success: provider_res.is_ok(),
+                ip_kind: match &provider_res {
+                    Ok(provider_update) => provider_update.ip_kind,
+                    Err(_) => {
+                        ip_kind_for_record_type(current_public_ips, &record_to_update.record_type)
+                    }
+                },
+                error_message: provider_res.as_ref().err().map(|e| format!("{e:#}")),
+                http_status: provider_res
+                    .as_ref()
+                    .err()
+                    .and_then(http_status_of_error)
+                    .map(|status| status.as_u16()),
+                record_value: provider_res
+                    .as_ref()
+                    .ok()
+                    .map(|provider_update| provider_update.record_value.clone()),
+                latency,
+                propagation: None,
+            });
+            if let Ok(ProviderRecordUpdate {
+                record_value,
+                previous_value: Some(previous_value),
+                ..
+            }) = &provider_res
+            {
+                updated_outcomes.push(provider_outcomes.len() - 1);
+                self.notifiers.notify(Notification {
+                    old_ip: Some(previous_value.clone()),
+                    new_ip: Some(record_value.clone()),
+                    fqdn: Some(record_to_update.fqdn()),
+                    record_type: Some(record_to_update.record_type.clone()),
+                    provider: Some(provider.provider_name().to_owned()),
+                    ..Notification::new(NotificationKind::RecordUpdated)
+                });
+            }
+
+            match provider_res {
+                Ok(provider_update) => {
                     if ip_kind_res.is_none() {
-                        ip_kind_res = ip_kind_res_inner;
+                        ip_kind_res = provider_update.ip_kind;
                     }
                     success_count += 1;
                     // Success - clear any previous error
@@ -100,6 +266,49 @@ impl Updater {
             }
         }
 
+        if let (Some(dns_verifier), Some(verification)) = (&self.dns_verifier, verification) {
+            match verification {
+                Ok(propagation) => {
+                    for index in &updated_outcomes {
+                        if let Some(outcome) = provider_outcomes.get_mut(*index) {
+                            outcome.propagation = Some(propagation);
+                        }
+                    }
+                }
+                Err(VerificationError::NotPropagated(e)) if dns_verifier.fails_on_timeout() => {
+                    let e = e.wrap_err(format!(
+                        "Failed to verify update of record '{}'",
+                        record_to_update.fqdn()
+                    ));
+                    error!("{:#}", e);
+                    for index in &updated_outcomes {
+                        if let Some(outcome) = provider_outcomes.get_mut(*index) {
+                            outcome.success = false;
+                            outcome.error_message = Some(format!("{e:#}"));
+                        }
+                    }
+                    success_count -= updated_outcomes.len();
+                    error_count += updated_outcomes.len();
+                    last_error = Some(e);
+                }
+                Err(VerificationError::NotPropagated(e)) => warn!(
+                    "Failed to verify update of record '{}': {:#}",
+                    record_to_update.fqdn(),
+                    e
+                ),
+                // The update itself succeeded, it just can't be checked.
+                Err(VerificationError::NameserverDiscovery(e)) => error!(
+                    "Can't verify update of record '{}', its nameservers weren't found: {:#}",
+                    record_to_update.fqdn(),
+                    e
+                ),
+                Err(VerificationError::Interrupted) => info!(
+                    "Stopped verifying update of record '{}' to shut down",
+                    record_to_update.fqdn()
+                ),
+            }
+        }
+
         // Check if all providers were filtered out
         if filtered_count > 0 && success_count == 0 && error_count == 0 {
             let err_msg = format!(
@@ -134,55 +343,258 @@ impl Updater {
         Ok(ip_kind_res)
     }
 
-    fn attempt_update_for_record_for_provider(
+    fn should_update_on(
+        &self,
+        provider: &dyn DomainRecordApi,
+        record_to_update: &DomainRecordToUpdate,
+    ) -> bool {
+        record_to_update.should_update_on(
+            provider.provider_type(),
+            self.global_state
+                .config
+                .general_options
+                .update_all_providers_by_default
+                .into(),
+        )
+    }
+
+    /// Updates the records on the providers they are configured for.
+    ///
+    /// The records of a zone share the fetched records of the zone, and their
+    /// changes are submitted in one batch if the provider supports it.
+    /// Different zones and providers are updated concurrently, up to
+    /// `update_concurrency` at a time. The results are keyed by record and
+    /// provider index.
+    async fn update_records_on_providers(
+        &self,
+        jobs: &[RecordUpdateJob<'_>],
+        scope: &UpdateScope,
+    ) -> HashMap<(usize, usize), RecordUpdateResult> {
+        let mut zones = BTreeMap::<_, Vec<_>>::new();
+        for (provider_index, provider) in self.dns_providers.iter().enumerate() {
+            if !scope.matches_provider(provider.provider_name()) {
+                continue;
+            }
+            for job in jobs
+                .iter()
+                .filter(|job| self.should_update_on(provider.as_ref(), job.record_to_update))
+            {
+                zones
+                    .entry((provider_index, job.record_to_update.domain_name.as_str()))
+                    .or_default()
+                    .push(job);
+            }
+        }
+
+        let zone_updates =
+            zones
+                .into_iter()
+                .filter_map(|((provider_index, domain_name), zone_jobs)| {
+                    let provider = self.dns_providers.get(provider_index)?;
+                    Some(self.update_zone_on_provider(
+                        provider_index,
+                        provider.as_ref(),
+                        domain_name,
+                        zone_jobs,
+                    ))
+                });
+        futures_util::stream::iter(zone_updates)
+            .buffer_unordered(self.global_state.config.general_options.update_concurrency)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// Verifies the new values of the records that the update changed on
+    /// any provider, all at once. The results are keyed by record index.
+    async fn verify_updated_records(
+        &self,
+        jobs: &[RecordUpdateJob<'_>],
+        provider_results: &HashMap<(usize, usize), RecordUpdateResult>,
+    ) -> HashMap<usize, Result<Duration, VerificationError>> {
+        let Some(dns_verifier) = &self.dns_verifier else {
+            return HashMap::new();
+        };
+        // The IP put into each changed record. Providers are checked in the
+        // order they are configured in, so the last one wins, as the
+        // nameservers queried are the same for all of them.
+        let mut updated_ips = BTreeMap::new();
+        for job in jobs {
+            for provider_index in 0..self.dns_providers.len() {
+                if let Some((
+                    Ok(ProviderRecordUpdate {
+                        previous_value: Some(_),
+                        updated_ip: Some(updated_ip),
+                        ..
+                    }),
+                    _,
+                )) = provider_results.get(&(job.record_index, provider_index))
+                {
+                    updated_ips.insert(job.record_index, (job.record_to_update, *updated_ip));
+                }
+            }
+        }
+        let updated_records = updated_ips.values().copied().collect::<Vec<_>>();
+        updated_ips
+            .into_keys()
+            .zip(dns_verifier.verify_all(&updated_records).await)
+            .collect()
+    }
+
+    async fn update_zone_on_provider(
+        &self,
+        provider_index: usize,
+        provider: &dyn DomainRecordApi,
+        domain_name: &str,
+        jobs: Vec<&RecordUpdateJob<'_>>,
+    ) -> Vec<((usize, usize), RecordUpdateResult)> {
+        let mut zone_records = None;
+        let mut planned_updates = vec![];
+        for job in &jobs {
+            let record_to_update = job.record_to_update;
+            self.events.publish(UpdaterEvent::RecordUpdateStarted {
+                name: record_to_update.fqdn(),
+                record_type: record_to_update.record_type.clone(),
+                provider: provider.provider_name().to_owned(),
+            });
+            let attempt_start = Instant::now();
+            let planned_update = self
+                .plan_record_update(
+                    provider,
+                    job.public_ips,
+                    record_to_update,
+                    &mut zone_records,
+                )
+                .await;
+            planned_updates.push((attempt_start, planned_update));
+        }
+
+        let mut change_results = {
+            let changes = jobs
+                .iter()
+                .zip(&planned_updates)
+                .filter_map(|(job, (_, planned_update))| {
+                    let planned_update = planned_update.as_ref().ok()?;
+                    Some(RecordSetChange {
+                        domain_record: &planned_update.domain_record,
+                        record_to_update: job.record_to_update,
+                        new_values: planned_update.new_values.as_deref()?,
+                    })
+                })
+                .collect::<Vec<_>>();
+            self.apply_record_changes(provider, domain_name, &changes)
+                .await
+        }
+        .into_iter();
+
+        let mut results = vec![];
+        for (job, (attempt_start, planned_update)) in jobs.iter().zip(planned_updates) {
+            let record_to_update = job.record_to_update;
+            let provider_res = planned_update.and_then(|planned_update| {
+                let change_result = if planned_update.new_values.is_some() {
+                    change_results.next()
+                } else {
+                    None
+                };
+                self.finish_record_update(planned_update, change_result)
+            });
+            self.events.publish(match &provider_res {
+                Ok(provider_update) => UpdaterEvent::RecordUpdateSucceeded {
+                    name: record_to_update.fqdn(),
+                    record_type: record_to_update.record_type.clone(),
+                    provider: provider.provider_name().to_owned(),
+                    value: provider_update.record_value.clone(),
+                },
+                Err(e) => UpdaterEvent::RecordUpdateFailed {
+                    name: record_to_update.fqdn(),
+                    record_type: record_to_update.record_type.clone(),
+                    provider: provider.provider_name().to_owned(),
+                    error_message: format!("{e:#}"),
+                },
+            });
+            results.push((
+                (job.record_index, provider_index),
+                (provider_res, attempt_start.elapsed()),
+            ));
+        }
+        results
+    }
+
+    /// Works out whether and to what values the record needs to be changed
+    /// on the provider.
+    async fn plan_record_update(
         &self,
-        provider: &(dyn DomainRecordApi + Send),
+        provider: &dyn DomainRecordApi,
         current_public_ips: &IpAddrV4AndV6,
         record_to_update: &DomainRecordToUpdate,
-        domain_record_cache: &mut DomainRecordCache,
-    ) -> Result<Option<IpAddrKind>> {
+        zone_records: &mut Option<DomainRecordsCommon>,
+    ) -> Result<PlannedRecordUpdate> {
         info!(
             "[{}] Attempting to update domain record '{}'",
             provider.provider_name(),
             record_to_update.fqdn()
         );
-        let records = match domain_record_cache.entry(format!(
-            "{}:{}",
-            provider.provider_name(),
-            record_to_update.domain_name
-        )) {
-            std::collections::hash_map::Entry::<_, _>::Vacant(o) => {
-                trace!(
-                    "[{}] Querying records for '{}'",
-                    provider.provider_name(),
-                    record_to_update.domain_name
-                );
-                let records = provider.get_domain_records(&record_to_update.domain_name)?;
-                o.insert(records)
-            }
-            std::collections::hash_map::Entry::<_, _>::Occupied(o) => {
-                trace!(
-                    "[{}] Reusing cached records for '{}'",
-                    provider.provider_name(),
-                    record_to_update.domain_name
-                );
-                o.into_mut()
-            }
+        let records = if let Some(records) = zone_records {
+            trace!(
+                "[{}] Reusing cached records for '{}'",
+                provider.provider_name(),
+                record_to_update.domain_name
+            );
+            records
+        } else {
+            trace!(
+                "[{}] Querying records for '{}'",
+                provider.provider_name(),
+                record_to_update.domain_name
+            );
+            let records = provider
+                .get_domain_records(&record_to_update.domain_name)
+                .await?;
+            zone_records.insert(records)
         };
         let api_domain_record = get_record_to_update(records, record_to_update)?;
-        let mut ip_kind_res = None;
+        let mut planned_update = PlannedRecordUpdate {
+            domain_record: api_domain_record.clone(),
+            ip_kind: None,
+            curr_ip: None,
+            dynamic_value_key: None,
+            new_values: None,
+        };
         if let Some((curr_ip, curr_ip_kind)) =
             get_single_ip_based_on_record_type(current_public_ips, api_domain_record)
         {
-            ip_kind_res = Some(curr_ip_kind);
-            if should_update_domain_ip(&curr_ip, api_domain_record) {
+            planned_update.ip_kind = Some(curr_ip_kind);
+            planned_update.curr_ip = Some(curr_ip);
+            if record_to_update.values_mode == RecordValuesMode::Merge {
+                planned_update.dynamic_value_key = Some(format!(
+                    "{}:{}:{}",
+                    provider.provider_name(),
+                    record_to_update.fqdn(),
+                    record_to_update.record_type
+                ));
+            }
+            let previous_dynamic_value = planned_update
+                .dynamic_value_key
+                .as_deref()
+                .and_then(|key| self.dynamic_values.get(key));
+            let new_values = desired_record_values(
+                record_to_update,
+                api_domain_record,
+                &curr_ip,
+                previous_dynamic_value.as_deref(),
+            );
+            if record_values_differ(api_domain_record, &new_values) {
                 info!(
-                    "[{}] Old domain record IP does not match current IP\n  current public IP:    '{}'\n  old domain record IP: '{}'.\nUpdating domain record",
+                    "[{}] Old domain record values do not match current IP\n  current public IP:        '{}'\n  old domain record values: '{}'\n  new domain record values: '{}'.\nUpdating domain record",
                     provider.provider_name(),
-                    curr_ip, api_domain_record.ip_value
+                    curr_ip,
+                    api_domain_record.joined_values(),
+                    new_values.join(", ")
                 );
                 if !self.global_state.config.general_options.dry_run {
-                    provider.update_domain_ip(&api_domain_record.id, record_to_update, &curr_ip)?;
+                    planned_update.new_values = Some(new_values);
                 } else {
                     info!(
                         "[{}] Skipping updating IP due to dry run",
@@ -197,13 +609,128 @@ impl Updater {
             }
         };
 
-        Ok(ip_kind_res)
+        Ok(planned_update)
+    }
+
+    /// Sets the new values of the changed records of a zone.
+    ///
+    /// If the provider supports it, all changes are submitted in one batch.
+    /// Changes that the batch didn't apply, or all of them if the batch
+    /// failed as a whole, are then made one by one. If the batch may have
+    /// been applied anyway, e.g. when it timed out, all changes fail instead,
+    /// because the planned changes may no longer match the provider records.
+    /// Returns one result per change.
+    async fn apply_record_changes(
+        &self,
+        provider: &dyn DomainRecordApi,
+        domain_name: &str,
+        changes: &[RecordSetChange<'_>],
+    ) -> Vec<Result<()>> {
+        let mut batch_outcomes = vec![];
+        if changes.len() > 1 && provider.supports_batch_updates() {
+            debug!(
+                "[{}] Updating {} records of '{}' in one batch",
+                provider.provider_name(),
+                changes.len(),
+                domain_name
+            );
+            let batch_error = match provider
+                .set_domain_record_values_batch(domain_name, changes)
+                .await
+            {
+                Ok(outcomes) if outcomes.len() == changes.len() => {
+                    batch_outcomes = outcomes;
+                    None
+                }
+                Ok(outcomes) => Some(format!(
+                    "Batch update of '{}' returned {} outcomes for {} changes",
+                    domain_name,
+                    outcomes.len(),
+                    changes.len()
+                )),
+                Err(e) if !request_may_have_been_applied(&e) => {
+                    warn!(
+                        "[{}] Batch update of '{}' failed, updating records one by one: {:#}",
+                        provider.provider_name(),
+                        domain_name,
+                        e
+                    );
+                    None
+                }
+                Err(e) => Some(format!("Batch update of '{domain_name}' failed: {e:#}")),
+            };
+            if let Some(batch_error) = batch_error {
+                error!(
+                    "[{}] {}. Not knowing which records were updated, they will be updated on the next attempt",
+                    provider.provider_name(),
+                    batch_error
+                );
+                return changes
+                    .iter()
+                    .map(|_| Err(eyre!("{}", batch_error)))
+                    .collect();
+            }
+        }
+
+        let mut batch_outcomes = batch_outcomes.into_iter();
+        let mut results = Vec::with_capacity(changes.len());
+        for change in changes {
+            let result = match batch_outcomes.next() {
+                Some(BatchChangeOutcome::Applied) => Ok(()),
+                Some(BatchChangeOutcome::Failed(e)) => Err(e),
+                Some(BatchChangeOutcome::NotApplied) | None => {
+                    provider
+                        .set_domain_record_values(
+                            change.domain_record,
+                            change.record_to_update,
+                            change.new_values,
+                        )
+                        .await
+                }
+            };
+            results.push(result);
+        }
+        results
+    }
+
+    /// Completes a planned record update with the result of changing its
+    /// values, if they needed to be changed.
+    fn finish_record_update(
+        &self,
+        planned_update: PlannedRecordUpdate,
+        change_result: Option<Result<()>>,
+    ) -> Result<ProviderRecordUpdate> {
+        let mut record_value = planned_update.domain_record.joined_values();
+        let mut previous_value = None;
+        let mut updated_ip = None;
+        if let (Some(new_values), Some(change_result)) = (planned_update.new_values, change_result)
+        {
+            change_result?;
+            previous_value = Some(std::mem::replace(&mut record_value, new_values.join(", ")));
+            updated_ip = planned_update.curr_ip;
+        }
+        if !self.global_state.config.general_options.dry_run {
+            if let (Some(ip), Some(dynamic_value_key)) =
+                (planned_update.curr_ip, planned_update.dynamic_value_key)
+            {
+                self.dynamic_values.set(dynamic_value_key, ip.to_string());
+            }
+        }
+
+        Ok(ProviderRecordUpdate {
+            ip_kind: planned_update.ip_kind,
+            record_value,
+            previous_value,
+            updated_ip,
+        })
     }
 
     fn attempt_update(
         &mut self,
-        ip_fetcher: &DnsIpFetcher,
+        ip_fetcher: &dyn PublicIpFetcher,
         records_to_update: &[DomainRecordToUpdate],
+        scope: &UpdateScope,
+        report: &mut UpdateReport,
     ) -> Result<()> {
         let current_public_ips_result = ip_fetcher.fetch_public_ips(
             self.global_state.config.general_options.ipv4,
@@ -215,22 +742,77 @@ impl Updater {
         };
         if let Some(ref e) = maybe_fetched_ip_err {
             error!("Ip fetching failed: {}", e);
+            self.events.publish(UpdaterEvent::IpFetchFailed {
+                error_message: format!("{e:#}"),
+            });
+        }
+        if let Some(ref current_public_ips) = maybe_current_public_ips {
+            self.publish_ip_fetch_events(current_public_ips);
         }
 
         self.stats_handler
-            .handle_ip_fetch(maybe_current_public_ips.clone())?;
+            .handle_ip_fetch(&ip_fetcher.source(), maybe_current_public_ips.clone())?;
 
         let mut first_error = maybe_fetched_ip_err;
-        let mut domain_record_cache = DomainRecordCache::new();
 
-        for record_to_update in records_to_update {
+        let mut records_with_ips = vec![];
+        for record_to_update in records_to_update
+            .iter()
+            .filter(|record_to_update| scope.matches_record(&record_to_update.fqdn()))
+        {
+            // Failover records use the IP of their healthy candidate instead
+            // of the public IP.
+            let record_public_ips = match &record_to_update.failover {
+                Some(failover) => match self.failover_monitor.select_ip(record_to_update, failover)
+                {
+                    Ok(ip) => Some(IpAddrV4AndV6::from(ip)),
+                    Err(e) => {
+                        error!("{}", e);
+                        if first_error.is_none() {
+                            first_error = Some(e);
+                        }
+                        None
+                    }
+                },
+                None => maybe_current_public_ips.clone(),
+            };
+            records_with_ips.push((record_to_update, record_public_ips));
+        }
+
+        let jobs = records_with_ips
+            .iter()
+            .enumerate()
+            .filter_map(|(record_index, (record_to_update, record_public_ips))| {
+                Some(RecordUpdateJob {
+                    record_index,
+                    record_to_update,
+                    public_ips: record_public_ips.as_ref()?,
+                })
+            })
+            .collect::<Vec<_>>();
+        let (mut provider_results, mut verifications) = tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                let provider_results = self.update_records_on_providers(&jobs, scope).await;
+                let verifications = self.verify_updated_records(&jobs, &provider_results).await;
+                (provider_results, verifications)
+            })
+        });
+
+        for (record_index, (record_to_update, record_public_ips)) in
+            records_with_ips.iter().enumerate()
+        {
             let mut is_domain_record_update_successful = false;
             let mut stats_ip_kind = None;
-            if let Some(ref current_public_ips) = maybe_current_public_ips {
+            let mut provider_outcomes = vec![];
+            if let Some(current_public_ips) = record_public_ips {
                 match self.attempt_update_for_record(
                     current_public_ips,
+                    record_index,
                     record_to_update,
-                    &mut domain_record_cache,
+                    &mut provider_results,
+                    verifications.remove(&record_index),
+                    &mut provider_outcomes,
+                    scope,
                 ) {
                     Ok(maybe_ip_kind) => {
                         is_domain_record_update_successful = true;
@@ -250,7 +832,23 @@ impl Updater {
                 &record_to_update.record_type,
                 is_domain_record_update_successful,
                 stats_ip_kind,
+                &provider_outcomes,
             )?;
+
+            report.records.push(RecordUpdateReport {
+                name: record_to_update.fqdn(),
+                record_type: record_to_update.record_type.clone(),
+                success: is_domain_record_update_successful,
+                providers: provider_outcomes
+                    .into_iter()
+                    .map(|outcome| ProviderUpdateReport {
+                        provider: outcome.provider_name.to_owned(),
+                        success: outcome.success,
+                        value: outcome.record_value,
+                        error_message: outcome.error_message,
+                    })
+                    .collect(),
+            });
         }
         if let Some(e) = first_error {
             return Err(e);
@@ -270,12 +868,9 @@ impl Updater {
     }
 
     fn build_starting_updater_mesage(
-        interval: &app_config::UpdateInterval,
-        records_to_update: &[DomainRecordToUpdate],
-        providers: &[Box<dyn DomainRecordApi + Send>],
+        scheduler: &UpdateScheduler,
+        providers: &[Box<dyn DomainRecordApi>],
     ) -> String {
-        let duration_formatted = format_duration(interval.0);
-
         // Build provider list
         let provider_names: Vec<&str> = providers.iter().map(|p| p.provider_name()).collect();
         let provider_list = if provider_names.is_empty() {
@@ -285,12 +880,12 @@ impl Updater {
         };
 
         let m = format!(
-            "Starting updater with update interval: {duration_formatted}.
+            "Starting updater.
 Configured DNS providers: {provider_list}
 The following domain records will be updated:",
         );
         let mut record_m = vec![];
-        for record in records_to_update {
+        for (record, cadence) in scheduler.records() {
             let fqdn = record.fqdn();
             let record_providers = match &record.providers {
                 None => "all".to_string(),
@@ -305,15 +900,27 @@ The following domain records will be updated:",
                 Some(providers) if providers.len() == 1 => "provider",
                 _ => "providers",
             };
+            let failover = record
+                .failover
+                .as_ref()
+                .map(|failover| {
+                    let candidates = failover
+                        .candidates
+                        .iter()
+                        .map(|candidate| format!("'{}'", candidate.name))
+                        .collect::<Vec<_>>();
+                    format!(", failover between {}", candidates.join(", "))
+                })
+                .unwrap_or_default();
             record_m.push(format!(
-                "    domain record '{}' of type '{}' ({}: {})",
-                fqdn, record.record_type, provider_label, record_providers
+                "    domain record '{}' of type '{}' ({}: {}, {}{})",
+                fqdn, record.record_type, provider_label, record_providers, cadence, failover
             ));
         }
         format!("{}\n{}", m, record_m.join("\n"))
     }
 
-    fn build_records_to_update(config: &AppConfig) -> Vec<DomainRecordToUpdate> {
+    pub fn build_records_to_update(config: &AppConfig) -> Vec<DomainRecordToUpdate> {
         config
             .domains
             .domains
@@ -327,63 +934,288 @@ The following domain records will be updated:",
                         record.record_type.as_str(),
                         record.providers.clone(),
                     )
+                    .with_values(record.values_mode, record.static_values.clone())
+                    .with_failover(record.failover.clone())
                 })
             })
             .collect::<Vec<_>>()
     }
 
+    /// Publishes the fetched public IPs, and each one that differs from the
+    /// previous fetch. Changes from a previously known IP are also sent to
+    /// the notifiers.
+    fn publish_ip_fetch_events(&mut self, current_public_ips: &IpAddrV4AndV6) {
+        self.events.publish(UpdaterEvent::IpFetched {
+            ipv4: current_public_ips.ipv4.map(|ip| ip.to_string()),
+            ipv6: current_public_ips.ipv6.map(|ip| ip.to_string()),
+        });
+
+        let previous_public_ips = self.last_public_ips.replace(current_public_ips.clone());
+        let previous_ipv4 = previous_public_ips.as_ref().and_then(|ips| ips.ipv4);
+        let previous_ipv6 = previous_public_ips.as_ref().and_then(|ips| ips.ipv6);
+        let ip_pairs = [
+            (
+                IpFamily::Ipv4,
+                previous_ipv4.map(IpAddr::V4),
+                current_public_ips.ipv4.map(IpAddr::V4),
+            ),
+            (
+                IpFamily::Ipv6,
+                previous_ipv6.map(IpAddr::V6),
+                current_public_ips.ipv6.map(IpAddr::V6),
+            ),
+        ];
+        for (family, old_ip, new_ip) in ip_pairs {
+            let Some(new_ip) = new_ip else {
+                continue;
+            };
+            if old_ip != Some(new_ip) {
+                self.events.publish(UpdaterEvent::IpChanged {
+                    family,
+                    old_ip: old_ip.map(|ip| ip.to_string()),
+                    new_ip: new_ip.to_string(),
+                });
+                if let Some(old_ip) = old_ip {
+                    self.notifiers.notify(Notification {
+                        old_ip: Some(old_ip.to_string()),
+                        new_ip: Some(new_ip.to_string()),
+                        ..Notification::new(NotificationKind::IpChanged)
+                    });
+                }
+            }
+        }
+    }
+
+    /// Runs a single update attempt and records its outcome.
+    ///
+    /// Only fails if the outcome can't be recorded.
+    fn run_update(
+        &mut self,
+        ip_fetcher: &dyn PublicIpFetcher,
+        records_to_update: &[DomainRecordToUpdate],
+        scope: &UpdateScope,
+    ) -> Result<UpdateReport> {
+        let mut report = UpdateReport::default();
+        let attempt_result = self.attempt_update(ip_fetcher, records_to_update, scope, &mut report);
+        self.stats_handler
+            .handle_updater_run(attempt_result.is_ok())?;
+        match attempt_result {
+            Ok(()) => {
+                report.success = true;
+                self.consecutive_failures = 0;
+            }
+            Err(e) => {
+                error!("Domain record update attempt failed: {}", e);
+                self.consecutive_failures += 1;
+                report.error_message = Some(format!("{e:#}"));
+                self.notify_update_failed(&report);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Notifies about a failed update attempt, naming the first record and
+    /// provider that failed, if the failure got that far.
+    fn notify_update_failed(&self, report: &UpdateReport) {
+        let failed_record = report.records.iter().find(|record| !record.success);
+        let failed_provider = failed_record
+            .and_then(|record| record.providers.iter().find(|provider| !provider.success));
+        self.notifiers.notify(Notification {
+            fqdn: failed_record.map(|record| record.name.clone()),
+            record_type: failed_record.map(|record| record.record_type.clone()),
+            provider: failed_provider.map(|provider| provider.provider.clone()),
+            error: report.error_message.clone(),
+            consecutive_failures: self.consecutive_failures,
+            ..Notification::new(NotificationKind::UpdateFailed)
+        });
+    }
+
+    /// Counts a failed attempt for each record that wasn't updated
+    /// successfully. Records the attempt failed before getting to count as
+    /// failed as well.
+    fn count_failed_attempts(&mut self, records: &[DomainRecordToUpdate], report: &UpdateReport) {
+        if report.success {
+            return;
+        }
+        for record in records {
+            let fqdn = record.fqdn();
+            let is_successful = report.records.iter().any(|reported| {
+                reported.success
+                    && reported.name == fqdn
+                    && reported.record_type == record.record_type
+            });
+            if !is_successful {
+                *self
+                    .failed_attempts
+                    .entry((fqdn, record.record_type.clone()))
+                    .or_default() += 1;
+            }
+        }
+    }
+
     pub fn start_update_loop(&mut self) -> Result<()> {
-        let ip_fetcher = DnsIpFetcher::default();
+        self.update_trigger.set_targets(UpdateTargets {
+            records: Updater::build_records_to_update(&self.global_state.config)
+                .iter()
+                .map(DomainRecordToUpdate::fqdn)
+                .collect(),
+            providers: self
+                .dns_providers
+                .iter()
+                .map(|provider| provider.provider_name().to_owned())
+                .collect(),
+        });
+        self.update_trigger.attach_updater_thread();
+        if self
+            .global_state
+            .config
+            .general_options
+            .network_watch
+            .watch_network_changes
+        {
+            match start_network_watcher(self.update_trigger.clone()) {
+                Ok(()) => info!("Watching for network changes to update right away"),
+                Err(e) => warn!(
+                    "Failed to watch for network changes, updating every update interval only: {:#}",
+                    e
+                ),
+            }
+        }
+        let result = self.run_update_loop();
+        self.update_trigger.detach_updater_thread();
+        result
+    }
+
+    fn run_update_loop(&mut self) -> Result<()> {
+        let ip_fetcher = DnsIpFetcher::with_bind(
+            self.global_state
+                .config
+                .general_options
+                .ip_fetch_bind
+                .clone(),
+        );
         let records_to_update = Updater::build_records_to_update(&self.global_state.config);
+        let mut scheduler = UpdateScheduler::new(&self.global_state.config, Utc::now());
 
         self.stats_handler.init()?;
 
-        let starting_message = Updater::build_starting_updater_mesage(
-            &self.global_state.config.general_options.update_interval,
-            &records_to_update,
-            &self.dns_providers,
-        );
+        let starting_message =
+            Updater::build_starting_updater_mesage(&scheduler, &self.dns_providers);
         info!("{}", starting_message);
 
+        let mut update_requests: Vec<UpdateRequest> = vec![];
+        let mut network_changed = false;
         loop {
-            let attempt_result = self.attempt_update(&ip_fetcher, &records_to_update);
-            if let Err(e) = attempt_result {
-                error!("Domain record update attempt failed: {}", e);
-                self.failed_attempts += 1;
+            if update_requests.is_empty() {
+                let now = Utc::now();
+                let due_records = scheduler.take_due(now);
+                // A network change can affect any record, not only the due
+                // ones, but records are still only updated within their schedule.
+                let network_records;
+                let records = if std::mem::take(&mut network_changed) {
+                    network_records = scheduler.in_window(now);
+                    &network_records
+                } else {
+                    &due_records
+                };
+                if !records.is_empty() {
+                    let report = self.run_update(&ip_fetcher, records, &UpdateScope::default())?;
+                    // Only regular updates count, so that failing requested
+                    // updates can't shut down the updater.
+                    self.count_failed_attempts(records, &report);
+                }
+            }
+            for update_request in update_requests.drain(..) {
+                // Also checked by the web API before queuing the request.
+                let report = match self.update_trigger.validate_scope(&update_request.scope) {
+                    Ok(()) => {
+                        self.run_update(&ip_fetcher, &records_to_update, &update_request.scope)?
+                    }
+                    Err(e) => UpdateReport {
+                        success: false,
+                        error_message: Some(format!("{e:#}")),
+                        records: vec![],
+                    },
+                };
+                update_request.respond(report);
             }
-            if self.failed_attempts > 10 {
+            if self.failed_attempts.values().any(|&count| count > 10) {
                 warn!("Too many failed domain record update attempts. Shutting down updater");
                 break;
             }
 
-            let duration_formatted =
-                format_duration(self.global_state.config.general_options.update_interval.0);
-            trace!("Sleeping for {}", duration_formatted);
+            let next_due = scheduler.next_due();
+            let sleep_duration = match next_due {
+                Some(next_due) => (next_due - Utc::now()).to_std().unwrap_or_default(),
+                // No record is ever due again, only requested updates remain.
+                None => {
+                    self.global_state
+                        .config
+                        .general_options
+                        .update_interval
+                        .clone()
+                        .unwrap_or_default()
+                        .0
+                }
+            };
+            trace!("Sleeping for {}", format_duration(sleep_duration));
+            if let Some(until) = next_due {
+                self.events.publish(UpdaterEvent::UpdaterSleeping { until });
+            }
 
-            // Exit if interrupted.
-            if self.was_interrupted_while_sleeping() {
-                info!("Updater recieved signal to shut down. Shutting down");
-                return Ok(());
+            match self.sleep_until_next_update(sleep_duration) {
+                SleepOutcome::TimedOut => {}
+                SleepOutcome::Triggered => {
+                    info!("Update was requested, updating right away");
+                    update_requests = self.update_trigger.take_pending_requests();
+                }
+                SleepOutcome::NetworkChanged => {
+                    info!("Network interfaces or addresses changed, updating right away");
+                    network_changed = true;
+                }
+                // Exit if interrupted.
+                SleepOutcome::Exit => {
+                    info!("Updater recieved signal to shut down. Shutting down");
+                    return Ok(());
+                }
             }
         }
         self.term_handler.notify_exit_and_stop_signal_handling();
         Ok(())
     }
 
-    fn was_interrupted_while_sleeping(&self) -> bool {
+    fn sleep_until_next_update(&self, timeout: Duration) -> SleepOutcome {
         if self.should_exit() {
-            return true;
+            return SleepOutcome::Exit;
+        }
+        if self.update_trigger.has_pending_requests() {
+            return SleepOutcome::Triggered;
         }
 
         let beginning_park = Instant::now();
-        let timeout = self.global_state.config.general_options.update_interval.0;
+        let debounce = self
+            .global_state
+            .config
+            .general_options
+            .network_watch
+            .network_change_debounce;
         let mut sleep_time_left = timeout;
         loop {
-            park_timeout(sleep_time_left);
+            match self.update_trigger.network_change_settles_in(debounce) {
+                Some(settles_in) if settles_in.is_zero() => {
+                    self.update_trigger.clear_network_change();
+                    return SleepOutcome::NetworkChanged;
+                }
+                Some(settles_in) => park_timeout(sleep_time_left.min(settles_in)),
+                None => park_timeout(sleep_time_left),
+            }
             let elapsed = beginning_park.elapsed();
             trace!("Interrupted, elapsed {:?}", elapsed);
             if self.should_exit() {
-                return true;
+                return SleepOutcome::Exit;
+            }
+            if self.update_trigger.has_pending_requests() {
+                return SleepOutcome::Triggered;
             }
             if elapsed >= timeout {
                 break;
@@ -391,7 +1223,7 @@ The following domain records will be updated:",
             trace!("restarting park_timeout after {:?}", elapsed);
             sleep_time_left = timeout - elapsed;
         }
-        false
+        SleepOutcome::TimedOut
     }
 
     fn should_exit(&self) -> bool {
@@ -458,19 +1290,638 @@ pub fn get_single_ip_based_on_record_type(
     }
 }
 
-pub fn should_update_domain_ip(
+/// Kind of the IP that an update of the given record type would have set,
+/// used for recording failed updates where no provider record was available.
+fn ip_kind_for_record_type(curr_ips: &IpAddrV4AndV6, record_type: &str) -> Option<IpAddrKind> {
+    match record_type {
+        "A" => curr_ips.has_ipv4().then_some(IpAddrKind::V4),
+        "AAAA" => curr_ips.has_ipv6().then_some(IpAddrKind::V6),
+        _ => curr_ips.has_any().then(|| curr_ips.to_ip_addr_from_any().1),
+    }
+}
+
+/// Values the record set should have with `curr_ip` as the public IP.
+///
+/// When merging, values other than the public IP set by the previous update
+/// (`previous_dynamic_value`) are kept.
+pub fn desired_record_values(
+    record_to_update: &DomainRecordToUpdate,
+    domain_record: &crate::types::DomainRecordCommon,
     curr_ip: &IpAddr,
+    previous_dynamic_value: Option<&str>,
+) -> Vec<String> {
+    let mut values: Vec<String> = vec![];
+    let mut add_value = |value: &str| {
+        if !values
+            .iter()
+            .any(|existing| record_values_match(existing, value))
+        {
+            values.push(value.to_owned());
+        }
+    };
+    if record_to_update.values_mode == RecordValuesMode::Merge {
+        for existing in &domain_record.values {
+            let is_stale_dynamic_value = previous_dynamic_value
+                .is_some_and(|previous| record_values_match(previous, &existing.value));
+            let is_static_value = record_to_update
+                .static_values
+                .iter()
+                .any(|value| record_values_match(value, &existing.value));
+            if !is_stale_dynamic_value || is_static_value {
+                add_value(&existing.value);
+            }
+        }
+    }
+    for value in &record_to_update.static_values {
+        add_value(value);
+    }
+    add_value(&curr_ip.to_string());
+    values
+}
+
+/// Whether the record set needs to be updated to have exactly `new_values`,
+/// regardless of their order.
+pub fn record_values_differ(
     domain_record: &crate::types::DomainRecordCommon,
+    new_values: &[String],
 ) -> bool {
-    let prev_ip = domain_record.ip_value.as_str();
-    if let Ok(prev_ip) = prev_ip.parse::<IpAddr>() {
-        curr_ip != &prev_ip
-    } else {
-        tracing::warn!(
-            "Domain record '{}' has a non-IP value '{}', will update to current IP",
-            domain_record.name,
-            prev_ip
+    for existing in &domain_record.values {
+        if existing.value.parse::<IpAddr>().is_err() {
+            debug!(
+                "Domain record '{}' has a non-IP value '{}'",
+                domain_record.name, existing.value
+            );
+        }
+    }
+    domain_record.values.len() != new_values.len()
+        || !new_values.iter().all(|value| {
+            domain_record
+                .values
+                .iter()
+                .any(|existing| record_values_match(&existing.value, value))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::app_config_builder::AppConfigBuilder;
+    use crate::config::provider_config::ProviderType;
+    use crate::config::verification_config::VerificationFailureMode;
+    use crate::dns_verifier::tests::stub_verifier;
+    use crate::ip_fetcher::tests::MockIpFetcher;
+    use crate::types::{test_record_set, DomainRecordValue};
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    const TWO_RECORDS_CONFIG: &str = r#"
+[[providers]]
+provider = "hetzner"
+token = "123"
+
+[[domains]]
+name = "site.com"
+
+[[domains.records]]
+type = "A"
+name = "home"
+
+[[domains.records]]
+type = "A"
+name = "www"
+"#;
+
+    type Calls = Arc<Mutex<Vec<String>>>;
+    type BatchHandler = fn(&[RecordSetChange<'_>]) -> Result<Vec<BatchChangeOutcome>>;
+
+    /// How many zones are fetched at the same time, and the most so far.
+    #[derive(Default)]
+    struct InFlight {
+        current: AtomicUsize,
+        max: AtomicUsize,
+    }
+
+    /// Provider with an `A` record holding `1.2.3.4` for each of
+    /// `record_names` in every zone.
+    struct MockProvider {
+        name: &'static str,
+        record_names: Vec<&'static str>,
+        /// How long fetching the records of a zone takes.
+        delay: Duration,
+        /// Records whose update fails.
+        failing_records: Vec<&'static str>,
+        /// Handles batch updates, if the provider supports them.
+        batch: Option<BatchHandler>,
+        calls: Calls,
+        in_flight: Arc<InFlight>,
+    }
+
+    impl MockProvider {
+        fn new(name: &'static str, record_names: &[&'static str]) -> Self {
+            Self {
+                name,
+                record_names: record_names.to_vec(),
+                delay: Duration::ZERO,
+                failing_records: vec![],
+                batch: None,
+                calls: Arc::default(),
+                in_flight: Arc::default(),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl DomainRecordApi for MockProvider {
+        fn provider_name(&self) -> &'static str {
+            self.name
+        }
+
+        fn provider_type(&self) -> ProviderType {
+            ProviderType::Hetzner
+        }
+
+        async fn get_domain_records(&self, domain_name: &str) -> Result<DomainRecordsCommon> {
+            let in_flight = self.in_flight.current.fetch_add(1, Ordering::SeqCst) + 1;
+            self.in_flight.max.fetch_max(in_flight, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            self.in_flight.current.fetch_sub(1, Ordering::SeqCst);
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("get {domain_name} from {}", self.name));
+            Ok(DomainRecordsCommon {
+                records: self
+                    .record_names
+                    .iter()
+                    .map(|name| DomainRecordCommon {
+                        id: format!("{name}/A"),
+                        record_type: "A".to_owned(),
+                        name: (*name).to_owned(),
+                        values: vec![DomainRecordValue {
+                            id: format!("{name}/A"),
+                            value: "1.2.3.4".to_owned(),
+                        }],
+                    })
+                    .collect(),
+            })
+        }
+
+        async fn set_domain_record_values(
+            &self,
+            domain_record: &DomainRecordCommon,
+            _record_to_update: &DomainRecordToUpdate,
+            _new_values: &[String],
+        ) -> Result<()> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("set {}", domain_record.name));
+            if self.failing_records.contains(&domain_record.name.as_str()) {
+                bail!("Failed to update '{}'", domain_record.name);
+            }
+            Ok(())
+        }
+
+        fn supports_batch_updates(&self) -> bool {
+            self.batch.is_some()
+        }
+
+        async fn set_domain_record_values_batch(
+            &self,
+            _domain_name: &str,
+            changes: &[RecordSetChange<'_>],
+        ) -> Result<Vec<BatchChangeOutcome>> {
+            let names = changes
+                .iter()
+                .map(|change| change.domain_record.name.as_str())
+                .collect::<Vec<_>>();
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("batch {}", names.join(",")));
+            match self.batch {
+                Some(batch) => batch(changes),
+                None => bail!("Batch updates are not supported"),
+            }
+        }
+    }
+
+    type Attempts = Arc<Mutex<Vec<(String, bool, Vec<ProviderUpdateOutcome>)>>>;
+
+    /// Records the outcome of each record update attempt.
+    struct RecordingStatsHandler {
+        attempts: Attempts,
+    }
+
+    impl StatsHandler for RecordingStatsHandler {
+        fn init(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn handle_ip_fetch(
+            &mut self,
+            _source: &str,
+            _maybe_fetched_ips: Option<IpAddrV4AndV6>,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        fn handle_updater_attempt(
+            &mut self,
+            domain_record_name: &str,
+            _record_type: &str,
+            is_domain_record_update_successful: bool,
+            _ip_kind: Option<IpAddrKind>,
+            provider_outcomes: &[ProviderUpdateOutcome],
+        ) -> Result<()> {
+            self.attempts.lock().unwrap().push((
+                domain_record_name.to_owned(),
+                is_domain_record_update_successful,
+                provider_outcomes.to_vec(),
+            ));
+            Ok(())
+        }
+
+        fn handle_updater_run(&mut self, _is_successful: bool) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    /// An updater for the records of the config, using the given providers
+    /// instead of the configured ones, and recording its stats.
+    fn test_updater(
+        config: &str,
+        dns_providers: Vec<Box<dyn DomainRecordApi>>,
+    ) -> (Updater, Attempts, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let state_path = dir.path().join("dynamic_values.json");
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(
+            &config_path,
+            format!(
+                "state_path = {}\n{}",
+                toml::Value::String(state_path.display().to_string()),
+                config
+            ),
+        )
+        .unwrap();
+        let config = AppConfigBuilder::new(None, Some(config_path.display().to_string()))
+            .unwrap()
+            .build()
+            .unwrap();
+        let mut updater = Updater::new(
+            GlobalState::from_config(config),
+            dns_providers,
+            Notifiers::default(),
+            AppTerminationHandler::default(),
+        );
+        let attempts = Attempts::default();
+        updater.stats_handler = Box::new(RecordingStatsHandler {
+            attempts: attempts.clone(),
+        });
+        (updater, attempts, dir)
+    }
+
+    /// Runs an update attempt of all configured records, with the public IP
+    /// `85.212.89.12`.
+    fn run_test_update(updater: &mut Updater) -> UpdateReport {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _runtime_guard = runtime.enter();
+        let records_to_update = Updater::build_records_to_update(&updater.global_state.config);
+        updater
+            .run_update(
+                &MockIpFetcher::default(),
+                &records_to_update,
+                &UpdateScope::default(),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_zones_are_updated_concurrently() {
+        let config = r#"
+update_concurrency = 3
+
+[[providers]]
+provider = "hetzner"
+token = "123"
+
+[[domains]]
+name = "a.com"
+
+[[domains.records]]
+type = "A"
+name = "home"
+
+[[domains]]
+name = "b.com"
+
+[[domains.records]]
+type = "A"
+name = "home"
+"#;
+        let calls = Calls::default();
+        let in_flight = Arc::<InFlight>::default();
+        let providers = [
+            ("Slow", Duration::from_millis(300)),
+            ("Fast", Duration::ZERO),
+        ]
+        .into_iter()
+        .map(|(name, delay)| -> Box<dyn DomainRecordApi> {
+            Box::new(MockProvider {
+                delay,
+                calls: calls.clone(),
+                in_flight: in_flight.clone(),
+                ..MockProvider::new(name, &["home"])
+            })
+        })
+        .collect();
+        let (mut updater, attempts, _dir) = test_updater(config, providers);
+
+        let report = run_test_update(&mut updater);
+
+        // Both zones of the fast provider are done before the slow ones.
+        assert_eq!(
+            calls.lock().unwrap().get(..4).unwrap(),
+            [
+                "get a.com from Fast",
+                "set home",
+                "get b.com from Fast",
+                "set home"
+            ]
+        );
+        // Only three of the four zones are updated at the same time.
+        assert_eq!(in_flight.max.load(Ordering::SeqCst), 3);
+
+        // Yet the records and providers are reported in the configured order.
+        assert!(report.success);
+        let reported = report
+            .records
+            .iter()
+            .map(|record| {
+                (
+                    record.name.as_str(),
+                    record
+                        .providers
+                        .iter()
+                        .map(|provider| provider.provider.as_str())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            reported,
+            [
+                ("home.a.com", vec!["Slow", "Fast"]),
+                ("home.b.com", vec!["Slow", "Fast"])
+            ]
+        );
+        let recorded = attempts
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, _, outcomes)| {
+                (
+                    name.clone(),
+                    outcomes
+                        .iter()
+                        .map(|outcome| outcome.provider_name)
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            recorded,
+            [
+                ("home.a.com".to_owned(), vec!["Slow", "Fast"]),
+                ("home.b.com".to_owned(), vec!["Slow", "Fast"])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failed_attempts_are_counted_per_record() {
+        let config = r#"
+[[providers]]
+provider = "hetzner"
+token = "123"
+
+[[domains]]
+name = "site.com"
+
+[[domains.records]]
+type = "A"
+name = "home"
+
+[[domains.records]]
+type = "A"
+name = "www"
+"#;
+        let provider = MockProvider {
+            failing_records: vec!["home"],
+            ..MockProvider::new("Mock", &["home", "www"])
+        };
+        let (mut updater, _attempts, _dir) = test_updater(config, vec![Box::new(provider)]);
+        let records_to_update = Updater::build_records_to_update(&updater.global_state.config);
+        let failed_attempts = |updater: &Updater, name: &str| {
+            updater
+                .failed_attempts
+                .get(&(name.to_owned(), "A".to_owned()))
+                .copied()
+        };
+
+        for _ in 0..3 {
+            let report = run_test_update(&mut updater);
+            updater.count_failed_attempts(&records_to_update, &report);
+        }
+        assert_eq!(failed_attempts(&updater, "home.site.com"), Some(3));
+        assert_eq!(failed_attempts(&updater, "www.site.com"), None);
+
+        // An attempt failing before any record was updated counts for all of them.
+        updater.count_failed_attempts(&records_to_update, &UpdateReport::default());
+        assert_eq!(failed_attempts(&updater, "home.site.com"), Some(4));
+        assert_eq!(failed_attempts(&updater, "www.site.com"), Some(1));
+    }
+
+    #[test]
+    fn test_batch_update_fallback() {
+        use crate::domain_record_api::ApiHttpError;
+
+        let run = |batch: BatchHandler| {
+            let provider = MockProvider {
+                batch: Some(batch),
+                ..MockProvider::new("Mock", &["home", "www"])
+            };
+            let calls = provider.calls.clone();
+            let (mut updater, _attempts, _dir) =
+                test_updater(TWO_RECORDS_CONFIG, vec![Box::new(provider)]);
+            let report = run_test_update(&mut updater);
+            let successes = report
+                .records
+                .iter()
+                .map(|record| record.success)
+                .collect::<Vec<_>>();
+            let calls = calls.lock().unwrap().clone();
+            (successes, calls)
+        };
+
+        // All changes are applied by the batch.
+        let (successes, calls) = run(|changes| {
+            Ok(changes
+                .iter()
+                .map(|_| BatchChangeOutcome::Applied)
+                .collect())
+        });
+        assert_eq!(successes, [true, true]);
+        assert_eq!(calls, ["get site.com from Mock", "batch home,www"]);
+
+        // A batch that failed as a whole is replaced by single updates.
+        let (successes, calls) = run(|_| {
+            Err(
+                ApiHttpError::new(reqwest::StatusCode::BAD_REQUEST, "Invalid batch".to_owned())
+                    .into(),
+            )
+        });
+        assert_eq!(successes, [true, true]);
+        assert_eq!(
+            calls,
+            [
+                "get site.com from Mock",
+                "batch home,www",
+                "set home",
+                "set www"
+            ]
         );
-        true
+
+        // Only the changes that the batch didn't apply are made again.
+        let (successes, calls) = run(|_| {
+            Ok(vec![
+                BatchChangeOutcome::Failed(eyre!("Invalid value")),
+                BatchChangeOutcome::NotApplied,
+            ])
+        });
+        assert_eq!(successes, [false, true]);
+        assert_eq!(
+            calls,
+            ["get site.com from Mock", "batch home,www", "set www"]
+        );
+
+        // A batch that may have been applied isn't followed by single
+        // updates planned against the records fetched before it.
+        let (successes, calls) = run(|_| {
+            Err(
+                ApiHttpError::new(reqwest::StatusCode::GATEWAY_TIMEOUT, "Timed out".to_owned())
+                    .into(),
+            )
+        });
+        assert_eq!(successes, [false, false]);
+        assert_eq!(calls, ["get site.com from Mock", "batch home,www"]);
+
+        // Neither is a batch whose outcomes don't match its changes.
+        let (successes, calls) = run(|_| Ok(vec![BatchChangeOutcome::Applied]));
+        assert_eq!(successes, [false, false]);
+        assert_eq!(calls, ["get site.com from Mock", "batch home,www"]);
+    }
+
+    #[test]
+    fn test_verification_outcomes() {
+        let provider = || -> Vec<Box<dyn DomainRecordApi>> {
+            vec![Box::new(MockProvider::new("Mock", &["home", "www"]))]
+        };
+
+        // Served by all nameservers, the propagation time is recorded.
+        let (mut updater, attempts, _dir) = test_updater(TWO_RECORDS_CONFIG, provider());
+        updater.dns_verifier = Some(stub_verifier(
+            VerificationFailureMode::Fail,
+            Some(vec![0, 1]),
+        ));
+        assert!(run_test_update(&mut updater).success);
+        assert!(attempts
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|(_, _, outcomes)| outcomes)
+            .all(|outcome| outcome.success && outcome.propagation.is_some()));
+
+        // Not served in time, which is only logged by default.
+        let (mut updater, attempts, _dir) = test_updater(TWO_RECORDS_CONFIG, provider());
+        updater.dns_verifier = Some(stub_verifier(
+            VerificationFailureMode::Warn,
+            Some(vec![u32::MAX]),
+        ));
+        assert!(run_test_update(&mut updater).success);
+        assert!(attempts
+            .lock()
+            .unwrap()
+            .iter()
+            .all(|(_, success, outcomes)| *success
+                && outcomes
+                    .iter()
+                    .all(|outcome| outcome.success && outcome.propagation.is_none())));
+
+        // Or fails the update.
+        let (mut updater, attempts, _dir) = test_updater(TWO_RECORDS_CONFIG, provider());
+        updater.dns_verifier = Some(stub_verifier(
+            VerificationFailureMode::Fail,
+            Some(vec![u32::MAX]),
+        ));
+        let report = run_test_update(&mut updater);
+        assert!(!report.success);
+        assert!(report
+            .records
+            .iter()
+            .all(|record| !record.success && record.providers.iter().all(|p| !p.success)));
+        assert!(attempts
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|(_, _, outcomes)| outcomes)
+            .all(|outcome| !outcome.success
+                && outcome
+                    .error_message
+                    .as_deref()
+                    .is_some_and(|error| error.contains("Failed to verify update"))));
+
+        // Nameservers that can't be found don't fail the update, regardless
+        // of the failure mode.
+        let (mut updater, _attempts, _dir) = test_updater(TWO_RECORDS_CONFIG, provider());
+        updater.dns_verifier = Some(stub_verifier(VerificationFailureMode::Fail, None));
+        assert!(run_test_update(&mut updater).success);
+    }
+
+    #[test]
+    fn test_desired_record_values() {
+        let curr_ip: IpAddr = "85.212.89.12".parse().unwrap();
+        let domain_record = test_record_set(&[("home/A", "1.2.3.4"), ("home/A", "5.6.7.8")]);
+
+        // Replacing drops all other values, except the static ones.
+        let record = DomainRecordToUpdate::new("site.com", "home", "A", None);
+        let new_values = desired_record_values(&record, &domain_record, &curr_ip, None);
+        assert_eq!(new_values, ["85.212.89.12"]);
+        assert!(record_values_differ(&domain_record, &new_values));
+
+        let record = record.with_values(RecordValuesMode::Replace, vec!["5.6.7.8".to_owned()]);
+        let new_values = desired_record_values(&record, &domain_record, &curr_ip, None);
+        assert_eq!(new_values, ["5.6.7.8", "85.212.89.12"]);
+
+        // Merging only swaps the previously set public IP.
+        let record = record.with_values(RecordValuesMode::Merge, vec![]);
+        let new_values = desired_record_values(&record, &domain_record, &curr_ip, Some("1.2.3.4"));
+        assert_eq!(new_values, ["5.6.7.8", "85.212.89.12"]);
+        let new_values = desired_record_values(&record, &domain_record, &curr_ip, None);
+        assert_eq!(new_values, ["1.2.3.4", "5.6.7.8", "85.212.89.12"]);
+
+        // Static values are kept even if they were the previous public IP.
+        let record = record.with_values(RecordValuesMode::Merge, vec!["1.2.3.4".to_owned()]);
+        let new_values = desired_record_values(&record, &domain_record, &curr_ip, Some("1.2.3.4"));
+        assert_eq!(new_values, ["1.2.3.4", "5.6.7.8", "85.212.89.12"]);
+
+        // Nothing to do when the values only differ in order.
+        let domain_record = test_record_set(&[("home/A", "85.212.89.12"), ("home/A", "5.6.7.8")]);
+        assert!(!record_values_differ(
+            &domain_record,
+            &["5.6.7.8".to_owned(), "85.212.89.12".to_owned()]
+        ));
     }
 }
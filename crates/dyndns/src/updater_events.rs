@@ -0,0 +1,146 @@
+use chrono::{DateTime, Utc};
+#[cfg(feature = "web")]
+use schemars::JsonSchema;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::types::IpAddrKind;
+
+/// How many events a slow subscriber can lag behind before it starts
+/// missing events.
+const EVENTS_CHANNEL_CAPACITY: usize = 256;
+
+#[cfg_attr(feature = "web", derive(JsonSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl From<IpAddrKind> for IpFamily {
+    fn from(ip_kind: IpAddrKind) -> Self {
+        match ip_kind {
+            IpAddrKind::V4 => IpFamily::Ipv4,
+            IpAddrKind::V6 => IpFamily::Ipv6,
+        }
+    }
+}
+
+#[cfg_attr(feature = "web", derive(JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UpdaterEvent {
+    IpFetched {
+        ipv4: Option<String>,
+        ipv6: Option<String>,
+    },
+    IpFetchFailed {
+        error_message: String,
+    },
+    IpChanged {
+        family: IpFamily,
+        old_ip: Option<String>,
+        new_ip: String,
+    },
+    RecordUpdateStarted {
+        name: String,
+        record_type: String,
+        provider: String,
+    },
+    RecordUpdateSucceeded {
+        name: String,
+        record_type: String,
+        provider: String,
+        value: String,
+    },
+    RecordUpdateFailed {
+        name: String,
+        record_type: String,
+        provider: String,
+        error_message: String,
+    },
+    UpdaterSleeping {
+        until: DateTime<Utc>,
+    },
+}
+
+impl UpdaterEvent {
+    /// Name of the event, same as its serialized `type` tag.
+    pub fn name(&self) -> &'static str {
+        match self {
+            UpdaterEvent::IpFetched { .. } => "ip_fetched",
+            UpdaterEvent::IpFetchFailed { .. } => "ip_fetch_failed",
+            UpdaterEvent::IpChanged { .. } => "ip_changed",
+            UpdaterEvent::RecordUpdateStarted { .. } => "record_update_started",
+            UpdaterEvent::RecordUpdateSucceeded { .. } => "record_update_succeeded",
+            UpdaterEvent::RecordUpdateFailed { .. } => "record_update_failed",
+            UpdaterEvent::UpdaterSleeping { .. } => "updater_sleeping",
+        }
+    }
+}
+
+/// Broadcasts updater activity, e.g. to the web server's event stream.
+pub struct UpdaterEvents {
+    sender: broadcast::Sender<UpdaterEvent>,
+}
+
+impl Default for UpdaterEvents {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(EVENTS_CHANNEL_CAPACITY);
+        Self { sender }
+    }
+}
+
+impl UpdaterEvents {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn publish(&self, event: UpdaterEvent) {
+        // Fails only when nobody is subscribed, which is fine.
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<UpdaterEvent> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_to_subscribers() {
+        let events = UpdaterEvents::new();
+        // Publishing without subscribers is a no-op.
+        events.publish(UpdaterEvent::IpFetchFailed {
+            error_message: "timeout".to_owned(),
+        });
+
+        let mut receiver = events.subscribe();
+        let event = UpdaterEvent::IpChanged {
+            family: IpFamily::Ipv4,
+            old_ip: None,
+            new_ip: "1.2.3.4".to_owned(),
+        };
+        events.publish(event.clone());
+        assert_eq!(
+            receiver.try_recv().expect("event should be received"),
+            event
+        );
+    }
+
+    #[test]
+    fn test_event_name_matches_type_tag() {
+        let event = UpdaterEvent::RecordUpdateSucceeded {
+            name: "home.site.com".to_owned(),
+            record_type: "A".to_owned(),
+            provider: "DigitalOcean".to_owned(),
+            value: "1.2.3.4".to_owned(),
+        };
+        let json = serde_json::to_value(&event).expect("event should serialize");
+        assert_eq!(json.get("type"), Some(&event.name().into()));
+    }
+}
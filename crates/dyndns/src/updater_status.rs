@@ -0,0 +1,271 @@
+use chrono::NaiveDateTime;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::Mutex;
+
+use crate::types::{IpAddrV4AndV6, ProviderUpdateOutcome};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdaterThreadState {
+    Starting,
+    Running,
+    Stopped,
+}
+
+// The updater is degraded when recent update attempts failed, and unhealthy
+// when it stopped or when at least `failure_threshold` consecutive attempts
+// failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthState {
+    Healthy,
+    Degraded,
+    Unhealthy,
+}
+
+impl HealthState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Healthy => "healthy",
+            Self::Degraded => "degraded",
+            Self::Unhealthy => "unhealthy",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ProviderRecordStatus {
+    pub provider: String,
+    pub value: Option<String>,
+    pub last_update_successful: bool,
+    pub last_error_message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct RecordStatus {
+    pub name: String,
+    pub record_type: String,
+    pub last_attempt_date: NaiveDateTime,
+    pub last_attempt_successful: bool,
+    pub providers: Vec<ProviderRecordStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct StatusResponse {
+    pub health: HealthState,
+    pub updater_state: UpdaterThreadState,
+    pub last_attempt_date: Option<NaiveDateTime>,
+    pub last_attempt_successful: Option<bool>,
+    pub consecutive_failures: u32,
+    pub failure_threshold: u32,
+    pub ipv4: Option<Ipv4Addr>,
+    pub ipv6: Option<Ipv6Addr>,
+    pub records: Vec<RecordStatus>,
+}
+
+struct UpdaterStatusInner {
+    updater_state: UpdaterThreadState,
+    last_attempt_date: Option<NaiveDateTime>,
+    last_attempt_successful: Option<bool>,
+    consecutive_failures: u32,
+    current_ips: IpAddrV4AndV6,
+    /// Keyed by record name and type, to keep a stable order.
+    records: BTreeMap<(String, String), RecordStatus>,
+}
+
+/// In-memory view of what the updater is doing, served by the status and
+/// health check endpoints.
+pub struct UpdaterStatus {
+    failure_threshold: u32,
+    inner: Mutex<UpdaterStatusInner>,
+}
+
+impl UpdaterStatus {
+    pub fn new(failure_threshold: u32) -> Self {
+        Self {
+            failure_threshold,
+            inner: Mutex::new(UpdaterStatusInner {
+                updater_state: UpdaterThreadState::Starting,
+                last_attempt_date: None,
+                last_attempt_successful: None,
+                consecutive_failures: 0,
+                current_ips: IpAddrV4AndV6::default(),
+                records: BTreeMap::new(),
+            }),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, UpdaterStatusInner> {
+        self.inner.lock().expect("updater status mutex poisoned")
+    }
+
+    pub fn set_updater_state(&self, updater_state: UpdaterThreadState) {
+        self.lock().updater_state = updater_state;
+    }
+
+    pub fn record_ip_fetch(&self, maybe_fetched_ips: Option<&IpAddrV4AndV6>) {
+        let mut inner = self.lock();
+        inner.last_attempt_date = Some(chrono::offset::Utc::now().naive_utc());
+        if let Some(fetched_ips) = maybe_fetched_ips {
+            inner.current_ips = fetched_ips.clone();
+        }
+    }
+
+    pub fn record_updater_attempt(
+        &self,
+        domain_record_name: &str,
+        record_type: &str,
+        is_domain_record_update_successful: bool,
+        provider_outcomes: &[ProviderUpdateOutcome],
+    ) {
+        let now = chrono::offset::Utc::now().naive_utc();
+        let mut inner = self.lock();
+        let record = inner
+            .records
+            .entry((domain_record_name.to_owned(), record_type.to_owned()))
+            .or_insert_with(|| RecordStatus {
+                name: domain_record_name.to_owned(),
+                record_type: record_type.to_owned(),
+                last_attempt_date: now,
+                last_attempt_successful: false,
+                providers: vec![],
+            });
+        record.last_attempt_date = now;
+        record.last_attempt_successful = is_domain_record_update_successful;
+
+        for outcome in provider_outcomes {
+            if !record
+                .providers
+                .iter()
+                .any(|provider| provider.provider == outcome.provider_name)
+            {
+                record.providers.push(ProviderRecordStatus {
+                    provider: outcome.provider_name.to_owned(),
+                    value: None,
+                    last_update_successful: false,
+                    last_error_message: None,
+                });
+            }
+            let provider = record
+                .providers
+                .iter_mut()
+                .find(|provider| provider.provider == outcome.provider_name)
+                .expect("provider status should exist");
+            // Keep the last known value if the provider couldn't be queried.
+            if outcome.record_value.is_some() {
+                provider.value.clone_from(&outcome.record_value);
+            }
+            provider.last_update_successful = outcome.success;
+            provider
+                .last_error_message
+                .clone_from(&outcome.error_message);
+        }
+    }
+
+    pub fn record_updater_run(&self, is_successful: bool) {
+        let mut inner = self.lock();
+        inner.last_attempt_successful = Some(is_successful);
+        if is_successful {
+            inner.consecutive_failures = 0;
+        } else {
+            inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+        }
+    }
+
+    pub fn health(&self) -> HealthState {
+        let inner = self.lock();
+        self.health_of(&inner)
+    }
+
+    fn health_of(&self, inner: &UpdaterStatusInner) -> HealthState {
+        if inner.updater_state == UpdaterThreadState::Stopped
+            || inner.consecutive_failures >= self.failure_threshold
+        {
+            HealthState::Unhealthy
+        } else if inner.consecutive_failures > 0 {
+            HealthState::Degraded
+        } else {
+            HealthState::Healthy
+        }
+    }
+
+    pub fn snapshot(&self) -> StatusResponse {
+        let inner = self.lock();
+        StatusResponse {
+            health: self.health_of(&inner),
+            updater_state: inner.updater_state,
+            last_attempt_date: inner.last_attempt_date,
+            last_attempt_successful: inner.last_attempt_successful,
+            consecutive_failures: inner.consecutive_failures,
+            failure_threshold: self.failure_threshold,
+            ipv4: inner.current_ips.ipv4,
+            ipv6: inner.current_ips.ipv6,
+            records: inner.records.values().cloned().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn outcome(success: bool, record_value: Option<&str>) -> ProviderUpdateOutcome {
+        ProviderUpdateOutcome {
+            provider_name: "DigitalOcean",
+            success,
+            ip_kind: None,
+            error_message: (!success).then(|| "boom".to_owned()),
+            http_status: None,
+            record_value: record_value.map(str::to_owned),
+            latency: Duration::from_millis(10),
+            propagation: None,
+        }
+    }
+
+    #[test]
+    fn test_health_transitions() {
+        let status = UpdaterStatus::new(2);
+        assert_eq!(status.health(), HealthState::Healthy);
+
+        status.record_updater_run(false);
+        assert_eq!(status.health(), HealthState::Degraded);
+        status.record_updater_run(false);
+        assert_eq!(status.health(), HealthState::Unhealthy);
+        status.record_updater_run(true);
+        assert_eq!(status.health(), HealthState::Healthy);
+
+        status.set_updater_state(UpdaterThreadState::Stopped);
+        assert_eq!(status.health(), HealthState::Unhealthy);
+    }
+
+    #[test]
+    fn test_last_known_provider_value_is_kept() {
+        let status = UpdaterStatus::new(3);
+        status.record_updater_attempt(
+            "home.site.com",
+            "A",
+            true,
+            &[outcome(true, Some("1.2.3.4"))],
+        );
+        status.record_updater_attempt("home.site.com", "A", false, &[outcome(false, None)]);
+
+        let snapshot = status.snapshot();
+        assert_eq!(snapshot.records.len(), 1);
+        let record = snapshot
+            .records
+            .first()
+            .expect("record status should exist");
+        assert!(!record.last_attempt_successful);
+        let provider = record
+            .providers
+            .first()
+            .expect("provider status should exist");
+        assert_eq!(provider.value.as_deref(), Some("1.2.3.4"));
+        assert!(!provider.last_update_successful);
+        assert_eq!(provider.last_error_message.as_deref(), Some("boom"));
+    }
+}
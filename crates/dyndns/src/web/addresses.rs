@@ -68,10 +68,10 @@ pub fn socket_acceptor_from_socket_addreses(addrs: &[SocketAddr]) -> Result<Mult
     MultipleAddrsIncoming { listeners: addrs }.into_ok()
 }
 
-pub fn print_listener_addresses(addrs: &[SocketAddr]) {
+pub fn print_listener_addresses(addrs: &[SocketAddr], scheme: &str) {
     let addrs = addrs
         .iter()
-        .map(|addr| format!("    http://{addr}"))
+        .map(|addr| format!("    {scheme}://{addr}"))
         .join("\n");
     info!("Web server listening on the following addresses:\n{addrs}");
 }
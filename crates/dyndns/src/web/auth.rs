@@ -0,0 +1,255 @@
+use aide::transform::TransformOperation;
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use http::{HeaderValue, Method, StatusCode};
+use secrecy::ExposeSecret;
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+use tokio::sync::Semaphore;
+use tracing::{debug, error};
+
+use crate::config::web_auth_config::{PasswordHashKind, WebAuthConfig, WebAuthScope};
+use crate::web::server::WebServerState;
+
+/// Name of the bearer token security scheme in the `OpenAPI` document.
+pub const BEARER_AUTH_SCHEME: &str = "bearerAuth";
+/// Name of the HTTP Basic security scheme in the `OpenAPI` document.
+pub const BASIC_AUTH_SCHEME: &str = "basicAuth";
+
+const BASIC_AUTH_CHALLENGE: &str = r#"Basic realm="ddns", charset="UTF-8""#;
+
+pub struct WebAuth {
+    config: WebAuthConfig,
+    /// Limits how many requests are authenticated at the same time, so that
+    /// a flood of requests can't tie up every blocking thread with password
+    /// hash verifications.
+    verification_permits: Arc<Semaphore>,
+}
+
+impl WebAuth {
+    pub fn new(config: WebAuthConfig) -> Self {
+        let max_verifications = std::thread::available_parallelism().map_or(4, usize::from);
+        Self {
+            config,
+            verification_permits: Arc::new(Semaphore::new(max_verifications)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.is_enabled()
+    }
+
+    /// Returns the scope granted by the credentials in the `Authorization`
+    /// header, or `None` if they are missing or invalid.
+    pub fn authenticate(&self, authorization: Option<&HeaderValue>) -> Option<WebAuthScope> {
+        let authorization = authorization?.to_str().ok()?;
+        let (auth_scheme, credentials) = authorization.split_once(' ')?;
+        let credentials = credentials.trim();
+        if auth_scheme.eq_ignore_ascii_case("bearer") {
+            self.authenticate_token(credentials)
+        } else if auth_scheme.eq_ignore_ascii_case("basic") {
+            self.authenticate_basic(credentials)
+        } else {
+            None
+        }
+    }
+
+    fn authenticate_token(&self, token: &str) -> Option<WebAuthScope> {
+        // Compare against all tokens, to not leak which one matched via timing.
+        let mut scope = None;
+        for configured_token in &self.config.tokens {
+            let matches: bool = configured_token
+                .token
+                .expose_secret()
+                .as_bytes()
+                .ct_eq(token.as_bytes())
+                .into();
+            if matches {
+                debug!(
+                    "Authenticated web request with token '{}'",
+                    configured_token.name
+                );
+                scope = Some(configured_token.scope);
+            }
+        }
+        scope
+    }
+
+    fn authenticate_basic(&self, credentials: &str) -> Option<WebAuthScope> {
+        let decoded = BASE64.decode(credentials).ok()?;
+        let decoded = String::from_utf8(decoded).ok()?;
+        let (username, password) = decoded.split_once(':')?;
+        let Some(user) = self
+            .config
+            .users
+            .iter()
+            .find(|user| user.username == username)
+        else {
+            // Take as long as for a known user, to not leak which usernames
+            // exist via timing. The hash of another user serves as the dummy
+            // hash, so that the cost of its verification matches.
+            if let Some(dummy_user) = self.config.users.first() {
+                verify_password(password, &dummy_user.password_hash);
+            }
+            return None;
+        };
+        verify_password(password, &user.password_hash).then_some(user.scope)
+    }
+}
+
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHashKind::detect(password_hash) {
+        Some(PasswordHashKind::Argon2) => PasswordHash::new(password_hash)
+            .and_then(|parsed_hash| {
+                Argon2::default().verify_password(password.as_bytes(), &parsed_hash)
+            })
+            .is_ok(),
+        Some(PasswordHashKind::Bcrypt) => bcrypt::verify(password, password_hash).unwrap_or(false),
+        None => false,
+    }
+}
+
+/// Safe methods only need the `read` scope, everything else needs `write`.
+fn required_scope_for_method(method: &Method) -> WebAuthScope {
+    if method == Method::GET || method == Method::HEAD {
+        WebAuthScope::Read
+    } else {
+        WebAuthScope::Write
+    }
+}
+
+/// Rejects requests without valid credentials, when authentication is
+/// configured.
+pub async fn require_auth(
+    State(state): State<WebServerState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // CORS preflight requests never carry credentials.
+    if !state.auth.is_enabled() || request.method() == Method::OPTIONS {
+        return next.run(request).await;
+    }
+
+    let required_scope = required_scope_for_method(request.method());
+    let auth = state.auth.clone();
+    let authorization = request.headers().get(AUTHORIZATION).cloned();
+    let _permit = auth
+        .verification_permits
+        .clone()
+        .acquire_owned()
+        .await
+        .expect("verification semaphore should never be closed");
+    // Password hash verification is slow on purpose, keep it off the runtime thread.
+    let granted_scope =
+        tokio::task::spawn_blocking(move || auth.authenticate(authorization.as_ref())).await;
+
+    match granted_scope {
+        Ok(Some(granted_scope)) if granted_scope.allows(required_scope) => next.run(request).await,
+        Ok(Some(granted_scope)) => (
+            StatusCode::FORBIDDEN,
+            format!(
+                "the '{}' scope is required, but only '{}' was granted",
+                required_scope.as_str(),
+                granted_scope.as_str()
+            ),
+        )
+            .into_response(),
+        Ok(None) => (
+            StatusCode::UNAUTHORIZED,
+            [(WWW_AUTHENTICATE, BASIC_AUTH_CHALLENGE)],
+            "missing or invalid credentials",
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Failed to verify web request credentials: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Documents the scope an operation requires when authentication is enabled.
+pub fn requires_scope(op: TransformOperation<'_>, scope: WebAuthScope) -> TransformOperation<'_> {
+    op.security_requirement_scopes(BEARER_AUTH_SCHEME, [scope.as_str()])
+        .security_requirement_scopes(BASIC_AUTH_SCHEME, [scope.as_str()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::web_auth_config::{WebAuthToken, WebAuthUser};
+
+    fn web_auth() -> WebAuth {
+        let bcrypt_hash = bcrypt::hash("hunter2", 4).expect("bcrypt hashing should succeed");
+        WebAuth::new(WebAuthConfig {
+            tokens: vec![WebAuthToken {
+                name: "hotplug".to_owned(),
+                token: "s3cr3t".into(),
+                scope: WebAuthScope::Write,
+            }],
+            users: vec![WebAuthUser {
+                username: "admin".to_owned(),
+                password_hash: bcrypt_hash,
+                scope: WebAuthScope::Read,
+            }],
+        })
+    }
+
+    fn header(value: &str) -> HeaderValue {
+        HeaderValue::from_str(value).expect("valid header value")
+    }
+
+    #[test]
+    fn test_authenticate_bearer_token() {
+        let auth = web_auth();
+        assert_eq!(
+            auth.authenticate(Some(&header("Bearer s3cr3t"))),
+            Some(WebAuthScope::Write)
+        );
+        assert_eq!(auth.authenticate(Some(&header("Bearer wrong"))), None);
+        assert_eq!(auth.authenticate(None), None);
+    }
+
+    #[test]
+    fn test_authenticate_basic_user() {
+        let auth = web_auth();
+        let valid = format!("Basic {}", BASE64.encode("admin:hunter2"));
+        let invalid = format!("Basic {}", BASE64.encode("admin:hunter3"));
+        let unknown = format!("Basic {}", BASE64.encode("root:hunter2"));
+        assert_eq!(
+            auth.authenticate(Some(&header(&valid))),
+            Some(WebAuthScope::Read)
+        );
+        assert_eq!(auth.authenticate(Some(&header(&invalid))), None);
+        assert_eq!(auth.authenticate(Some(&header(&unknown))), None);
+    }
+
+    #[test]
+    fn test_password_hash_kinds() {
+        assert_eq!(
+            PasswordHashKind::detect("$2b$04$abcdefghijklmnopqrstuu"),
+            Some(PasswordHashKind::Bcrypt)
+        );
+        assert_eq!(
+            PasswordHashKind::detect("$argon2id$v=19$m=19456,t=2,p=1$c2FsdA$aGFzaA"),
+            Some(PasswordHashKind::Argon2)
+        );
+        assert_eq!(PasswordHashKind::detect("plaintext"), None);
+        assert!(!verify_password("hunter2", "plaintext"));
+    }
+
+    #[test]
+    fn test_required_scope_for_method() {
+        assert_eq!(required_scope_for_method(&Method::GET), WebAuthScope::Read);
+        assert_eq!(
+            required_scope_for_method(&Method::POST),
+            WebAuthScope::Write
+        );
+        assert!(WebAuthScope::Write.allows(WebAuthScope::Read));
+        assert!(!WebAuthScope::Read.allows(WebAuthScope::Write));
+    }
+}
@@ -1,12 +1,13 @@
 use std::sync::Arc;
 
+use super::auth::{BASIC_AUTH_SCHEME, BEARER_AUTH_SCHEME};
 use super::{errors::WebApiError, server::WebServerState};
 use aide::{
     axum::{
         routing::{get, get_with},
         ApiRouter, IntoApiResponse,
     },
-    openapi::OpenApi,
+    openapi::{OpenApi, SecurityScheme},
     redoc::Redoc,
 };
 use aide::{operation::OperationIo, transform::TransformOpenApi};
@@ -50,6 +51,28 @@ pub fn api_docs(api: TransformOpenApi<'_>) -> TransformOpenApi<'_> {
         .default_response_with::<AxumJson<WebApiError>, _>(|res| {
             res.example(WebApiError::generic())
         })
+        .security_scheme(
+            BEARER_AUTH_SCHEME,
+            SecurityScheme::Http {
+                scheme: "bearer".to_owned(),
+                bearer_format: None,
+                description: Some(
+                    "A static token from the `web_auth.tokens` config. Only enforced when authentication is configured.".to_owned(),
+                ),
+                extensions: Default::default(),
+            },
+        )
+        .security_scheme(
+            BASIC_AUTH_SCHEME,
+            SecurityScheme::Http {
+                scheme: "basic".to_owned(),
+                bearer_format: None,
+                description: Some(
+                    "A user from the `web_auth.users` config. Only enforced when authentication is configured.".to_owned(),
+                ),
+                extensions: Default::default(),
+            },
+        )
 }
 
 #[derive(FromRequest, OperationIo)]
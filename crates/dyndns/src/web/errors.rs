@@ -61,6 +61,12 @@ impl From<Report> for WebError {
 #[derive(Debug, OperationIo, Serialize)]
 pub enum WebApiError {
     GenericError(#[serde_as(as = "DisplayFromStr")] Report),
+    /// The updater isn't running, or stopped while handling the request.
+    Unavailable(#[serde_as(as = "DisplayFromStr")] Report),
+    /// The updater didn't answer in time.
+    Timeout(#[serde_as(as = "DisplayFromStr")] Report),
+    /// The requested domain record or provider isn't configured.
+    NotFound(#[serde_as(as = "DisplayFromStr")] Report),
 }
 
 impl JsonSchema for WebApiError {
@@ -77,6 +83,24 @@ impl JsonSchema for WebApiError {
                     "GenericError": gen.subschema_for::<String>()
                 },
                 "required": ["GenericError"]
+            }, {
+                "type": "object",
+                "properties": {
+                    "Unavailable": gen.subschema_for::<String>()
+                },
+                "required": ["Unavailable"]
+            }, {
+                "type": "object",
+                "properties": {
+                    "Timeout": gen.subschema_for::<String>()
+                },
+                "required": ["Timeout"]
+            }, {
+                "type": "object",
+                "properties": {
+                    "NotFound": gen.subschema_for::<String>()
+                },
+                "required": ["NotFound"]
             }]
         })
     }
@@ -92,6 +116,9 @@ impl Display for WebApiError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::GenericError(e) => write!(f, "generic error: {e}"),
+            Self::Unavailable(e) => write!(f, "unavailable: {e}"),
+            Self::Timeout(e) => write!(f, "timeout: {e}"),
+            Self::NotFound(e) => write!(f, "not found: {e}"),
         }
     }
 }
@@ -99,6 +126,9 @@ impl IntoResponse for WebApiError {
     fn into_response(self) -> Response {
         let (status, body) = match self {
             Self::GenericError(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(self)),
+            Self::Unavailable(_) => (StatusCode::SERVICE_UNAVAILABLE, Json(self)),
+            Self::Timeout(_) => (StatusCode::GATEWAY_TIMEOUT, Json(self)),
+            Self::NotFound(_) => (StatusCode::NOT_FOUND, Json(self)),
         };
         (status, body).into_response()
     }
@@ -107,7 +137,10 @@ impl IntoResponse for WebApiError {
 impl StdError for WebApiError {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
         match self {
-            Self::GenericError(err) => Some(&**err),
+            Self::GenericError(err)
+            | Self::Unavailable(err)
+            | Self::Timeout(err)
+            | Self::NotFound(err) => Some(&**err),
         }
     }
 }
@@ -1,6 +1,8 @@
 pub mod addresses;
+pub mod auth;
 pub mod docs;
 pub mod errors;
 pub mod routes;
 pub mod server;
 pub mod static_server;
+pub mod tls;
@@ -1,11 +1,18 @@
+mod domain_ip_fetches;
 mod domain_record_ip_changes;
+mod events;
+mod metrics;
+mod status;
+mod update;
+mod updater_attempts;
 mod version;
 
 use std::sync::Arc;
 
-use aide::axum::routing::get_with;
+use aide::axum::routing::{get_with, post_with};
 use aide::axum::ApiRouter;
 use aide::openapi::OpenApi;
+use axum::middleware::from_fn_with_state;
 use axum::Extension;
 use axum::Router;
 #[cfg(debug_assertions)]
@@ -14,13 +21,25 @@ use tower::ServiceBuilder;
 use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
 
+use self::domain_ip_fetches::list_domain_ip_fetches_docs;
 use self::domain_record_ip_changes::list_domain_record_ip_changes_docs;
+use self::events::stream_updater_events_docs;
+use self::status::get_status_docs;
+use self::update::trigger_update_docs;
+use self::updater_attempts::list_updater_attempts_docs;
 use self::version::get_version_docs;
 
+use super::auth::require_auth;
 use super::docs::api_docs;
 use super::docs::docs_routes;
 use super::server::WebServerState;
+use crate::web::routes::domain_ip_fetches::list_domain_ip_fetches;
 use crate::web::routes::domain_record_ip_changes::list_domain_record_ip_changes;
+use crate::web::routes::events::stream_updater_events;
+use crate::web::routes::metrics::get_metrics;
+use crate::web::routes::status::{get_healthz, get_status};
+use crate::web::routes::update::trigger_update;
+use crate::web::routes::updater_attempts::list_updater_attempts;
 use crate::web::routes::version::get_version;
 use crate::web::static_server::serve_static_decisor;
 
@@ -51,6 +70,13 @@ pub fn get_final_router(state: WebServerState) -> Router {
     let (router, api) = get_pure_router_and_open_api();
 
     router
+        .layer(from_fn_with_state(state.clone(), require_auth))
+        // Liveness probes can't be expected to carry credentials.
+        .route("/healthz", axum::routing::get(get_healthz))
+        // The web UI assets are public, only the API they call needs credentials.
+        // Explicitly set fallback on outer router, to avoid
+        // https://github.com/tokio-rs/axum/discussions/2012
+        .fallback(serve_static_decisor)
         .layer(TraceLayer::new_for_http())
         .layer(cors_service)
         .layer(Extension(Arc::new(api)))
@@ -71,15 +97,18 @@ fn get_pure_router_and_open_api() -> (Router<WebServerState>, OpenApi) {
     let final_router = ApiRouter::new()
         .nest(WEB_API_PATH_URL_PART, api_router)
         .nest("/docs", docs_routes())
-        // Explicitly set fallback on outer router, to avoid
-        // https://github.com/tokio-rs/axum/discussions/2012
-        .fallback(serve_static_decisor)
+        // Scraped by Prometheus, so it's not part of the versioned API.
+        .route("/metrics", axum::routing::get(get_metrics))
         .finish_api_with(&mut api, api_docs);
     (final_router, api)
 }
 
 fn api_routes() -> ApiRouter<WebServerState> {
     ApiRouter::new()
+        .api_route(
+            "/domain_ip_fetches",
+            get_with(list_domain_ip_fetches, list_domain_ip_fetches_docs),
+        )
         .api_route(
             "/domain_record_ip_changes",
             get_with(
@@ -87,6 +116,16 @@ fn api_routes() -> ApiRouter<WebServerState> {
                 list_domain_record_ip_changes_docs,
             ),
         )
+        .api_route(
+            "/events",
+            get_with(stream_updater_events, stream_updater_events_docs),
+        )
+        .api_route("/status", get_with(get_status, get_status_docs))
+        .api_route("/update", post_with(trigger_update, trigger_update_docs))
+        .api_route(
+            "/updater_attempts",
+            get_with(list_updater_attempts, list_updater_attempts_docs),
+        )
         .api_route("/version", get_with(get_version, get_version_docs))
 }
 
@@ -0,0 +1,26 @@
+use aide::transform::TransformOperation;
+use axum::extract::{Query, State};
+use axum::Json;
+
+use crate::config::web_auth_config::WebAuthScope;
+use crate::db::crud::domain_ip_fetches::{get_domain_ip_fetches_page, DomainIpFetches};
+use crate::db::history::IpFetchHistoryQuery;
+use crate::web::auth::requires_scope;
+use crate::web::errors::WebApiResult;
+use crate::web::server::WebServerState;
+
+pub async fn list_domain_ip_fetches(
+    State(state): State<WebServerState>,
+    Query(query): Query<IpFetchHistoryQuery>,
+) -> WebApiResult<Json<DomainIpFetches>> {
+    let mut conn = state.db_conn()?.lock().expect("conn mutex poisoned");
+    let fetches = get_domain_ip_fetches_page(&mut conn, &query)?;
+    Ok(Json(fetches))
+}
+
+pub fn list_domain_ip_fetches_docs(op: TransformOperation<'_>) -> TransformOperation<'_> {
+    requires_scope(op, WebAuthScope::Read)
+        .description("List public ip fetches")
+        .summary("Returns a page of public ip fetch attempts, optionally filtered by date range. Pass the returned next_cursor as cursor to get the next page")
+        .tag("history")
+}
@@ -1,20 +1,28 @@
 use aide::transform::TransformOperation;
-use axum::{extract::State, Json};
+use axum::extract::{Query, State};
+use axum::Json;
 
+use crate::config::web_auth_config::WebAuthScope;
 use crate::db::crud::domain_record_ip_changes::{
-    get_domain_record_ip_changes, DomainRecordIpChanges,
+    get_domain_record_ip_changes_page, DomainRecordIpChanges,
 };
+use crate::db::history::RecordHistoryQuery;
+use crate::web::auth::requires_scope;
 use crate::web::errors::WebApiResult;
 use crate::web::server::WebServerState;
 
 pub async fn list_domain_record_ip_changes(
     State(state): State<WebServerState>,
+    Query(query): Query<RecordHistoryQuery>,
 ) -> WebApiResult<Json<DomainRecordIpChanges>> {
-    let mut conn = state.conn.lock().expect("conn mutex poisoned");
-    let ip_changes = get_domain_record_ip_changes(&mut conn)?;
+    let mut conn = state.db_conn()?.lock().expect("conn mutex poisoned");
+    let ip_changes = get_domain_record_ip_changes_page(&mut conn, &query)?;
     Ok(Json(ip_changes))
 }
 
 pub fn list_domain_record_ip_changes_docs(op: TransformOperation<'_>) -> TransformOperation<'_> {
-    op.description("List all recent domain record ip changes")
+    requires_scope(op, WebAuthScope::Read)
+        .description("List all recent domain record ip changes")
+        .summary("Returns a page of domain record ip changes, optionally filtered by record name, record type and date range. Pass the returned next_cursor as cursor to get the next page")
+        .tag("history")
 }
@@ -0,0 +1,111 @@
+use std::convert::Infallible;
+
+use aide::generate::GenContext;
+use aide::openapi::{MediaType, Operation, SchemaObject};
+use aide::transform::TransformOperation;
+use aide::OperationOutput;
+use axum::extract::State;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use futures_util::{stream, StreamExt};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, watch};
+use tracing::{error, warn};
+
+use crate::config::web_auth_config::WebAuthScope;
+use crate::updater_events::UpdaterEvent;
+use crate::web::auth::requires_scope;
+use crate::web::errors::WebApiResult;
+use crate::web::server::WebServerState;
+
+const EVENT_STREAM_CONTENT_TYPE: &str = "text/event-stream";
+
+/// Server-Sent Events stream of [`UpdaterEvent`]s, ending when the web server
+/// shuts down.
+pub struct UpdaterEventStream {
+    receiver: broadcast::Receiver<UpdaterEvent>,
+    shutdown_rx: watch::Receiver<()>,
+}
+
+impl IntoResponse for UpdaterEventStream {
+    fn into_response(self) -> Response {
+        let mut shutdown_rx = self.shutdown_rx;
+        let events = stream::unfold(self.receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => match Event::default().event(event.name()).json_data(&event) {
+                        Ok(sse_event) => return Some((Ok::<_, Infallible>(sse_event), receiver)),
+                        Err(e) => error!("Failed to serialize updater event: {}", e),
+                    },
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(
+                            "Event stream client is too slow, skipped {} events",
+                            skipped
+                        );
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+        .take_until(async move {
+            // Either a shutdown notification or the sender going away.
+            let _ = shutdown_rx.changed().await;
+        });
+
+        Sse::new(events)
+            .keep_alive(KeepAlive::default())
+            .into_response()
+    }
+}
+
+impl OperationOutput for UpdaterEventStream {
+    type Inner = UpdaterEvent;
+
+    fn operation_response(
+        ctx: &mut GenContext,
+        _operation: &mut Operation,
+    ) -> Option<aide::openapi::Response> {
+        let mut response = aide::openapi::Response {
+            description: "Each event has the `type` of the updater event as its name, and the JSON encoded updater event as its data".to_owned(),
+            ..Default::default()
+        };
+        response.content.insert(
+            EVENT_STREAM_CONTENT_TYPE.to_owned(),
+            MediaType {
+                schema: Some(SchemaObject {
+                    json_schema: ctx.schema.subschema_for::<UpdaterEvent>(),
+                    example: None,
+                    external_docs: None,
+                }),
+                ..Default::default()
+            },
+        );
+        Some(response)
+    }
+
+    fn inferred_responses(
+        ctx: &mut GenContext,
+        operation: &mut Operation,
+    ) -> Vec<(Option<u16>, aide::openapi::Response)> {
+        Self::operation_response(ctx, operation)
+            .into_iter()
+            .map(|response| (Some(200), response))
+            .collect()
+    }
+}
+
+pub async fn stream_updater_events(
+    State(state): State<WebServerState>,
+) -> WebApiResult<UpdaterEventStream> {
+    Ok(UpdaterEventStream {
+        receiver: state.events.subscribe(),
+        shutdown_rx: state.shutdown_rx,
+    })
+}
+
+pub fn stream_updater_events_docs(op: TransformOperation<'_>) -> TransformOperation<'_> {
+    requires_scope(op, WebAuthScope::Read)
+        .description("Stream updater events")
+        .summary("Pushes updater activity as Server-Sent Events as it happens: fetched and changed public IPs, per provider domain record updates, and when the next update is due")
+        .tag("events")
+}
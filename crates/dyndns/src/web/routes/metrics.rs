@@ -0,0 +1,13 @@
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use http::header::CONTENT_TYPE;
+
+use crate::metrics::METRICS_CONTENT_TYPE;
+use crate::web::errors::WebResult;
+use crate::web::server::WebServerState;
+
+/// Serves the in-memory metrics in the Prometheus / `OpenMetrics` text format.
+pub async fn get_metrics(State(state): State<WebServerState>) -> WebResult<Response> {
+    let body = state.metrics.encode()?;
+    Ok(([(CONTENT_TYPE, METRICS_CONTENT_TYPE)], body).into_response())
+}
@@ -0,0 +1,34 @@
+use aide::transform::TransformOperation;
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use http::StatusCode;
+
+use crate::config::web_auth_config::WebAuthScope;
+use crate::updater_status::{HealthState, StatusResponse};
+use crate::web::auth::requires_scope;
+use crate::web::errors::WebApiResult;
+use crate::web::server::WebServerState;
+
+pub async fn get_status(State(state): State<WebServerState>) -> WebApiResult<Json<StatusResponse>> {
+    Ok(Json(state.updater_status.snapshot()))
+}
+
+pub fn get_status_docs(op: TransformOperation<'_>) -> TransformOperation<'_> {
+    requires_scope(op, WebAuthScope::Read)
+        .description("Get the updater status")
+        .summary("Returns the health of the updater, the last update attempt, the current public IPs and the last known value of each managed domain record")
+        .tag("status")
+}
+
+/// Liveness / readiness probe for Docker and Kubernetes.
+///
+/// Responds with 503 when the updater is unhealthy, 200 otherwise.
+pub async fn get_healthz(State(state): State<WebServerState>) -> Response {
+    let health = state.updater_status.health();
+    let status_code = match health {
+        HealthState::Healthy | HealthState::Degraded => StatusCode::OK,
+        HealthState::Unhealthy => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (status_code, health.as_str()).into_response()
+}
@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use aide::transform::TransformOperation;
+use axum::extract::{Query, State};
+use axum::Json;
+use color_eyre::eyre::{eyre, WrapErr};
+use humantime::format_duration;
+
+use crate::config::web_auth_config::WebAuthScope;
+use crate::update_trigger::{UpdateReport, UpdateScope};
+use crate::web::auth::requires_scope;
+use crate::web::errors::{WebApiError, WebApiResult};
+use crate::web::server::WebServerState;
+
+/// How long to wait for the result of a triggered update. The updater still
+/// finishes the update after the request timed out.
+const UPDATE_RESPONSE_TIMEOUT: Duration = Duration::from_secs(120);
+
+pub async fn trigger_update(
+    State(state): State<WebServerState>,
+    Query(scope): Query<UpdateScope>,
+) -> WebApiResult<Json<UpdateReport>> {
+    state
+        .update_trigger
+        .validate_scope(&scope)
+        .map_err(WebApiError::NotFound)?;
+    let report_rx = state
+        .update_trigger
+        .trigger(scope)
+        .map_err(WebApiError::Unavailable)?;
+    let report = match tokio::time::timeout(UPDATE_RESPONSE_TIMEOUT, report_rx).await {
+        Ok(report) => report
+            .wrap_err("The updater stopped before running the requested update")
+            .map_err(WebApiError::Unavailable)?,
+        Err(_elapsed) => {
+            return Err(WebApiError::Timeout(eyre!(
+                "The requested update didn't finish within {}",
+                format_duration(UPDATE_RESPONSE_TIMEOUT)
+            )))
+        }
+    };
+    Ok(Json(report))
+}
+
+pub fn trigger_update_docs(op: TransformOperation<'_>) -> TransformOperation<'_> {
+    requires_scope(op, WebAuthScope::Write)
+        .description("Trigger an immediate update")
+        .summary("Wakes up the updater to update the domain records right away, optionally only a single record and / or provider, and returns the result of the update. Responds with 404 if the record or provider isn't configured")
+        .tag("update")
+}
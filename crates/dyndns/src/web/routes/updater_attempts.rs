@@ -0,0 +1,26 @@
+use aide::transform::TransformOperation;
+use axum::extract::{Query, State};
+use axum::Json;
+
+use crate::config::web_auth_config::WebAuthScope;
+use crate::db::crud::updater_attempts::{get_updater_attempts_page, UpdaterAttemptEntries};
+use crate::db::history::RecordHistoryQuery;
+use crate::web::auth::requires_scope;
+use crate::web::errors::WebApiResult;
+use crate::web::server::WebServerState;
+
+pub async fn list_updater_attempts(
+    State(state): State<WebServerState>,
+    Query(query): Query<RecordHistoryQuery>,
+) -> WebApiResult<Json<UpdaterAttemptEntries>> {
+    let mut conn = state.db_conn()?.lock().expect("conn mutex poisoned");
+    let attempts = get_updater_attempts_page(&mut conn, &query)?;
+    Ok(Json(attempts))
+}
+
+pub fn list_updater_attempts_docs(op: TransformOperation<'_>) -> TransformOperation<'_> {
+    requires_scope(op, WebAuthScope::Read)
+        .description("List updater attempts")
+        .summary("Returns a page of domain record update attempts, optionally filtered by record name, record type and date range. Pass the returned next_cursor as cursor to get the next page")
+        .tag("history")
+}
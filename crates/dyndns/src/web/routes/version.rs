@@ -3,6 +3,8 @@ use axum::Json;
 use schemars::JsonSchema;
 use serde::Serialize;
 
+use crate::config::web_auth_config::WebAuthScope;
+use crate::web::auth::requires_scope;
 use crate::web::errors::WebApiResult;
 
 #[derive(Debug, Serialize, JsonSchema)]
@@ -31,7 +33,8 @@ pub async fn get_version() -> WebApiResult<Json<VersionResponse>> {
 }
 
 pub fn get_version_docs(op: TransformOperation<'_>) -> TransformOperation<'_> {
-    op.description("Get version information")
+    requires_scope(op, WebAuthScope::Read)
+        .description("Get version information")
         .summary("Returns version, build date, and Git information for the running application")
         .tag("version")
 }
@@ -1,4 +1,5 @@
-use color_eyre::eyre::{Result, WrapErr};
+use axum::Router;
+use color_eyre::eyre::{eyre, Result, WrapErr};
 use diesel::SqliteConnection;
 use futures_util::FutureExt;
 use std::sync::{Arc, Condvar, Mutex};
@@ -8,21 +9,50 @@ use tracing::info;
 
 use crate::config::app_config::AppConfig;
 use crate::db::setup::setup_db;
+use crate::global_state::SharedState;
+use crate::metrics::Metrics;
 use crate::signal_handlers::AppTerminationHandler;
+use crate::update_trigger::UpdateTrigger;
+use crate::updater_events::UpdaterEvents;
+use crate::updater_status::UpdaterStatus;
 use crate::web::addresses::{
     print_listener_addresses, socket_acceptor_from_socket_addreses,
-    socket_addresses_from_host_and_port,
+    socket_addresses_from_host_and_port, MultipleAddrsIncoming,
 };
+use crate::web::auth::WebAuth;
 use crate::web::routes::get_final_router;
 use crate::web::static_server::print_where_files_are_served_from;
+use crate::web::tls::{spawn_cert_reloader, tls_acceptor_from_options};
 
 #[derive(Clone)]
 pub struct WebServerState {
-    pub conn: Arc<Mutex<SqliteConnection>>,
+    /// Connection to the statistics database. Only available when
+    /// `collect_stats` is enabled.
+    pub conn: Option<Arc<Mutex<SqliteConnection>>>,
+    pub metrics: Arc<Metrics>,
+    pub updater_status: Arc<UpdaterStatus>,
+    pub update_trigger: Arc<UpdateTrigger>,
+    pub events: Arc<UpdaterEvents>,
+    pub auth: Arc<WebAuth>,
+    /// Notified when the web server starts shutting down, to end long-lived
+    /// responses like event streams.
+    pub shutdown_rx: tokio::sync::watch::Receiver<()>,
 }
 
-pub fn start_web_server_and_wait(term_handler: AppTerminationHandler, config: &AppConfig) {
-    if !config.general_options.collect_stats || !config.general_options.enable_web {
+impl WebServerState {
+    pub fn db_conn(&self) -> Result<&Arc<Mutex<SqliteConnection>>> {
+        self.conn
+            .as_ref()
+            .ok_or_else(|| eyre!("statistics collection is disabled"))
+    }
+}
+
+pub fn start_web_server_and_wait(
+    term_handler: AppTerminationHandler,
+    config: &AppConfig,
+    shared: SharedState,
+) {
+    if !config.general_options.enable_web {
         return;
     }
 
@@ -32,7 +62,8 @@ pub fn start_web_server_and_wait(term_handler: AppTerminationHandler, config: &A
     let wait_updater = Arc::new((Mutex::new(false), Condvar::new()));
     let notify_updater = Arc::clone(&wait_updater);
 
-    let web_thread_handle = start_web_server_thread(web_exit_rx, config.clone(), notify_updater);
+    let web_thread_handle =
+        start_web_server_thread(web_exit_rx, config.clone(), shared, notify_updater);
     term_handler.set_web_thread(web_thread_handle);
 
     // Wait until the web server has started before returning from the function.
@@ -49,21 +80,30 @@ pub fn start_web_server_and_wait(term_handler: AppTerminationHandler, config: &A
 pub fn start_web_server_thread(
     web_exit_rx: tokio::sync::oneshot::Receiver<()>,
     config: AppConfig,
+    shared: SharedState,
     notify_updater: Arc<(Mutex<bool>, Condvar)>,
 ) -> JoinHandle<Result<()>> {
-    std::thread::spawn(move || start_web_server_runtime(web_exit_rx, config, notify_updater))
+    std::thread::spawn(move || {
+        start_web_server_runtime(web_exit_rx, config, shared, notify_updater)
+    })
 }
 
 pub fn start_web_server_runtime(
     web_exit_rx: tokio::sync::oneshot::Receiver<()>,
     config: AppConfig,
+    shared: SharedState,
     notify_updater: Arc<(Mutex<bool>, Condvar)>,
 ) -> Result<()> {
     let rt = tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()?;
 
-    rt.block_on(start_web_server(web_exit_rx, config, notify_updater))?;
+    rt.block_on(start_web_server(
+        web_exit_rx,
+        config,
+        shared,
+        notify_updater,
+    ))?;
     Ok(())
 }
 
@@ -75,11 +115,26 @@ fn notify_updater_server_addresses_printed(notify_updater: Arc<(Mutex<bool>, Con
     cvar.notify_one();
 }
 
-fn make_web_state(config: AppConfig) -> Result<WebServerState> {
-    let db_conn = setup_db(config.general_options.db_path.clone())
-        .wrap_err("missing db connection to create web app state")?;
+fn make_web_state(
+    config: AppConfig,
+    shared: SharedState,
+    shutdown_rx: tokio::sync::watch::Receiver<()>,
+) -> Result<WebServerState> {
+    let conn = if config.general_options.collect_stats {
+        let db_conn = setup_db(config.general_options.db_path.clone())
+            .wrap_err("missing db connection to create web app state")?;
+        Some(Arc::new(Mutex::new(db_conn)))
+    } else {
+        None
+    };
     WebServerState {
-        conn: Arc::new(Mutex::new(db_conn)),
+        conn,
+        metrics: shared.metrics,
+        updater_status: shared.updater_status,
+        update_trigger: shared.update_trigger,
+        events: shared.events,
+        auth: Arc::new(WebAuth::new(config.general_options.web_auth.clone())),
+        shutdown_rx,
     }
     .into_ok()
 }
@@ -87,30 +142,62 @@ fn make_web_state(config: AppConfig) -> Result<WebServerState> {
 async fn start_web_server(
     web_exit_rx: tokio::sync::oneshot::Receiver<()>,
     config: AppConfig,
+    shared: SharedState,
     notify_updater: Arc<(Mutex<bool>, Condvar)>,
 ) -> Result<()> {
-    let state = make_web_state(config.clone())?;
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(());
+    let state = make_web_state(config.clone(), shared, shutdown_rx)?;
+
+    let general_options = &config.general_options;
+    let hostname = general_options.listen_hostname.as_str();
+    let tls_options = &general_options.web_tls;
+
+    let tls_acceptor = match tls_acceptor_from_options(tls_options)? {
+        Some((tls_acceptor, resolver)) => {
+            spawn_cert_reloader(resolver);
+            Some(tls_acceptor)
+        }
+        None => None,
+    };
+
+    // Without a dedicated TLS port, HTTPS replaces plain HTTP on listen_port.
+    let http_port = match (&tls_acceptor, tls_options.tls_listen_port) {
+        (Some(_), None) => None,
+        _ => Some(general_options.listen_port),
+    };
+    let https_port = tls_acceptor.as_ref().map(|_| {
+        tls_options
+            .tls_listen_port
+            .unwrap_or(general_options.listen_port)
+    });
 
-    let addrs = socket_addresses_from_host_and_port(
-        config.general_options.listen_hostname.as_str(),
-        config.general_options.listen_port,
-    )?;
+    let http_addrs = http_port
+        .map(|port| socket_addresses_from_host_and_port(hostname, port))
+        .transpose()?;
+    let https_addrs = https_port
+        .map(|port| socket_addresses_from_host_and_port(hostname, port))
+        .transpose()?;
 
-    print_listener_addresses(&addrs);
+    if let Some(addrs) = &http_addrs {
+        print_listener_addresses(addrs, "http");
+    }
+    if let Some(addrs) = &https_addrs {
+        print_listener_addresses(addrs, "https");
+    }
     notify_updater_server_addresses_printed(notify_updater);
 
-    let mut incoming = socket_acceptor_from_socket_addreses(&addrs)?;
+    let mut http_incoming = http_addrs
+        .as_deref()
+        .map(socket_acceptor_from_socket_addreses)
+        .transpose()?;
+    let mut https_incoming = https_addrs
+        .as_deref()
+        .map(socket_acceptor_from_socket_addreses)
+        .transpose()?;
     print_where_files_are_served_from();
 
     let router = get_final_router(state);
 
-    use axum::extract::Request;
-    use futures_util::pin_mut;
-    use hyper::body::Incoming;
-    use hyper_util::rt::TokioExecutor;
-    use hyper_util::server::conn::auto::Builder;
-    use tower::Service;
-
     // signal_tx.closed() will be awaited in each task at each yield point,
     // to find out when the  shutdown future (web_exit_rx) and signal_rx have completed.
     let (signal_tx, signal_rx) = tokio::sync::watch::channel(());
@@ -123,15 +210,23 @@ async fn start_web_server(
     tokio::spawn(async move {
         let _ = web_exit_rx.await;
         tracing::trace!("Received graceful shutdown signal. Telling tasks to shutdown");
+        let _ = shutdown_tx.send(());
         drop(signal_rx);
     });
 
     // Loop over incoming connections, and spawn a new task for each connection,
     // while also checking for the signal to shutdown.
     loop {
-        let (tcp_stream, remote_addr) = tokio::select! {
-            conn_res = &mut incoming => {
-                conn_res.wrap_err("Failed to accept connection from listening socket")?
+        let (tcp_stream, remote_addr, connection_tls_acceptor) = tokio::select! {
+            conn_res = accept_next(http_incoming.as_mut()) => {
+                let (tcp_stream, remote_addr) =
+                    conn_res.wrap_err("Failed to accept connection from listening socket")?;
+                (tcp_stream, remote_addr, None)
+            }
+            conn_res = accept_next(https_incoming.as_mut()) => {
+                let (tcp_stream, remote_addr) =
+                    conn_res.wrap_err("Failed to accept connection from listening socket")?;
+                (tcp_stream, remote_addr, tls_acceptor.clone())
             }
             _ = signal_tx.closed() => {
                 tracing::trace!("signal received, not accepting new connections");
@@ -145,35 +240,23 @@ async fn start_web_server(
         let close_rx = close_rx.clone();
 
         tokio::spawn(async move {
-            // Compatibility code to allow inter-op between hyper services and
-            // axum services (tower services). axum can't depend on on hyper_util, that's why
-            // we need this dance.
-            let socket = hyper_util::rt::TokioIo::new(tcp_stream);
-            let hyper_service = hyper::service::service_fn(move |request: Request<Incoming>| {
-                router_clone.clone().call(request)
-            });
-
-            let builder = Builder::new(TokioExecutor::new());
-            let conn = builder.serve_connection_with_upgrades(socket, hyper_service);
-            pin_mut!(conn);
-
-            let signal_closed = signal_tx.closed().fuse();
-            pin_mut!(signal_closed);
-
-            loop {
-                tokio::select! {
-                    result = conn.as_mut() => {
-                        if let Err(err) = result {
-                            tracing::debug!("failed to serve connection: {err:#}");
+            match connection_tls_acceptor {
+                Some(tls_acceptor) => {
+                    let handshake = tokio::select! {
+                        handshake = tls_acceptor.accept(tcp_stream) => Some(handshake),
+                        _ = signal_tx.closed() => None,
+                    };
+                    match handshake {
+                        Some(Ok(tls_stream)) => {
+                            serve_connection(tls_stream, router_clone, signal_tx).await;
                         }
-                        break;
-                    }
-                    _ = &mut signal_closed => {
-                        tracing::debug!("signal received in task, starting graceful shutdown");
-                        conn.as_mut().graceful_shutdown();
-
+                        Some(Err(err)) => {
+                            tracing::debug!("TLS handshake with {remote_addr} failed: {err:#}");
+                        }
+                        None => (),
                     }
                 }
+                None => serve_connection(tcp_stream, router_clone, signal_tx).await,
             }
 
             tracing::trace!("connection {remote_addr} closed");
@@ -181,7 +264,8 @@ async fn start_web_server(
         });
     }
 
-    drop(incoming);
+    drop(http_incoming);
+    drop(https_incoming);
     drop(close_rx);
 
     tracing::trace!(
@@ -194,3 +278,55 @@ async fn start_web_server(
 
     Ok(())
 }
+
+async fn accept_next(
+    incoming: Option<&mut MultipleAddrsIncoming>,
+) -> std::io::Result<(tokio::net::TcpStream, std::net::SocketAddr)> {
+    match incoming {
+        Some(incoming) => incoming.await,
+        // Listener is not configured, never resolve.
+        None => std::future::pending().await,
+    }
+}
+
+async fn serve_connection<I>(io: I, router: Router, signal_tx: Arc<tokio::sync::watch::Sender<()>>)
+where
+    I: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
+    use axum::extract::Request;
+    use futures_util::pin_mut;
+    use hyper::body::Incoming;
+    use hyper_util::rt::TokioExecutor;
+    use hyper_util::server::conn::auto::Builder;
+    use tower::Service;
+
+    // Compatibility code to allow inter-op between hyper services and
+    // axum services (tower services). axum can't depend on on hyper_util, that's why
+    // we need this dance.
+    let socket = hyper_util::rt::TokioIo::new(io);
+    let hyper_service =
+        hyper::service::service_fn(move |request: Request<Incoming>| router.clone().call(request));
+
+    let builder = Builder::new(TokioExecutor::new());
+    let conn = builder.serve_connection_with_upgrades(socket, hyper_service);
+    pin_mut!(conn);
+
+    let signal_closed = signal_tx.closed().fuse();
+    pin_mut!(signal_closed);
+
+    loop {
+        tokio::select! {
+            result = conn.as_mut() => {
+                if let Err(err) = result {
+                    tracing::debug!("failed to serve connection: {err:#}");
+                }
+                break;
+            }
+            _ = &mut signal_closed => {
+                tracing::debug!("signal received in task, starting graceful shutdown");
+                conn.as_mut().graceful_shutdown();
+
+            }
+        }
+    }
+}
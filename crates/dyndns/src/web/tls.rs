@@ -0,0 +1,262 @@
+use color_eyre::eyre::{bail, Result, WrapErr};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio_rustls::rustls::crypto::{aws_lc_rs, CryptoProvider};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert};
+use tokio_rustls::rustls::sign::CertifiedKey;
+use tokio_rustls::rustls::{InconsistentKeys, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use tracing::{error, info};
+
+use crate::config::app_config::WebTlsOptions;
+
+/// How often the certificate files are checked for changes.
+const CERT_RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Serves the most recently loaded certificate, so renewed certificates are
+/// picked up by new connections without restarting the web server.
+#[derive(Debug)]
+pub struct ReloadingCertResolver {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    /// Loads the private keys, the same provider the TLS connections use.
+    provider: Arc<CryptoProvider>,
+    certified_key: RwLock<Arc<CertifiedKey>>,
+    modification_times: RwLock<Option<(SystemTime, SystemTime)>>,
+}
+
+impl ReloadingCertResolver {
+    pub fn new(
+        cert_path: PathBuf,
+        key_path: PathBuf,
+        provider: Arc<CryptoProvider>,
+    ) -> Result<Self> {
+        let modification_times = modification_times(&cert_path, &key_path);
+        let certified_key = load_certified_key(&cert_path, &key_path, &provider)?;
+        Ok(Self {
+            cert_path,
+            key_path,
+            provider,
+            certified_key: RwLock::new(Arc::new(certified_key)),
+            modification_times: RwLock::new(modification_times),
+        })
+    }
+
+    /// Reloads the certificate and key when either file was modified since
+    /// the last load. Returns whether a new certificate was loaded.
+    ///
+    /// The previous certificate stays in use if the new one fails to load,
+    /// e.g. because only one of the two files was replaced so far and the key
+    /// doesn't match the certificate.
+    pub fn reload_if_changed(&self) -> Result<bool> {
+        let current_times = modification_times(&self.cert_path, &self.key_path);
+        let last_times = *self
+            .modification_times
+            .read()
+            .expect("modification_times lock poisoned");
+        if current_times.is_none() || current_times == last_times {
+            return Ok(false);
+        }
+
+        let certified_key = load_certified_key(&self.cert_path, &self.key_path, &self.provider)?;
+        *self
+            .certified_key
+            .write()
+            .expect("certified_key lock poisoned") = Arc::new(certified_key);
+        *self
+            .modification_times
+            .write()
+            .expect("modification_times lock poisoned") = current_times;
+        Ok(true)
+    }
+}
+
+impl ResolvesServerCert for ReloadingCertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(Arc::clone(
+            &self
+                .certified_key
+                .read()
+                .expect("certified_key lock poisoned"),
+        ))
+    }
+}
+
+fn modification_times(cert_path: &Path, key_path: &Path) -> Option<(SystemTime, SystemTime)> {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    Some((modified(cert_path)?, modified(key_path)?))
+}
+
+pub fn load_certified_key(
+    cert_path: &Path,
+    key_path: &Path,
+    provider: &CryptoProvider,
+) -> Result<CertifiedKey> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .wrap_err_with(|| {
+            format!(
+                "Failed to read TLS certificates from '{}'",
+                cert_path.display()
+            )
+        })?;
+    if certs.is_empty() {
+        bail!("No TLS certificates found in '{}'", cert_path.display());
+    }
+
+    let key = PrivateKeyDer::from_pem_file(key_path).wrap_err_with(|| {
+        format!(
+            "Failed to read TLS private key from '{}'",
+            key_path.display()
+        )
+    })?;
+    let signing_key = provider
+        .key_provider
+        .load_private_key(key)
+        .wrap_err("Unsupported TLS private key type")?;
+
+    let certified_key = CertifiedKey::new(certs, signing_key);
+    match certified_key.keys_match() {
+        // Keys that can't tell their public key can't be checked.
+        Ok(()) | Err(tokio_rustls::rustls::Error::InconsistentKeys(InconsistentKeys::Unknown)) => {
+            Ok(certified_key)
+        }
+        Err(e) => Err(e).wrap_err_with(|| {
+            format!(
+                "TLS private key '{}' doesn't match the certificate '{}'",
+                key_path.display(),
+                cert_path.display()
+            )
+        }),
+    }
+}
+
+/// Creates the acceptor that wraps accepted connections with TLS, or `None`
+/// when TLS is not configured.
+pub fn tls_acceptor_from_options(
+    options: &WebTlsOptions,
+) -> Result<Option<(TlsAcceptor, Arc<ReloadingCertResolver>)>> {
+    let (Some(cert_path), Some(key_path)) = (&options.tls_cert_path, &options.tls_key_path) else {
+        return Ok(None);
+    };
+
+    let provider = Arc::new(aws_lc_rs::default_provider());
+    let resolver = Arc::new(ReloadingCertResolver::new(
+        cert_path.clone(),
+        key_path.clone(),
+        provider.clone(),
+    )?);
+    let mut server_config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .wrap_err("Failed to configure the TLS protocol versions")?
+        .with_no_client_auth()
+        .with_cert_resolver(resolver.clone());
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(Some((TlsAcceptor::from(Arc::new(server_config)), resolver)))
+}
+
+/// Periodically checks the certificate files for changes and reloads them.
+/// Runs until the web server runtime shuts down.
+pub fn spawn_cert_reloader(resolver: Arc<ReloadingCertResolver>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CERT_RELOAD_CHECK_INTERVAL);
+        // The first tick completes immediately, and the certificate was just loaded.
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let reloader = Arc::clone(&resolver);
+            match tokio::task::spawn_blocking(move || reloader.reload_if_changed()).await {
+                Ok(Ok(true)) => info!("Reloaded TLS certificate"),
+                Ok(Ok(false)) => (),
+                Ok(Err(e)) => error!("Failed to reload TLS certificate: {:#}", e),
+                Err(e) => error!("TLS certificate reload task failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn test_data_path(file_name: &str) -> PathBuf {
+        [env!("CARGO_MANIFEST_DIR"), "tests/data/", file_name]
+            .iter()
+            .collect()
+    }
+
+    #[test]
+    fn test_load_certified_key_errors() {
+        let provider = aws_lc_rs::default_provider();
+        let missing = Path::new("/nonexistent/dyndns/cert.pem");
+        assert!(load_certified_key(missing, missing, &provider).is_err());
+
+        let mut empty_cert = tempfile::NamedTempFile::new().unwrap();
+        writeln!(empty_cert, "not a certificate").unwrap();
+        let error_msg = load_certified_key(empty_cert.path(), missing, &provider)
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("No TLS certificates found"));
+
+        let cert_path = test_data_path("tls_cert.pem");
+        assert!(load_certified_key(&cert_path, &test_data_path("tls_key.pem"), &provider).is_ok());
+        let error_msg =
+            load_certified_key(&cert_path, &test_data_path("tls_other_key.pem"), &provider)
+                .unwrap_err()
+                .to_string();
+        assert!(error_msg.contains("doesn't match the certificate"));
+    }
+
+    #[test]
+    fn test_reload_keeps_certificate_with_mismatched_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert_path = dir.path().join("cert.pem");
+        let key_path = dir.path().join("key.pem");
+        std::fs::copy(test_data_path("tls_cert.pem"), &cert_path).unwrap();
+        std::fs::copy(test_data_path("tls_key.pem"), &key_path).unwrap();
+        let resolver = ReloadingCertResolver::new(
+            cert_path,
+            key_path.clone(),
+            Arc::new(aws_lc_rs::default_provider()),
+        )
+        .unwrap();
+        let loaded_key = Arc::clone(&resolver.certified_key.read().unwrap());
+
+        // Only the key was replaced so far.
+        std::fs::copy(test_data_path("tls_other_key.pem"), &key_path).unwrap();
+        *resolver.modification_times.write().unwrap() = None;
+        assert!(resolver.reload_if_changed().is_err());
+        assert!(Arc::ptr_eq(
+            &loaded_key,
+            &resolver.certified_key.read().unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_tls_options_validation() {
+        let options = WebTlsOptions::default();
+        assert!(!options.is_enabled());
+        assert!(options.validate(8095).is_ok());
+        assert!(tls_acceptor_from_options(&options).unwrap().is_none());
+
+        let options = WebTlsOptions {
+            tls_cert_path: Some(PathBuf::from("cert.pem")),
+            ..Default::default()
+        };
+        assert!(options.validate(8095).is_err());
+
+        let options = WebTlsOptions {
+            tls_cert_path: Some(PathBuf::from("cert.pem")),
+            tls_key_path: Some(PathBuf::from("key.pem")),
+            tls_listen_port: Some(8095),
+        };
+        assert!(options.is_enabled());
+        assert!(options.validate(8095).is_err());
+        assert!(options.validate(8096).is_ok());
+    }
+}
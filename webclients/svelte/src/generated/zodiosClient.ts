@@ -1,18 +1,163 @@
 import { makeApi, Zodios, type ZodiosOptions } from '@zodios/core';
 import { z } from 'zod';
 
+const SortOrder = z.enum(['asc', 'desc']);
+const DomainIpFetch = z
+	.object({
+		attempt_date: z.string(),
+		fetched_ipv4: z.union([z.string(), z.null()]).optional(),
+		fetched_ipv6: z.union([z.string(), z.null()]).optional(),
+		id: z.number().int(),
+		success: z.boolean()
+	})
+	.passthrough();
+const DomainIpFetches = z
+	.object({
+		fetches: z.array(DomainIpFetch),
+		next_cursor: z.union([z.number().int(), z.null()]).optional()
+	})
+	.passthrough();
+const WebApiError = z.object({ GenericError: z.string() }).passthrough();
 const DomainRecordIpChange = z
 	.object({
 		attempt_date: z.string(),
 		domain_record_id: z.number().int(),
+		error_message: z.union([z.string(), z.null()]).optional(),
+		http_status: z.union([z.number().int(), z.null()]).optional(),
 		id: z.number().int(),
+		latency_ms: z.union([z.number().int(), z.null()]).optional(),
 		name: z.string(),
+		provider_id: z.union([z.number().int(), z.null()]).optional(),
 		set_ip: z.string(),
 		success: z.boolean()
 	})
 	.passthrough();
-const DomainRecordIpChanges = z.object({ changes: z.array(DomainRecordIpChange) }).passthrough();
-const WebApiError = z.object({ GenericError: z.string() }).passthrough();
+const DomainRecordIpChanges = z
+	.object({
+		changes: z.array(DomainRecordIpChange),
+		next_cursor: z.union([z.number().int(), z.null()]).optional()
+	})
+	.passthrough();
+const IpFamily = z.enum(['ipv4', 'ipv6']);
+const UpdaterEvent = z.union([
+	z
+		.object({
+			ipv4: z.union([z.string(), z.null()]).optional(),
+			ipv6: z.union([z.string(), z.null()]).optional(),
+			type: z.literal('ip_fetched')
+		})
+		.passthrough(),
+	z.object({ error_message: z.string(), type: z.literal('ip_fetch_failed') }).passthrough(),
+	z
+		.object({
+			family: IpFamily,
+			new_ip: z.string(),
+			old_ip: z.union([z.string(), z.null()]).optional(),
+			type: z.literal('ip_changed')
+		})
+		.passthrough(),
+	z
+		.object({
+			name: z.string(),
+			provider: z.string(),
+			record_type: z.string(),
+			type: z.literal('record_update_started')
+		})
+		.passthrough(),
+	z
+		.object({
+			name: z.string(),
+			provider: z.string(),
+			record_type: z.string(),
+			type: z.literal('record_update_succeeded'),
+			value: z.string()
+		})
+		.passthrough(),
+	z
+		.object({
+			error_message: z.string(),
+			name: z.string(),
+			provider: z.string(),
+			record_type: z.string(),
+			type: z.literal('record_update_failed')
+		})
+		.passthrough(),
+	z
+		.object({ type: z.literal('updater_sleeping'), until: z.string().datetime({ offset: true }) })
+		.passthrough()
+]);
+const HealthState = z.enum(['healthy', 'degraded', 'unhealthy']);
+const UpdaterThreadState = z.enum(['starting', 'running', 'stopped']);
+const ProviderRecordStatus = z
+	.object({
+		last_error_message: z.union([z.string(), z.null()]).optional(),
+		last_update_successful: z.boolean(),
+		provider: z.string(),
+		value: z.union([z.string(), z.null()]).optional()
+	})
+	.passthrough();
+const RecordStatus = z
+	.object({
+		last_attempt_date: z.string(),
+		last_attempt_successful: z.boolean(),
+		name: z.string(),
+		providers: z.array(ProviderRecordStatus),
+		record_type: z.string()
+	})
+	.passthrough();
+const StatusResponse = z
+	.object({
+		consecutive_failures: z.number().int().gte(0),
+		failure_threshold: z.number().int().gte(0),
+		health: HealthState,
+		ipv4: z.union([z.string(), z.null()]).optional(),
+		ipv6: z.union([z.string(), z.null()]).optional(),
+		last_attempt_date: z.union([z.string(), z.null()]).optional(),
+		last_attempt_successful: z.union([z.boolean(), z.null()]).optional(),
+		records: z.array(RecordStatus),
+		updater_state: UpdaterThreadState
+	})
+	.passthrough();
+const ProviderUpdateReport = z
+	.object({
+		error_message: z.union([z.string(), z.null()]).optional(),
+		provider: z.string(),
+		success: z.boolean(),
+		value: z.union([z.string(), z.null()]).optional()
+	})
+	.passthrough();
+const RecordUpdateReport = z
+	.object({
+		name: z.string(),
+		providers: z.array(ProviderUpdateReport),
+		record_type: z.string(),
+		success: z.boolean()
+	})
+	.passthrough();
+const UpdateReport = z
+	.object({
+		error_message: z.union([z.string(), z.null()]).optional(),
+		records: z.array(RecordUpdateReport),
+		success: z.boolean()
+	})
+	.passthrough();
+const UpdaterAttemptEntry = z
+	.object({
+		attempt_date: z.string(),
+		domain_ip_fetches_id: z.number().int(),
+		domain_record_id: z.number().int(),
+		domain_record_updates_id: z.union([z.number().int(), z.null()]).optional(),
+		id: z.number().int(),
+		name: z.string(),
+		record_type: z.string()
+	})
+	.passthrough();
+const UpdaterAttemptEntries = z
+	.object({
+		attempts: z.array(UpdaterAttemptEntry),
+		next_cursor: z.union([z.number().int(), z.null()]).optional()
+	})
+	.passthrough();
 const VersionResponse = z
 	.object({
 		build_date: z.string(),
@@ -24,19 +169,112 @@ const VersionResponse = z
 	.passthrough();
 
 export const schemas = {
+	SortOrder,
+	DomainIpFetch,
+	DomainIpFetches,
+	WebApiError,
 	DomainRecordIpChange,
 	DomainRecordIpChanges,
-	WebApiError,
+	IpFamily,
+	UpdaterEvent,
+	HealthState,
+	UpdaterThreadState,
+	ProviderRecordStatus,
+	RecordStatus,
+	StatusResponse,
+	ProviderUpdateReport,
+	RecordUpdateReport,
+	UpdateReport,
+	UpdaterAttemptEntry,
+	UpdaterAttemptEntries,
 	VersionResponse
 };
 
 const endpoints = makeApi([
+	{
+		method: 'get',
+		path: '/api/v1/domain_ip_fetches',
+		alias: 'getApiv1domain_ip_fetches',
+		description: `List public ip fetches`,
+		requestFormat: 'json',
+		parameters: [
+			{
+				name: 'since',
+				type: 'Query',
+				schema: z.union([z.string(), z.null()]).optional()
+			},
+			{
+				name: 'until',
+				type: 'Query',
+				schema: z.union([z.string(), z.null()]).optional()
+			},
+			{
+				name: 'limit',
+				type: 'Query',
+				schema: z.union([z.number().int().gte(0), z.null()]).optional()
+			},
+			{
+				name: 'cursor',
+				type: 'Query',
+				schema: z.union([z.number().int(), z.null()]).optional()
+			},
+			{
+				name: 'order',
+				type: 'Query',
+				schema: z.union([SortOrder, z.null()]).optional()
+			}
+		],
+		response: DomainIpFetches,
+		errors: [
+			{
+				status: 'default',
+				schema: WebApiError
+			}
+		]
+	},
 	{
 		method: 'get',
 		path: '/api/v1/domain_record_ip_changes',
 		alias: 'getApiv1domain_record_ip_changes',
 		description: `List all recent domain record ip changes`,
 		requestFormat: 'json',
+		parameters: [
+			{
+				name: 'record',
+				type: 'Query',
+				schema: z.union([z.string(), z.null()]).optional()
+			},
+			{
+				name: 'record_type',
+				type: 'Query',
+				schema: z.union([z.string(), z.null()]).optional()
+			},
+			{
+				name: 'since',
+				type: 'Query',
+				schema: z.union([z.string(), z.null()]).optional()
+			},
+			{
+				name: 'until',
+				type: 'Query',
+				schema: z.union([z.string(), z.null()]).optional()
+			},
+			{
+				name: 'limit',
+				type: 'Query',
+				schema: z.union([z.number().int().gte(0), z.null()]).optional()
+			},
+			{
+				name: 'cursor',
+				type: 'Query',
+				schema: z.union([z.number().int(), z.null()]).optional()
+			},
+			{
+				name: 'order',
+				type: 'Query',
+				schema: z.union([SortOrder, z.null()]).optional()
+			}
+		],
 		response: DomainRecordIpChanges,
 		errors: [
 			{
@@ -45,6 +283,111 @@ const endpoints = makeApi([
 			}
 		]
 	},
+	{
+		method: 'get',
+		path: '/api/v1/events',
+		alias: 'getApiv1events',
+		description: `Stream updater events`,
+		requestFormat: 'json',
+		response: UpdaterEvent,
+		errors: [
+			{
+				status: 'default',
+				schema: WebApiError
+			}
+		]
+	},
+	{
+		method: 'get',
+		path: '/api/v1/status',
+		alias: 'getApiv1status',
+		description: `Get the updater status`,
+		requestFormat: 'json',
+		response: StatusResponse,
+		errors: [
+			{
+				status: 'default',
+				schema: WebApiError
+			}
+		]
+	},
+	{
+		method: 'post',
+		path: '/api/v1/update',
+		alias: 'postApiv1update',
+		description: `Trigger an immediate update`,
+		requestFormat: 'json',
+		parameters: [
+			{
+				name: 'record',
+				type: 'Query',
+				schema: z.union([z.string(), z.null()]).optional()
+			},
+			{
+				name: 'provider',
+				type: 'Query',
+				schema: z.union([z.string(), z.null()]).optional()
+			}
+		],
+		response: UpdateReport,
+		errors: [
+			{
+				status: 'default',
+				schema: WebApiError
+			}
+		]
+	},
+	{
+		method: 'get',
+		path: '/api/v1/updater_attempts',
+		alias: 'getApiv1updater_attempts',
+		description: `List updater attempts`,
+		requestFormat: 'json',
+		parameters: [
+			{
+				name: 'record',
+				type: 'Query',
+				schema: z.union([z.string(), z.null()]).optional()
+			},
+			{
+				name: 'record_type',
+				type: 'Query',
+				schema: z.union([z.string(), z.null()]).optional()
+			},
+			{
+				name: 'since',
+				type: 'Query',
+				schema: z.union([z.string(), z.null()]).optional()
+			},
+			{
+				name: 'until',
+				type: 'Query',
+				schema: z.union([z.string(), z.null()]).optional()
+			},
+			{
+				name: 'limit',
+				type: 'Query',
+				schema: z.union([z.number().int().gte(0), z.null()]).optional()
+			},
+			{
+				name: 'cursor',
+				type: 'Query',
+				schema: z.union([z.number().int(), z.null()]).optional()
+			},
+			{
+				name: 'order',
+				type: 'Query',
+				schema: z.union([SortOrder, z.null()]).optional()
+			}
+		],
+		response: UpdaterAttemptEntries,
+		errors: [
+			{
+				status: 'default',
+				schema: WebApiError
+			}
+		]
+	},
 	{
 		method: 'get',
 		path: '/api/v1/version',
@@ -32,6 +32,17 @@ export class DomainRecordIpChangesStore {
 		return promise;
 	}
 
+	// Refetches the changes whenever the updater reports a successful record
+	// update, instead of polling. Returns a function that stops listening.
+	public subscribeToEvents(): () => void {
+		const baseUrl = import.meta.env.VITE_SERVER_API_BASE_URL || window.location.origin;
+		const eventSource = new EventSource(new URL('/api/v1/events', baseUrl));
+		eventSource.addEventListener('record_update_succeeded', () => {
+			this.queryClient.invalidateQueries({ queryKey: ['domain_record_ip_changes'], exact: true });
+		});
+		return () => eventSource.close();
+	}
+
 	public reset() {
 		this.queryClient.resetQueries({ queryKey: ['domain_record_ip_changes'], exact: true });
 	}